@@ -0,0 +1,400 @@
+// Compares two ephemeris sources of the same object over an interval.
+// The errors are split into radial, along track and cross track components of the reference state,
+// as that tells a lot more than the plain distance: timing errors are along track, plane errors cross track.
+
+use crate::accuracy::ephemeris_source::EphemerisSource;
+use crate::bodies::Planet;
+use crate::timebase::Timebase;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::*;
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ComparisonSample {
+    /// ET
+    pub time: f64,
+    /// km
+    pub position_error: f64,
+    /// km/s
+    pub velocity_error: f64,
+    /// km, signed
+    pub radial: f64,
+    pub along: f64,
+    pub cross: f64,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ErrorStatistics {
+    pub max: f64,
+    pub rms: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ComparisonReport {
+    pub tested: String,
+    pub reference: String,
+    pub center: Planet,
+    pub samples: Vec<ComparisonSample>,
+    /// times where one of the sources had no state
+    pub skipped: usize,
+    pub position: ErrorStatistics,
+    pub velocity: ErrorStatistics,
+    pub radial: ErrorStatistics,
+    pub along: ErrorStatistics,
+    pub cross: ErrorStatistics,
+}
+
+/// Evaluates both sources at evenly spaced times between from and to (ET).
+/// Errors are tested minus reference.
+pub fn compare_ephemerides(
+    tested: &EphemerisSource,
+    reference: &EphemerisSource,
+    center: Planet,
+    from: f64,
+    to: f64,
+    steps: usize,
+) -> Result<ComparisonReport, String> {
+    compare(tested, reference, center, from, to, steps, |_, _| true)
+}
+
+/// compare_ephemerides, telling how far it got, stops when keep_going returns false
+fn compare(
+    tested: &EphemerisSource,
+    reference: &EphemerisSource,
+    center: Planet,
+    from: f64,
+    to: f64,
+    steps: usize,
+    keep_going: impl Fn(usize, usize) -> bool,
+) -> Result<ComparisonReport, String> {
+    if to <= from {
+        return Err("The interval is empty".to_string());
+    }
+    let steps = steps.max(2);
+    let mut timebase = Timebase::new();
+    let mut samples = Vec::with_capacity(steps);
+    let mut skipped = 0;
+    let mut last_error = None;
+    for i in 0..steps {
+        if !keep_going(i, steps) {
+            return Err("Cancelled".to_string());
+        }
+        let time = from + (to - from) * i as f64 / (steps - 1) as f64;
+        timebase.set_now(time);
+        let (a, b) = match (
+            tested.state(center, &timebase),
+            reference.state(center, &timebase),
+        ) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(e), _) | (_, Err(e)) => {
+                skipped += 1;
+                last_error = Some(e);
+                continue;
+            }
+        };
+
+        let delta = a.position - b.position;
+        let radial_axis = b.position.normalize_or_zero();
+        let cross_axis = b.position.cross(b.velocity).normalize_or_zero();
+        let along_axis = cross_axis.cross(radial_axis);
+        samples.push(ComparisonSample {
+            time,
+            position_error: delta.length(),
+            velocity_error: (a.velocity - b.velocity).length(),
+            radial: delta.dot(radial_axis),
+            along: delta.dot(along_axis),
+            cross: delta.dot(cross_axis),
+        });
+    }
+    if samples.is_empty() {
+        return Err(last_error.unwrap_or_else(|| "No samples".to_string()));
+    }
+
+    Ok(ComparisonReport {
+        tested: tested.to_string(),
+        reference: reference.to_string(),
+        center,
+        skipped,
+        position: statistics(&samples, |s| s.position_error),
+        velocity: statistics(&samples, |s| s.velocity_error),
+        radial: statistics(&samples, |s| s.radial),
+        along: statistics(&samples, |s| s.along),
+        cross: statistics(&samples, |s| s.cross),
+        samples,
+    })
+}
+
+/// Max of the absolute value and root mean square
+fn statistics(
+    samples: &[ComparisonSample],
+    value: impl Fn(&ComparisonSample) -> f64,
+) -> ErrorStatistics {
+    let (max, sum_squares) = samples
+        .iter()
+        .map(value)
+        .fold((0.0f64, 0.0), |(max, sum), v| {
+            (max.max(v.abs()), sum + v * v)
+        });
+    ErrorStatistics {
+        max,
+        rms: (sum_squares / samples.len() as f64).sqrt(),
+    }
+}
+
+pub enum ComparisonStatus {
+    Invalid,
+    Progress { done: usize, total: usize },
+    Cancelled,
+    Complete(Result<ComparisonReport, String>),
+}
+
+/// compare_ephemerides on a separate thread, SPICE and SGP4 take a while for many samples
+pub struct EphemerisComparison {
+    rx: Receiver<ComparisonStatus>,
+    cancel: Arc<AtomicBool>,
+    last_status: ComparisonStatus,
+}
+
+impl EphemerisComparison {
+    pub fn start(
+        tested: EphemerisSource,
+        reference: EphemerisSource,
+        center: Planet,
+        from: f64,
+        to: f64,
+        steps: usize,
+    ) -> Self {
+        let (tx, rx) = sync_channel(10);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let result = compare(
+                &tested,
+                &reference,
+                center,
+                from,
+                to,
+                steps,
+                |done, total| {
+                    // if the ui is busy, it just misses a progress update
+                    let _ = tx.try_send(ComparisonStatus::Progress { done, total });
+                    !worker_cancel.load(Ordering::Relaxed)
+                },
+            );
+            if worker_cancel.load(Ordering::Relaxed) {
+                tx.send(ComparisonStatus::Cancelled).ok();
+            } else {
+                tx.send(ComparisonStatus::Complete(result)).ok();
+            }
+        });
+
+        Self {
+            rx,
+            cancel,
+            last_status: ComparisonStatus::Invalid,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn update_status(&mut self) {
+        while let Ok(new_status) = self.rx.try_recv() {
+            self.last_status = new_status;
+        }
+    }
+
+    pub fn get_status(&self) -> &ComparisonStatus {
+        &self.last_status
+    }
+
+    /// The report or why there is none once done, leaving the comparison invalid
+    pub fn take_result(&mut self) -> Option<Result<ComparisonReport, String>> {
+        match std::mem::replace(&mut self.last_status, ComparisonStatus::Invalid) {
+            ComparisonStatus::Complete(result) => Some(result),
+            other => {
+                self.last_status = other;
+                None
+            }
+        }
+    }
+}
+
+impl ComparisonReport {
+    /// One value per sample, for plotting
+    pub fn series(&self, value: impl Fn(&ComparisonSample) -> f64) -> Vec<f32> {
+        self.samples.iter().map(|s| value(s) as f32).collect()
+    }
+
+    /// Plain text summary, to paste somewhere
+    pub fn summary(&self) -> String {
+        let line = |name: &str, s: &ErrorStatistics, unit: &str| {
+            format!(
+                "{:<10} max {:>14.6} {}  rms {:>14.6} {}\n",
+                name, s.max, unit, s.rms, unit
+            )
+        };
+        let mut text = format!(
+            "{} against {}, relative to {}, {} samples, {} skipped\n",
+            self.tested,
+            self.reference,
+            self.center,
+            self.samples.len(),
+            self.skipped
+        );
+        text += &line("Position", &self.position, "km");
+        text += &line("Velocity", &self.velocity, "km/s");
+        text += &line("Radial", &self.radial, "km");
+        text += &line("Along", &self.along, "km");
+        text += &line("Cross", &self.cross, "km");
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateUnit, PlanetaryReferenceFrame, PlanetaryStateVector};
+    use crate::nasa_horizons::VectorTable;
+    use glam::f64::DVec3;
+
+    /// Linear motion in low earth orbit every 10 s from 0 to 100 s, what the hermite interpolation gives exactly
+    fn reference_states() -> Vec<(f64, PlanetaryStateVector)> {
+        (0..=10)
+            .map(|i| {
+                let time = i as f64 * 10.0;
+                let velocity = DVec3::new(0.0, 7.5, 0.1);
+                (
+                    time,
+                    PlanetaryStateVector {
+                        planet: Planet::Earth,
+                        reference_frame: PlanetaryReferenceFrame::Inertial,
+                        unit: CoordinateUnit::KiloMeter,
+                        position: DVec3::new(7000.0, 0.0, 0.0) + velocity * time,
+                        velocity,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn table(target: &str, states: Vec<(f64, PlanetaryStateVector)>) -> EphemerisSource {
+        EphemerisSource::Horizons(VectorTable {
+            target: target.to_string(),
+            center: Planet::Earth,
+            states,
+        })
+    }
+
+    /// The reference moved by radial, along and cross track km, and the velocity by dv km/s
+    fn offset(radial: f64, along: f64, cross: f64, dv: DVec3) -> Vec<(f64, PlanetaryStateVector)> {
+        reference_states()
+            .into_iter()
+            .map(|(time, mut state)| {
+                let r = state.position.normalize();
+                let n = state.position.cross(state.velocity).normalize();
+                let t = n.cross(r);
+                state.position += r * radial + t * along + n * cross;
+                state.velocity += dv;
+                (time, state)
+            })
+            .collect()
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn errors_split_into_rtn() {
+        let tested = table(
+            "tested",
+            offset(1.0, -2.0, 3.0, DVec3::new(0.001, 0.0, 0.0)),
+        );
+        let reference = table("reference", reference_states());
+        let report =
+            compare_ephemerides(&tested, &reference, Planet::Earth, 0.0, 100.0, 11).unwrap();
+        assert_eq!(report.samples.len(), 11);
+        assert_eq!(report.skipped, 0);
+        for sample in report.samples.iter() {
+            assert!(close(sample.radial, 1.0), "{:?}", sample);
+            assert!(close(sample.along, -2.0), "{:?}", sample);
+            assert!(close(sample.cross, 3.0), "{:?}", sample);
+            assert!(close(sample.position_error, 14f64.sqrt()), "{:?}", sample);
+            assert!(close(sample.velocity_error, 0.001), "{:?}", sample);
+        }
+        assert!(close(report.along.max, 2.0));
+        assert!(close(report.along.rms, 2.0));
+        assert!(close(report.position.max, 14f64.sqrt()));
+    }
+
+    #[test]
+    fn times_without_state_are_skipped() {
+        let mut states = offset(0.0, 5.0, 0.0, DVec3::ZERO);
+        states.truncate(6);
+        let tested = table("tested", states);
+        let reference = table("reference", reference_states());
+        let report =
+            compare_ephemerides(&tested, &reference, Planet::Earth, 0.0, 100.0, 11).unwrap();
+        assert_eq!(report.samples.len(), 6);
+        assert_eq!(report.skipped, 5);
+        assert_eq!(report.samples.last().unwrap().time, 50.0);
+
+        assert_eq!(
+            compare_ephemerides(&tested, &reference, Planet::Moon, 0.0, 100.0, 11).unwrap_err(),
+            "The Horizons table is relative to Earth"
+        );
+        assert!(compare_ephemerides(&tested, &reference, Planet::Earth, 10.0, 10.0, 11).is_err());
+    }
+
+    #[test]
+    fn max_and_rms() {
+        let samples = [3.0, -4.0, 0.0, 0.0]
+            .map(|radial| ComparisonSample {
+                time: 0.0,
+                position_error: 0.0,
+                velocity_error: 0.0,
+                radial,
+                along: 0.0,
+                cross: 0.0,
+            })
+            .to_vec();
+        let radial = statistics(&samples, |s| s.radial);
+        assert_eq!(radial.max, 4.0);
+        assert_eq!(radial.rms, 2.5);
+        let along = statistics(&samples, |s| s.along);
+        assert_eq!((along.max, along.rms), (0.0, 0.0));
+    }
+
+    fn wait(comparison: &mut EphemerisComparison) -> Option<Result<ComparisonReport, String>> {
+        for _i in 0..6000 {
+            comparison.update_status();
+            if let ComparisonStatus::Cancelled = comparison.get_status() {
+                return None;
+            }
+            if let Some(result) = comparison.take_result() {
+                return Some(result);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("comparison did not finish");
+    }
+
+    #[test]
+    fn background_comparison() {
+        let tested = table("tested", offset(0.0, 0.0, 2.0, DVec3::ZERO));
+        let reference = table("reference", reference_states());
+        let mut comparison =
+            EphemerisComparison::start(tested, reference, Planet::Earth, 0.0, 100.0, 11);
+        let report = wait(&mut comparison).unwrap().unwrap();
+        assert!(close(report.cross.rms, 2.0));
+
+        let tested = table("tested", reference_states());
+        let reference = table("reference", reference_states());
+        let mut comparison =
+            EphemerisComparison::start(tested, reference, Planet::Earth, 0.0, 100.0, 100000);
+        comparison.cancel();
+        assert!(wait(&mut comparison).is_none());
+    }
+}
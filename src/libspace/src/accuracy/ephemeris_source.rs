@@ -0,0 +1,114 @@
+use crate::bodies::Planet;
+use crate::coordinate::{
+    teme_to_icrf, CoordinateUnit, PlanetaryReferenceFrame, PlanetaryStateVector,
+};
+use crate::kernels::spice_object::{body_code, relative_state};
+use crate::nasa_horizons::VectorTable;
+use crate::timebase::{date_time_to_et, Timebase};
+use chrono::{DateTime, Utc};
+use glam::f64::DVec3;
+use sgp4::Elements;
+use std::fmt::{Display, Formatter};
+
+/// Something that knows where an object is
+pub enum EphemerisSource {
+    /// the keplerian planets and the moon model satwatch draws
+    Analytic(Planet),
+    /// any NAIF id the loaded SPKs cover
+    Spice(i32),
+    /// a TLE, only relative to the earth. SGP4 gives TEME, which is turned into J2000.
+    Sgp4(Elements),
+    /// a vector table fetched from Horizons, relative to the center it was requested for
+    Horizons(VectorTable),
+}
+
+impl EphemerisSource {
+    /// A NAIF id or a name spice knows
+    pub fn spice(name_or_id: &str) -> Result<Self, String> {
+        let name_or_id = name_or_id.trim();
+        match name_or_id.parse::<i32>() {
+            Ok(id) => Ok(Self::Spice(id)),
+            Err(_) => body_code(name_or_id)
+                .map(Self::Spice)
+                .ok_or_else(|| format!("Spice does not know {}", name_or_id)),
+        }
+    }
+
+    /// Inertial state relative to the center in km and km/s
+    pub fn state(
+        &self,
+        center: Planet,
+        timebase: &Timebase,
+    ) -> Result<PlanetaryStateVector, String> {
+        let (position, velocity) = match self {
+            EphemerisSource::Analytic(planet) => {
+                let planet = planet.pos_icrf(timebase).as_unit(CoordinateUnit::KiloMeter);
+                let center = center.pos_icrf(timebase).as_unit(CoordinateUnit::KiloMeter);
+                (
+                    planet.position - center.position,
+                    planet.velocity - center.velocity,
+                )
+            }
+            // the barycenter would be off by up to thousands of km for the outer planets
+            EphemerisSource::Spice(id) => relative_state(*id, center.naif_id(), timebase.now())
+                .map_err(|e| {
+                    format!(
+                        "{}, the loaded SPKs may only have the barycenter {} of {}",
+                        e,
+                        center.naif_barycenter_id(),
+                        center
+                    )
+                })?,
+            EphemerisSource::Sgp4(elements) => {
+                if center != Planet::Earth {
+                    return Err("TLEs are only relative to the earth".to_string());
+                }
+                let epoch = date_time_to_et(DateTime::<Utc>::from_naive_utc_and_offset(
+                    elements.datetime,
+                    Utc,
+                ));
+                let constants =
+                    sgp4::Constants::from_elements(elements).map_err(|e| format!("{:?}", e))?;
+                let prediction = constants
+                    .propagate(timebase.minutes_since(epoch))
+                    .map_err(|e| format!("{:?}", e))?;
+                // SGP4 works in TEME, the other sources in J2000
+                (
+                    teme_to_icrf(DVec3::from(prediction.position), timebase.now()),
+                    teme_to_icrf(DVec3::from(prediction.velocity), timebase.now()),
+                )
+            }
+            EphemerisSource::Horizons(table) => {
+                if center != table.center {
+                    return Err(format!(
+                        "The Horizons table is relative to {}",
+                        table.center
+                    ));
+                }
+                let state = table
+                    .state_at(timebase.now())
+                    .ok_or_else(|| "Outside of the Horizons table".to_string())?
+                    .as_unit(CoordinateUnit::KiloMeter);
+                (state.position, state.velocity)
+            }
+        };
+        Ok(PlanetaryStateVector {
+            planet: center,
+            reference_frame: PlanetaryReferenceFrame::Inertial,
+            unit: CoordinateUnit::KiloMeter,
+            position,
+            velocity,
+        })
+    }
+}
+
+impl Display for EphemerisSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EphemerisSource::Analytic(planet) => write!(f, "Analytic {}", planet),
+            EphemerisSource::Spice(id) => write!(f, "SPICE {}", id),
+            EphemerisSource::Sgp4(elements) => write!(f, "SGP4 {}", elements.norad_id),
+            EphemerisSource::Horizons(table) => write!(f, "Horizons {}", table.target),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod ephemeris_comparison;
+pub mod ephemeris_source;
+
+pub use ephemeris_comparison::*;
+pub use ephemeris_source::*;
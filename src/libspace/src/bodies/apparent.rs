@@ -0,0 +1,249 @@
+// How a body looks from an observer: phase, illumination, size, elongation and visual magnitude.
+// Planet magnitudes follow Mallama and Hilton, "Computing apparent planetary magnitudes for
+// The Astronomical Almanac" (2018), without the small corrections for the rotation of Mars
+// and for the time dependence of Neptune. Saturn includes its rings while they are the major term.
+
+use crate::bodies::Planet;
+use crate::coordinate::*;
+use crate::timebase::Timebase;
+
+/// Pole directions (right ascension, declination in degree) for the planets whose magnitude depends on the tilt
+const SATURN_POLE: (f64, f64) = (40.589, 83.537);
+const URANUS_POLE: (f64, f64) = (257.311, -15.175);
+
+/// Everything appearance() can be asked about
+pub const APPEARANCE_BODIES: [Planet; 10] = [
+    Planet::Sun,
+    Planet::Moon,
+    Planet::Mercury,
+    Planet::Venus,
+    Planet::Earth,
+    Planet::Mars,
+    Planet::Jupiter,
+    Planet::Saturn,
+    Planet::Uranus,
+    Planet::Neptune,
+];
+
+#[derive(Copy, Clone, Debug)]
+pub struct Appearance {
+    /// angle between sun and observer as seen from the body, degree
+    pub phase_angle: f64,
+    /// 0 to 1
+    pub illuminated_fraction: f64,
+    /// degree
+    pub angular_diameter: f64,
+    /// angle between sun and body as seen from the observer, degree
+    pub elongation: f64,
+    /// au
+    pub distance: f64,
+    /// au
+    pub sun_distance: f64,
+    /// visual magnitude, none where there is no model or the observer sits inside the body
+    pub magnitude: Option<f64>,
+}
+
+fn pole_direction((ra, dec): (f64, f64)) -> DVec3 {
+    let (ra, dec) = (ra.to_radians(), dec.to_radians());
+    DVec3::new(dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin())
+}
+
+/// Absolute value of the latitude of the observer above the equator of the body, degree
+fn sub_observer_latitude(pole: (f64, f64), body_to_observer: DVec3) -> f64 {
+    pole_direction(pole)
+        .dot(body_to_observer.normalize())
+        .clamp(-1.0, 1.0)
+        .asin()
+        .to_degrees()
+        .abs()
+}
+
+/// Magnitude at unit distances, for the given phase angle in degree
+fn reduced_magnitude(planet: Planet, alpha: f64, body_to_observer: DVec3) -> Option<f64> {
+    let magnitude = match planet {
+        Planet::Mercury => {
+            -0.613 + 6.3280e-02 * alpha - 1.6336e-03 * alpha.powi(2) + 3.3644e-05 * alpha.powi(3)
+                - 3.4265e-07 * alpha.powi(4)
+                + 1.6893e-09 * alpha.powi(5)
+                - 3.0334e-12 * alpha.powi(6)
+        }
+        Planet::Venus => {
+            if alpha < 163.7 {
+                -4.384 - 1.044e-03 * alpha + 3.687e-04 * alpha.powi(2) - 2.814e-06 * alpha.powi(3)
+                    + 8.938e-09 * alpha.powi(4)
+            } else {
+                236.05828 - 2.81914 * alpha + 8.39034e-03 * alpha.powi(2)
+            }
+        }
+        Planet::Earth => -3.99 - 1.060e-03 * alpha + 2.054e-04 * alpha.powi(2),
+        Planet::Mars => {
+            if alpha <= 50.0 {
+                -1.601 + 2.267e-02 * alpha - 1.302e-04 * alpha.powi(2)
+            } else {
+                -0.367 - 0.02573 * alpha + 3.445e-04 * alpha.powi(2)
+            }
+        }
+        Planet::Jupiter => {
+            if alpha <= 12.0 {
+                -9.395 - 3.7e-04 * alpha + 6.16e-04 * alpha.powi(2)
+            } else {
+                let x = alpha / 180.0;
+                -9.428
+                    - 2.5
+                        * (1.0 - 1.507 * x - 0.363 * x.powi(2) - 0.062 * x.powi(3)
+                            + 2.809 * x.powi(4)
+                            - 1.876 * x.powi(5))
+                        .log10()
+            }
+        }
+        Planet::Saturn => {
+            let ring_tilt = sub_observer_latitude(SATURN_POLE, body_to_observer);
+            if alpha <= 6.5 && ring_tilt <= 27.0 {
+                let sin_tilt = ring_tilt.to_radians().sin();
+                -8.914 - 1.825 * sin_tilt + 0.026 * alpha - 0.378 * sin_tilt * (-2.25 * alpha).exp()
+            } else {
+                // globe alone, the rings are edge on or we look from behind the sun
+                -8.94 + 2.446e-04 * alpha + 2.672e-04 * alpha.powi(2) - 1.505e-06 * alpha.powi(3)
+                    + 4.767e-09 * alpha.powi(4)
+            }
+        }
+        Planet::Uranus => {
+            let latitude = sub_observer_latitude(URANUS_POLE, body_to_observer);
+            -7.110 - 8.4e-04 * latitude + 6.587e-03 * alpha + 1.045e-04 * alpha.powi(2)
+        }
+        Planet::Neptune => -7.00 + 7.944e-03 * alpha + 9.617e-05 * alpha.powi(2),
+        // not Mallama and Hilton, but the usual approximation from the Explanatory Supplement
+        Planet::Moon => 0.21 + 0.026 * alpha + 4.0e-09 * alpha.powi(4),
+        Planet::Sun => return None,
+    };
+    if magnitude.is_finite() {
+        Some(magnitude)
+    } else {
+        None
+    }
+}
+
+/// Appearance of a planet (or the sun, or the moon) from an observer given in ICRF coordinates
+pub fn appearance(planet: Planet, observer: &IcrfStateVector, timebase: &Timebase) -> Appearance {
+    let body = planet
+        .pos_icrf(timebase)
+        .as_unit(CoordinateUnit::Au)
+        .position;
+    let observer = observer.as_unit(CoordinateUnit::Au).position;
+
+    let body_to_observer = observer - body;
+    let distance = body_to_observer.length();
+    let sun_distance = body.length();
+    let radius = CoordinateUnit::KiloMeter.to(CoordinateUnit::Au, &planet.body().radius_mean);
+    let inside = distance <= radius;
+
+    let phase_angle = if planet == Planet::Sun || inside {
+        0.0
+    } else {
+        (-body).angle_between(body_to_observer).to_degrees()
+    };
+    let elongation = if planet == Planet::Sun || inside || observer.length() == 0.0 {
+        0.0
+    } else {
+        (-observer).angle_between(-body_to_observer).to_degrees()
+    };
+    let angular_diameter = if inside {
+        180.0
+    } else {
+        2.0 * (radius / distance).asin().to_degrees()
+    };
+
+    let magnitude = if inside {
+        None
+    } else if planet == Planet::Sun {
+        Some(-26.74 + 5.0 * distance.log10())
+    } else {
+        reduced_magnitude(planet, phase_angle, body_to_observer)
+            .map(|m| m + 5.0 * (sun_distance * distance).log10())
+    };
+
+    Appearance {
+        phase_angle,
+        illuminated_fraction: (1.0 + phase_angle.to_radians().cos()) / 2.0,
+        angular_diameter,
+        elongation,
+        distance,
+        sun_distance,
+        magnitude,
+    }
+}
+
+/// Appearance from the center of the earth
+pub fn appearance_from_earth(planet: Planet, timebase: &Timebase) -> Appearance {
+    appearance(planet, &Planet::Earth.pos_icrf(timebase), timebase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timebase::{jd_to_et, julian_date};
+
+    fn at(year: i32, month: u32, day: f64) -> Timebase {
+        let mut timebase = Timebase::new();
+        timebase.set_now(jd_to_et(julian_date(year, month, day)));
+        timebase
+    }
+
+    /// Magnitudes as The Astronomical Almanac and its sky calendars give them, to their 0.1
+    #[test]
+    fn almanac_magnitudes() {
+        for (planet, (year, month, day), expected) in [
+            // opposition, the closest since 1963
+            (Planet::Jupiter, (2022, 9, 26.0), -2.9),
+            // greatest brilliancy
+            (Planet::Venus, (2023, 7, 7.0), -4.7),
+            // greatest eastern elongation
+            (Planet::Venus, (2023, 6, 4.0), -4.4),
+            (Planet::Mars, (2020, 10, 13.0), -2.6),
+            (Planet::Mars, (2025, 1, 16.0), -1.4),
+            // the rings are narrowing, opposition
+            (Planet::Saturn, (2023, 8, 27.0), 0.4),
+            // greatest western elongation
+            (Planet::Mercury, (2024, 1, 12.0), -0.2),
+        ] {
+            let appearance = appearance_from_earth(planet, &at(year, month, day));
+            let magnitude = appearance.magnitude.unwrap();
+            assert!(
+                (magnitude - expected).abs() < 0.11,
+                "{} on {}-{}-{}: {}",
+                planet,
+                year,
+                month,
+                day,
+                magnitude
+            );
+        }
+    }
+
+    #[test]
+    fn greatest_elongations() {
+        let venus = appearance_from_earth(Planet::Venus, &at(2023, 6, 4.0));
+        assert!((venus.elongation - 45.4).abs() < 0.2, "{:?}", venus);
+        // half lit at greatest elongation
+        assert!(
+            (venus.illuminated_fraction - 0.5).abs() < 0.02,
+            "{:?}",
+            venus
+        );
+        let mercury = appearance_from_earth(Planet::Mercury, &at(2024, 1, 12.0));
+        assert!((mercury.elongation - 23.5).abs() < 0.2, "{:?}", mercury);
+    }
+
+    #[test]
+    fn moon_phases() {
+        // full moon 2024-01-25 17:54 UT, almost 5 degree off the ecliptic
+        let full = appearance_from_earth(Planet::Moon, &at(2024, 1, 25.746));
+        assert!(full.phase_angle < 6.0, "{:?}", full);
+        assert!(full.illuminated_fraction > 0.99, "{:?}", full);
+        assert!((full.magnitude.unwrap() + 12.6).abs() < 0.2, "{:?}", full);
+        // new moon 2024-01-11 11:57 UT
+        let new = appearance_from_earth(Planet::Moon, &at(2024, 1, 11.498));
+        assert!(new.phase_angle > 174.0, "{:?}", new);
+        assert!(new.illuminated_fraction < 0.01, "{:?}", new);
+    }
+}
@@ -3,6 +3,10 @@ pub struct Body {
     pub radius_equatorial: f64,
     pub radius_polar: f64,
     pub mass: f64,
+    /// standard gravitational parameter, km^3/s^2
+    pub gm: f64,
+    /// second zonal harmonic of the gravity field, referred to radius_equatorial
+    pub j2: f64,
     pub angular_speed_per_second: f64,
     pub sidereal_period: f64,
 }
@@ -0,0 +1,733 @@
+// Solar and lunar eclipses from the shadow cones of the moon and the earth.
+// The sun comes from the approximate elements, the moon from the truncated lunar theory, so contact
+// times are good to a minute or two. Contacts are global (Besselian style, on the plane through
+// the center of the earth), the earth is a sphere, and the ground path uses a fixed delta T.
+
+use crate::bodies::moon::moon_geocentric_icrf_since_j2000;
+use crate::bodies::{earth_rotation_angle, Planet};
+use crate::coordinate::*;
+use crate::timebase::Timebase;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::*;
+use std::sync::Arc;
+
+/// km
+const SUN_RADIUS: f64 = 696000.0;
+/// Danjon's enlargement of the earth's shadow by the atmosphere
+const ATMOSPHERE_ENLARGEMENT: f64 = 1.0 + 1.0 / 85.0;
+/// TT - UT1 in seconds, good for the 2020s
+const DELTA_T: f64 = 69.2;
+/// days
+const SEARCH_STEP: f64 = 1.0;
+/// days, eclipses are over well within this from new or full moon
+const SEARCH_WINDOW: f64 = 0.3;
+/// days, a tenth of a second
+const CONTACT_TOLERANCE: f64 = 1.0 / 864000.0;
+/// days, one minute
+const PATH_STEP: f64 = 1.0 / 1440.0;
+const GREATEST_STEPS: usize = 100;
+/// days searched by the background search between looks at the cancel flag
+const SEARCH_CHUNK: f64 = 365.25;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EclipseKind {
+    Solar,
+    Lunar,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EclipseType {
+    Total,
+    Annular,
+    /// annular at the ends of the path, total in the middle
+    Hybrid,
+    Partial,
+    Penumbral,
+}
+
+/// Contact times in days since J2000 (TT). For solar eclipses these are global:
+/// penumbra (P) and umbra (U) touching the earth. For lunar ones the moon touching the shadows of the earth.
+#[derive(Copy, Clone, Debug)]
+pub struct EclipseContacts {
+    pub p1: f64,
+    pub u1: Option<f64>,
+    pub u2: Option<f64>,
+    pub u3: Option<f64>,
+    pub u4: Option<f64>,
+    pub p4: f64,
+}
+
+/// A point on the center line of a solar eclipse
+#[derive(Copy, Clone, Debug)]
+pub struct GroundPoint {
+    /// days since J2000 (TT)
+    pub time: f64,
+    /// geocentric, degree
+    pub latitude: f64,
+    /// degree, east positive
+    pub longitude: f64,
+    /// width of the umbra (or antumbra) across the shadow axis, km
+    pub width: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Eclipse {
+    pub kind: EclipseKind,
+    pub eclipse_type: EclipseType,
+    /// days since J2000 (TT)
+    pub greatest: f64,
+    pub contacts: EclipseContacts,
+    /// closest distance of the shadow axis from the center of the earth (solar)
+    /// or of the moon from the axis of the earth shadow (lunar), in earth radii
+    pub gamma: f64,
+    /// solar: fraction of the sun diameter covered, or the ratio of apparent diameters for central eclipses.
+    /// lunar: umbral magnitude, penumbral magnitude for penumbral eclipses.
+    pub magnitude: f64,
+    /// center line, empty for lunar and non-central eclipses
+    pub path: Vec<GroundPoint>,
+}
+
+/// The shadow cone of a spherical body lit by the sun, everything in km
+#[derive(Copy, Clone, Debug)]
+pub struct Shadow {
+    /// position of the body casting the shadow
+    pub caster: DVec3,
+    /// unit vector pointing away from the sun
+    pub direction: DVec3,
+    pub caster_radius: f64,
+    sun_distance: f64,
+}
+
+impl Shadow {
+    pub fn new(sun: DVec3, caster: DVec3, caster_radius: f64) -> Self {
+        let away = caster - sun;
+        Self {
+            caster,
+            direction: away.normalize(),
+            caster_radius,
+            sun_distance: away.length(),
+        }
+    }
+
+    /// Radius of the umbra at distance x behind the caster, negative beyond the tip (antumbra)
+    pub fn umbra_radius(&self, x: f64) -> f64 {
+        self.caster_radius - x * (SUN_RADIUS - self.caster_radius) / self.sun_distance
+    }
+
+    pub fn penumbra_radius(&self, x: f64) -> f64 {
+        self.caster_radius + x * (SUN_RADIUS + self.caster_radius) / self.sun_distance
+    }
+
+    /// Distance along the axis behind the caster and distance from the axis of a point
+    pub fn axis_coordinates(&self, point: DVec3) -> (f64, f64) {
+        let relative = point - self.caster;
+        let x = relative.dot(self.direction);
+        (x, (relative - self.direction * x).length())
+    }
+}
+
+/// Geocentric sun and moon in km
+fn sun_moon_geocentric(time: f64) -> (DVec3, DVec3) {
+    let moon = moon_geocentric_icrf_since_j2000(time).position;
+    let earth = Planet::Earth
+        .pos_icrf_since_j2000(time)
+        .as_unit(CoordinateUnit::KiloMeter)
+        .position;
+    (-earth, moon)
+}
+
+fn moon_shadow(time: f64) -> Shadow {
+    let (sun, moon) = sun_moon_geocentric(time);
+    Shadow::new(sun, moon, Planet::Moon.body().radius_mean)
+}
+
+fn earth_shadow(time: f64) -> Shadow {
+    let (sun, _) = sun_moon_geocentric(time);
+    Shadow::new(
+        sun,
+        DVec3::ZERO,
+        Planet::Earth.body().radius_mean * ATMOSPHERE_ENLARGEMENT,
+    )
+}
+
+/// Times of new (true) and full (false) moon, from the sign changes of the sine of the
+/// ecliptic longitude difference
+fn find_syzygies(from: f64, to: f64) -> Vec<(f64, bool)> {
+    let longitude_difference = |t: f64| {
+        let (sun, moon) = sun_moon_geocentric(t);
+        let (sun, moon) = (icrf_to_ecliptic(sun), icrf_to_ecliptic(moon));
+        moon.y.atan2(moon.x) - sun.y.atan2(sun.x)
+    };
+    let mut results = Vec::new();
+    let mut a = from;
+    let mut fa = longitude_difference(a).sin();
+    while a < to {
+        let b = (a + SEARCH_STEP).min(to);
+        let fb = longitude_difference(b).sin();
+        if fa.signum() != fb.signum() && fa != 0.0 {
+            let time = bisect(|t| longitude_difference(t).sin(), a, b);
+            results.push((time, longitude_difference(time).cos() > 0.0));
+        }
+        a = b;
+        fa = fb;
+    }
+    results
+}
+
+/// Zero of f between a and b, f has to change its sign in there
+fn bisect<F: Fn(f64) -> f64>(f: F, a: f64, b: f64) -> f64 {
+    let (mut lo, mut hi) = (a, b);
+    let lo_sign = f(lo).signum();
+    while hi - lo > CONTACT_TOLERANCE {
+        let mid = 0.5 * (lo + hi);
+        if f(mid).signum() == lo_sign {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+/// Golden section search for the minimum of f
+fn minimize<F: Fn(f64) -> f64>(f: F, a: f64, b: f64) -> f64 {
+    let ratio = (5.0f64.sqrt() - 1.0) / 2.0;
+    let (mut a, mut b) = (a, b);
+    for _i in 0..GREATEST_STEPS {
+        let c = b - ratio * (b - a);
+        let d = a + ratio * (b - a);
+        if f(c) < f(d) {
+            b = d;
+        } else {
+            a = c;
+        }
+        if b - a < CONTACT_TOLERANCE {
+            break;
+        }
+    }
+    0.5 * (a + b)
+}
+
+/// Contacts where `outside` goes from positive to negative and back, around the greatest eclipse
+fn contact_pair<F: Fn(f64) -> f64>(outside: F, greatest: f64) -> Option<(f64, f64)> {
+    if outside(greatest) >= 0.0 {
+        return None;
+    }
+    let from = greatest - SEARCH_WINDOW;
+    let to = greatest + SEARCH_WINDOW;
+    if outside(from) < 0.0 || outside(to) < 0.0 {
+        return None;
+    }
+    Some((
+        bisect(&outside, from, greatest),
+        bisect(&outside, greatest, to),
+    ))
+}
+
+fn solar_eclipse(new_moon: f64) -> Option<Eclipse> {
+    let earth_radius = Planet::Earth.body().radius_mean;
+    let axis = |t: f64| moon_shadow(t).axis_coordinates(DVec3::ZERO);
+
+    let greatest = minimize(
+        |t| axis(t).1,
+        new_moon - SEARCH_WINDOW,
+        new_moon + SEARCH_WINDOW,
+    );
+    let shadow = moon_shadow(greatest);
+    let (x, distance) = axis(greatest);
+    let umbra = shadow.umbra_radius(x);
+    let penumbra = shadow.penumbra_radius(x);
+    if distance >= earth_radius + penumbra {
+        return None;
+    }
+
+    let penumbra_outside = |t: f64| {
+        let (x, distance) = axis(t);
+        distance - earth_radius - moon_shadow(t).penumbra_radius(x)
+    };
+    let umbra_outside = |t: f64| {
+        let (x, distance) = axis(t);
+        distance - earth_radius - moon_shadow(t).umbra_radius(x).abs()
+    };
+    let umbra_inside = |t: f64| {
+        let (x, distance) = axis(t);
+        distance - earth_radius + moon_shadow(t).umbra_radius(x).abs()
+    };
+    let (p1, p4) = contact_pair(penumbra_outside, greatest)?;
+    let u14 = contact_pair(umbra_outside, greatest);
+    let u23 = contact_pair(umbra_inside, greatest);
+
+    let path = match contact_pair(|t| axis(t).1 - earth_radius, greatest) {
+        Some((c1, c2)) => center_line(c1, c2),
+        None => Vec::new(),
+    };
+
+    let eclipse_type = if u14.is_none() {
+        EclipseType::Partial
+    } else if path.is_empty() {
+        // non-central, only a polar edge of the umbra touches
+        if umbra > 0.0 {
+            EclipseType::Total
+        } else {
+            EclipseType::Annular
+        }
+    } else {
+        let total = path.iter().filter(|p| p.width > 0.0).count();
+        if total == path.len() {
+            EclipseType::Total
+        } else if total == 0 {
+            EclipseType::Annular
+        } else {
+            EclipseType::Hybrid
+        }
+    };
+    let path = path
+        .into_iter()
+        .map(|p| GroundPoint {
+            width: p.width.abs(),
+            ..p
+        })
+        .collect();
+
+    let magnitude = if eclipse_type == EclipseType::Partial {
+        (earth_radius + penumbra - distance) / (penumbra - umbra)
+    } else {
+        // ratio of apparent diameters, on the ground below the axis
+        let x_surface = x
+            - (earth_radius * earth_radius - distance * distance)
+                .max(0.0)
+                .sqrt();
+        let umbra = shadow.umbra_radius(x_surface);
+        let penumbra = shadow.penumbra_radius(x_surface);
+        (penumbra + umbra) / (penumbra - umbra)
+    };
+
+    Some(Eclipse {
+        kind: EclipseKind::Solar,
+        eclipse_type,
+        greatest,
+        contacts: EclipseContacts {
+            p1,
+            u1: u14.map(|u| u.0),
+            u2: u23.map(|u| u.0),
+            u3: u23.map(|u| u.1),
+            u4: u14.map(|u| u.1),
+            p4,
+        },
+        gamma: distance / earth_radius,
+        magnitude,
+        path,
+    })
+}
+
+/// Where the shadow axis meets the earth, from c1 to c2. The width is negative for the antumbra.
+fn center_line(c1: f64, c2: f64) -> Vec<GroundPoint> {
+    let earth_radius = Planet::Earth.body().radius_mean;
+    let steps = ((c2 - c1) / PATH_STEP).ceil().max(1.0) as usize;
+    (0..=steps)
+        .filter_map(|i| {
+            let time = c1 + (c2 - c1) * i as f64 / steps as f64;
+            let shadow = moon_shadow(time);
+            let (x, distance) = shadow.axis_coordinates(DVec3::ZERO);
+            if distance > earth_radius {
+                return None;
+            }
+            // the near one of the two intersections with the sphere
+            let x_surface = x - (earth_radius * earth_radius - distance * distance).sqrt();
+            let surface = shadow.caster + shadow.direction * x_surface;
+            let rotation = earth_rotation_angle(time - DELTA_T / 86400.0);
+            let longitude = (surface.y.atan2(surface.x) - rotation).to_degrees();
+            Some(GroundPoint {
+                time,
+                latitude: (surface.z / earth_radius).asin().to_degrees(),
+                longitude: (longitude + 180.0).rem_euclid(360.0) - 180.0,
+                width: 2.0 * shadow.umbra_radius(x_surface),
+            })
+        })
+        .collect()
+}
+
+fn lunar_eclipse(full_moon: f64) -> Option<Eclipse> {
+    let earth_radius = Planet::Earth.body().radius_mean;
+    let moon_radius = Planet::Moon.body().radius_mean;
+    let axis = |t: f64| {
+        let (_, moon) = sun_moon_geocentric(t);
+        earth_shadow(t).axis_coordinates(moon)
+    };
+
+    let greatest = minimize(
+        |t| axis(t).1,
+        full_moon - SEARCH_WINDOW,
+        full_moon + SEARCH_WINDOW,
+    );
+    let shadow = earth_shadow(greatest);
+    let (x, distance) = axis(greatest);
+    let umbra = shadow.umbra_radius(x);
+    let penumbra = shadow.penumbra_radius(x);
+    if distance >= penumbra + moon_radius {
+        return None;
+    }
+
+    let (p1, p4) = contact_pair(
+        |t| {
+            let (x, distance) = axis(t);
+            distance - earth_shadow(t).penumbra_radius(x) - moon_radius
+        },
+        greatest,
+    )?;
+    let u14 = contact_pair(
+        |t| {
+            let (x, distance) = axis(t);
+            distance - earth_shadow(t).umbra_radius(x) - moon_radius
+        },
+        greatest,
+    );
+    let u23 = contact_pair(
+        |t| {
+            let (x, distance) = axis(t);
+            distance - earth_shadow(t).umbra_radius(x) + moon_radius
+        },
+        greatest,
+    );
+
+    let (eclipse_type, magnitude) = if u14.is_none() {
+        (
+            EclipseType::Penumbral,
+            (penumbra + moon_radius - distance) / (2.0 * moon_radius),
+        )
+    } else {
+        let eclipse_type = if u23.is_some() {
+            EclipseType::Total
+        } else {
+            EclipseType::Partial
+        };
+        (
+            eclipse_type,
+            (umbra + moon_radius - distance) / (2.0 * moon_radius),
+        )
+    };
+
+    Some(Eclipse {
+        kind: EclipseKind::Lunar,
+        eclipse_type,
+        greatest,
+        contacts: EclipseContacts {
+            p1,
+            u1: u14.map(|u| u.0),
+            u2: u23.map(|u| u.0),
+            u3: u23.map(|u| u.1),
+            u4: u14.map(|u| u.1),
+            p4,
+        },
+        gamma: distance / earth_radius,
+        magnitude,
+        path: Vec::new(),
+    })
+}
+
+/// Solar and lunar eclipses with their greatest eclipse between two times in days since J2000, sorted by time
+pub fn find_eclipses(from: f64, to: f64) -> Vec<Eclipse> {
+    // the greatest eclipse may be a bit before or after its syzygy
+    find_syzygies(from - SEARCH_WINDOW, to + SEARCH_WINDOW)
+        .into_iter()
+        .filter_map(|(time, new_moon)| {
+            if new_moon {
+                solar_eclipse(time)
+            } else {
+                lunar_eclipse(time)
+            }
+        })
+        .filter(|eclipse| eclipse.greatest >= from && eclipse.greatest < to)
+        .collect()
+}
+
+pub enum EclipseSearchStatus {
+    Invalid,
+    /// years searched so far, of how many
+    Progress {
+        done: usize,
+        total: usize,
+    },
+    Cancelled,
+    Complete(Vec<Eclipse>),
+}
+
+/// Searches on a separate thread, a few decades take a while
+pub struct EclipseSearch {
+    rx: Receiver<EclipseSearchStatus>,
+    cancel: Arc<AtomicBool>,
+    last_status: EclipseSearchStatus,
+}
+
+impl EclipseSearch {
+    /// Like find_eclipses, a year at a time
+    pub fn start(from: f64, to: f64) -> Self {
+        let (tx, rx) = sync_channel(10);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let total = ((to - from) / SEARCH_CHUNK).ceil().max(1.0) as usize;
+            let mut results = Vec::new();
+            for i in 0..total {
+                if worker_cancel.load(Ordering::Relaxed) {
+                    tx.send(EclipseSearchStatus::Cancelled).ok();
+                    return;
+                }
+                // if the ui is busy, it just misses a progress update
+                let _ = tx.try_send(EclipseSearchStatus::Progress { done: i, total });
+                let chunk_from = from + i as f64 * SEARCH_CHUNK;
+                let chunk_to = (chunk_from + SEARCH_CHUNK).min(to);
+                results.extend(find_eclipses(chunk_from, chunk_to));
+            }
+            tx.send(EclipseSearchStatus::Complete(results)).ok();
+        });
+
+        Self {
+            rx,
+            cancel,
+            last_status: EclipseSearchStatus::Invalid,
+        }
+    }
+
+    /// Stops before the next year, nothing is returned
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn update_status(&mut self) {
+        while let Ok(new_status) = self.rx.try_recv() {
+            self.last_status = new_status;
+        }
+    }
+
+    pub fn get_status(&self) -> &EclipseSearchStatus {
+        &self.last_status
+    }
+
+    /// Takes the eclipses out once the search is done, leaving the search invalid
+    pub fn take_result(&mut self) -> Option<Vec<Eclipse>> {
+        match std::mem::replace(&mut self.last_status, EclipseSearchStatus::Invalid) {
+            EclipseSearchStatus::Complete(results) => Some(results),
+            other => {
+                self.last_status = other;
+                None
+            }
+        }
+    }
+}
+
+/// Points outlining a shadow cone for drawing: the rim at the caster and at the given distance (km) behind it,
+/// zig-zagging along the cone so it can be drawn as one line. The umbra stops at its tip.
+pub fn shadow_cone(
+    caster: Planet,
+    penumbra: bool,
+    length: f64,
+    segments: usize,
+    timebase: &Timebase,
+) -> Vec<IcrfStateVector> {
+    let sun = Planet::Sun
+        .pos_icrf(timebase)
+        .as_unit(CoordinateUnit::KiloMeter);
+    let caster_state = caster.pos_icrf(timebase);
+    let radius = caster.body().radius_mean;
+    let shadow = Shadow::new(
+        sun.position,
+        caster_state.as_unit(CoordinateUnit::KiloMeter).position,
+        radius,
+    );
+    let (length, end_radius) = if penumbra {
+        (length, shadow.penumbra_radius(length))
+    } else {
+        let tip = radius * shadow.sun_distance / (SUN_RADIUS - radius);
+        let length = length.min(tip);
+        (length, shadow.umbra_radius(length).max(0.0))
+    };
+
+    let u = shadow.direction.any_orthonormal_vector();
+    let v = shadow.direction.cross(u);
+    let rim = |i: usize, r: f64, x: f64| {
+        let angle = 2.0 * std::f64::consts::PI * i as f64 / segments as f64;
+        shadow.caster + shadow.direction * x + (u * angle.cos() + v * angle.sin()) * r
+    };
+    let mut points = Vec::new();
+    for i in 0..=segments {
+        let near = rim(i, radius, 0.0);
+        let far = rim(i, end_radius, length);
+        if i % 2 == 0 {
+            points.push(near);
+            points.push(far);
+        } else {
+            points.push(far);
+            points.push(near);
+        }
+    }
+    points
+        .into_iter()
+        .map(|position| {
+            IcrfStateVector {
+                unit: CoordinateUnit::KiloMeter,
+                position,
+                velocity: DVec3::ZERO,
+            }
+            .as_unit(caster_state.unit)
+        })
+        .collect()
+}
+
+impl Display for EclipseKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EclipseKind::Solar => write!(f, "Solar"),
+            EclipseKind::Lunar => write!(f, "Lunar"),
+        }
+    }
+}
+
+impl Display for EclipseType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EclipseType::Total => write!(f, "Total"),
+            EclipseType::Annular => write!(f, "Annular"),
+            EclipseType::Hybrid => write!(f, "Hybrid"),
+            EclipseType::Partial => write!(f, "Partial"),
+            EclipseType::Penumbral => write!(f, "Penumbral"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// Days since J2000 (TT) of a UT time
+    fn days(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> f64 {
+        let j2000 = NaiveDate::from_ymd_opt(2000, 1, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let time = NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+        ((time - j2000).num_seconds() as f64 + DELTA_T) / 86400.0
+    }
+
+    /// The only eclipse of its kind between a week before and after
+    fn eclipse_near(kind: EclipseKind, time: f64) -> Eclipse {
+        let found = find_eclipses(time - 7.0, time + 7.0)
+            .into_iter()
+            .filter(|e| e.kind == kind)
+            .collect::<Vec<Eclipse>>();
+        assert_eq!(found.len(), 1, "{:?}", found);
+        found.into_iter().next().unwrap()
+    }
+
+    fn assert_minutes(time: f64, expected: f64, minutes: f64) {
+        let off = (time - expected) * 1440.0;
+        assert!(off.abs() < minutes, "{} minutes off", off);
+    }
+
+    // greatest eclipses, gammas and magnitudes from the NASA eclipse pages by Espenak
+
+    #[test]
+    fn total_solar_eclipse_2024_04_08() {
+        let eclipse = eclipse_near(EclipseKind::Solar, days(2024, 4, 8, 18, 17));
+        assert_eq!(eclipse.eclipse_type, EclipseType::Total);
+        assert_minutes(eclipse.greatest, days(2024, 4, 8, 18, 17), 3.0);
+        assert!((eclipse.gamma - 0.3431).abs() < 0.01, "{}", eclipse.gamma);
+        assert!((eclipse.magnitude - 1.0566).abs() < 0.005);
+        let contacts = eclipse.contacts;
+        assert!(contacts.p1 < contacts.u1.unwrap() && contacts.u1 < contacts.u2);
+        assert!(contacts.u3 < contacts.u4 && contacts.u4.unwrap() < contacts.p4);
+        // the path crosses Mexico, the USA and Canada
+        assert!(!eclipse.path.is_empty());
+        let greatest = eclipse
+            .path
+            .iter()
+            .min_by(|a, b| {
+                (a.time - eclipse.greatest)
+                    .abs()
+                    .total_cmp(&(b.time - eclipse.greatest).abs())
+            })
+            .unwrap();
+        assert!(
+            (greatest.latitude - 25.3).abs() < 1.0,
+            "{}",
+            greatest.latitude
+        );
+        assert!(
+            (greatest.longitude + 104.1).abs() < 1.0,
+            "{}",
+            greatest.longitude
+        );
+    }
+
+    #[test]
+    fn annular_solar_eclipse_2023_10_14() {
+        let eclipse = eclipse_near(EclipseKind::Solar, days(2023, 10, 14, 17, 59));
+        assert_eq!(eclipse.eclipse_type, EclipseType::Annular);
+        assert_minutes(eclipse.greatest, days(2023, 10, 14, 17, 59), 3.0);
+    }
+
+    #[test]
+    fn total_lunar_eclipse_2022_11_08() {
+        let eclipse = eclipse_near(EclipseKind::Lunar, days(2022, 11, 8, 10, 59));
+        assert_eq!(eclipse.eclipse_type, EclipseType::Total);
+        assert_minutes(eclipse.greatest, days(2022, 11, 8, 10, 59), 3.0);
+        assert!((eclipse.gamma - 0.2570).abs() < 0.01, "{}", eclipse.gamma);
+        assert!(
+            (eclipse.magnitude - 1.359).abs() < 0.01,
+            "{}",
+            eclipse.magnitude
+        );
+        // totality lasted 85 minutes
+        let totality = (eclipse.contacts.u3.unwrap() - eclipse.contacts.u2.unwrap()) * 1440.0;
+        assert!((totality - 85.0).abs() < 5.0, "{}", totality);
+    }
+
+    fn wait(search: &mut EclipseSearch) -> Option<Vec<Eclipse>> {
+        for _i in 0..6000 {
+            search.update_status();
+            if let EclipseSearchStatus::Cancelled = search.get_status() {
+                return None;
+            }
+            if let Some(results) = search.take_result() {
+                return Some(results);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("search did not finish");
+    }
+
+    #[test]
+    fn background_search_finds_the_same() {
+        // the 2024 season is split across two chunks
+        let (from, to) = (days(2023, 4, 8, 18, 17), days(2025, 1, 1, 0, 0));
+        let mut search = EclipseSearch::start(from, to);
+        let background = wait(&mut search).unwrap();
+        let direct = find_eclipses(from, to);
+        assert_eq!(background.len(), direct.len());
+        for (a, b) in background.iter().zip(direct.iter()) {
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.greatest, b.greatest);
+        }
+        assert!(background
+            .iter()
+            .any(|e| e.eclipse_type == EclipseType::Total && e.kind == EclipseKind::Solar));
+    }
+
+    #[test]
+    fn cancelled_search_returns_nothing() {
+        let mut search = EclipseSearch::start(0.0, 36525.0);
+        search.cancel();
+        assert!(wait(&mut search).is_none());
+    }
+
+    #[test]
+    fn no_eclipses_between_the_seasons() {
+        // the seasons of 2024 were in March/April and September/October
+        assert!(find_eclipses(days(2024, 5, 1, 0, 0), days(2024, 8, 15, 0, 0)).is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+// Kepler's equation, solved by Newton's method. Shared by everything that starts from a mean anomaly.
+
+use std::f64::consts::PI;
+
+const KEPLER_TOLERANCE: f64 = 1e-12;
+const KEPLER_MAX_STEPS: usize = 64;
+
+/// Eccentric anomaly for the mean anomaly of an ellipse, both in rad. Solves M = E - e sin E.
+pub fn eccentric_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let e = eccentricity;
+    let m = mean_anomaly.rem_euclid(2.0 * PI);
+    // starting at pi converges for all eccentricities, starting at M is faster for round orbits
+    let mut ea = if e > 0.8 { PI } else { m };
+    for _i in 0..KEPLER_MAX_STEPS {
+        let delta = (ea - e * ea.sin() - m) / (1.0 - e * ea.cos());
+        ea -= delta;
+        if delta.abs() <= KEPLER_TOLERANCE {
+            // same revolution as the mean anomaly given
+            return ea + (mean_anomaly - m);
+        }
+    }
+    log::warn!(
+        "Kepler solver did not converge for M = {}, e = {}, ended with E = {}",
+        mean_anomaly,
+        e,
+        ea
+    );
+    ea + (mean_anomaly - m)
+}
+
+/// Hyperbolic anomaly for the mean anomaly of a hyperbola. Solves M = e sinh H - H.
+pub fn hyperbolic_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let e = eccentricity;
+    let m = mean_anomaly;
+    let mut ha = (m / e).asinh();
+    for _i in 0..KEPLER_MAX_STEPS {
+        let delta = (e * ha.sinh() - ha - m) / (e * ha.cosh() - 1.0);
+        ha -= delta;
+        if delta.abs() <= KEPLER_TOLERANCE * ha.abs().max(1.0) {
+            return ha;
+        }
+    }
+    log::warn!(
+        "Kepler solver did not converge for M = {}, e = {}, ended with H = {}",
+        mean_anomaly,
+        e,
+        ha
+    );
+    ha
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ellipse_solution_satisfies_equation() {
+        for e in [0.0, 0.1, 0.5, 0.8, 0.95, 0.999] {
+            for i in -20..=20 {
+                let m = i as f64 * 0.4;
+                let ea = eccentric_anomaly(m, e);
+                assert!(
+                    (ea - e * ea.sin() - m).abs() < 1e-10,
+                    "M = {}, e = {}, E = {}",
+                    m,
+                    e,
+                    ea
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ellipse_known_value() {
+        // Meeus, Astronomical Algorithms, example 30.a
+        let ea = eccentric_anomaly(5f64.to_radians(), 0.1);
+        assert!((ea.to_degrees() - 5.554589).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hyperbola_solution_satisfies_equation() {
+        for e in [1.001, 1.2, 2.0, 10.0] {
+            for m in [-50.0, -3.0, -0.1, 0.0, 0.1, 3.0, 50.0] {
+                let ha = hyperbolic_anomaly(m, e);
+                assert!(
+                    (e * ha.sinh() - ha - m).abs() < 1e-9 * m.abs().max(1.0),
+                    "M = {}, e = {}, H = {}",
+                    m,
+                    e,
+                    ha
+                );
+            }
+        }
+    }
+}
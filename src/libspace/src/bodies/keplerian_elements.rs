@@ -1,6 +1,7 @@
 // straight forwawrd implementation of https://ssd.jpl.nasa.gov/planets/approx_pos.html
 // Archive version: https://web.archive.org/web/20211128162928/https://ssd.jpl.nasa.gov/planets/approx_pos.html
 
+use crate::bodies::kepler_equation::eccentric_anomaly;
 use crate::coordinate::*;
 use crate::timebase::Timebase;
 
@@ -39,8 +40,8 @@ pub struct KeplerianElements {
     pub f: f64,
 }
 
-const KEPLER_TOLERANCE: f64 = 1e-6;
-const KEPLER_MAX_STEPS: usize = 1024;
+/// days
+const VELOCITY_STEP: f64 = 0.01;
 
 impl KeplerianElements {
     pub fn position_ecliptic_since_j2000(&self, time: f64) -> [f64; 3] {
@@ -62,7 +63,7 @@ impl KeplerianElements {
         let mean_anomaly = ((mean_anomaly_no_modulo + std::f64::consts::PI)
             % (2.0 * std::f64::consts::PI))
             - std::f64::consts::PI;
-        let eccentric_anomaly = self.solve_keplers_equation(mean_anomaly);
+        let eccentric_anomaly = eccentric_anomaly(mean_anomaly, eccentricity);
 
         let x_hel = semi_mayor * (eccentric_anomaly.cos() - eccentricity);
         let y_hel =
@@ -91,18 +92,14 @@ impl KeplerianElements {
     }
 
     pub fn position_icrf_since_j2000(&self, time: f64) -> IcrfStateVector {
-        let epsilon = 23.43928f64.to_radians();
-        let cos_e = epsilon.cos();
-        let sin_e = epsilon.sin();
-
-        let [x_ecl, y_ecl, z_ecl] = self.position_ecliptic_since_j2000(time);
-        let x_eq = x_ecl;
-        let y_eq = cos_e * y_ecl - sin_e * z_ecl;
-        let z_eq = sin_e * y_ecl + cos_e * z_ecl;
+        let position = ecliptic_to_icrf(DVec3::from(self.position_ecliptic_since_j2000(time)));
+        // the elements only give positions, so the velocity is a central difference
+        let before = DVec3::from(self.position_ecliptic_since_j2000(time - VELOCITY_STEP));
+        let after = DVec3::from(self.position_ecliptic_since_j2000(time + VELOCITY_STEP));
         IcrfStateVector {
             unit: CoordinateUnit::Au,
-            position: DVec3::new(x_eq, y_eq, z_eq),
-            velocity: DVec3::new(0.0, 0.0, 0.0),
+            position,
+            velocity: ecliptic_to_icrf(after - before) / (2.0 * VELOCITY_STEP * 86400.0),
         }
     }
 
@@ -110,20 +107,4 @@ impl KeplerianElements {
         let time = timebase.now_jd_j2000();
         self.position_icrf_since_j2000(time)
     }
-
-    fn solve_keplers_equation(&self, mean_anomaly: f64) -> f64 {
-        let mut eccentric_anomaly = mean_anomaly - self.eccentricity_0 * mean_anomaly.sin();
-        let mut delta_ea = 0.0;
-        for _i in 0..KEPLER_MAX_STEPS {
-            let delta_m =
-                mean_anomaly - (eccentric_anomaly - self.eccentricity_0 * eccentric_anomaly.sin());
-            delta_ea = delta_m / (1.0 - self.eccentricity_0 * eccentric_anomaly.cos());
-            eccentric_anomaly = eccentric_anomaly + delta_ea;
-            if delta_ea <= KEPLER_TOLERANCE {
-                return eccentric_anomaly;
-            }
-        }
-        log::warn!("Kepler Solver could find a solution for the eccentric anomaly. Ended with {}, which still has a delta-E of {}", eccentric_anomaly, delta_ea);
-        eccentric_anomaly
-    }
 }
@@ -0,0 +1,272 @@
+// Lagrange points of the circular restricted three body problem, placed into the frame that
+// co-rotates with the actual states of the two bodies. Good enough to see where the points are,
+// not to design a halo orbit around them.
+
+use crate::bodies::Planet;
+use crate::coordinate::*;
+use crate::timebase::Timebase;
+use std::fmt::{Display, Formatter};
+
+const COLLINEAR_TOLERANCE: f64 = 1e-14;
+const COLLINEAR_MAX_STEPS: usize = 64;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum LagrangePoint {
+    L1,
+    L2,
+    L3,
+    L4,
+    L5,
+}
+
+pub const ALL_LAGRANGE_POINTS: [LagrangePoint; 5] = [
+    LagrangePoint::L1,
+    LagrangePoint::L2,
+    LagrangePoint::L3,
+    LagrangePoint::L4,
+    LagrangePoint::L5,
+];
+
+/// Two bodies, the secondary orbiting the primary
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct LagrangeSystem {
+    pub primary: Planet,
+    pub secondary: Planet,
+}
+
+impl LagrangeSystem {
+    pub fn sun_planet(planet: Planet) -> Self {
+        Self {
+            primary: Planet::Sun,
+            secondary: planet,
+        }
+    }
+
+    pub fn earth_moon() -> Self {
+        Self {
+            primary: Planet::Earth,
+            secondary: Planet::Moon,
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::sun_planet(Planet::Mercury),
+            Self::sun_planet(Planet::Venus),
+            Self::sun_planet(Planet::Earth),
+            Self::earth_moon(),
+            Self::sun_planet(Planet::Mars),
+            Self::sun_planet(Planet::Jupiter),
+            Self::sun_planet(Planet::Saturn),
+            Self::sun_planet(Planet::Uranus),
+            Self::sun_planet(Planet::Neptune),
+        ]
+    }
+
+    /// m2 / (m1 + m2)
+    pub fn mass_ratio(&self) -> f64 {
+        let gm1 = self.primary.body().gm;
+        let mut gm2 = self.secondary.body().gm;
+        if self.around_barycenter() {
+            gm2 += Planet::Moon.body().gm;
+        }
+        gm2 / (gm1 + gm2)
+    }
+
+    /// The sun pulls the earth and the moon as one, which goes around it as their barycenter
+    fn around_barycenter(&self) -> bool {
+        self.primary == Planet::Sun && self.secondary == Planet::Earth
+    }
+
+    /// The secondary, or the earth-moon barycenter for the earth around the sun
+    fn secondary_icrf(&self, timebase: &Timebase) -> IcrfStateVector {
+        let secondary = self.secondary.pos_icrf(timebase);
+        if !self.around_barycenter() {
+            return secondary;
+        }
+        let moon = Planet::Moon.pos_icrf(timebase).as_unit(secondary.unit);
+        let moon_fraction =
+            Planet::Moon.body().gm / (Planet::Earth.body().gm + Planet::Moon.body().gm);
+        IcrfStateVector {
+            unit: secondary.unit,
+            position: secondary.position * (1.0 - moon_fraction) + moon.position * moon_fraction,
+            velocity: secondary.velocity * (1.0 - moon_fraction) + moon.velocity * moon_fraction,
+        }
+    }
+
+    pub fn points_icrf(&self, timebase: &Timebase) -> [IcrfStateVector; 5] {
+        lagrange_points(
+            &self.primary.pos_icrf(timebase),
+            &self.secondary_icrf(timebase),
+            self.mass_ratio(),
+        )
+    }
+
+    pub fn point_icrf(&self, point: LagrangePoint, timebase: &Timebase) -> IcrfStateVector {
+        self.points_icrf(timebase)[point.index()]
+    }
+}
+
+impl LagrangePoint {
+    pub fn index(&self) -> usize {
+        match self {
+            LagrangePoint::L1 => 0,
+            LagrangePoint::L2 => 1,
+            LagrangePoint::L3 => 2,
+            LagrangePoint::L4 => 3,
+            LagrangePoint::L5 => 4,
+        }
+    }
+}
+
+/// Position of a collinear point on the line through both bodies, in units of their distance
+/// and measured from the barycenter towards the secondary
+fn collinear_point(mass_ratio: f64, point: LagrangePoint) -> f64 {
+    let mu = mass_ratio;
+    let gamma = (mu / 3.0).cbrt();
+    let mut x = match point {
+        LagrangePoint::L1 => 1.0 - mu - gamma,
+        LagrangePoint::L2 => 1.0 - mu + gamma,
+        _ => -1.0 - 5.0 * mu / 12.0,
+    };
+
+    // newton on the x component of the effective acceleration in the rotating frame
+    for _i in 0..COLLINEAR_MAX_STEPS {
+        let d1 = x + mu;
+        let d2 = x - 1.0 + mu;
+        let f = x - (1.0 - mu) * d1 / d1.abs().powi(3) - mu * d2 / d2.abs().powi(3);
+        let df = 1.0 + 2.0 * (1.0 - mu) / d1.abs().powi(3) + 2.0 * mu / d2.abs().powi(3);
+        let delta = f / df;
+        x -= delta;
+        if delta.abs() <= COLLINEAR_TOLERANCE {
+            break;
+        }
+    }
+    x
+}
+
+/// All five points from the states of both bodies. The result has the unit of the primary state.
+pub fn lagrange_points(
+    primary: &IcrfStateVector,
+    secondary: &IcrfStateVector,
+    mass_ratio: f64,
+) -> [IcrfStateVector; 5] {
+    let unit = primary.unit;
+    let secondary = secondary.as_unit(unit);
+    let r = secondary.position - primary.position;
+    let v = secondary.velocity - primary.velocity;
+    let distance = r.length();
+
+    // rotating frame, x towards the secondary and z along the orbit normal
+    let x_dir = r / distance;
+    let h = r.cross(v);
+    let z_dir = h.normalize();
+    let y_dir = z_dir.cross(x_dir);
+    let omega = h / (distance * distance);
+
+    let barycenter = primary.position + r * mass_ratio;
+    let barycenter_velocity = primary.velocity + v * mass_ratio;
+
+    let in_plane = |x: f64, y: f64| {
+        // barycentric, in units of the distance
+        let offset = (x_dir * x + y_dir * y) * distance;
+        IcrfStateVector {
+            unit,
+            position: barycenter + offset,
+            velocity: barycenter_velocity + omega.cross(offset),
+        }
+    };
+
+    let triangle_x = 0.5 - mass_ratio;
+    let triangle_y = 3.0f64.sqrt() / 2.0;
+    [
+        in_plane(collinear_point(mass_ratio, LagrangePoint::L1), 0.0),
+        in_plane(collinear_point(mass_ratio, LagrangePoint::L2), 0.0),
+        in_plane(collinear_point(mass_ratio, LagrangePoint::L3), 0.0),
+        in_plane(triangle_x, triangle_y),
+        in_plane(triangle_x, -triangle_y),
+    ]
+}
+
+impl Display for LagrangePoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LagrangePoint::L1 => write!(f, "L1"),
+            LagrangePoint::L2 => write!(f, "L2"),
+            LagrangePoint::L3 => write!(f, "L3"),
+            LagrangePoint::L4 => write!(f, "L4"),
+            LagrangePoint::L5 => write!(f, "L5"),
+        }
+    }
+}
+
+impl Display for LagrangeSystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.primary, self.secondary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timebase::{jd_to_et, julian_date};
+
+    fn distance(a: &IcrfStateVector, b: &IcrfStateVector) -> f64 {
+        let b = b.as_unit(a.unit);
+        a.unit
+            .to(CoordinateUnit::KiloMeter, &(a.position - b.position))
+            .length()
+    }
+
+    #[test]
+    fn mass_ratios() {
+        // the usual 3.0404e-6 of the sun-earth system includes the moon
+        let sun_earth = LagrangeSystem::sun_planet(Planet::Earth).mass_ratio();
+        assert!((sun_earth - 3.0404e-6).abs() < 1e-9, "{}", sun_earth);
+        let earth_moon = LagrangeSystem::earth_moon().mass_ratio();
+        assert!((earth_moon - 0.012150).abs() < 1e-6, "{}", earth_moon);
+    }
+
+    #[test]
+    fn collinear_points() {
+        // distances from the secondary in units of the distance of the bodies, after Szebehely
+        for (mass_ratio, l1, l2, l3) in [
+            (3.0404e-6, 0.0100111, 0.0100782, 0.9999982),
+            (0.012150, 0.1509, 0.1678, 0.9929),
+        ] {
+            let secondary = 1.0 - mass_ratio;
+            let gamma1 = secondary - collinear_point(mass_ratio, LagrangePoint::L1);
+            let gamma2 = collinear_point(mass_ratio, LagrangePoint::L2) - secondary;
+            let gamma3 = -collinear_point(mass_ratio, LagrangePoint::L3) - mass_ratio;
+            let tolerance = l1 * 1e-3;
+            assert!((gamma1 - l1).abs() < tolerance, "{} {}", mass_ratio, gamma1);
+            assert!((gamma2 - l2).abs() < tolerance, "{} {}", mass_ratio, gamma2);
+            assert!((gamma3 - l3).abs() < 1e-3, "{} {}", mass_ratio, gamma3);
+        }
+    }
+
+    #[test]
+    fn sun_earth_points() {
+        let mut timebase = Timebase::new();
+        timebase.set_now(jd_to_et(julian_date(2024, 3, 20.0)));
+        let system = LagrangeSystem::sun_planet(Planet::Earth);
+        let points = system.points_icrf(&timebase);
+        let barycenter = system.secondary_icrf(&timebase);
+        let sun = Planet::Sun.pos_icrf(&timebase);
+        let earth = Planet::Earth.pos_icrf(&timebase);
+
+        // about 1.5 million km, a hundredth of the distance to the sun
+        let scale = distance(&barycenter, &sun) / 1.496e8;
+        let l1 = distance(&points[0], &barycenter);
+        let l2 = distance(&points[1], &barycenter);
+        assert!((l1 / scale - 1.4977e6).abs() < 2e3, "{}", l1);
+        assert!((l2 / scale - 1.5077e6).abs() < 2e3, "{}", l2);
+        // the barycenter is within the earth, some 4700 km from its center
+        assert!((distance(&barycenter, &earth) - 4670.0).abs() < 300.0);
+        // L4 and L5 are as far from the sun as the barycenter
+        for point in &points[3..] {
+            let ratio = distance(point, &sun) / distance(&barycenter, &sun);
+            assert!((ratio - 1.0).abs() < 1e-5, "{}", ratio);
+        }
+    }
+}
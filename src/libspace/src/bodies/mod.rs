@@ -1,6 +1,17 @@
+pub mod apparent;
 pub mod body;
+pub mod eclipse;
+pub mod kepler_equation;
 pub mod keplerian_elements;
+pub mod lagrange;
+pub mod moon;
 pub mod orbit;
+pub mod orbiter;
+pub mod osculating_elements;
+pub mod phenomena;
 pub mod planets;
+pub mod rotation_model;
+pub mod shape_model;
+pub mod two_body;
 
 pub use planets::*;
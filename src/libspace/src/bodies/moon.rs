@@ -0,0 +1,142 @@
+// Geocentric position of the moon, following Meeus, "Astronomical Algorithms", Chapter 47.
+// The periodic terms are truncated to the larger ones, which keeps the error in the order of 10 arc seconds.
+
+use crate::coordinate::*;
+
+/// Multiples of D, M, M' and F, then the longitude (1e-6 degree) and distance (1e-3 km) coefficients
+const LONGITUDE_DISTANCE_TERMS: [(f64, f64, f64, f64, f64, f64); 32] = [
+    (0.0, 0.0, 1.0, 0.0, 6288774.0, -20905355.0),
+    (2.0, 0.0, -1.0, 0.0, 1274027.0, -3699111.0),
+    (2.0, 0.0, 0.0, 0.0, 658314.0, -2955968.0),
+    (0.0, 0.0, 2.0, 0.0, 213618.0, -569925.0),
+    (0.0, 1.0, 0.0, 0.0, -185116.0, 48888.0),
+    (0.0, 0.0, 0.0, 2.0, -114332.0, -3149.0),
+    (2.0, 0.0, -2.0, 0.0, 58793.0, 246158.0),
+    (2.0, -1.0, -1.0, 0.0, 57066.0, -152138.0),
+    (2.0, 0.0, 1.0, 0.0, 53322.0, -170733.0),
+    (2.0, -1.0, 0.0, 0.0, 45758.0, -204586.0),
+    (0.0, 1.0, -1.0, 0.0, -40923.0, -129620.0),
+    (1.0, 0.0, 0.0, 0.0, -34720.0, 108743.0),
+    (0.0, 1.0, 1.0, 0.0, -30383.0, 104755.0),
+    (2.0, 0.0, 0.0, -2.0, 15327.0, 10321.0),
+    (0.0, 0.0, 1.0, 2.0, -12528.0, 0.0),
+    (0.0, 0.0, 1.0, -2.0, 10980.0, 79661.0),
+    (4.0, 0.0, -1.0, 0.0, 10675.0, -34782.0),
+    (0.0, 0.0, 3.0, 0.0, 10034.0, -23210.0),
+    (4.0, 0.0, -2.0, 0.0, 8548.0, -21636.0),
+    (2.0, 1.0, -1.0, 0.0, -7888.0, 24208.0),
+    (2.0, 1.0, 0.0, 0.0, -6766.0, 30824.0),
+    (1.0, 0.0, -1.0, 0.0, -5163.0, -8379.0),
+    (1.0, 1.0, 0.0, 0.0, 4987.0, -16675.0),
+    (2.0, -1.0, 1.0, 0.0, 4036.0, -12831.0),
+    (2.0, 0.0, 2.0, 0.0, 3994.0, -10445.0),
+    (4.0, 0.0, 0.0, 0.0, 3861.0, -11650.0),
+    (2.0, 0.0, -3.0, 0.0, 3665.0, 14403.0),
+    (0.0, 1.0, -2.0, 0.0, -2689.0, -7003.0),
+    (2.0, 0.0, -1.0, 2.0, -2602.0, 0.0),
+    (2.0, -1.0, -2.0, 0.0, 2390.0, 10056.0),
+    (1.0, 0.0, 1.0, 0.0, -2348.0, 6322.0),
+    (2.0, -2.0, 0.0, 0.0, 2236.0, -9884.0),
+];
+
+/// Multiples of D, M, M' and F, then the latitude coefficient (1e-6 degree)
+const LATITUDE_TERMS: [(f64, f64, f64, f64, f64); 30] = [
+    (0.0, 0.0, 0.0, 1.0, 5128122.0),
+    (0.0, 0.0, 1.0, 1.0, 280602.0),
+    (0.0, 0.0, 1.0, -1.0, 277693.0),
+    (2.0, 0.0, 0.0, -1.0, 173237.0),
+    (2.0, 0.0, -1.0, 1.0, 55413.0),
+    (2.0, 0.0, -1.0, -1.0, 46271.0),
+    (2.0, 0.0, 0.0, 1.0, 32573.0),
+    (0.0, 0.0, 2.0, 1.0, 17198.0),
+    (2.0, 0.0, 1.0, -1.0, 9266.0),
+    (0.0, 0.0, 2.0, -1.0, 8822.0),
+    (2.0, -1.0, 0.0, -1.0, 8216.0),
+    (2.0, 0.0, -2.0, -1.0, 4324.0),
+    (2.0, 0.0, 1.0, 1.0, 4200.0),
+    (2.0, 1.0, 0.0, -1.0, -3359.0),
+    (2.0, -1.0, -1.0, 1.0, 2463.0),
+    (2.0, -1.0, 0.0, 1.0, 2211.0),
+    (2.0, -1.0, -1.0, -1.0, 2065.0),
+    (0.0, 1.0, -1.0, -1.0, -1870.0),
+    (4.0, 0.0, -1.0, -1.0, 1828.0),
+    (0.0, 1.0, 0.0, 1.0, -1794.0),
+    (0.0, 0.0, 0.0, 3.0, -1749.0),
+    (0.0, 1.0, -1.0, 1.0, -1565.0),
+    (1.0, 0.0, 0.0, 1.0, -1491.0),
+    (0.0, 1.0, 1.0, 1.0, -1475.0),
+    (0.0, 1.0, 1.0, -1.0, -1410.0),
+    (0.0, 1.0, 0.0, -1.0, -1344.0),
+    (1.0, 0.0, 0.0, -1.0, -1335.0),
+    (0.0, 0.0, 3.0, 1.0, 1107.0),
+    (4.0, 0.0, 0.0, -1.0, 1021.0),
+    (4.0, 0.0, -1.0, 1.0, 833.0),
+];
+
+/// General precession in longitude, degree/century
+const PRECESSION_RATE: f64 = 1.396971;
+/// days
+const VELOCITY_STEP: f64 = 0.001;
+
+/// Geocentric position in km, referred to the J2000 ecliptic. Time is in days since J2000.
+pub fn moon_position_ecliptic_since_j2000(time: f64) -> DVec3 {
+    let t = time / 36525.0;
+
+    let mean_longitude = 218.3164477 + 481267.88123421 * t;
+    let elongation = (297.8501921 + 445267.1114034 * t).to_radians();
+    let sun_anomaly = (357.5291092 + 35999.0502909 * t).to_radians();
+    let moon_anomaly = (134.9633964 + 477198.8675055 * t).to_radians();
+    let latitude_argument = (93.2720950 + 483202.0175233 * t).to_radians();
+    // decreasing eccentricity of the earth's orbit
+    let e = 1.0 - 0.002516 * t;
+
+    let mut sum_l = 0.0;
+    let mut sum_r = 0.0;
+    for (d, m, mm, f, l, r) in LONGITUDE_DISTANCE_TERMS {
+        let arg = d * elongation + m * sun_anomaly + mm * moon_anomaly + f * latitude_argument;
+        let factor = e.powi(m.abs() as i32);
+        sum_l += l * factor * arg.sin();
+        sum_r += r * factor * arg.cos();
+    }
+
+    let mut sum_b = 0.0;
+    for (d, m, mm, f, b) in LATITUDE_TERMS {
+        let arg = d * elongation + m * sun_anomaly + mm * moon_anomaly + f * latitude_argument;
+        sum_b += b * e.powi(m.abs() as i32) * arg.sin();
+    }
+
+    // venus, jupiter and the flattening of the earth
+    let a1 = (119.75 + 131.849 * t).to_radians();
+    let a2 = (53.09 + 479264.290 * t).to_radians();
+    let a3 = (313.45 + 481266.484 * t).to_radians();
+    let l_rad = mean_longitude.to_radians();
+    sum_l += 3958.0 * a1.sin() + 1962.0 * (l_rad - latitude_argument).sin() + 318.0 * a2.sin();
+    sum_b += -2235.0 * l_rad.sin()
+        + 382.0 * a3.sin()
+        + 175.0 * (a1 - latitude_argument).sin()
+        + 175.0 * (a1 + latitude_argument).sin()
+        + 127.0 * (l_rad - moon_anomaly).sin()
+        - 115.0 * (l_rad + moon_anomaly).sin();
+
+    // the theory gives the mean equinox of date, so undo the precession since J2000
+    let longitude = (mean_longitude + sum_l / 1e6 - PRECESSION_RATE * t).to_radians();
+    let latitude = (sum_b / 1e6).to_radians();
+    let distance = 385000.56 + sum_r / 1000.0;
+
+    DVec3::new(
+        distance * latitude.cos() * longitude.cos(),
+        distance * latitude.cos() * longitude.sin(),
+        distance * latitude.sin(),
+    )
+}
+
+/// Geocentric state in km and km/s
+pub fn moon_geocentric_icrf_since_j2000(time: f64) -> IcrfStateVector {
+    let before = moon_position_ecliptic_since_j2000(time - VELOCITY_STEP);
+    let after = moon_position_ecliptic_since_j2000(time + VELOCITY_STEP);
+    IcrfStateVector {
+        unit: CoordinateUnit::KiloMeter,
+        position: ecliptic_to_icrf(moon_position_ecliptic_since_j2000(time)),
+        velocity: ecliptic_to_icrf(after - before) / (2.0 * VELOCITY_STEP * 86400.0),
+    }
+}
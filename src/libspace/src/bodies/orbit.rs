@@ -17,7 +17,7 @@ impl Orbit {
     }
 
     pub fn position_icrf_since_j2000(&self, time: f64) -> IcrfStateVector {
-        if time > 365.0 * 200.0 {
+        if time.abs() > 365.0 * 200.0 {
             self.elements_long.position_icrf_since_j2000(time)
         } else {
             self.elements_short.position_icrf_since_j2000(time)
@@ -0,0 +1,241 @@
+// Orbiters of any planet or the moon: two-body motion plus the secular drift of node, periapsis and
+// mean anomaly caused by J2 of the central body (Vallado, "Fundamentals of Astrodynamics and Applications", 9.6).
+// The osculating elements at epoch are taken as mean elements, which is fine for sketching orbits
+// but leaves out the short periodic terms.
+
+use crate::bodies::osculating_elements::OsculatingElements;
+use crate::bodies::Planet;
+use crate::coordinate::*;
+use crate::timebase::Timebase;
+use glam::f64::DQuat;
+
+/// degree of node or periapsis drift before a drawn orbit counts as changed
+const TRAJECTORY_DRIFT: f64 = 0.05;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Orbiter {
+    /// days since J2000 (TT)
+    pub epoch: f64,
+    /// elements at epoch, referred to the equator of the central body at epoch
+    pub elements: OsculatingElements,
+    pub use_j2: bool,
+}
+
+impl Orbiter {
+    /// Elements referred to the equator of the central body
+    pub fn from_equatorial_elements(
+        elements: OsculatingElements,
+        epoch: f64,
+    ) -> Result<Self, String> {
+        if elements.semi_mayor.is_infinite() {
+            return Err("Parabolic orbiters are not supported".to_string());
+        }
+        Ok(Self {
+            epoch,
+            elements,
+            use_j2: true,
+        })
+    }
+
+    /// Position (km) and velocity (km/s) relative to the central body, in ICRF axes
+    pub fn from_vectors(
+        central_body: Planet,
+        position: DVec3,
+        velocity: DVec3,
+        epoch: f64,
+    ) -> Result<Self, String> {
+        let to_equator = equator_to_icrf(central_body, epoch).inverse();
+        let elements = OsculatingElements::from_vectors(
+            central_body,
+            to_equator * position,
+            to_equator * velocity,
+        )?;
+        Self::from_equatorial_elements(elements, epoch)
+    }
+
+    /// Any state, the epoch is the time of the timebase
+    pub fn from_state(state: &PlanetaryStateVector, timebase: &Timebase) -> Result<Self, String> {
+        let inertial = state
+            .transform_reference(PlanetaryReferenceFrame::Inertial, timebase)
+            .as_unit(CoordinateUnit::KiloMeter);
+        Self::from_vectors(
+            state.planet,
+            inertial.position,
+            inertial.velocity,
+            timebase.now_jd_j2000(),
+        )
+    }
+
+    /// Elements referred to the ICRF, as OsculatingElements::from_state gives them
+    pub fn from_icrf_elements(elements: &OsculatingElements, epoch: f64) -> Result<Self, String> {
+        let state = elements.to_state()?;
+        Self::from_vectors(elements.central_body, state.position, state.velocity, epoch)
+    }
+
+    pub fn central_body(&self) -> Planet {
+        self.elements.central_body
+    }
+
+    /// Drift of node, argument of periapsis and mean anomaly, degree/s
+    pub fn rates(&self) -> (f64, f64, f64) {
+        let planet = self.central_body();
+        let body = planet.body();
+        let a = self.elements.semi_mayor;
+        let n = (body.gm / a.abs().powi(3)).sqrt().to_degrees();
+        if !self.use_j2 || self.elements.eccentricity >= 1.0 {
+            return (0.0, 0.0, n);
+        }
+
+        let e2 = self.elements.eccentricity * self.elements.eccentricity;
+        let cos_i = self.elements.inclination.to_radians().cos();
+        let factor =
+            n * body.j2 * (body.radius_equatorial / self.elements.semi_latus_rectum()).powi(2);
+        (
+            -1.5 * factor * cos_i,
+            0.75 * factor * (5.0 * cos_i * cos_i - 1.0),
+            n + 0.75 * factor * (1.0 - e2).sqrt() * (3.0 * cos_i * cos_i - 1.0),
+        )
+    }
+
+    /// Elements at a time in days since J2000, referred to the equator of the central body at epoch
+    pub fn elements_at(&self, time: f64) -> OsculatingElements {
+        let dt = (time - self.epoch) * 86400.0;
+        let (node_rate, periapsis_rate, mean_rate) = self.rates();
+        let mean_anomaly = self.elements.mean_anomaly() + mean_rate * dt;
+        let mean_anomaly = if self.elements.eccentricity < 1.0 {
+            mean_anomaly.rem_euclid(360.0)
+        } else {
+            mean_anomaly
+        };
+        OsculatingElements {
+            long_ascending: (self.elements.long_ascending + node_rate * dt).rem_euclid(360.0),
+            argument_periapsis: (self.elements.argument_periapsis + periapsis_rate * dt)
+                .rem_euclid(360.0),
+            ..self.elements
+        }
+        .with_mean_anomaly(mean_anomaly)
+    }
+
+    /// Inertial state relative to the central body in ICRF axes, km and km/s
+    pub fn state_at(&self, time: f64) -> Result<PlanetaryStateVector, String> {
+        let equatorial = self.elements_at(time).to_state()?;
+        let rotation = equator_to_icrf(self.central_body(), self.epoch);
+        Ok(PlanetaryStateVector {
+            position: rotation * equatorial.position,
+            velocity: rotation * equatorial.velocity,
+            ..equatorial
+        })
+    }
+
+    pub fn state(&self, timebase: &Timebase) -> Result<PlanetaryStateVector, String> {
+        self.state_at(timebase.now_jd_j2000())
+    }
+
+    /// Points along one revolution starting at time, or along the given span (days) for open orbits
+    pub fn trajectory(
+        &self,
+        time: f64,
+        points: usize,
+        open_span: f64,
+    ) -> Vec<PlanetaryStateVector> {
+        let span = match self.elements.period() {
+            Some(period) => period / 86400.0,
+            None => open_span,
+        };
+        (0..=points)
+            .filter_map(|i| self.state_at(time + span * i as f64 / points as f64).ok())
+            .collect()
+    }
+
+    /// Whether a trajectory made at since differs from one made at time.
+    /// Closed orbits only change by the drift of node and periapsis, open ones as the span moves on.
+    pub fn trajectory_changed(&self, since: f64, time: f64, points: usize, open_span: f64) -> bool {
+        let days = (time - since).abs();
+        match self.elements.period() {
+            Some(_) => {
+                let (node_rate, periapsis_rate, _) = self.rates();
+                node_rate.abs().max(periapsis_rate.abs()) * days * 86400.0 > TRAJECTORY_DRIFT
+            }
+            None => days > open_span / points as f64,
+        }
+    }
+}
+
+fn equator_to_icrf(planet: Planet, time: f64) -> DQuat {
+    planet.rotation_model().equator_to_icrf(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orbiter(eccentricity: f64, use_j2: bool) -> Orbiter {
+        let radius = Planet::Earth.body().radius_equatorial + 400.0;
+        let mut orbiter = Orbiter::from_equatorial_elements(
+            OsculatingElements {
+                central_body: Planet::Earth,
+                semi_mayor: radius / (1.0 - eccentricity),
+                eccentricity,
+                inclination: 51.6,
+                long_ascending: 10.0,
+                argument_periapsis: 20.0,
+                true_anomaly: 0.0,
+            },
+            9000.0,
+        )
+        .unwrap();
+        orbiter.use_j2 = use_j2;
+        orbiter
+    }
+
+    #[test]
+    fn two_body_trajectory_never_changes() {
+        let orbiter = orbiter(0.001, false);
+        assert!(!orbiter.trajectory_changed(9000.0, 19000.0, 128, 2.0));
+    }
+
+    #[test]
+    fn j2_trajectory_changes_with_the_drift() {
+        let orbiter = orbiter(0.001, true);
+        // the node of the ISS moves about 5 degree a day
+        assert!(!orbiter.trajectory_changed(9000.0, 9000.0 + 1.0 / 1440.0, 128, 2.0));
+        assert!(orbiter.trajectory_changed(9000.0, 9000.0 + 1.0 / 24.0, 128, 2.0));
+        assert!(orbiter.trajectory_changed(9000.0, 9000.0 - 1.0 / 24.0, 128, 2.0));
+    }
+
+    #[test]
+    fn open_trajectory_changes_with_the_span() {
+        let orbiter = orbiter(1.5, true);
+        assert!(!orbiter.trajectory_changed(9000.0, 9000.0 + 1.0 / 128.0, 128, 2.0));
+        assert!(orbiter.trajectory_changed(9000.0, 9000.0 + 1.0 / 32.0, 128, 2.0));
+    }
+
+    #[test]
+    fn j2_rates() {
+        let per_day = |rate: f64| rate * 86400.0;
+        // Vallado 9.6: the node of the ISS regresses about 5 degree a day, the perigee advances
+        let (node, periapsis, _) = orbiter(0.001, true).rates();
+        assert!((per_day(node) + 4.985).abs() < 0.005, "{}", per_day(node));
+        assert!(
+            (per_day(periapsis) - 3.728).abs() < 0.005,
+            "{}",
+            per_day(periapsis)
+        );
+
+        // sun synchronous at 800 km, the node follows the sun
+        let mut sun_synchronous = orbiter(0.0, true);
+        sun_synchronous.elements.semi_mayor = Planet::Earth.body().radius_equatorial + 800.0;
+        sun_synchronous.elements.inclination = 98.6;
+        let (node, _, _) = sun_synchronous.rates();
+        assert!((per_day(node) - 0.985).abs() < 0.005, "{}", per_day(node));
+
+        // the perigee stands still at the critical inclination
+        let mut critical = orbiter(0.001, true);
+        critical.elements.inclination = (1.0f64 / 5.0).sqrt().acos().to_degrees();
+        let (_, periapsis, _) = critical.rates();
+        assert!(per_day(periapsis).abs() < 1e-9, "{}", per_day(periapsis));
+
+        let (node, periapsis, _) = orbiter(0.001, false).rates();
+        assert_eq!((node, periapsis), (0.0, 0.0));
+    }
+}
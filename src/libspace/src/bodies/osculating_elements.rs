@@ -0,0 +1,440 @@
+// Conversion between state vectors and osculating elements around any of the bodies we know the GM of.
+// Classical elements follow Vallado, "Fundamentals of Astrodynamics and Applications", Algorithms 9 and 10,
+// equinoctial elements are the modified ones from Walker, Ireland and Owens (1985).
+// Elements are referred to the axes the state vector is given in, so for inertial states that is the ICRF.
+
+use crate::bodies::kepler_equation::{eccentric_anomaly, hyperbolic_anomaly};
+use crate::bodies::Planet;
+use crate::coordinate::*;
+use crate::timebase::Timebase;
+use glam::f64::DMat3;
+use std::f64::consts::PI;
+
+/// Below this, orbits count as circular or equatorial
+const SINGULAR_TOLERANCE: f64 = 1e-10;
+
+/// Classical osculating elements.
+/// For circular orbits the argument of periapsis is zero and the true anomaly is the argument of latitude,
+/// for equatorial ones the ascending node is zero and the argument of periapsis is its longitude.
+#[derive(Copy, Clone, Debug)]
+pub struct OsculatingElements {
+    pub central_body: Planet,
+    /// km, negative for hyperbolic and infinite for parabolic orbits
+    pub semi_mayor: f64,
+    pub eccentricity: f64,
+    /// degree
+    pub inclination: f64,
+    /// degree
+    pub long_ascending: f64,
+    /// degree
+    pub argument_periapsis: f64,
+    /// degree
+    pub true_anomaly: f64,
+}
+
+/// Modified equinoctial elements. These have no singularity for circular or equatorial orbits,
+/// only for exactly retrograde equatorial ones.
+#[derive(Copy, Clone, Debug)]
+pub struct EquinoctialElements {
+    pub central_body: Planet,
+    /// semi-latus rectum, km
+    pub p: f64,
+    /// e cos(argument of periapsis + ascending node)
+    pub f: f64,
+    /// e sin(argument of periapsis + ascending node)
+    pub g: f64,
+    /// tan(i/2) cos(ascending node)
+    pub h: f64,
+    /// tan(i/2) sin(ascending node)
+    pub k: f64,
+    /// true longitude, degree
+    pub true_longitude: f64,
+}
+
+fn inertial_km(state: &PlanetaryStateVector, timebase: &Timebase) -> (DVec3, DVec3) {
+    let inertial = state
+        .transform_reference(PlanetaryReferenceFrame::Inertial, timebase)
+        .as_unit(CoordinateUnit::KiloMeter);
+    (inertial.position, inertial.velocity)
+}
+
+fn inertial_state(central_body: Planet, position: DVec3, velocity: DVec3) -> PlanetaryStateVector {
+    PlanetaryStateVector {
+        planet: central_body,
+        reference_frame: PlanetaryReferenceFrame::Inertial,
+        unit: CoordinateUnit::KiloMeter,
+        position,
+        velocity,
+    }
+}
+
+/// Angle from a to b, measured counterclockwise around axis. Radians in [0, 2pi)
+fn angle_around(a: DVec3, b: DVec3, axis: DVec3) -> f64 {
+    axis.dot(a.cross(b)).atan2(a.dot(b)).rem_euclid(2.0 * PI)
+}
+
+impl OsculatingElements {
+    /// Elements from position (km) and velocity (km/s) relative to the central body
+    pub fn from_vectors(
+        central_body: Planet,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> Result<Self, String> {
+        let mu = central_body.body().gm;
+        let r = position.length();
+        let h = position.cross(velocity);
+        if r == 0.0 || h.length() == 0.0 {
+            return Err("Degenerate state vector, no orbital plane".to_string());
+        }
+        let h_unit = h.normalize();
+
+        let e_vec = ((velocity.length_squared() - mu / r) * position
+            - position.dot(velocity) * velocity)
+            / mu;
+        let eccentricity = e_vec.length();
+        let energy = velocity.length_squared() / 2.0 - mu / r;
+        let semi_mayor = if energy.abs() < SINGULAR_TOLERANCE * mu / r {
+            f64::INFINITY
+        } else {
+            -mu / (2.0 * energy)
+        };
+
+        let inclination = h.truncate().length().atan2(h.z);
+        let node = DVec3::Z.cross(h);
+        // reference directions that stay defined when the orbit is equatorial or circular
+        let node_dir = if node.length() > SINGULAR_TOLERANCE * h.length() {
+            node.normalize()
+        } else {
+            DVec3::X
+        };
+        let periapsis_dir = if eccentricity > SINGULAR_TOLERANCE {
+            e_vec.normalize()
+        } else {
+            node_dir
+        };
+
+        Ok(Self {
+            central_body,
+            semi_mayor,
+            eccentricity,
+            inclination: inclination.to_degrees(),
+            long_ascending: node_dir
+                .y
+                .atan2(node_dir.x)
+                .rem_euclid(2.0 * PI)
+                .to_degrees(),
+            argument_periapsis: angle_around(node_dir, periapsis_dir, h_unit).to_degrees(),
+            true_anomaly: angle_around(periapsis_dir, position, h_unit).to_degrees(),
+        })
+    }
+
+    pub fn from_state(state: &PlanetaryStateVector, timebase: &Timebase) -> Result<Self, String> {
+        let (position, velocity) = inertial_km(state, timebase);
+        Self::from_vectors(state.planet, position, velocity)
+    }
+
+    pub fn from_equinoctial(elements: &EquinoctialElements) -> Result<Self, String> {
+        let state = elements.to_state();
+        Self::from_vectors(elements.central_body, state.position, state.velocity)
+    }
+
+    /// Same orbit, at the given mean anomaly (degree)
+    pub fn with_mean_anomaly(&self, mean_anomaly: f64) -> Self {
+        Self {
+            true_anomaly: true_from_mean_anomaly(mean_anomaly.to_radians(), self.eccentricity)
+                .to_degrees(),
+            ..*self
+        }
+    }
+
+    /// Semi-latus rectum, km
+    pub fn semi_latus_rectum(&self) -> f64 {
+        if self.semi_mayor.is_infinite() {
+            // a parabola has no semi-mayor axis to get it from
+            f64::NAN
+        } else {
+            self.semi_mayor * (1.0 - self.eccentricity * self.eccentricity)
+        }
+    }
+
+    /// km
+    pub fn periapsis(&self) -> f64 {
+        self.semi_latus_rectum() / (1.0 + self.eccentricity)
+    }
+
+    /// km, none for open orbits
+    pub fn apoapsis(&self) -> Option<f64> {
+        if self.eccentricity < 1.0 {
+            Some(self.semi_mayor * (1.0 + self.eccentricity))
+        } else {
+            None
+        }
+    }
+
+    /// Orbital period in seconds, none for open orbits
+    pub fn period(&self) -> Option<f64> {
+        if self.eccentricity < 1.0 {
+            Some(2.0 * PI * (self.semi_mayor.powi(3) / self.central_body.body().gm).sqrt())
+        } else {
+            None
+        }
+    }
+
+    /// Eccentric (or hyperbolic, or parabolic) anomaly in degree
+    pub fn eccentric_anomaly(&self) -> f64 {
+        let nu = self.true_anomaly.to_radians();
+        let e = self.eccentricity;
+        let anomaly = if e < 1.0 {
+            2.0 * (((1.0 - e) / (1.0 + e)).sqrt() * (nu / 2.0).tan()).atan()
+        } else if e > 1.0 {
+            2.0 * (((e - 1.0) / (e + 1.0)).sqrt() * (nu / 2.0).tan()).atanh()
+        } else {
+            (nu / 2.0).tan()
+        };
+        anomaly.to_degrees()
+    }
+
+    /// degree. For open orbits this is not an angle, but the usual hyperbolic/Barker mean anomaly.
+    pub fn mean_anomaly(&self) -> f64 {
+        let anomaly = self.eccentric_anomaly().to_radians();
+        let e = self.eccentricity;
+        let mean = if e < 1.0 {
+            (anomaly - e * anomaly.sin()).rem_euclid(2.0 * PI)
+        } else if e > 1.0 {
+            e * anomaly.sinh() - anomaly
+        } else {
+            anomaly + anomaly.powi(3) / 3.0
+        };
+        mean.to_degrees()
+    }
+
+    /// Rotation from the perifocal frame into the frame the elements are referred to
+    pub fn perifocal_rotation(&self) -> DMat3 {
+        DMat3::from_rotation_z(self.long_ascending.to_radians())
+            * DMat3::from_rotation_x(self.inclination.to_radians())
+            * DMat3::from_rotation_z(self.argument_periapsis.to_radians())
+    }
+
+    /// Inertial state relative to the central body, in km and km/s.
+    /// Parabolic orbits have no semi-mayor axis to start from, use equinoctial elements for those.
+    pub fn to_state(&self) -> Result<PlanetaryStateVector, String> {
+        let p = self.semi_latus_rectum();
+        if !p.is_finite() || p <= 0.0 {
+            return Err(format!(
+                "Elements with a = {} km and e = {} do not describe an orbit",
+                self.semi_mayor, self.eccentricity
+            ));
+        }
+
+        let mu = self.central_body.body().gm;
+        let (sin_nu, cos_nu) = self.true_anomaly.to_radians().sin_cos();
+        let r = p / (1.0 + self.eccentricity * cos_nu);
+        let position = DVec3::new(r * cos_nu, r * sin_nu, 0.0);
+        let velocity = (mu / p).sqrt() * DVec3::new(-sin_nu, self.eccentricity + cos_nu, 0.0);
+
+        let rot = self.perifocal_rotation();
+        Ok(inertial_state(
+            self.central_body,
+            rot * position,
+            rot * velocity,
+        ))
+    }
+
+    pub fn to_equinoctial(&self) -> Result<EquinoctialElements, String> {
+        let state = self.to_state()?;
+        EquinoctialElements::from_vectors(self.central_body, state.position, state.velocity)
+    }
+}
+
+impl EquinoctialElements {
+    /// Elements from position (km) and velocity (km/s) relative to the central body
+    pub fn from_vectors(
+        central_body: Planet,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> Result<Self, String> {
+        let mu = central_body.body().gm;
+        let r = position.length();
+        let h_vec = position.cross(velocity);
+        if r == 0.0 || h_vec.length() == 0.0 {
+            return Err("Degenerate state vector, no orbital plane".to_string());
+        }
+        let w = h_vec.normalize();
+        if 1.0 + w.z < SINGULAR_TOLERANCE {
+            return Err(
+                "Equinoctial elements are singular for retrograde equatorial orbits".to_string(),
+            );
+        }
+
+        let h = -w.y / (1.0 + w.z);
+        let k = w.x / (1.0 + w.z);
+        let (f_dir, g_dir) = equinoctial_frame(h, k);
+        let e_vec = ((velocity.length_squared() - mu / r) * position
+            - position.dot(velocity) * velocity)
+            / mu;
+
+        Ok(Self {
+            central_body,
+            p: h_vec.length_squared() / mu,
+            f: e_vec.dot(f_dir),
+            g: e_vec.dot(g_dir),
+            h,
+            k,
+            true_longitude: position
+                .dot(g_dir)
+                .atan2(position.dot(f_dir))
+                .rem_euclid(2.0 * PI)
+                .to_degrees(),
+        })
+    }
+
+    pub fn from_state(state: &PlanetaryStateVector, timebase: &Timebase) -> Result<Self, String> {
+        let (position, velocity) = inertial_km(state, timebase);
+        Self::from_vectors(state.planet, position, velocity)
+    }
+
+    pub fn eccentricity(&self) -> f64 {
+        (self.f * self.f + self.g * self.g).sqrt()
+    }
+
+    /// Inertial state relative to the central body, in km and km/s
+    pub fn to_state(&self) -> PlanetaryStateVector {
+        let mu = self.central_body.body().gm;
+        let (f_dir, g_dir) = equinoctial_frame(self.h, self.k);
+        let (sin_l, cos_l) = self.true_longitude.to_radians().sin_cos();
+        let r = self.p / (1.0 + self.f * cos_l + self.g * sin_l);
+        let position = r * (cos_l * f_dir + sin_l * g_dir);
+        let velocity =
+            (mu / self.p).sqrt() * (-(sin_l + self.g) * f_dir + (cos_l + self.f) * g_dir);
+        inertial_state(self.central_body, position, velocity)
+    }
+}
+
+/// Unit vectors of the equinoctial frame, in the reference frame
+fn equinoctial_frame(h: f64, k: f64) -> (DVec3, DVec3) {
+    let s2 = 1.0 + h * h + k * k;
+    let f_dir = DVec3::new(1.0 - k * k + h * h, 2.0 * h * k, -2.0 * k) / s2;
+    let g_dir = DVec3::new(2.0 * h * k, 1.0 + k * k - h * h, 2.0 * h) / s2;
+    (f_dir, g_dir)
+}
+
+/// Solves Kepler's equation for the matching conic section and returns the true anomaly, all in rad
+pub fn true_from_mean_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let e = eccentricity;
+    if e < 1.0 {
+        let ea = eccentric_anomaly(mean_anomaly, e);
+        2.0 * (((1.0 + e) / (1.0 - e)).sqrt() * (ea / 2.0).tan()).atan()
+    } else if e > 1.0 {
+        let ha = hyperbolic_anomaly(mean_anomaly, e);
+        2.0 * (((e + 1.0) / (e - 1.0)).sqrt() * (ha / 2.0).tanh()).atan()
+    } else {
+        // Barker's equation has a closed form solution
+        let b = 1.5 * mean_anomaly;
+        let d = (b + (b * b + 1.0).sqrt()).cbrt();
+        2.0 * (d - 1.0 / d).atan()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vec_close(a: DVec3, b: DVec3, tolerance: f64) {
+        assert!((a - b).length() <= tolerance, "{} != {}", a, b);
+    }
+
+    fn states() -> Vec<(DVec3, DVec3)> {
+        vec![
+            // ISS like
+            (DVec3::new(6778.0, 0.0, 0.0), DVec3::new(0.0, 5.91, 4.97)),
+            // Molniya like, near apogee
+            (
+                DVec3::new(-20000.0, 30000.0, 25000.0),
+                DVec3::new(-0.9, -0.7, 1.1),
+            ),
+            // hyperbolic flyby
+            (
+                DVec3::new(7000.0, 1000.0, -500.0),
+                DVec3::new(1.0, 11.5, 2.0),
+            ),
+            // circular and equatorial
+            (
+                DVec3::new(42164.0, 0.0, 0.0),
+                DVec3::new(0.0, (3.986004418e5f64 / 42164.0).sqrt(), 0.0),
+            ),
+        ]
+    }
+
+    #[test]
+    fn classical_round_trip() {
+        for (position, velocity) in states() {
+            let elements =
+                OsculatingElements::from_vectors(Planet::Earth, position, velocity).unwrap();
+            let state = elements.to_state().unwrap();
+            assert_vec_close(state.position, position, 1e-6);
+            assert_vec_close(state.velocity, velocity, 1e-9);
+        }
+    }
+
+    #[test]
+    fn equinoctial_round_trip() {
+        for (position, velocity) in states() {
+            let elements =
+                EquinoctialElements::from_vectors(Planet::Earth, position, velocity).unwrap();
+            let state = elements.to_state();
+            assert_vec_close(state.position, position, 1e-6);
+            assert_vec_close(state.velocity, velocity, 1e-9);
+            let classical = OsculatingElements::from_equinoctial(&elements).unwrap();
+            assert!((classical.eccentricity - elements.eccentricity()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn known_elements() {
+        // Vallado, example 2-5
+        let elements = OsculatingElements::from_vectors(
+            Planet::Earth,
+            DVec3::new(6524.834, 6862.875, 6448.296),
+            DVec3::new(4.901327, 5.533756, -1.976341),
+        )
+        .unwrap();
+        assert!((elements.semi_mayor - 36127.343).abs() < 0.1);
+        assert!((elements.eccentricity - 0.832853).abs() < 1e-5);
+        assert!((elements.inclination - 87.870).abs() < 1e-3);
+        assert!((elements.long_ascending - 227.89).abs() < 1e-2);
+        assert!((elements.argument_periapsis - 53.38).abs() < 1e-2);
+        assert!((elements.true_anomaly - 92.335).abs() < 1e-3);
+    }
+
+    #[test]
+    fn mean_anomaly_round_trip() {
+        for e in [0.0f64, 0.3, 0.9, 1.0, 1.5, 4.0] {
+            for nu in [-150.0, -60.0, 0.0, 10.0, 95.0] {
+                let limit = if e > 1.0 {
+                    (-1.0 / e).acos().to_degrees()
+                } else {
+                    180.0
+                };
+                if nu >= limit || nu <= -limit {
+                    continue;
+                }
+                let elements = OsculatingElements {
+                    central_body: Planet::Earth,
+                    semi_mayor: if e == 1.0 {
+                        f64::INFINITY
+                    } else {
+                        10000.0 / (1.0 - e * e)
+                    },
+                    eccentricity: e,
+                    inclination: 30.0,
+                    long_ascending: 40.0,
+                    argument_periapsis: 50.0,
+                    true_anomaly: nu,
+                };
+                let back = elements.with_mean_anomaly(elements.mean_anomaly());
+                let difference = (back.true_anomaly - nu + 180.0).rem_euclid(360.0) - 180.0;
+                assert!(difference.abs() < 1e-8, "e = {}, nu = {}", e, nu);
+            }
+        }
+    }
+}
@@ -0,0 +1,426 @@
+// Finds planetary phenomena by scanning for sign changes and bisecting down to a minute.
+// Positions are geometric (no light time or aberration) and come from the approximate elements,
+// so expect the times to be off by a few hours for the outer planets.
+
+use crate::bodies::Planet;
+use crate::coordinate::*;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::*;
+use std::sync::Arc;
+
+/// days
+const SEARCH_STEP: f64 = 1.0;
+/// days, one minute
+const SEARCH_TOLERANCE: f64 = 1.0 / 1440.0;
+/// days, for the numerical derivatives
+const DERIVATIVE_STEP: f64 = 0.01;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PhenomenonKind {
+    Opposition,
+    Conjunction,
+    InferiorConjunction,
+    SuperiorConjunction,
+    GreatestElongationEast,
+    GreatestElongationWest,
+    StationRetrograde,
+    StationDirect,
+    Perihelion,
+    Aphelion,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Phenomenon {
+    pub planet: Planet,
+    pub kind: PhenomenonKind,
+    /// days since J2000 (TT)
+    pub time: f64,
+    /// angle between sun and planet as seen from the earth, degree
+    pub elongation: f64,
+    /// au
+    pub earth_distance: f64,
+    /// au
+    pub sun_distance: f64,
+}
+
+pub const PHENOMENA_PLANETS: [Planet; 8] = [
+    Planet::Mercury,
+    Planet::Venus,
+    Planet::Earth,
+    Planet::Mars,
+    Planet::Jupiter,
+    Planet::Saturn,
+    Planet::Uranus,
+    Planet::Neptune,
+];
+
+fn heliocentric(planet: Planet, time: f64) -> DVec3 {
+    planet.pos_icrf_since_j2000(time).position
+}
+
+fn geocentric(planet: Planet, time: f64) -> DVec3 {
+    heliocentric(planet, time) - heliocentric(Planet::Earth, time)
+}
+
+fn ecliptic_longitude(v: DVec3) -> f64 {
+    let ecl = icrf_to_ecliptic(v);
+    ecl.y.atan2(ecl.x).to_degrees()
+}
+
+fn wrap_180(angle: f64) -> f64 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Geocentric ecliptic longitude of the planet minus that of the sun, in degree (-180, 180]. Positive is east.
+pub fn longitude_from_sun(planet: Planet, time: f64) -> f64 {
+    let sun = -heliocentric(Planet::Earth, time);
+    wrap_180(ecliptic_longitude(geocentric(planet, time)) - ecliptic_longitude(sun))
+}
+
+/// Angle between the sun and the planet as seen from the earth, in degree
+pub fn elongation(planet: Planet, time: f64) -> f64 {
+    let sun = -heliocentric(Planet::Earth, time);
+    geocentric(planet, time).angle_between(sun).to_degrees()
+}
+
+/// Rate of the geocentric ecliptic longitude in degree/day. Negative while retrograde.
+pub fn longitude_rate(planet: Planet, time: f64) -> f64 {
+    let before = ecliptic_longitude(geocentric(planet, time - DERIVATIVE_STEP));
+    let after = ecliptic_longitude(geocentric(planet, time + DERIVATIVE_STEP));
+    wrap_180(after - before) / (2.0 * DERIVATIVE_STEP)
+}
+
+fn derivative<F: Fn(f64) -> f64>(f: &F, time: f64) -> f64 {
+    (f(time + DERIVATIVE_STEP) - f(time - DERIVATIVE_STEP)) / (2.0 * DERIVATIVE_STEP)
+}
+
+/// Times where f changes sign, along with the direction: true for - to +.
+/// None if keep_going returned false before the end.
+fn find_sign_changes<F: Fn(f64) -> f64>(
+    f: F,
+    from: f64,
+    to: f64,
+    keep_going: &dyn Fn() -> bool,
+) -> Option<Vec<(f64, bool)>> {
+    let mut results = Vec::new();
+    let mut a = from;
+    let mut fa = f(a);
+    while a < to {
+        if !keep_going() {
+            return None;
+        }
+        let b = (a + SEARCH_STEP).min(to);
+        let fb = f(b);
+        if fa.signum() != fb.signum() && fa != 0.0 {
+            let rising = fb > fa;
+            let (mut lo, mut hi) = (a, b);
+            let mut f_lo = fa;
+            while hi - lo > SEARCH_TOLERANCE {
+                let mid = 0.5 * (lo + hi);
+                let f_mid = f(mid);
+                if f_mid.signum() == f_lo.signum() {
+                    lo = mid;
+                    f_lo = f_mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            results.push((0.5 * (lo + hi), rising));
+        }
+        a = b;
+        fa = fb;
+    }
+    Some(results)
+}
+
+fn make_phenomenon(planet: Planet, kind: PhenomenonKind, time: f64) -> Phenomenon {
+    Phenomenon {
+        planet,
+        kind,
+        time,
+        elongation: if planet == Planet::Earth {
+            0.0
+        } else {
+            elongation(planet, time)
+        },
+        earth_distance: geocentric(planet, time).length(),
+        sun_distance: heliocentric(planet, time).length(),
+    }
+}
+
+fn is_inferior(planet: Planet) -> bool {
+    planet == Planet::Mercury || planet == Planet::Venus
+}
+
+/// All phenomena of one planet between two times in days since J2000, sorted by time.
+/// The sun and the moon have none, the earth only has perihelion and aphelion.
+pub fn find_phenomena(planet: Planet, from: f64, to: f64) -> Vec<Phenomenon> {
+    search_phenomena(planet, from, to, &|| true).unwrap_or_default()
+}
+
+/// find_phenomena, None if keep_going returned false before the end
+fn search_phenomena(
+    planet: Planet,
+    from: f64,
+    to: f64,
+    keep_going: &dyn Fn() -> bool,
+) -> Option<Vec<Phenomenon>> {
+    if !PHENOMENA_PLANETS.contains(&planet) {
+        return Some(Vec::new());
+    }
+
+    let mut results = Vec::new();
+
+    let sun_distance = |t: f64| heliocentric(planet, t).length();
+    for (time, rising) in find_sign_changes(|t| derivative(&sun_distance, t), from, to, keep_going)?
+    {
+        let kind = if rising {
+            PhenomenonKind::Perihelion
+        } else {
+            PhenomenonKind::Aphelion
+        };
+        results.push(make_phenomenon(planet, kind, time));
+    }
+
+    if planet != Planet::Earth {
+        // sin of the longitude difference crosses zero at both conjunction and opposition
+        let sin_diff = |t: f64| longitude_from_sun(planet, t).to_radians().sin();
+        for (time, _) in find_sign_changes(sin_diff, from, to, keep_going)? {
+            let phenomenon = make_phenomenon(planet, PhenomenonKind::Conjunction, time);
+            let kind = if longitude_from_sun(planet, time).abs() > 90.0 {
+                PhenomenonKind::Opposition
+            } else if !is_inferior(planet) {
+                PhenomenonKind::Conjunction
+            } else if phenomenon.earth_distance < heliocentric(Planet::Earth, time).length() {
+                PhenomenonKind::InferiorConjunction
+            } else {
+                PhenomenonKind::SuperiorConjunction
+            };
+            results.push(Phenomenon { kind, ..phenomenon });
+        }
+
+        for (time, rising) in
+            find_sign_changes(|t| longitude_rate(planet, t), from, to, keep_going)?
+        {
+            let kind = if rising {
+                PhenomenonKind::StationDirect
+            } else {
+                PhenomenonKind::StationRetrograde
+            };
+            results.push(make_phenomenon(planet, kind, time));
+        }
+    }
+
+    if is_inferior(planet) {
+        let elongation_of = |t: f64| elongation(planet, t);
+        for (time, rising) in
+            find_sign_changes(|t| derivative(&elongation_of, t), from, to, keep_going)?
+        {
+            if rising {
+                // minimum, that is a conjunction
+                continue;
+            }
+            let kind = if longitude_from_sun(planet, time) > 0.0 {
+                PhenomenonKind::GreatestElongationEast
+            } else {
+                PhenomenonKind::GreatestElongationWest
+            };
+            results.push(make_phenomenon(planet, kind, time));
+        }
+    }
+
+    results.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Some(results)
+}
+
+/// Phenomena of all planets, sorted by time
+pub fn find_all_phenomena(from: f64, to: f64) -> Vec<Phenomenon> {
+    let mut results = PHENOMENA_PLANETS
+        .iter()
+        .flat_map(|planet| find_phenomena(*planet, from, to))
+        .collect::<Vec<Phenomenon>>();
+    results.sort_by(|a, b| a.time.total_cmp(&b.time));
+    results
+}
+
+pub enum PhenomenaSearchStatus {
+    Invalid,
+    /// planets searched so far, of how many
+    Progress {
+        done: usize,
+        total: usize,
+    },
+    Cancelled,
+    Complete(Vec<Phenomenon>),
+}
+
+/// Searches on a separate thread, decades of all planets take a while
+pub struct PhenomenaSearch {
+    rx: Receiver<PhenomenaSearchStatus>,
+    cancel: Arc<AtomicBool>,
+    last_status: PhenomenaSearchStatus,
+}
+
+impl PhenomenaSearch {
+    /// All of PHENOMENA_PLANETS if planet is None
+    pub fn start(planet: Option<Planet>, from: f64, to: f64) -> Self {
+        let (tx, rx) = sync_channel(10);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let planets = match planet {
+                Some(planet) => vec![planet],
+                None => PHENOMENA_PLANETS.to_vec(),
+            };
+            let keep_going = || !worker_cancel.load(Ordering::Relaxed);
+            let mut results = Vec::new();
+            for (i, planet) in planets.iter().enumerate() {
+                // if the ui is busy, it just misses a progress update
+                let _ = tx.try_send(PhenomenaSearchStatus::Progress {
+                    done: i,
+                    total: planets.len(),
+                });
+                match search_phenomena(*planet, from, to, &keep_going) {
+                    Some(found) => results.extend(found),
+                    None => {
+                        tx.send(PhenomenaSearchStatus::Cancelled).ok();
+                        return;
+                    }
+                }
+            }
+            results.sort_by(|a, b| a.time.total_cmp(&b.time));
+            tx.send(PhenomenaSearchStatus::Complete(results)).ok();
+        });
+
+        Self {
+            rx,
+            cancel,
+            last_status: PhenomenaSearchStatus::Invalid,
+        }
+    }
+
+    /// Stops within a day of the scan, nothing is returned
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn update_status(&mut self) {
+        while let Ok(new_status) = self.rx.try_recv() {
+            self.last_status = new_status;
+        }
+    }
+
+    pub fn get_status(&self) -> &PhenomenaSearchStatus {
+        &self.last_status
+    }
+
+    /// Takes the phenomena out once the search is done, leaving the search invalid
+    pub fn take_result(&mut self) -> Option<Vec<Phenomenon>> {
+        match std::mem::replace(&mut self.last_status, PhenomenaSearchStatus::Invalid) {
+            PhenomenaSearchStatus::Complete(results) => Some(results),
+            other => {
+                self.last_status = other;
+                None
+            }
+        }
+    }
+}
+
+impl Display for PhenomenonKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhenomenonKind::Opposition => write!(f, "Opposition"),
+            PhenomenonKind::Conjunction => write!(f, "Conjunction"),
+            PhenomenonKind::InferiorConjunction => write!(f, "Inferior conjunction"),
+            PhenomenonKind::SuperiorConjunction => write!(f, "Superior conjunction"),
+            PhenomenonKind::GreatestElongationEast => write!(f, "Greatest elongation east"),
+            PhenomenonKind::GreatestElongationWest => write!(f, "Greatest elongation west"),
+            PhenomenonKind::StationRetrograde => write!(f, "Stationary, retrograde"),
+            PhenomenonKind::StationDirect => write!(f, "Stationary, direct"),
+            PhenomenonKind::Perihelion => write!(f, "Perihelion"),
+            PhenomenonKind::Aphelion => write!(f, "Aphelion"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timebase::julian_date;
+    use std::cell::Cell;
+
+    fn wait(search: &mut PhenomenaSearch) -> Option<Vec<Phenomenon>> {
+        for _i in 0..6000 {
+            search.update_status();
+            if let PhenomenaSearchStatus::Cancelled = search.get_status() {
+                return None;
+            }
+            if let Some(results) = search.take_result() {
+                return Some(results);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("search did not finish");
+    }
+
+    #[test]
+    fn background_search_finds_the_same() {
+        let (from, to) = (8000.0, 9000.0);
+        let mut search = PhenomenaSearch::start(Some(Planet::Mars), from, to);
+        let background = wait(&mut search).unwrap();
+        let direct = find_phenomena(Planet::Mars, from, to);
+        assert!(!direct.is_empty());
+        assert_eq!(background.len(), direct.len());
+        for (a, b) in background.iter().zip(direct.iter()) {
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.time, b.time);
+        }
+
+        let mut search = PhenomenaSearch::start(None, from, to);
+        let all = wait(&mut search).unwrap();
+        assert!(all.windows(2).all(|w| w[0].time <= w[1].time));
+        assert_eq!(all.len(), find_all_phenomena(from, to).len());
+    }
+
+    #[test]
+    fn cancelled_search_returns_nothing() {
+        let mut search = PhenomenaSearch::start(None, 0.0, 36525.0);
+        search.cancel();
+        assert!(wait(&mut search).is_none());
+    }
+
+    #[test]
+    fn mars_opposition_2025() {
+        // 2025 January 16, 2h UT
+        let expected = julian_date(2025, 1, 16.1) - 2451545.0;
+        let oppositions = find_phenomena(Planet::Mars, expected - 100.0, expected + 100.0)
+            .into_iter()
+            .filter(|p| p.kind == PhenomenonKind::Opposition)
+            .collect::<Vec<Phenomenon>>();
+        assert_eq!(oppositions.len(), 1);
+        let opposition = &oppositions[0];
+        assert!(
+            (opposition.time - expected).abs() < 1.0,
+            "{} days off",
+            opposition.time - expected
+        );
+        assert!(opposition.elongation > 170.0, "{}", opposition.elongation);
+        assert!(
+            (opposition.earth_distance - 0.642).abs() < 0.01,
+            "{}",
+            opposition.earth_distance
+        );
+    }
+
+    #[test]
+    fn scans_stop_when_told() {
+        let days = Cell::new(0);
+        let keep_going = || {
+            days.set(days.get() + 1);
+            days.get() <= 100
+        };
+        assert!(search_phenomena(Planet::Neptune, 0.0, 36525.0, &keep_going).is_none());
+        assert_eq!(days.get(), 101);
+    }
+}
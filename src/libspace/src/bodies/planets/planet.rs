@@ -1,6 +1,8 @@
 use crate::bodies::body::Body;
+use crate::bodies::moon::moon_geocentric_icrf_since_j2000;
 use crate::bodies::orbit::Orbit;
 use crate::bodies::planets::planet_bodies::*;
+use crate::bodies::rotation_model::RotationModel;
 use crate::coordinate::{CoordinateUnit, IcrfStateVector};
 use crate::timebase::Timebase;
 use glam::Quat;
@@ -11,12 +13,19 @@ include!(concat!(env!("OUT_DIR"), "/kepler_short.rs"));
 include!(concat!(env!("OUT_DIR"), "/kepler_long.rs"));
 include!(concat!(env!("OUT_DIR"), "/kepler_orbits.rs"));
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Earth rotation angle in radians, for a time in days since J2000
+pub fn earth_rotation_angle(time: f64) -> f64 {
+    (2.0 * std::f64::consts::PI * (0.7790572732640 + 1.00273781191135448 * time))
+        % (2.0 * std::f64::consts::PI)
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Planet {
     Sun,
     Mercury,
     Venus,
     Earth,
+    Moon,
     Mars,
     Jupiter,
     Saturn,
@@ -31,6 +40,7 @@ impl Planet {
             Planet::Mercury => &MERCURY_BODY,
             Planet::Venus => &VENUS_BODY,
             Planet::Earth => &EARTH_BODY,
+            Planet::Moon => &MOON_BODY,
             Planet::Mars => &MARS_BODY,
             Planet::Jupiter => &JUPITER_BODY,
             Planet::Saturn => &SATURN_BODY,
@@ -40,45 +50,107 @@ impl Planet {
     }
 
     pub fn pos_icrf(&self, timebase: &Timebase) -> IcrfStateVector {
-        // specal case sun
-        if *self == Planet::Sun {
-            IcrfStateVector {
+        self.pos_icrf_since_j2000(timebase.now_jd_j2000())
+    }
+
+    /// Heliocentric position, for a time in days since J2000
+    pub fn pos_icrf_since_j2000(&self, time: f64) -> IcrfStateVector {
+        match self {
+            Planet::Sun => IcrfStateVector {
                 unit: CoordinateUnit::Au,
                 position: Default::default(),
                 velocity: Default::default(),
+            },
+            // the elements of the earth are those of the earth-moon barycenter
+            Planet::Earth | Planet::Moon => {
+                let barycenter = EARTH_ORBIT.position_icrf_since_j2000(time);
+                let moon = moon_geocentric_icrf_since_j2000(time);
+                let moon_position = moon.unit.to(barycenter.unit, &moon.position);
+                let moon_velocity = moon.unit.to(barycenter.unit, &moon.velocity);
+                let moon_fraction = MOON_BODY.gm / (EARTH_BODY.gm + MOON_BODY.gm);
+                let earth = IcrfStateVector {
+                    unit: barycenter.unit,
+                    position: barycenter.position - moon_position * moon_fraction,
+                    velocity: barycenter.velocity - moon_velocity * moon_fraction,
+                };
+                if *self == Planet::Earth {
+                    earth
+                } else {
+                    IcrfStateVector {
+                        unit: earth.unit,
+                        position: earth.position + moon_position,
+                        velocity: earth.velocity + moon_velocity,
+                    }
+                }
             }
-        } else {
-            self.orbit().position_icrf(timebase)
+            _ => self.orbit().unwrap().position_icrf_since_j2000(time),
         }
     }
 
-    pub fn rough_pos_list(&self, timebase: &Timebase) -> Vec<IcrfStateVector> {
-        if *self == Planet::Sun {
-            Vec::new()
-        } else {
-            let mut results = Vec::new();
-            for i in 0..((self.body().sidereal_period.ceil() + 1.0) as usize) {
-                let t = timebase.now_jd_j2000() + i as f64;
-                results.push(self.orbit().position_icrf_since_j2000(t));
-            }
+    /// The body this one orbits, none for the sun
+    pub fn parent(&self) -> Option<Planet> {
+        match self {
+            Planet::Sun => None,
+            Planet::Moon => Some(Planet::Earth),
+            _ => Some(Planet::Sun),
+        }
+    }
 
-            results
+    /// NAIF id of the body itself
+    pub fn naif_id(&self) -> i32 {
+        match self {
+            Planet::Sun => 10,
+            Planet::Mercury => 199,
+            Planet::Venus => 299,
+            Planet::Earth => 399,
+            Planet::Moon => 301,
+            Planet::Mars => 499,
+            Planet::Jupiter => 599,
+            Planet::Saturn => 699,
+            Planet::Uranus => 799,
+            Planet::Neptune => 899,
         }
     }
 
-    pub fn orbit(&self) -> &Orbit {
+    /// NAIF id of the barycenter of the planet system, generic SPKs often only have these
+    pub fn naif_barycenter_id(&self) -> i32 {
         match self {
-            Planet::Sun => {
-                panic!("Sun has no orbit");
+            Planet::Sun => 0,
+            _ => self.naif_id() / 100,
+        }
+    }
+
+    /// Positions over one revolution from now, relative to the parent body
+    pub fn rough_pos_list(&self, timebase: &Timebase) -> Vec<IcrfStateVector> {
+        let now = timebase.now_jd_j2000();
+        match self {
+            Planet::Sun => Vec::new(),
+            // a day apart would make the moon's orbit a 27-gon
+            Planet::Moon => (0..=(self.body().sidereal_period * 8.0).ceil() as usize)
+                .map(|i| moon_geocentric_icrf_since_j2000(now + i as f64 / 8.0))
+                .collect(),
+            _ => {
+                let orbit = self.orbit().unwrap();
+                (0..((self.body().sidereal_period.ceil() + 1.0) as usize))
+                    .map(|i| orbit.position_icrf_since_j2000(now + i as f64))
+                    .collect()
             }
-            Planet::Mercury => &MERCURY_ORBIT,
-            Planet::Venus => &VENUS_ORBIT,
-            Planet::Earth => &EARTH_ORBIT,
-            Planet::Mars => &MARS_ORBIT,
-            Planet::Jupiter => &JUPITER_ORBIT,
-            Planet::Saturn => &SATURN_ORBIT,
-            Planet::Uranus => &URANUS_ORBIT,
-            Planet::Neptune => &NEPTUNE_ORBIT,
+        }
+    }
+
+    /// Heliocentric orbit, none for the sun and the moon.
+    /// The one of the earth is that of the earth-moon barycenter.
+    pub fn orbit(&self) -> Option<&Orbit> {
+        match self {
+            Planet::Sun | Planet::Moon => None,
+            Planet::Mercury => Some(&MERCURY_ORBIT),
+            Planet::Venus => Some(&VENUS_ORBIT),
+            Planet::Earth => Some(&EARTH_ORBIT),
+            Planet::Mars => Some(&MARS_ORBIT),
+            Planet::Jupiter => Some(&JUPITER_ORBIT),
+            Planet::Saturn => Some(&SATURN_ORBIT),
+            Planet::Uranus => Some(&URANUS_ORBIT),
+            Planet::Neptune => Some(&NEPTUNE_ORBIT),
         }
     }
 
@@ -87,11 +159,8 @@ impl Planet {
             Planet::Sun => 0.0,
             Planet::Mercury => 0.0,
             Planet::Venus => 0.0,
-            Planet::Earth => {
-                let t = time.now_jd_j2000();
-                (2.0 * std::f64::consts::PI * (0.7790572732640 + 1.00273781191135448 * t))
-                    % (2.0 * std::f64::consts::PI)
-            }
+            Planet::Earth => earth_rotation_angle(time.now_jd_j2000()),
+            Planet::Moon => 0.0,
             Planet::Mars => 0.0,
             Planet::Jupiter => 0.0,
             Planet::Saturn => 0.0,
@@ -100,17 +169,15 @@ impl Planet {
         }
     }
 
+    pub fn rotation_model(&self) -> RotationModel {
+        RotationModel::for_planet(*self)
+    }
+
     pub fn gl_rotation_at(&self, timebase: &Timebase) -> Quat {
         match self {
-            Planet::Sun => Quat::default(),
-            Planet::Mercury => Quat::default(),
-            Planet::Venus => Quat::default(),
+            // keep the earth on the same rotation angle as its body fixed frame
             Planet::Earth => Quat::from_rotation_y(self.angle_at(timebase) as f32),
-            Planet::Mars => Quat::default(),
-            Planet::Jupiter => Quat::default(),
-            Planet::Saturn => Quat::default(),
-            Planet::Uranus => Quat::default(),
-            Planet::Neptune => Quat::default(),
+            _ => self.rotation_model().gl_rotation(timebase.now_jd_j2000()),
         }
     }
 }
@@ -122,6 +189,7 @@ impl Display for Planet {
             Planet::Mercury => write!(f, "Mercury"),
             Planet::Venus => write!(f, "Venus"),
             Planet::Earth => write!(f, "Earth"),
+            Planet::Moon => write!(f, "Moon"),
             Planet::Mars => write!(f, "Mars"),
             Planet::Jupiter => write!(f, "Jupiter"),
             Planet::Saturn => write!(f, "Saturn"),
@@ -130,3 +198,33 @@ impl Display for Planet {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moon_goes_around_the_earth() {
+        assert!(Planet::Moon.orbit().is_none());
+        assert!(Planet::Sun.orbit().is_none());
+        let time = 9000.0;
+        let earth = Planet::Earth.pos_icrf_since_j2000(time);
+        let moon = Planet::Moon.pos_icrf_since_j2000(time);
+        let geocentric = moon_geocentric_icrf_since_j2000(time);
+        let relative = earth
+            .unit
+            .to(CoordinateUnit::KiloMeter, &(moon.position - earth.position));
+        assert!((relative - geocentric.position).length() < 1e-3);
+
+        // weighted by mass the two are where the elements put the barycenter
+        let barycenter = EARTH_ORBIT.position_icrf_since_j2000(time).position;
+        let moon_fraction = MOON_BODY.gm / (EARTH_BODY.gm + MOON_BODY.gm);
+        let weighted = earth.position * (1.0 - moon_fraction) + moon.position * moon_fraction;
+        assert!((weighted - barycenter).length() < 1e-12);
+
+        let orbit = Planet::Moon.rough_pos_list(&Timebase::new());
+        assert!(orbit
+            .iter()
+            .all(|p| (350000.0..410000.0).contains(&p.position.length())));
+    }
+}
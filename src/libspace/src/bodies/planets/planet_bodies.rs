@@ -5,6 +5,8 @@ pub const SUN_BODY: Body = Body {
     radius_equatorial: 696342.0,
     radius_polar: 696342.0,
     mass: 1.9885e30,
+    gm: 1.32712440018e11,
+    j2: 2.2e-7,
     angular_speed_per_second: 0.0,
     sidereal_period: 0.0,
 };
@@ -14,6 +16,8 @@ pub const MERCURY_BODY: Body = Body {
     radius_equatorial: 2439.7,
     radius_polar: 2439.7,
     mass: 3.3011e23,
+    gm: 2.2031868551e4,
+    j2: 5.03e-5,
     angular_speed_per_second: 0.0,
     sidereal_period: 87.9691,
 };
@@ -23,6 +27,8 @@ pub const VENUS_BODY: Body = Body {
     radius_equatorial: 6051.8,
     radius_polar: 6051.8,
     mass: 4.8675e24,
+    gm: 3.24858592e5,
+    j2: 4.458e-6,
     angular_speed_per_second: 0.0,
     sidereal_period: 243.0226,
 };
@@ -32,15 +38,30 @@ pub const EARTH_BODY: Body = Body {
     radius_equatorial: 6378.137,
     radius_polar: 6356.752,
     mass: 5.97237e24,
+    gm: 3.986004418e5,
+    j2: 1.08263e-3,
     angular_speed_per_second: 7.2921150e-5,
     sidereal_period: 365.256,
 };
 
+pub const MOON_BODY: Body = Body {
+    radius_mean: 1737.4,
+    radius_equatorial: 1738.1,
+    radius_polar: 1736.0,
+    mass: 7.342e22,
+    gm: 4.9028e3,
+    j2: 2.0323e-4,
+    angular_speed_per_second: 2.6617e-6,
+    sidereal_period: 27.321661,
+};
+
 pub const MARS_BODY: Body = Body {
     radius_mean: 3389.5,
     radius_equatorial: 3396.2,
     radius_polar: 3376.2,
     mass: 6.4171e23,
+    gm: 4.282837362e4,
+    j2: 1.96045e-3,
     angular_speed_per_second: 0.0,
     sidereal_period: 779.96,
 };
@@ -50,6 +71,8 @@ pub const JUPITER_BODY: Body = Body {
     radius_equatorial: 71492.0,
     radius_polar: 66854.0,
     mass: 1.8982e27,
+    gm: 1.26712764e8,
+    j2: 1.4736e-2,
     angular_speed_per_second: 0.0,
     sidereal_period: 4332.59,
 };
@@ -59,6 +82,8 @@ pub const SATURN_BODY: Body = Body {
     radius_equatorial: 60268.0,
     radius_polar: 54364.0,
     mass: 5.6834e26,
+    gm: 3.7940585e7,
+    j2: 1.6298e-2,
     angular_speed_per_second: 0.0,
     sidereal_period: 10759.22,
 };
@@ -68,6 +93,8 @@ pub const URANUS_BODY: Body = Body {
     radius_equatorial: 25559.0,
     radius_polar: 24973.0,
     mass: 8.6810e25,
+    gm: 5.794556e6,
+    j2: 3.34343e-3,
     angular_speed_per_second: 0.0,
     sidereal_period: 30688.5,
 };
@@ -77,6 +104,8 @@ pub const NEPTUNE_BODY: Body = Body {
     radius_equatorial: 24764.0,
     radius_polar: 24341.0,
     mass: 1.02413e26,
+    gm: 6.836527e6,
+    j2: 3.411e-3,
     angular_speed_per_second: 0.0,
     sidereal_period: 60195.0,
 };
@@ -0,0 +1,88 @@
+// IAU style rotation models: pole direction and prime meridian as linear functions of time.
+// Values from the IAU WGCCRE 2015 report, without the small periodic terms.
+
+use crate::bodies::Planet;
+use glam::f64::{DQuat, DVec3};
+use glam::Quat;
+
+#[derive(Copy, Clone, Debug)]
+pub struct RotationModel {
+    /// right ascension of the north pole at J2000 and its rate, degree and degree/century
+    pub pole_ra: f64,
+    pub pole_ra_rate: f64,
+    /// declination of the north pole at J2000 and its rate, degree and degree/century
+    pub pole_dec: f64,
+    pub pole_dec_rate: f64,
+    /// prime meridian at J2000 and its rate, degree and degree/day
+    pub prime_meridian: f64,
+    pub rotation_rate: f64,
+}
+
+impl RotationModel {
+    pub fn for_planet(planet: Planet) -> Self {
+        let (pole_ra, pole_ra_rate, pole_dec, pole_dec_rate, prime_meridian, rotation_rate) =
+            match planet {
+                Planet::Sun => (286.13, 0.0, 63.87, 0.0, 84.176, 14.1844000),
+                Planet::Mercury => (281.0103, -0.0328, 61.4155, -0.0049, 329.5988, 6.1385108),
+                Planet::Venus => (272.76, 0.0, 67.16, 0.0, 160.20, -1.4813688),
+                Planet::Earth => (0.0, -0.641, 90.0, -0.557, 190.147, 360.9856235),
+                Planet::Moon => (269.9949, 0.0031, 66.5392, 0.0130, 38.3213, 13.17635815),
+                Planet::Mars => (
+                    317.269202,
+                    -0.10927547,
+                    54.432516,
+                    -0.05827105,
+                    176.049863,
+                    350.891982443297,
+                ),
+                Planet::Jupiter => (
+                    268.056595,
+                    -0.006499,
+                    64.495303,
+                    0.002413,
+                    284.95,
+                    870.5360000,
+                ),
+                Planet::Saturn => (40.589, -0.036, 83.537, -0.004, 38.90, 810.7939024),
+                Planet::Uranus => (257.311, 0.0, -15.175, 0.0, 203.81, -501.1600928),
+                Planet::Neptune => (299.36, 0.0, 43.46, 0.0, 249.978, 541.1397757),
+            };
+        Self {
+            pole_ra,
+            pole_ra_rate,
+            pole_dec,
+            pole_dec_rate,
+            prime_meridian,
+            rotation_rate,
+        }
+    }
+
+    /// Rotation taking body fixed vectors to ICRF, time in days since J2000
+    pub fn body_to_icrf(&self, time: f64) -> DQuat {
+        let w = (self.prime_meridian + self.rotation_rate * time).to_radians();
+        self.equator_to_icrf(time) * DQuat::from_rotation_z(w)
+    }
+
+    /// Rotation taking vectors in the (non-rotating) equator frame to ICRF, x along the ascending node of the equator
+    pub fn equator_to_icrf(&self, time: f64) -> DQuat {
+        let t = time / 36525.0;
+        let ra = (self.pole_ra + self.pole_ra_rate * t).to_radians();
+        let dec = (self.pole_dec + self.pole_dec_rate * t).to_radians();
+        DQuat::from_rotation_z(std::f64::consts::FRAC_PI_2 + ra)
+            * DQuat::from_rotation_x(std::f64::consts::FRAC_PI_2 - dec)
+    }
+
+    pub fn pole(&self, time: f64) -> DVec3 {
+        self.body_to_icrf(time) * DVec3::Z
+    }
+
+    /// The same rotation, in the axes of the gl world
+    pub fn gl_rotation(&self, time: f64) -> Quat {
+        icrf_to_gl_rotation(self.body_to_icrf(time))
+    }
+}
+
+/// A rotation of ICRF vectors as rotation in the axes of the gl world (x, y, z = icrf y, z, x)
+pub fn icrf_to_gl_rotation(q: DQuat) -> Quat {
+    Quat::from_xyzw(q.y as f32, q.z as f32, q.x as f32, q.w as f32)
+}
@@ -0,0 +1,430 @@
+// Plate models of irregular bodies, from SPICE DSK type 2 segments or Wavefront OBJ files.
+// Vertices are in km in the body fixed frame of the body.
+
+use crate::kernels::{furnished_dsks, spice_return_on_error};
+use glam::f64::DVec3;
+use spice::SpiceLock;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+/// Vertices and plates are read in chunks of this many
+const DSK_BUFFER_SIZE: usize = 10000;
+const DSK_TYPE_PLATES: i32 = 2;
+
+#[derive(Clone, Debug, Default)]
+pub struct ShapeModel {
+    /// km, body fixed
+    pub vertices: Vec<DVec3>,
+    /// counter clockwise seen from outside, zero based
+    pub plates: Vec<[u32; 3]>,
+    /// NAIF id of the body, if the file says so
+    pub center: Option<i32>,
+    /// body fixed frame, if the file says so
+    pub frame: Option<String>,
+}
+
+impl ShapeModel {
+    /// DSK for .bds and .dsk, OBJ otherwise
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if extension == "bds" || extension == "dsk" {
+            Self::from_dsk(path)
+        } else {
+            Self::from_obj(path)
+        }
+    }
+
+    pub fn from_obj(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        Self::from_obj_str(&content).map_err(|e| format!("Cannot load {}: {}", path.display(), e))
+    }
+
+    /// Vertices and faces only. Faces with more than three corners are split into a fan.
+    pub fn from_obj_str(content: &str) -> Result<Self, String> {
+        let mut model = Self::default();
+        for (line_number, line) in content.lines().enumerate() {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("v") => {
+                    let coords = parts
+                        .take(3)
+                        .map(|p| p.parse::<f64>())
+                        .collect::<Result<Vec<f64>, _>>()
+                        .map_err(|e| format!("line {}: {}", line_number + 1, e))?;
+                    if coords.len() != 3 {
+                        return Err(format!(
+                            "line {}: vertex needs 3 coordinates",
+                            line_number + 1
+                        ));
+                    }
+                    model
+                        .vertices
+                        .push(DVec3::new(coords[0], coords[1], coords[2]));
+                }
+                Some("f") => {
+                    let mut corners = Vec::new();
+                    for part in parts {
+                        // v, v/vt, v//vn or v/vt/vn, negative counts from the end
+                        let index = part
+                            .split('/')
+                            .next()
+                            .unwrap_or("")
+                            .parse::<i64>()
+                            .map_err(|e| format!("line {}: {}", line_number + 1, e))?;
+                        let index = if index < 0 {
+                            model.vertices.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        if index < 0 || index >= model.vertices.len() as i64 {
+                            return Err(format!(
+                                "line {}: vertex {} does not exist",
+                                line_number + 1,
+                                part
+                            ));
+                        }
+                        corners.push(index as u32);
+                    }
+                    if corners.len() < 3 {
+                        return Err(format!("line {}: face needs 3 corners", line_number + 1));
+                    }
+                    for i in 1..corners.len() - 1 {
+                        model.plates.push([corners[0], corners[i], corners[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if model.plates.is_empty() {
+            return Err("no faces".to_string());
+        }
+        Ok(model)
+    }
+
+    /// First type 2 segment of a DSK file
+    pub fn from_dsk(path: &Path) -> Result<Self, String> {
+        with_dsk(path, |handle| unsafe { read_dsk_plates(handle) })
+    }
+
+    /// The first loaded DSK with a shape of the NAIF id, if there is one
+    pub fn find_dsk(id: i32) -> Option<PathBuf> {
+        furnished_dsks()
+            .into_iter()
+            .find(|path| dsk_center(path) == Ok(id))
+    }
+
+    pub fn scaled(mut self, factor: f64) -> Self {
+        for v in self.vertices.iter_mut() {
+            *v *= factor;
+        }
+        self
+    }
+
+    /// Largest distance of a vertex from the origin, km
+    pub fn max_radius(&self) -> f64 {
+        self.vertices.iter().map(|v| v.length()).fold(0.0, f64::max)
+    }
+
+    /// Radius of the sphere with the same volume, km
+    pub fn equivalent_radius(&self) -> f64 {
+        let volume: f64 = self
+            .plates
+            .iter()
+            .map(|p| {
+                let (a, b, c) = self.corners(p);
+                a.dot(b.cross(c)) / 6.0
+            })
+            .sum();
+        (3.0 * volume.abs() / (4.0 * std::f64::consts::PI)).cbrt()
+    }
+
+    /// Area weighted normal for every vertex
+    pub fn vertex_normals(&self) -> Vec<DVec3> {
+        let mut normals = vec![DVec3::ZERO; self.vertices.len()];
+        for plate in self.plates.iter() {
+            let (a, b, c) = self.corners(plate);
+            let normal = (b - a).cross(c - a);
+            for i in plate {
+                normals[*i as usize] += normal;
+            }
+        }
+        normals.into_iter().map(|n| n.normalize_or_zero()).collect()
+    }
+
+    fn corners(&self, plate: &[u32; 3]) -> (DVec3, DVec3, DVec3) {
+        (
+            self.vertices[plate[0] as usize],
+            self.vertices[plate[1] as usize],
+            self.vertices[plate[2] as usize],
+        )
+    }
+}
+
+/// NAIF id of the body of the first type 2 segment of a DSK file
+pub fn dsk_center(path: &Path) -> Result<i32, String> {
+    with_dsk(path, |handle| unsafe {
+        first_plate_segment(handle).map(|(_, descriptor)| descriptor.center)
+    })
+}
+
+/// Opens a DSK for f and closes it again. Errors of f are reset before closing, so the file is
+/// closed in any case and spice is not left failed.
+fn with_dsk<T>(
+    path: &Path,
+    f: impl FnOnce(spice::c::SpiceInt) -> Result<T, String>,
+) -> Result<T, String> {
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| format!("Invalid path {}", path.display()))?;
+    let file_c = CString::new(path_str).map_err(|e| format!("Invalid path {}: {}", path_str, e))?;
+
+    spice_return_on_error();
+    let _lock = SpiceLock::acquire().map_err(|_| "spice lock poisoned".to_string())?;
+    unsafe {
+        let mut handle: spice::c::SpiceInt = 0;
+        spice::c::dasopr_c(file_c.as_ptr() as *mut c_char, &mut handle);
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!("Cannot open DSK {}", path.display()));
+        }
+
+        let mut result = f(handle);
+        // a failed spice does nothing until reset, not even close the file
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            if result.is_ok() {
+                result = Err("spice error".to_string());
+            }
+        }
+        spice::c::dascls_c(handle);
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!("Cannot close DSK {}", path.display()));
+        }
+        result.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Needs the spice lock to be held and the file to be open
+unsafe fn first_plate_segment(
+    handle: spice::c::SpiceInt,
+) -> Result<(spice::c::SpiceDLADescr, spice::c::SpiceDSKDescr), String> {
+    let mut dladsc: spice::c::SpiceDLADescr = std::mem::zeroed();
+    let mut found: spice::c::SpiceBoolean = 0;
+    spice::c::dlabfs_c(handle, &mut dladsc, &mut found);
+    while found != 0 {
+        let mut dskdsc: spice::c::SpiceDSKDescr = std::mem::zeroed();
+        spice::c::dskgd_c(handle, &mut dladsc, &mut dskdsc);
+        if spice::c::failed_c() != 0 {
+            return Err("cannot read segment descriptor".to_string());
+        }
+        if dskdsc.dtype == DSK_TYPE_PLATES {
+            return Ok((dladsc, dskdsc));
+        }
+        let current = dladsc;
+        spice::c::dlafns_c(handle, &current, &mut dladsc, &mut found);
+    }
+    if spice::c::failed_c() != 0 {
+        return Err("cannot read segments".to_string());
+    }
+    Err("no type 2 segment".to_string())
+}
+
+/// Needs the spice lock to be held and the file to be open
+unsafe fn read_dsk_plates(handle: spice::c::SpiceInt) -> Result<ShapeModel, String> {
+    let (mut dladsc, dskdsc) = first_plate_segment(handle)?;
+    let mut model = ShapeModel {
+        center: Some(dskdsc.center),
+        frame: frame_name(dskdsc.frmcde),
+        ..Default::default()
+    };
+
+    let mut vertex_count: spice::c::SpiceInt = 0;
+    let mut plate_count: spice::c::SpiceInt = 0;
+    spice::c::dskz02_c(handle, &mut dladsc, &mut vertex_count, &mut plate_count);
+    if spice::c::failed_c() != 0 {
+        return Err("cannot read segment size".to_string());
+    }
+
+    let mut vertex_buffer = vec![[0.0f64; 3]; DSK_BUFFER_SIZE];
+    while (model.vertices.len() as i32) < vertex_count {
+        let mut n: spice::c::SpiceInt = 0;
+        spice::c::dskv02_c(
+            handle,
+            &mut dladsc,
+            model.vertices.len() as i32 + 1,
+            DSK_BUFFER_SIZE as i32,
+            &mut n,
+            vertex_buffer.as_mut_ptr(),
+        );
+        if spice::c::failed_c() != 0 || n <= 0 {
+            return Err("cannot read vertices".to_string());
+        }
+        model.vertices.extend(
+            vertex_buffer[..n as usize]
+                .iter()
+                .map(|v| DVec3::new(v[0], v[1], v[2])),
+        );
+    }
+
+    let mut plate_buffer = vec![[0i32; 3]; DSK_BUFFER_SIZE];
+    while (model.plates.len() as i32) < plate_count {
+        let mut n: spice::c::SpiceInt = 0;
+        spice::c::dskp02_c(
+            handle,
+            &mut dladsc,
+            model.plates.len() as i32 + 1,
+            DSK_BUFFER_SIZE as i32,
+            &mut n,
+            plate_buffer.as_mut_ptr(),
+        );
+        if spice::c::failed_c() != 0 || n <= 0 {
+            return Err("cannot read plates".to_string());
+        }
+        for plate in plate_buffer[..n as usize].iter() {
+            model.plates.push(dsk_plate(plate, vertex_count)?);
+        }
+    }
+    Ok(model)
+}
+
+/// Zero based corners of a plate, which are one based in the file
+fn dsk_plate(plate: &[i32; 3], vertex_count: i32) -> Result<[u32; 3], String> {
+    let mut corners = [0; 3];
+    for (corner, index) in corners.iter_mut().zip(plate) {
+        if !(1..=vertex_count).contains(index) {
+            return Err(format!(
+                "plate corner {} is not one of the {} vertices",
+                index, vertex_count
+            ));
+        }
+        *corner = *index as u32 - 1;
+    }
+    Ok(corners)
+}
+
+/// Needs the spice lock to be held
+unsafe fn frame_name(frame_code: i32) -> Option<String> {
+    let mut name = vec![0 as c_char; 33];
+    spice::c::frmnam_c(frame_code, name.len() as i32, name.as_mut_ptr());
+    let name = CStr::from_ptr(name.as_ptr()).to_str().ok()?.to_owned();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "# a square of two triangles
+o square
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+s off
+";
+
+    #[test]
+    fn obj_faces() {
+        for (faces, expected) in [
+            ("f 1 2 3", vec![[0, 1, 2]]),
+            // a fan
+            ("f 1 2 3 4", vec![[0, 1, 2], [0, 2, 3]]),
+            ("f 1/1 2/1 3/1", vec![[0, 1, 2]]),
+            ("f 1//1 2//1 3//1", vec![[0, 1, 2]]),
+            (
+                "f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1",
+                vec![[0, 1, 2], [0, 2, 3]],
+            ),
+            // relative to the vertices so far
+            ("f -4 -3 -2", vec![[0, 1, 2]]),
+            ("f -1/1/1 -4/1/1 -2/1/1", vec![[3, 0, 2]]),
+        ] {
+            let model = ShapeModel::from_obj_str(&format!("{}{}\n", SQUARE, faces)).unwrap();
+            assert_eq!(model.vertices.len(), 4, "{}", faces);
+            assert_eq!(model.plates, expected, "{}", faces);
+        }
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_vertices_before_the_face() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 0 0 1\nf -4 -2 -1\n";
+        let model = ShapeModel::from_obj_str(text).unwrap();
+        assert_eq!(model.plates, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn malformed_obj() {
+        for (text, expected) in [
+            ("v 1 2\n", "line 1: vertex needs 3 coordinates"),
+            ("v 1 x 3\n", "line 1: invalid float literal"),
+            (
+                &format!("{}f 1 2\n", SQUARE),
+                "line 10: face needs 3 corners",
+            ),
+            (
+                &format!("{}f 1 2 5\n", SQUARE),
+                "line 10: vertex 5 does not exist",
+            ),
+            (
+                &format!("{}f 0 1 2\n", SQUARE),
+                "line 10: vertex 0 does not exist",
+            ),
+            (
+                &format!("{}f -5 1 2\n", SQUARE),
+                "line 10: vertex -5 does not exist",
+            ),
+            (
+                &format!("{}f a b c\n", SQUARE),
+                "line 10: invalid digit found in string",
+            ),
+            (
+                &format!("{}f /1 2 3\n", SQUARE),
+                "line 10: cannot parse integer from empty string",
+            ),
+            (SQUARE, "no faces"),
+        ] {
+            assert_eq!(
+                ShapeModel::from_obj_str(text).unwrap_err(),
+                expected,
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn dsk_plates_are_checked() {
+        assert_eq!(dsk_plate(&[1, 2, 3], 3), Ok([0, 1, 2]));
+        assert!(dsk_plate(&[0, 1, 2], 3).is_err());
+        assert!(dsk_plate(&[1, 2, 4], 3).is_err());
+        assert!(dsk_plate(&[-1, 2, 3], 3).is_err());
+        assert!(dsk_plate(&[1, 2, 3], 0).is_err());
+    }
+
+    #[test]
+    fn volume_of_a_cube() {
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1
+f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 2 3 7 6\nf 3 4 8 7\nf 4 1 5 8\n";
+        let model = ShapeModel::from_obj_str(text).unwrap();
+        assert_eq!(model.plates.len(), 12);
+        let radius = (3.0 / (4.0 * std::f64::consts::PI)).cbrt();
+        assert!((model.equivalent_radius() - radius).abs() < 1e-12);
+        assert_eq!(model.max_radius(), 3f64.sqrt());
+    }
+}
@@ -0,0 +1,261 @@
+// Universal variable formulation, following Vallado, "Fundamentals of Astrodynamics and Applications", Algorithm 8
+// Works for elliptical, parabolic and hyperbolic orbits alike
+
+use crate::coordinate::*;
+use crate::timebase::Timebase;
+
+/// au^(3/2)/day, as used by the MPC
+pub const GAUSSIAN_GRAVITATIONAL_CONSTANT: f64 = 0.01720209895;
+/// GM of the sun in au^3/day^2
+pub const SUN_GM_AU_DAY: f64 = GAUSSIAN_GRAVITATIONAL_CONSTANT * GAUSSIAN_GRAVITATIONAL_CONSTANT;
+
+const UNIVERSAL_TOLERANCE: f64 = 1e-12;
+const UNIVERSAL_MAX_STEPS: usize = 128;
+
+/// A two-body orbit given by a state at epoch. Units are whatever mu is given in,
+/// for heliocentric orbits that is au and days.
+#[derive(Copy, Clone, Debug)]
+pub struct TwoBodyOrbit {
+    /// julian date (TT) of the state
+    pub epoch_jd: f64,
+    /// ICRF, relative to the central body
+    pub position: DVec3,
+    /// ICRF, per day
+    pub velocity: DVec3,
+    pub mu: f64,
+}
+
+pub fn stumpff_c2(psi: f64) -> f64 {
+    if psi > 1e-6 {
+        (1.0 - psi.sqrt().cos()) / psi
+    } else if psi < -1e-6 {
+        (1.0 - (-psi).sqrt().cosh()) / psi
+    } else {
+        0.5 - psi / 24.0
+    }
+}
+
+pub fn stumpff_c3(psi: f64) -> f64 {
+    if psi > 1e-6 {
+        let sqrt_psi = psi.sqrt();
+        (sqrt_psi - sqrt_psi.sin()) / (sqrt_psi * psi)
+    } else if psi < -1e-6 {
+        let sqrt_psi = (-psi).sqrt();
+        (sqrt_psi.sinh() - sqrt_psi) / (sqrt_psi * -psi)
+    } else {
+        1.0 / 6.0 - psi / 120.0
+    }
+}
+
+/// Propagates position and velocity by dt. Returns the new position and velocity.
+pub fn propagate_universal(
+    position: DVec3,
+    velocity: DVec3,
+    dt: f64,
+    mu: f64,
+) -> Result<(DVec3, DVec3), String> {
+    if dt == 0.0 {
+        return Ok((position, velocity));
+    }
+
+    let sqrt_mu = mu.sqrt();
+    let r0 = position.length();
+    let r_dot_v = position.dot(velocity);
+    // reciprocal of the semi-mayor axis
+    let alpha = 2.0 / r0 - velocity.length_squared() / mu;
+
+    let (dt, mut chi) = if alpha > 1e-9 {
+        // ellipse, full revolutions dont matter and only slow the iteration down
+        let period = 2.0 * std::f64::consts::PI / (sqrt_mu * alpha.powf(1.5));
+        let dt = dt % period;
+        (dt, sqrt_mu * dt * alpha)
+    } else if alpha < -1e-9 {
+        let a = 1.0 / alpha;
+        let sign = dt.signum();
+        let chi = sign
+            * (-a).sqrt()
+            * ((-2.0 * mu * alpha * dt) / (r_dot_v + sign * (-mu * a).sqrt() * (1.0 - r0 * alpha)))
+                .ln();
+        (dt, chi)
+    } else {
+        // parabola, via Barker's equation
+        let p = position.cross(velocity).length_squared() / mu;
+        let s = 0.5 * (std::f64::consts::FRAC_PI_2 - (3.0 * (mu / p.powi(3)).sqrt() * dt).atan());
+        let w = s.tan().cbrt().atan();
+        (dt, p.sqrt() * 2.0 / (2.0 * w).tan())
+    };
+
+    let mut converged = false;
+    let mut psi = 0.0;
+    let mut r = r0;
+    for _i in 0..UNIVERSAL_MAX_STEPS {
+        psi = chi * chi * alpha;
+        let c2 = stumpff_c2(psi);
+        let c3 = stumpff_c3(psi);
+        r = chi * chi * c2 + r_dot_v / sqrt_mu * chi * (1.0 - psi * c3) + r0 * (1.0 - psi * c2);
+        let delta_chi = (sqrt_mu * dt
+            - chi * chi * chi * c3
+            - r_dot_v / sqrt_mu * chi * chi * c2
+            - r0 * chi * (1.0 - psi * c3))
+            / r;
+        chi += delta_chi;
+        if delta_chi.abs() <= UNIVERSAL_TOLERANCE * chi.abs().max(1.0) {
+            converged = true;
+            break;
+        }
+    }
+
+    if !converged || !chi.is_finite() {
+        return Err(format!(
+            "Universal variable propagation did not converge (dt {}, chi {})",
+            dt, chi
+        ));
+    }
+
+    let c2 = stumpff_c2(psi);
+    let c3 = stumpff_c3(psi);
+    let f = 1.0 - chi * chi / r0 * c2;
+    let g = dt - chi * chi * chi / sqrt_mu * c3;
+    let g_dot = 1.0 - chi * chi / r * c2;
+    let f_dot = sqrt_mu / (r * r0) * chi * (psi * c3 - 1.0);
+
+    Ok((
+        position * f + velocity * g,
+        position * f_dot + velocity * g_dot,
+    ))
+}
+
+impl TwoBodyOrbit {
+    /// Heliocentric orbit from an ICRF state vector
+    pub fn from_icrf_state(state: &IcrfStateVector, epoch_jd: f64) -> Self {
+        let au = state.as_unit(CoordinateUnit::Au);
+        Self {
+            epoch_jd,
+            position: au.position,
+            velocity: au.velocity * 86400.0,
+            mu: SUN_GM_AU_DAY,
+        }
+    }
+
+    /// Heliocentric orbit from perihelion elements, as given for comets.
+    /// Angles in degree and referred to the J2000 ecliptic, q in au.
+    pub fn from_perihelion_elements(
+        perihelion_jd: f64,
+        perihelion_distance: f64,
+        eccentricity: f64,
+        inclination: f64,
+        long_ascending: f64,
+        argument_perihelion: f64,
+    ) -> Self {
+        let rot = glam::f64::DMat3::from_rotation_z(long_ascending.to_radians())
+            * glam::f64::DMat3::from_rotation_x(inclination.to_radians())
+            * glam::f64::DMat3::from_rotation_z(argument_perihelion.to_radians());
+        let speed = (SUN_GM_AU_DAY * (1.0 + eccentricity) / perihelion_distance).sqrt();
+        Self {
+            epoch_jd: perihelion_jd,
+            position: ecliptic_to_icrf(rot * DVec3::new(perihelion_distance, 0.0, 0.0)),
+            velocity: ecliptic_to_icrf(rot * DVec3::new(0.0, speed, 0.0)),
+            mu: SUN_GM_AU_DAY,
+        }
+    }
+
+    /// Reciprocal semi-mayor axis. Positive for ellipses, zero for parabolas and negative for hyperbolas.
+    pub fn alpha(&self) -> f64 {
+        2.0 / self.position.length() - self.velocity.length_squared() / self.mu
+    }
+
+    pub fn eccentricity(&self) -> f64 {
+        let h = self.position.cross(self.velocity);
+        let e_vec = self.velocity.cross(h) / self.mu - self.position.normalize();
+        e_vec.length()
+    }
+
+    /// Orbital period in days, none for open orbits
+    pub fn period(&self) -> Option<f64> {
+        let alpha = self.alpha();
+        if alpha > 1e-9 {
+            Some(2.0 * std::f64::consts::PI / (self.mu.sqrt() * alpha.powf(1.5)))
+        } else {
+            None
+        }
+    }
+
+    pub fn state_icrf_since_j2000(&self, time: f64) -> Result<IcrfStateVector, String> {
+        let dt = time + 2451545.0 - self.epoch_jd;
+        let (position, velocity) = propagate_universal(self.position, self.velocity, dt, self.mu)?;
+        Ok(IcrfStateVector {
+            unit: CoordinateUnit::Au,
+            position,
+            velocity: velocity / 86400.0,
+        })
+    }
+
+    pub fn state_icrf(&self, timebase: &Timebase) -> Result<IcrfStateVector, String> {
+        self.state_icrf_since_j2000(timebase.now_jd_j2000())
+    }
+
+    /// Samples the trajectory between two times (days since J2000)
+    pub fn sample(&self, from: f64, to: f64, points: usize) -> Vec<IcrfStateVector> {
+        (0..=points)
+            .filter_map(|i| {
+                let t = from + (to - from) * i as f64 / points as f64;
+                self.state_icrf_since_j2000(t).ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// propagates from perihelion and checks the states stay on the same conic
+    fn check_conic(q: f64, e: f64) {
+        let orbit = TwoBodyOrbit::from_perihelion_elements(2451545.0, q, e, 20.0, 30.0, 40.0);
+        let h = orbit.position.cross(orbit.velocity);
+        let energy = orbit.velocity.length_squared() / 2.0 - orbit.mu / orbit.position.length();
+        let p = q * (1.0 + e);
+        for dt in [-400.0, -30.0, -1.0, 0.5, 20.0, 365.0] {
+            let (position, velocity) =
+                propagate_universal(orbit.position, orbit.velocity, dt, orbit.mu).unwrap();
+            let r = position.length();
+            assert!(r >= q - 1e-9, "e = {}, dt = {}", e, dt);
+            // angular momentum and energy stay the same
+            assert!((position.cross(velocity) - h).length() < 1e-12);
+            let energy_dt = velocity.length_squared() / 2.0 - orbit.mu / r;
+            assert!((energy_dt - energy).abs() < 1e-12);
+            // and the point is on the conic
+            let cos_nu = (p / r - 1.0) / e;
+            assert!(cos_nu.abs() <= 1.0 + 1e-9);
+        }
+        assert!((orbit.eccentricity() - e).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ellipse() {
+        check_conic(1.0, 0.3);
+        check_conic(0.5, 0.97);
+    }
+
+    #[test]
+    fn parabola() {
+        check_conic(1.2, 1.0);
+    }
+
+    #[test]
+    fn hyperbola() {
+        check_conic(2.0, 3.36);
+        check_conic(0.3, 1.01);
+    }
+
+    #[test]
+    fn period_brings_back() {
+        let orbit = TwoBodyOrbit::from_perihelion_elements(2451545.0, 1.0, 0.5, 10.0, 0.0, 0.0);
+        let period = orbit.period().unwrap();
+        assert!((period - 365.25 * 2f64.powf(1.5)).abs() < 0.1);
+        let (position, _) =
+            propagate_universal(orbit.position, orbit.velocity, period * 3.0, orbit.mu).unwrap();
+        assert!((position - orbit.position).length() < 1e-9);
+        assert!(orbit.sample(0.0, period, 16).len() == 17);
+    }
+}
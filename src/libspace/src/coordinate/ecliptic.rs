@@ -0,0 +1,14 @@
+use glam::f64::{DMat3, DVec3};
+
+/// Mean obliquity of the ecliptic at J2000, degree
+pub const J2000_OBLIQUITY: f64 = 23.43928;
+
+/// Rotates a vector given in J2000 ecliptic coordinates into ICRF (equatorial) coordinates
+pub fn ecliptic_to_icrf(v: DVec3) -> DVec3 {
+    DMat3::from_rotation_x(J2000_OBLIQUITY.to_radians()) * v
+}
+
+/// Rotates a vector given in ICRF (equatorial) coordinates into J2000 ecliptic coordinates
+pub fn icrf_to_ecliptic(v: DVec3) -> DVec3 {
+    DMat3::from_rotation_x(-J2000_OBLIQUITY.to_radians()) * v
+}
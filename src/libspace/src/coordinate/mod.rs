@@ -1,7 +1,11 @@
 pub mod coordinate_unit;
+pub mod ecliptic;
 pub mod icrf_state_vector;
 pub mod planetary_state_vector;
+pub mod teme;
 
 pub use coordinate_unit::*;
+pub use ecliptic::*;
 pub use icrf_state_vector::*;
 pub use planetary_state_vector::*;
+pub use teme::*;
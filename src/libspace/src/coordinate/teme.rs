@@ -0,0 +1,97 @@
+// TEME, the frame SGP4 works in: the true equator of date, with the x axis on the mean equinox
+// measured along it. IAU 1976 precession and the largest terms of the IAU 1980 nutation,
+// good to about half an arcsecond, take it back to the mean equator and equinox of J2000.
+
+use glam::f64::{DMat3, DVec3};
+
+const ARCSECOND: f64 = std::f64::consts::PI / (180.0 * 3600.0);
+
+/// Rotation from TEME into J2000 (ICRF) axes, time is ET (TDB) in seconds since J2000
+pub fn teme_to_j2000(et: f64) -> DMat3 {
+    let t = et / (86400.0 * 36525.0);
+
+    // precession, mean J2000 to mean of date
+    let zeta = (2306.2181 * t + 0.30188 * t * t + 0.017998 * t * t * t) * ARCSECOND;
+    let z = (2306.2181 * t + 1.09468 * t * t + 0.018203 * t * t * t) * ARCSECOND;
+    let theta = (2004.3109 * t - 0.42665 * t * t - 0.041833 * t * t * t) * ARCSECOND;
+    let precession =
+        DMat3::from_rotation_z(z) * DMat3::from_rotation_y(-theta) * DMat3::from_rotation_z(zeta);
+
+    // nutation, mean of date to true of date
+    let (nutation_longitude, nutation_obliquity) = nutation(t);
+    let mean_obliquity =
+        (84381.448 - 46.8150 * t - 0.00059 * t * t + 0.001813 * t * t * t) * ARCSECOND;
+    let true_obliquity = mean_obliquity + nutation_obliquity;
+    let nutation = DMat3::from_rotation_x(true_obliquity)
+        * DMat3::from_rotation_z(nutation_longitude)
+        * DMat3::from_rotation_x(-mean_obliquity);
+
+    // equation of the equinoxes, true of date to TEME
+    let equinoxes = DMat3::from_rotation_z(-nutation_longitude * mean_obliquity.cos());
+
+    (equinoxes * nutation * precession).transpose()
+}
+
+/// Rotates a TEME vector, like an SGP4 position or velocity, into J2000 (ICRF) axes
+pub fn teme_to_icrf(v: DVec3, et: f64) -> DVec3 {
+    teme_to_j2000(et) * v
+}
+
+/// Nutation in longitude and obliquity in rad, from the four largest terms
+fn nutation(t: f64) -> (f64, f64) {
+    // longitude of the ascending node of the moon, mean longitudes of the sun and the moon
+    let node = (125.04452 - 1934.136261 * t).to_radians();
+    let sun = (280.4665 + 36000.7698 * t).to_radians();
+    let moon = (218.3165 + 481267.8813 * t).to_radians();
+    let longitude = -17.20 * node.sin() - 1.32 * (2.0 * sun).sin() - 0.23 * (2.0 * moon).sin()
+        + 0.21 * (2.0 * node).sin();
+    let obliquity = 9.20 * node.cos() + 0.57 * (2.0 * sun).cos() + 0.10 * (2.0 * moon).cos()
+        - 0.09 * (2.0 * node).cos();
+    (longitude * ARCSECOND, obliquity * ARCSECOND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_is_orthonormal() {
+        for years in [-50.0, 0.0, 24.0, 100.0] {
+            let m = teme_to_j2000(years * 365.25 * 86400.0);
+            let identity = m * m.transpose();
+            for (column, expected) in [
+                (identity.x_axis, DVec3::X),
+                (identity.y_axis, DVec3::Y),
+                (identity.z_axis, DVec3::Z),
+            ] {
+                assert!((column - expected).length() < 1e-12);
+            }
+            assert!((m.determinant() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn only_nutation_at_j2000() {
+        // no precession yet, the frames differ by the nutation, some ten arcseconds
+        let m = teme_to_j2000(0.0);
+        let angle = (m * DVec3::X).angle_between(DVec3::X);
+        assert!(
+            angle > 1.0 * ARCSECOND && angle < 20.0 * ARCSECOND,
+            "{}",
+            angle / ARCSECOND
+        );
+    }
+
+    #[test]
+    fn vallado_example() {
+        // Vallado, Fundamentals of Astrodynamics and Applications, example 3-15, 2004-04-06 07:51:28.386 UTC
+        let et = (2453101.828154745 - 2451545.0) * 86400.0;
+        let teme = DVec3::new(5094.18016210, 6127.64465950, 6380.34453270);
+        let j2000 = DVec3::new(5102.50895790, 6123.01151990, 6378.13630330);
+        let rotated = teme_to_icrf(teme, et);
+        // the truncated nutation is good to some metres here
+        assert!((rotated - j2000).length() < 0.02, "{}", rotated);
+        // without the rotation it would be off by some kilometres
+        assert!((teme - j2000).length() > 5.0);
+    }
+}
@@ -1,68 +1,272 @@
+use chrono::NaiveDateTime;
 use sgp4::Elements;
 use std::collections::HashMap;
 use std::fs::File;
+use std::io::Write;
 use std::path::Path;
 
-use crate::elements::element_store::ElementStore;
+use crate::elements::element_fetch::{ElementFetch, FetchedElements};
+use crate::elements::element_query::ElementQuery;
+use crate::elements::element_source::{ParsedElements, SourceRegistry};
+use crate::elements::element_storage::ElementStorage;
+use crate::elements::element_store::{ElementSet, ElementStore};
 use crate::elements::element_util::*;
-use crate::elements::ElementIndex;
-use crate::utility::init_dirs;
+use crate::elements::omm::{Omm, OmmFormat};
+use crate::elements::satcat::{Satcat, SatcatEntry};
+use crate::elements::tle::{parse_tle, write_tle, TleError};
+use crate::elements::{ElementIndex, ElementSort};
+
+/// Elements older or newer than this, relative to the time looked at, are flagged as stale
+pub const STALE_AGE_DAYS: f64 = 7.0;
+
+/// What came of importing an element file
+pub struct ElementImport {
+    pub read: usize,
+    pub taken: usize,
+    pub errors: Vec<TleError>,
+}
 
 pub struct ElementDb {
     element_store: ElementStore,
     index: ElementIndex,
+    satcat: Satcat,
+    /// why nothing is saved, if the storage could not be loaded
+    storage: Result<ElementStorage, String>,
+    /// merges since the running fetch copied the elements, made again on its result
+    merged_during_fetch: Option<Vec<Merged>>,
+}
+
+/// A merge with the precedence it was made with
+struct Merged {
+    parsed: ParsedElements,
+    source: String,
+    priorities: HashMap<String, i32>,
 }
 
 impl ElementDb {
+    /// Loads the stored elements. If that fails, the db starts empty and saves nothing,
+    /// so the stored elements are not overwritten, see storage_error.
     pub fn new() -> Self {
-        let data_dir = init_dirs().expect("Something is wrong, you cant write home!");
-        let data_filename = data_dir.join(Path::new("elements.json"));
-        if let Ok(file) = std::fs::File::open(data_filename) {
-            if let Ok(store) = serde_json::from_reader(file) {
-                return Self {
-                    index: ElementIndex::from_store(&store),
-                    element_store: store,
-                };
+        let loaded = ElementStorage::open()
+            .and_then(|mut storage| storage.load().map(|store| (store, storage)));
+        let (element_store, storage) = match loaded {
+            Ok((store, storage)) => (store, Ok(storage)),
+            Err(e) => {
+                log::error!("Cannot load elements, nothing will be saved: {}", e);
+                (ElementStore::default(), Err(e))
             }
-        }
+        };
 
         Self {
-            element_store: ElementStore {
-                elements: HashMap::new(),
-            },
-            index: ElementIndex::empty(),
+            index: ElementIndex::from_store(&element_store),
+            element_store,
+            satcat: Satcat::load(),
+            storage,
+            merged_during_fetch: None,
+        }
+    }
+
+    /// Why the elements are not saved, if they are not
+    pub fn storage_error(&self) -> Option<&str> {
+        self.storage.as_ref().err().map(|e| e.as_str())
+    }
+
+    /// Updates from the sources of the registry in the background, see swap_in for when it is done.
+    /// Without all, only sources due for an update are fetched.
+    pub fn start_fetch(
+        &mut self,
+        registry: &SourceRegistry,
+        all: bool,
+        force: bool,
+    ) -> ElementFetch {
+        self.merged_during_fetch = Some(Vec::new());
+        ElementFetch::start(
+            registry.to_fetch(all, chrono::Utc::now().timestamp()),
+            registry.priorities(),
+            &self.element_store,
+            force,
+            self.storage.is_ok(),
+        )
+    }
+
+    /// Replaces the elements and the index with a finished fetch, which already saved them.
+    /// The fetch worked on a copy, so what was merged in the meantime is merged into it again.
+    pub fn swap_in(&mut self, fetched: &mut FetchedElements) {
+        let merged = self.merged_during_fetch.take().unwrap_or_default();
+        if let Some((store, index)) = fetched.update.take() {
+            self.element_store = store;
+            self.index = index;
+            for m in merged.iter() {
+                self.element_store
+                    .merge_parsed(m.parsed.copy(), &m.source, &m.priorities);
+            }
+            if !merged.is_empty() {
+                self.index = ElementIndex::from_store(&self.element_store);
+                self.save();
+            }
+        }
+    }
+
+    /// A cancelled fetch has nothing to swap in, what was merged meanwhile is already there
+    pub fn fetch_cancelled(&mut self) {
+        self.merged_during_fetch = None;
+    }
+
+    /// Merges elements from elsewhere, like a Space-Track query, then reindexes and saves.
+    /// The source should be in the registry, else its elements only fill gaps.
+    pub fn merge(
+        &mut self,
+        elements: Vec<Elements>,
+        source: &str,
+        registry: &SourceRegistry,
+    ) -> usize {
+        self.merge_parsed(ParsedElements::Elements(elements), source, registry)
+    }
+
+    /// Like merge, OMM messages are kept to be exported whole
+    fn merge_parsed(
+        &mut self,
+        parsed: ParsedElements,
+        source: &str,
+        registry: &SourceRegistry,
+    ) -> usize {
+        let priorities = registry.priorities();
+        if let Some(merged) = self.merged_during_fetch.as_mut() {
+            merged.push(Merged {
+                parsed: parsed.copy(),
+                source: source.to_string(),
+                priorities: priorities.clone(),
+            });
+        }
+        let taken = self.element_store.merge_parsed(parsed, source, &priorities);
+        if taken > 0 {
+            self.index = ElementIndex::from_store(&self.element_store);
+            self.save();
+        }
+        taken
+    }
+
+    /// Reads an element file and merges it as the given source, which should be in the registry.
+    /// .xml, .kvn and .csv files are read as OMM, all else as 2LE/3LE.
+    /// Broken TLE sets are skipped and reported, a broken OMM fails the import.
+    pub fn import_file(
+        &mut self,
+        path: &Path,
+        source: &str,
+        registry: &SourceRegistry,
+    ) -> Result<ElementImport, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let (parsed, errors) = match OmmFormat::from_path(path) {
+            Some(format) => (ParsedElements::Messages(format.read(&text)?), Vec::new()),
+            None => {
+                let parsed = parse_tle(&text);
+                (ParsedElements::Elements(parsed.elements), parsed.errors)
+            }
+        };
+        let read = parsed.len();
+        Ok(ElementImport {
+            read,
+            taken: self.merge_parsed(parsed, source, registry),
+            errors,
+        })
+    }
+
+    /// Writes the elements of the ids as 2LE or 3LE, returns how many were written
+    pub fn export_tle(&self, path: &Path, ids: &[u64], three_line: bool) -> Result<usize, String> {
+        let mut text = String::new();
+        let mut count = 0;
+        for id in ids {
+            if let Some(elements) = self.get(*id) {
+                text += &write_tle(elements, three_line)?;
+                count += 1;
+            }
         }
+        write_text(path, &text)?;
+        Ok(count)
+    }
+
+    /// Writes the elements of the ids as OMM, returns how many were written.
+    /// Sets that came as OMM are written as they came, the rest gets the originator.
+    pub fn export_omm(
+        &self,
+        path: &Path,
+        ids: &[u64],
+        format: OmmFormat,
+        originator: &str,
+    ) -> Result<usize, String> {
+        let messages = ids
+            .iter()
+            .filter_map(|id| {
+                self.element_store
+                    .message(*id)
+                    .cloned()
+                    .or_else(|| self.get(*id).map(|e| Omm::from_elements(e, originator)))
+            })
+            .collect::<Vec<Omm>>();
+        write_text(path, &format.write(&messages))?;
+        Ok(messages.len())
     }
 
-    pub fn fetch_full_celestrak(&mut self) -> Result<(), String> {
-        match ureq::get("https://celestrak.com/NORAD/elements/gp.php")
-            .query("GROUP", "active")
-            .query("FORMAT", "json")
-            .call()
-        {
-            Ok(response) => match response.into_json::<Vec<Elements>>() {
-                Ok(elements_group) => {
-                    for e in elements_group {
-                        self.element_store.elements.insert(e.norad_id, e);
-                    }
-                    Ok(())
-                }
-                Err(_) => Err("Deserialization error".to_string()),
-            },
-            Err(ureq::Error::Status(status, response)) => Err(format!(
-                "Celestrak network error: {}: {}",
-                status,
-                response.into_string().unwrap()
-            )),
-            Err(_) => Err("Unknown network error".to_string()),
+    /// Replaces the catalogue, like after Satcat::fetch_in_background, and saves it
+    pub fn set_satcat(&mut self, satcat: Satcat) {
+        self.satcat = satcat;
+        if let Err(e) = self.satcat.save() {
+            log::error!("Cannot save the SATCAT: {}", e);
         }
     }
 
-    pub fn save(&self) {
-        let data_dir = init_dirs().expect("Something is wrong, you cant write home!");
-        let data_filename = data_dir.join(Path::new("elements.json"));
-        let f = File::create(data_filename).expect("Could not open elements file");
-        serde_json::to_writer(f, &self.element_store).unwrap();
+    /// Reads a SATCAT file in the CSV or JSON of Celestrak, returns what it had and skipped
+    pub fn import_satcat(&mut self, path: &Path) -> Result<String, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let satcat = Satcat::parse(&text)?;
+        let report = satcat.report();
+        self.set_satcat(satcat);
+        Ok(report)
+    }
+
+    /// Catalogue data of an object, if the SATCAT knows it
+    pub fn satcat(&self, key: u64) -> Option<&SatcatEntry> {
+        self.satcat.get(key)
+    }
+
+    pub fn satcat_len(&self) -> usize {
+        self.satcat.entries.len()
+    }
+
+    /// Ids of the elements matching the query, in the order of the index
+    pub fn query(&self, query: &ElementQuery, order: ElementSort) -> Vec<u64> {
+        self.index
+            .get_by(order)
+            .iter()
+            .copied()
+            .filter(|id| self.get(*id).map_or(false, |e| query.matches(e)))
+            .collect()
+    }
+
+    /// Every known set of an object, oldest first
+    pub fn history(&self, key: u64) -> &[ElementSet] {
+        self.element_store.history(key)
+    }
+
+    /// The set with the epoch closest to the time, the one propagation uses
+    pub fn closest(&self, key: u64, time: NaiveDateTime) -> Option<&ElementSet> {
+        self.element_store.closest(key, time)
+    }
+
+    /// Name of the source an element came from
+    pub fn source_of(&self, key: u64) -> Option<&str> {
+        self.element_store.sources.get(&key).map(|s| s.as_str())
+    }
+
+    /// Writes what changed since the last save
+    pub fn save(&mut self) {
+        if let Ok(storage) = self.storage.as_mut() {
+            if let Err(e) = storage.save(&mut self.element_store) {
+                log::error!("Cannot save elements: {}", e);
+            }
+        }
     }
 
     pub fn get(&self, key: u64) -> Option<&sgp4::Elements> {
@@ -85,3 +289,140 @@ impl ElementDb {
         &self.index
     }
 }
+
+/// Days from the epoch to the time, negative if the epoch is later
+pub fn element_age_days(elements: &Elements, time: NaiveDateTime) -> f64 {
+    (time - elements.datetime).num_seconds() as f64 / 86400.0
+}
+
+fn write_text(path: &Path, text: &str) -> Result<(), String> {
+    File::create(path)
+        .and_then(|mut f| f.write_all(text.as_bytes()))
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::element_fetch::FetchStatus;
+    use crate::elements::element_source::ElementSource;
+    use crate::elements::element_util::test_elements as elements;
+    use crate::elements::omm::OmmCovariance;
+
+    /// A db that saves nothing
+    fn db() -> ElementDb {
+        let element_store = ElementStore::default();
+        ElementDb {
+            index: ElementIndex::from_store(&element_store),
+            element_store,
+            satcat: Satcat::default(),
+            storage: Err("not saved in tests".to_string()),
+            merged_during_fetch: None,
+        }
+    }
+
+    fn finish(fetch: &mut ElementFetch) -> FetchedElements {
+        for _ in 0..500 {
+            fetch.update_status();
+            if let Some(fetched) = fetch.take_result() {
+                return fetched;
+            }
+            if let FetchStatus::Cancelled = fetch.get_status() {
+                panic!("fetch cancelled");
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("fetch did not finish");
+    }
+
+    #[test]
+    fn merges_during_a_fetch_are_kept() {
+        let path = std::env::temp_dir().join(format!("satwatch-fetch-{}.kvn", std::process::id()));
+        write_text(
+            &path,
+            &OmmFormat::Kvn.write(&[Omm::from_elements(&elements(2, 5), "test")]),
+        )
+        .unwrap();
+        let mut source = ElementSource::file("fetched", &path, 10);
+        source.enabled = true;
+        let registry = SourceRegistry {
+            sources: vec![source],
+        };
+
+        let mut db = db();
+        db.merge(vec![elements(1, 1)], "fetched", &registry);
+        let mut fetch = db.start_fetch(&registry, true, false);
+        // an import and a newer set while the fetch works on its copy
+        db.merge(vec![elements(3, 2)], "imported", &registry);
+        db.merge(vec![elements(1, 3)], "fetched", &registry);
+        let mut fetched = finish(&mut fetch);
+        db.swap_in(&mut fetched);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(db.index().get_by(ElementSort::ById), &vec![1, 2, 3]);
+        assert_eq!(db.get(1).unwrap().datetime, elements(1, 3).datetime);
+        assert_eq!(db.history(1).len(), 2);
+        assert_eq!(db.source_of(3), Some("imported"));
+        assert_eq!(db.source_of(2), Some("fetched"));
+        assert_eq!(
+            db.element_store.message(2).map(|m| m.originator.as_str()),
+            Some("test")
+        );
+        assert!(db.element_store.message(1).is_none());
+        // nothing left to merge again on the next fetch
+        assert!(db.merged_during_fetch.is_none());
+    }
+
+    #[test]
+    fn merges_are_only_remembered_during_a_fetch() {
+        let registry = SourceRegistry { sources: vec![] };
+        let mut db = db();
+        db.merge(vec![elements(1, 1)], "imported", &registry);
+        assert!(db.merged_during_fetch.is_none());
+        let _fetch = db.start_fetch(&registry, true, false);
+        db.merge(vec![elements(2, 1)], "imported", &registry);
+        assert_eq!(db.merged_during_fetch.as_ref().map(|m| m.len()), Some(1));
+        db.fetch_cancelled();
+        assert!(db.merged_during_fetch.is_none());
+    }
+
+    #[test]
+    fn imported_omm_is_exported_whole() {
+        let mut message = Omm::from_elements(&elements(1, 5), "ORIGIN");
+        message.comments = vec!["imported".to_string()];
+        message.covariance = Some(OmmCovariance {
+            ref_frame: None,
+            values: [1e-3; 21],
+        });
+        message.user_defined = vec![("RCS".to_string(), "SMALL".to_string())];
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("satwatch-import-{}.kvn", std::process::id()));
+        let output = dir.join(format!("satwatch-export-{}.xml", std::process::id()));
+        write_text(&input, &OmmFormat::Kvn.write(&[message.clone()])).unwrap();
+
+        let registry = SourceRegistry { sources: vec![] };
+        let mut db = db();
+        let import = db.import_file(&input, "imported", &registry).unwrap();
+        assert_eq!((import.read, import.taken), (1, 1));
+        db.merge(vec![elements(2, 5)], "imported", &registry);
+        db.export_omm(&output, &[1, 2], OmmFormat::Xml, "SATWATCH")
+            .unwrap();
+        let exported = OmmFormat::Xml
+            .read(&std::fs::read_to_string(&output).unwrap())
+            .unwrap();
+        assert_eq!(exported[0], message);
+        assert_eq!(exported[1].originator, "SATWATCH");
+
+        // a set of the same epoch replacing it has no message
+        db.merge(vec![elements(1, 5)], "imported", &registry);
+        db.export_omm(&output, &[1], OmmFormat::Xml, "SATWATCH")
+            .unwrap();
+        let exported = OmmFormat::Xml
+            .read(&std::fs::read_to_string(&output).unwrap())
+            .unwrap();
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&output).ok();
+        assert_eq!(exported[0].originator, "SATWATCH");
+        assert_eq!(exported[0].covariance, None);
+    }
+}
@@ -10,18 +10,21 @@ use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::elements::element_util::element_copy;
+use crate::elements::element_store::ElementSet;
+use crate::elements::element_util::{closest_epoch, element_copy};
 use log::{error, warn};
 
 pub struct ElementUpdate {
     pub id: u64,
     pub state: PlanetaryStateVector,
+    /// epoch of the element set that was used, in ET
+    pub epoch: f64,
     pub orbit_points: Option<Vec<PlanetaryStateVector>>,
 }
 
 pub struct ElementEngine {
     element_rx: Receiver<ElementUpdate>,
-    add_tx: Sender<Elements>,
+    add_tx: Sender<(u64, Vec<Elements>)>,
     remove_tx: Sender<u64>,
     timebase_tx: Sender<Timebase>,
 }
@@ -32,10 +35,11 @@ pub struct WrappedElements {
 }
 
 struct WorkerData {
-    elements: HashMap<u64, WrappedElements>,
+    /// sorted by epoch
+    elements: HashMap<u64, Vec<WrappedElements>>,
     timebase: Timebase,
     element_tx: Sender<ElementUpdate>,
-    add_rx: Receiver<Elements>,
+    add_rx: Receiver<(u64, Vec<Elements>)>,
     remove_rx: Receiver<u64>,
     timebase_rx: Receiver<Timebase>,
 }
@@ -71,7 +75,22 @@ impl ElementEngine {
     }
 
     pub fn add(&self, element: &Elements) {
-        self.add_tx.send(element_copy(element)).unwrap();
+        self.add_tx
+            .send((element.norad_id, vec![element_copy(element)]))
+            .unwrap();
+    }
+
+    /// Propagates with the set closest to the current time, replacing what was there.
+    /// The sets have to be of the same object and sorted by epoch.
+    pub fn add_history(&self, sets: &[ElementSet]) {
+        if let Some(first) = sets.first() {
+            self.add_tx
+                .send((
+                    first.elements.norad_id,
+                    sets.iter().map(|s| element_copy(&s.elements)).collect(),
+                ))
+                .unwrap();
+        }
     }
 
     pub fn remove(&self, element: u64) {
@@ -127,17 +146,17 @@ impl WorkerData {
     fn process_adds(&mut self) {
         'add_loop: loop {
             match self.add_rx.try_recv() {
-                Ok(elements) => {
-                    self.elements.insert(
-                        elements.norad_id,
-                        WrappedElements {
-                            epoch: crate::timebase::date_time_to_et(DateTime::<Utc>::from_utc(
-                                elements.datetime,
-                                Utc,
-                            )),
+                Ok((id, sets)) => {
+                    let wrapped = sets
+                        .into_iter()
+                        .map(|elements| WrappedElements {
+                            epoch: crate::timebase::date_time_to_et(
+                                DateTime::<Utc>::from_naive_utc_and_offset(elements.datetime, Utc),
+                            ),
                             elements,
-                        },
-                    );
+                        })
+                        .collect();
+                    self.elements.insert(id, wrapped);
                 }
                 Err(_) => {
                     break 'add_loop;
@@ -161,7 +180,12 @@ impl WorkerData {
 
     fn update(&mut self) {
         let start = Instant::now();
-        for (id, element) in &self.elements {
+        let now = self.timebase.now();
+        for (id, sets) in &self.elements {
+            let element = match closest_epoch(sets, now, |s| s.epoch) {
+                Some(element) => element,
+                None => continue,
+            };
             let tle_epoch = element.epoch;
             let minutes = self.timebase.minutes_since(tle_epoch);
             if let Ok(constants) = sgp4::Constants::from_elements(&element.elements) {
@@ -179,6 +203,7 @@ impl WorkerData {
                     if let Err(e) = self.element_tx.send(ElementUpdate {
                         id: *id,
                         state,
+                        epoch: tle_epoch,
                         orbit_points: None,
                     }) {
                         error!(
@@ -0,0 +1,274 @@
+// Downloads elements from the configured sources on a separate thread.
+// The new store and its index are built on that thread too, so the ui only has to swap them in.
+// ETag and Last-Modified of each source are sent back, to not download an unchanged catalogue again.
+
+use crate::elements::element_source::{ElementSource, ParsedElements, FILE_URL_PREFIX};
+use crate::elements::element_storage::ElementStorage;
+use crate::elements::element_store::ElementStore;
+use crate::elements::space_track::{SpaceTrackClient, SPACE_TRACK_URL};
+use crate::elements::ElementIndex;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::*;
+use std::sync::Arc;
+
+const PROGRESS_STEP: u64 = 256 * 1024;
+
+pub enum SourceOutcome {
+    Updated {
+        fetched: usize,
+        /// how many won against what was there
+        taken: usize,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    NotModified,
+    Failed(String),
+}
+
+pub struct SourceResult {
+    pub source: String,
+    pub outcome: SourceOutcome,
+}
+
+/// A finished fetch, ready to be swapped into the ElementDb
+pub struct FetchedElements {
+    /// None if no source delivered anything new
+    pub(crate) update: Option<(ElementStore, ElementIndex)>,
+    pub results: Vec<SourceResult>,
+}
+
+pub enum FetchStatus {
+    Invalid,
+    Connecting(String),
+    /// the total is unknown when the server compresses or chunks the answer
+    Progress {
+        source: String,
+        bytes_read: u64,
+        bytes_total: Option<u64>,
+    },
+    /// all downloaded, now merging and indexing
+    Indexing,
+    Cancelled,
+    Complete(FetchedElements),
+}
+
+pub struct ElementFetch {
+    rx: Receiver<FetchStatus>,
+    cancel: Arc<AtomicBool>,
+    last_status: FetchStatus,
+}
+
+struct ProgressReader<R: Read> {
+    inner: R,
+    source: String,
+    bytes_read: u64,
+    bytes_total: Option<u64>,
+    last_report: u64,
+    cancel: Arc<AtomicBool>,
+    tx: SyncSender<FetchStatus>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "cancelled"));
+        }
+        let count = self.inner.read(buf)?;
+        self.bytes_read += count as u64;
+        if self.bytes_read - self.last_report > PROGRESS_STEP {
+            self.last_report = self.bytes_read;
+            // if the ui is busy, it just misses a progress update
+            let _ = self.tx.try_send(FetchStatus::Progress {
+                source: self.source.clone(),
+                bytes_read: self.bytes_read,
+                bytes_total: self.bytes_total,
+            });
+        }
+        Ok(count)
+    }
+}
+
+impl ElementFetch {
+    /// Fetches the sources one after another and merges them into a copy of the current elements.
+    /// Without force, the cache validators of the sources are sent along.
+    /// Without save, the result is only kept in memory.
+    pub(crate) fn start(
+        sources: Vec<ElementSource>,
+        priorities: HashMap<String, i32>,
+        current: &ElementStore,
+        force: bool,
+        save: bool,
+    ) -> Self {
+        let (tx, rx) = sync_channel(10);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let current = current.copy();
+        let worker_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let status = fetch_all(
+                sources,
+                priorities,
+                current,
+                force,
+                save,
+                worker_cancel,
+                &tx,
+            );
+            tx.send(status).ok();
+        });
+
+        Self {
+            rx,
+            cancel,
+            last_status: FetchStatus::Invalid,
+        }
+    }
+
+    /// The download stops at the next read, nothing is swapped in
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn update_status(&mut self) {
+        while let Ok(new_status) = self.rx.try_recv() {
+            self.last_status = new_status;
+        }
+    }
+
+    pub fn get_status(&self) -> &FetchStatus {
+        &self.last_status
+    }
+
+    /// Takes the finished fetch out, leaving the fetch invalid
+    pub fn take_result(&mut self) -> Option<FetchedElements> {
+        match std::mem::replace(&mut self.last_status, FetchStatus::Invalid) {
+            FetchStatus::Complete(fetched) => Some(fetched),
+            other => {
+                self.last_status = other;
+                None
+            }
+        }
+    }
+}
+
+fn fetch_all(
+    sources: Vec<ElementSource>,
+    priorities: HashMap<String, i32>,
+    mut store: ElementStore,
+    force: bool,
+    save: bool,
+    cancel: Arc<AtomicBool>,
+    tx: &SyncSender<FetchStatus>,
+) -> FetchStatus {
+    let mut results = Vec::new();
+    let mut changed = false;
+    for source in sources.iter() {
+        let _ = tx.try_send(FetchStatus::Connecting(source.name.clone()));
+        let outcome = match fetch_source(source, force, &cancel, tx) {
+            Ok(Some((parsed, etag, last_modified))) => {
+                let fetched = parsed.len();
+                let taken = store.merge_parsed(parsed, &source.name, &priorities);
+                changed = true;
+                SourceOutcome::Updated {
+                    fetched,
+                    taken,
+                    etag,
+                    last_modified,
+                }
+            }
+            Ok(None) => SourceOutcome::NotModified,
+            Err(e) => SourceOutcome::Failed(e),
+        };
+        if cancel.load(Ordering::Relaxed) {
+            return FetchStatus::Cancelled;
+        }
+        results.push(SourceResult {
+            source: source.name.clone(),
+            outcome,
+        });
+    }
+
+    let update = if changed {
+        let _ = tx.try_send(FetchStatus::Indexing);
+        let index = ElementIndex::from_store(&store);
+        if cancel.load(Ordering::Relaxed) {
+            return FetchStatus::Cancelled;
+        }
+        if save {
+            if let Err(e) = ElementStorage::open().and_then(|mut s| s.save(&mut store)) {
+                log::error!("Cannot save elements: {}", e);
+            }
+        }
+        Some((store, index))
+    } else {
+        None
+    };
+    FetchStatus::Complete(FetchedElements { update, results })
+}
+
+type Fetched = (ParsedElements, Option<String>, Option<String>);
+
+/// The elements and the new cache validators, none if nothing changed
+fn fetch_source(
+    source: &ElementSource,
+    force: bool,
+    cancel: &Arc<AtomicBool>,
+    tx: &SyncSender<FetchStatus>,
+) -> Result<Option<Fetched>, String> {
+    if let Some(path) = source.url.strip_prefix(FILE_URL_PREFIX) {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        return Ok(Some((source.format.read(&text)?, None, None)));
+    }
+    // space-track needs the session of the stored account, and sends no cache validators
+    if source.url.starts_with(SPACE_TRACK_URL) {
+        let text = SpaceTrackClient::from_stored()?.get(&source.url())?;
+        return Ok(Some((source.format.read(&text)?, None, None)));
+    }
+
+    let mut request = ureq::get(&source.url());
+    if !force {
+        if let Some(etag) = &source.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &source.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+    }
+
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, response)) => {
+            return Err(format!(
+                "Network error: {}: {}",
+                status,
+                response.into_string().unwrap_or_default()
+            ))
+        }
+        Err(e) => return Err(format!("Network error: {}", e)),
+    };
+    if response.status() == 304 {
+        return Ok(None);
+    }
+
+    let etag = response.header("ETag").map(|s| s.to_string());
+    let last_modified = response.header("Last-Modified").map(|s| s.to_string());
+    let bytes_total = response
+        .header("Content-Length")
+        .and_then(|l| l.parse::<u64>().ok());
+    let mut reader = ProgressReader {
+        inner: response.into_reader(),
+        source: source.name.clone(),
+        bytes_read: 0,
+        bytes_total,
+        last_report: 0,
+        cancel: cancel.clone(),
+        tx: tx.clone(),
+    };
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Download failed: {}", e))?;
+    let parsed = source.format.read(&text)?;
+    Ok(Some((parsed, etag, last_modified)))
+}
@@ -1,10 +1,37 @@
+// Lookup of element sets as the user types. Names are split into words, which are kept sorted for prefix
+// search and split into trigrams to find words with typos. NORAD and COSPAR ids are looked up directly.
+// Every way an object is found gives it a score, the words of a query add up, the best score is first.
+
 use crate::elements::element_store::ElementStore;
+use crate::elements::element_util::launch_year_of;
 use std::collections::HashMap;
 
+const SCORE_NORAD: f64 = 1000.0;
+const SCORE_COSPAR: f64 = 900.0;
+const SCORE_NAME: f64 = 800.0;
+const SCORE_COSPAR_PREFIX: f64 = 600.0;
+const SCORE_NAME_PREFIX: f64 = 500.0;
+const SCORE_WORD: f64 = 100.0;
+const SCORE_WORD_PREFIX: f64 = 60.0;
+const SCORE_WORD_FUZZY: f64 = 40.0;
+/// less per edit
+const SCORE_EDIT: f64 = 15.0;
+/// words shorter than this are not searched with typos, too much matches
+const FUZZY_MIN_LENGTH: usize = 4;
+
 pub struct ElementIndex {
     by_name: Vec<u64>,
     by_id: Vec<u64>,
-    text_index: HashMap<String, Vec<u64>>,
+    /// upper case names with their id, sorted
+    names: Vec<(String, u64)>,
+    /// words of the names, sorted, with the ids having them
+    words: Vec<(String, Vec<u64>)>,
+    /// trigrams of the padded words, to positions in words
+    trigrams: HashMap<String, Vec<usize>>,
+    /// COSPAR ids like 1998-067A, sorted
+    cospar: Vec<(String, u64)>,
+    /// position in by_name, to order equal scores
+    name_rank: HashMap<u64, usize>,
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -13,21 +40,94 @@ pub enum ElementSort {
     ById,
 }
 
+/// An object found by a search, higher scores are better matches
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SearchHit {
+    pub id: u64,
+    pub score: f64,
+}
+
 fn sat_name_split(input: &str) -> Vec<String> {
     input
         .to_uppercase()
-        .clone()
-        .split(&['-', '+', ' ', '=', '/'][..])
+        .split(&['-', '+', ' ', '=', '/', '(', ')', ','][..])
+        .filter(|x| !x.is_empty())
         .map(|x| x.to_owned())
         .collect()
 }
 
+/// 1998-067A for 1998-067A, 98067A or 98-067a, None if it does not look like one.
+/// Without the piece letters only the dash tells it from a NORAD id, 1998-067 is one, 25544 is not.
+pub fn normalize_cospar(input: &str) -> Option<String> {
+    let input = input.trim().to_uppercase();
+    let compact = input.replace(['-', ' '], "");
+    let digits = compact.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (year, rest) = match digits {
+        5 => (launch_year_of(compact[..2].parse().ok()?), &compact[2..]),
+        7 => (compact[..4].parse().ok()?, &compact[4..]),
+        _ => return None,
+    };
+    let pieces = &rest[3..];
+    if pieces.len() > 3 || !pieces.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    if pieces.is_empty() && !input.contains('-') {
+        return None;
+    }
+    Some(format!("{}-{}", year, rest))
+}
+
+fn trigrams(word: &str) -> Vec<String> {
+    let padded = format!("${}$", word).chars().collect::<Vec<char>>();
+    padded
+        .windows(3)
+        .map(|w| w.iter().collect::<String>())
+        .collect()
+}
+
+/// Edits from a to b, swapping neighbours counts as one. None if more than max.
+fn edit_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut before = Vec::new();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for j in 0..b.len() {
+            let mut edits = (previous[j] + usize::from(a[i] != b[j]))
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                edits = edits.min(before[j - 1] + 1);
+            }
+            current[j + 1] = edits;
+        }
+        if current.iter().min().copied().unwrap_or(0) > max {
+            return None;
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous.last().copied().filter(|d| *d <= max)
+}
+
+/// The entries of a sorted list starting with the prefix
+fn prefix_range<'a, T>(sorted: &'a [(String, T)], prefix: &str) -> &'a [(String, T)] {
+    let start = sorted.partition_point(|(s, _)| s.as_str() < prefix);
+    let len = sorted[start..].partition_point(|(s, _)| s.starts_with(prefix));
+    &sorted[start..start + len]
+}
+
 impl ElementIndex {
     pub fn empty() -> Self {
         Self {
             by_name: vec![],
             by_id: vec![],
-            text_index: HashMap::new(),
+            names: vec![],
+            words: vec![],
+            trigrams: HashMap::new(),
+            cospar: vec![],
+            name_rank: HashMap::new(),
         }
     }
 
@@ -36,31 +136,44 @@ impl ElementIndex {
         let elements = &store.elements;
 
         new_index.by_id = elements.keys().cloned().collect::<Vec<u64>>();
+        new_index.by_id.sort_unstable();
         let mut values = elements
             .iter()
             .map(|(id, element)| (element.object_name.clone().unwrap_or("".to_owned()), *id))
             .collect::<Vec<(String, u64)>>();
         values.sort_by(|(str, _id), (str2, _id2)| str.cmp(str2));
 
+        let mut words: HashMap<String, Vec<u64>> = HashMap::new();
         for (name, id) in values {
+            new_index.name_rank.insert(id, new_index.by_name.len());
             new_index.by_name.push(id);
-            if let Some(e) = new_index.text_index.get_mut(&name) {
-                e.push(id);
-            } else {
-                new_index.text_index.insert(name.clone(), vec![id]);
-            }
-
-            let split_name = sat_name_split(&name);
-            for n in split_name {
-                if !n.is_empty() && n != name {
-                    if let Some(e) = new_index.text_index.get_mut(&n) {
-                        e.push(id);
-                    } else {
-                        new_index.text_index.insert(n, vec![id]);
-                    }
+            new_index.names.push((name.to_uppercase(), id));
+            for word in sat_name_split(&name) {
+                let ids = words.entry(word).or_default();
+                if ids.last() != Some(&id) {
+                    ids.push(id);
                 }
             }
         }
+        new_index.names.sort();
+        new_index.words = words.into_iter().collect();
+        new_index.words.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (i, (word, _)) in new_index.words.iter().enumerate() {
+            for trigram in trigrams(word) {
+                new_index.trigrams.entry(trigram).or_default().push(i);
+            }
+        }
+
+        new_index.cospar = elements
+            .iter()
+            .filter_map(|(id, e)| {
+                e.international_designator
+                    .as_deref()
+                    .and_then(normalize_cospar)
+                    .map(|c| (c, *id))
+            })
+            .collect();
+        new_index.cospar.sort();
 
         new_index
     }
@@ -72,19 +185,283 @@ impl ElementIndex {
         }
     }
 
+    /// Ranked search, or with exact_match only whole names and words, each object once
     pub fn find_str(&self, s: &str, exact_match: bool) -> Vec<u64> {
-        let words = if exact_match {
-            vec![s.to_string()]
-        } else {
-            sat_name_split(s)
+        if !exact_match {
+            return self.search(s).into_iter().map(|hit| hit.id).collect();
+        }
+
+        let s = s.trim().to_uppercase();
+        let mut result = prefix_range(&self.names, &s)
+            .iter()
+            .filter(|(name, _)| *name == s)
+            .map(|(_, id)| *id)
+            .collect::<Vec<u64>>();
+        if let Ok(i) = self.words.binary_search_by(|(w, _)| w.as_str().cmp(&s)) {
+            result.extend(self.words[i].1.iter());
+        }
+        let mut seen = std::collections::HashSet::new();
+        result.retain(|id| seen.insert(*id));
+        result
+    }
+
+    /// Objects matching the text by NORAD id, COSPAR id, name or name words, best first.
+    /// Words match whole, as prefix or with a typo or two, the scores of all words add up.
+    pub fn search(&self, text: &str) -> Vec<SearchHit> {
+        let text = text.trim().to_uppercase();
+        if text.is_empty() {
+            return Vec::new();
+        }
+        let mut scores: HashMap<u64, f64> = HashMap::new();
+        let mut add = |id: u64, score: f64| {
+            let entry = scores.entry(id).or_insert(0.0);
+            *entry += score;
         };
 
-        let mut result = Vec::new();
-        for word in words {
-            if let Some(ids) = self.text_index.get(&word) {
-                result.extend(ids.iter());
+        if let Ok(id) = text.parse::<u64>() {
+            if self.by_id.binary_search(&id).is_ok() {
+                add(id, SCORE_NORAD);
             }
         }
-        result
+        if let Some(cospar) = normalize_cospar(&text) {
+            for (c, id) in prefix_range(&self.cospar, &cospar) {
+                add(
+                    *id,
+                    if *c == cospar {
+                        SCORE_COSPAR
+                    } else {
+                        SCORE_COSPAR_PREFIX
+                    },
+                );
+            }
+        }
+        for (name, id) in prefix_range(&self.names, &text) {
+            add(
+                *id,
+                if *name == text {
+                    SCORE_NAME
+                } else {
+                    SCORE_NAME_PREFIX
+                },
+            );
+        }
+        for word in sat_name_split(&text) {
+            for (id, score) in self.word_scores(&word) {
+                add(id, score);
+            }
+        }
+
+        let mut hits = scores
+            .into_iter()
+            .map(|(id, score)| SearchHit { id, score })
+            .collect::<Vec<SearchHit>>();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| self.name_rank.get(&a.id).cmp(&self.name_rank.get(&b.id)))
+        });
+        hits
+    }
+
+    /// Best score of each object for one word of a query
+    fn word_scores(&self, word: &str) -> HashMap<u64, f64> {
+        let mut scores: HashMap<u64, f64> = HashMap::new();
+        let mut add = |ids: &[u64], score: f64| {
+            for id in ids {
+                let entry = scores.entry(*id).or_insert(score);
+                *entry = entry.max(score);
+            }
+        };
+
+        for (w, ids) in prefix_range(&self.words, word) {
+            // shorter words are closer to what was typed
+            let score = if w == word {
+                SCORE_WORD
+            } else {
+                SCORE_WORD_PREFIX - (w.len() - word.len()).min(20) as f64
+            };
+            add(ids, score);
+        }
+
+        let chars = word.chars().collect::<Vec<char>>();
+        // a number with a typo is just another number
+        if chars.len() < FUZZY_MIN_LENGTH || chars.iter().all(char::is_ascii_digit) {
+            return scores;
+        }
+        let max_edits = if chars.len() < 8 { 1 } else { 2 };
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for trigram in trigrams(word) {
+            for i in self.trigrams.get(&trigram).into_iter().flatten() {
+                *shared.entry(*i).or_insert(0) += 1;
+            }
+        }
+        for (i, _) in shared.into_iter().filter(|(_, count)| *count >= 2) {
+            let (w, ids) = &self.words[i];
+            let w = w.chars().collect::<Vec<char>>();
+            // a typo in what is still being typed
+            let prefix = &w[..w.len().min(chars.len())];
+            let edits = edit_distance(&chars, &w, max_edits)
+                .into_iter()
+                .chain(edit_distance(&chars, prefix, max_edits))
+                .min();
+            if let Some(edits) = edits.filter(|e| *e > 0) {
+                add(ids, SCORE_WORD_FUZZY - SCORE_EDIT * edits as f64);
+            }
+        }
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::element_util::test_elements;
+
+    fn index() -> ElementIndex {
+        let mut store = ElementStore::default();
+        for (norad_id, name, cospar) in [
+            (25544, "ISS (ZARYA)", "1998-067A"),
+            (49000, "ISS DEB", "1998-067ZZ"),
+            (48274, "CSS (TIANHE)", "2021-035A"),
+            (44713, "STARLINK-1007", "2019-074A"),
+            (44714, "STARLINK-1008", "2019-074B"),
+            (20580, "HST", "1990-037B"),
+            (43013, "NOAA 20", "17073A"),
+            (33591, "NOAA 19", "09005A"),
+            (60000, "OBJECT X", "2025-544A"),
+        ] {
+            let mut elements = test_elements(norad_id, 1);
+            elements.object_name = Some(name.to_string());
+            elements.international_designator = Some(cospar.to_string());
+            store.elements.insert(norad_id, elements);
+        }
+        ElementIndex::from_store(&store)
+    }
+
+    #[test]
+    fn search_ranks() {
+        let index = index();
+        for (query, expected) in [
+            ("25544", vec![25544]),
+            ("98067A", vec![25544]),
+            ("1998-067a", vec![25544]),
+            ("2017-073A", vec![43013]),
+            // prefixes of the designator, equal scores by name
+            ("1998-067", vec![25544, 49000]),
+            ("ISS", vec![25544, 49000]),
+            ("iss (zarya)", vec![25544, 49000]),
+            ("NOAA", vec![33591, 43013]),
+            // the words add up
+            ("starlink 1008", vec![44714, 44713]),
+            ("TIANHE", vec![48274]),
+            // with typos
+            ("TAINHE", vec![48274]),
+            ("STARLNK", vec![44713, 44714]),
+            ("ISX", vec![]),
+            ("", vec![]),
+        ] {
+            let hits = index.search(query);
+            let ids = hits.iter().map(|hit| hit.id).collect::<Vec<u64>>();
+            assert_eq!(ids, expected, "{}", query);
+            assert!(
+                hits.windows(2).all(|w| w[0].score >= w[1].score),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn search_finds_each_object_once() {
+        let index = index();
+        // by name, name prefix and two words
+        let hits = index.search("ISS (ZARYA)");
+        assert_eq!(hits.iter().filter(|hit| hit.id == 25544).count(), 1);
+        assert_eq!(hits[0].score, SCORE_NAME + 2.0 * SCORE_WORD);
+    }
+
+    #[test]
+    fn find_str_exact() {
+        let index = index();
+        for (query, expected) in [
+            ("ISS", vec![25544, 49000]),
+            ("iss (zarya)", vec![25544]),
+            ("ZARYA", vec![25544]),
+            // the whole name and a word, once
+            ("HST", vec![20580]),
+            ("ZARY", vec![]),
+        ] {
+            assert_eq!(index.find_str(query, true), expected, "{}", query);
+        }
+        assert_eq!(index.find_str("TAINHE", false), vec![48274]);
+    }
+
+    #[test]
+    fn cospar_normalized() {
+        for (input, expected) in [
+            ("1998-067A", Some("1998-067A")),
+            ("98067A", Some("1998-067A")),
+            (" 98-067a ", Some("1998-067A")),
+            ("09005A", Some("2009-005A")),
+            ("1998-067ZZ", Some("1998-067ZZ")),
+            ("1998-067", Some("1998-067")),
+            ("98-067", Some("1998-067")),
+            // NORAD ids
+            ("25544", None),
+            ("1998067", None),
+            ("1998-067ABCD", None),
+            ("1998-06", None),
+            ("1998-067A1", None),
+            ("98067Ä", None),
+            ("ISS", None),
+            ("", None),
+        ] {
+            assert_eq!(normalize_cospar(input).as_deref(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn prefix_ranges() {
+        let sorted = [("A", 1), ("AB", 2), ("ABC", 3), ("B", 4)]
+            .map(|(s, i)| (s.to_string(), i))
+            .to_vec();
+        for (prefix, expected) in [
+            ("AB", vec![2, 3]),
+            ("A", vec![1, 2, 3]),
+            ("", vec![1, 2, 3, 4]),
+            ("B", vec![4]),
+            ("ABCD", vec![]),
+            ("C", vec![]),
+        ] {
+            let found = prefix_range(&sorted, prefix)
+                .iter()
+                .map(|(_, i)| *i)
+                .collect::<Vec<i32>>();
+            assert_eq!(found, expected, "{}", prefix);
+        }
+    }
+
+    #[test]
+    fn edit_distances() {
+        for (a, b, max, expected) in [
+            ("TIANHE", "TIANHE", 2, Some(0)),
+            ("TIANHE", "TAINHE", 1, Some(1)),
+            ("AC", "CA", 1, Some(1)),
+            ("STARLNK", "STARLINK", 1, Some(1)),
+            ("KITTEN", "SITTING", 3, Some(3)),
+            ("KITTEN", "SITTING", 2, None),
+            ("", "ABC", 3, Some(3)),
+            ("ABCDEFGH", "ABC", 2, None),
+        ] {
+            let a = a.chars().collect::<Vec<char>>();
+            let b = b.chars().collect::<Vec<char>>();
+            assert_eq!(edit_distance(&a, &b, max), expected, "{:?} {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn trigrams_of_padded_words() {
+        assert_eq!(trigrams("ISS"), vec!["$IS", "ISS", "SS$"]);
+        assert_eq!(trigrams("X"), vec!["$X$"]);
     }
 }
@@ -0,0 +1,701 @@
+// Queries over the orbit and the identity of element sets, built in code or parsed from text like
+//   regime leo and inc 50..60 and (name /^STARLINK/ or norad < 30000) and not launch ..2010
+// Conditions next to each other without and/or are joined with and, and binds stronger than or.
+// Numbers take a range a..b, a.. or ..b, a comparison < <= > >= or = , or a single value.
+// Ranges include their ends, < and > do not.
+// Altitudes are above the equatorial radius in km, period in minutes, angles in degrees.
+
+use crate::bodies::Planet;
+use crate::elements::element_util::launch_year_of;
+use crate::elements::maneuver::semi_major_axis;
+use regex::{Regex, RegexBuilder};
+use sgp4::Elements;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Fields a query knows, for help texts
+pub const QUERY_FIELDS: &str = "perigee, apogee, inc, period, ecc, regime, launch, norad, name";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Regime {
+    /// apogee below 2000 km
+    Leo,
+    Meo,
+    /// period of about a sidereal day, near circular
+    Geo,
+    /// eccentric, or beyond geostationary
+    Heo,
+}
+
+/// Inclusive unless exclusive, None is open
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Bounds {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// the bound itself does not match, like for < and >
+    pub min_exclusive: bool,
+    pub max_exclusive: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum QueryCondition {
+    /// km
+    PerigeeAltitude(Bounds),
+    ApogeeAltitude(Bounds),
+    /// deg
+    Inclination(Bounds),
+    /// minutes
+    Period(Bounds),
+    Eccentricity(Bounds),
+    Regime(Regime),
+    /// from the COSPAR id
+    LaunchYear(Bounds),
+    NoradId(Bounds),
+    /// case insensitive, anywhere in the name
+    Name(Regex),
+}
+
+#[derive(Clone, Debug)]
+pub enum ElementQuery {
+    Condition(QueryCondition),
+    And(Vec<ElementQuery>),
+    Or(Vec<ElementQuery>),
+    Not(Box<ElementQuery>),
+}
+
+/// What the conditions look at, derived from the mean elements
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrbitParameters {
+    /// km
+    pub perigee_altitude: f64,
+    pub apogee_altitude: f64,
+    /// minutes
+    pub period: f64,
+    pub regime: Regime,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    /// quoted or between slashes
+    Pattern(String),
+    Operator(String),
+    Open,
+    Close,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Bounds {
+    pub fn new(min: Option<f64>, max: Option<f64>) -> Self {
+        Self {
+            min,
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Values up to max, without it if exclusive
+    pub fn below(max: f64, exclusive: bool) -> Self {
+        Self {
+            max: Some(max),
+            max_exclusive: exclusive,
+            ..Default::default()
+        }
+    }
+
+    /// Values from min on, without it if exclusive
+    pub fn above(min: f64, exclusive: bool) -> Self {
+        Self {
+            min: Some(min),
+            min_exclusive: exclusive,
+            ..Default::default()
+        }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        let above = match self.min {
+            Some(min) if self.min_exclusive => value > min,
+            Some(min) => value >= min,
+            None => true,
+        };
+        let below = match self.max {
+            Some(max) if self.max_exclusive => value < max,
+            Some(max) => value <= max,
+            None => true,
+        };
+        above && below
+    }
+}
+
+impl Regime {
+    pub fn of(elements: &Elements, apogee_altitude: f64) -> Self {
+        if apogee_altitude < 2000.0 {
+            Regime::Leo
+        } else if elements.eccentricity >= 0.25 {
+            Regime::Heo
+        } else if (0.9..=1.1).contains(&elements.mean_motion) {
+            Regime::Geo
+        } else if elements.mean_motion > 1.1 {
+            Regime::Meo
+        } else {
+            Regime::Heo
+        }
+    }
+}
+
+impl OrbitParameters {
+    pub fn from_elements(elements: &Elements) -> Self {
+        let radius = Planet::Earth.body().radius_equatorial;
+        let a = semi_major_axis(elements.mean_motion);
+        let perigee_altitude = a * (1.0 - elements.eccentricity) - radius;
+        let apogee_altitude = a * (1.0 + elements.eccentricity) - radius;
+        Self {
+            perigee_altitude,
+            apogee_altitude,
+            period: 1440.0 / elements.mean_motion,
+            regime: Regime::of(elements, apogee_altitude),
+        }
+    }
+}
+
+impl QueryCondition {
+    pub fn matches(&self, elements: &Elements, orbit: &OrbitParameters) -> bool {
+        match self {
+            QueryCondition::PerigeeAltitude(b) => b.contains(orbit.perigee_altitude),
+            QueryCondition::ApogeeAltitude(b) => b.contains(orbit.apogee_altitude),
+            QueryCondition::Inclination(b) => b.contains(elements.inclination),
+            QueryCondition::Period(b) => b.contains(orbit.period),
+            QueryCondition::Eccentricity(b) => b.contains(elements.eccentricity),
+            QueryCondition::Regime(r) => orbit.regime == *r,
+            QueryCondition::LaunchYear(b) => {
+                launch_year(elements).map_or(false, |y| b.contains(y as f64))
+            }
+            QueryCondition::NoradId(b) => b.contains(elements.norad_id as f64),
+            QueryCondition::Name(regex) => elements
+                .object_name
+                .as_ref()
+                .map_or(false, |name| regex.is_match(name)),
+        }
+    }
+
+    /// Case insensitive name regex
+    pub fn name(pattern: &str) -> Result<Self, String> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(QueryCondition::Name)
+            .map_err(|e| format!("Invalid name pattern: {}", e))
+    }
+}
+
+impl ElementQuery {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Err("Empty query".to_string());
+        }
+        let query = parser.or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(token) => Err(format!("Unexpected {}", token)),
+        }
+    }
+
+    pub fn and(self, other: ElementQuery) -> Self {
+        match self {
+            ElementQuery::And(mut all) => {
+                all.push(other);
+                ElementQuery::And(all)
+            }
+            query => ElementQuery::And(vec![query, other]),
+        }
+    }
+
+    pub fn or(self, other: ElementQuery) -> Self {
+        match self {
+            ElementQuery::Or(mut any) => {
+                any.push(other);
+                ElementQuery::Or(any)
+            }
+            query => ElementQuery::Or(vec![query, other]),
+        }
+    }
+
+    pub fn negate(self) -> Self {
+        ElementQuery::Not(Box::new(self))
+    }
+
+    pub fn matches(&self, elements: &Elements) -> bool {
+        self.matches_with(elements, &OrbitParameters::from_elements(elements))
+    }
+
+    fn matches_with(&self, elements: &Elements, orbit: &OrbitParameters) -> bool {
+        match self {
+            ElementQuery::Condition(c) => c.matches(elements, orbit),
+            ElementQuery::And(all) => all.iter().all(|q| q.matches_with(elements, orbit)),
+            ElementQuery::Or(any) => any.iter().any(|q| q.matches_with(elements, orbit)),
+            ElementQuery::Not(q) => !q.matches_with(elements, orbit),
+        }
+    }
+}
+
+impl From<QueryCondition> for ElementQuery {
+    fn from(condition: QueryCondition) -> Self {
+        ElementQuery::Condition(condition)
+    }
+}
+
+impl FromStr for ElementQuery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl FromStr for Regime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "leo" => Ok(Regime::Leo),
+            "meo" => Ok(Regime::Meo),
+            "geo" => Ok(Regime::Geo),
+            "heo" => Ok(Regime::Heo),
+            _ => Err(format!(
+                "Unknown regime {}, expected leo, meo, geo or heo",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Regime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Regime::Leo => write!(f, "LEO"),
+            Regime::Meo => write!(f, "MEO"),
+            Regime::Geo => write!(f, "GEO"),
+            Regime::Heo => write!(f, "HEO"),
+        }
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{}'", w),
+            Token::Pattern(p) => write!(f, "\"{}\"", p),
+            Token::Operator(o) => write!(f, "'{}'", o),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+        }
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(word))
+    }
+
+    fn or(&mut self) -> Result<ElementQuery, String> {
+        let mut query = self.and()?;
+        while self.is_word("or") {
+            self.next();
+            query = query.or(self.and()?);
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<ElementQuery, String> {
+        let mut query = self.not()?;
+        loop {
+            if self.is_word("and") {
+                self.next();
+            } else if self.is_word("or") || matches!(self.peek(), None | Some(Token::Close)) {
+                return Ok(query);
+            }
+            query = query.and(self.not()?);
+        }
+    }
+
+    fn not(&mut self) -> Result<ElementQuery, String> {
+        if self.is_word("not") {
+            self.next();
+            return Ok(self.not()?.negate());
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err("Missing )".to_string()),
+                }
+            }
+            Some(Token::Word(field)) => self.condition(&field).map(ElementQuery::from),
+            Some(token) => Err(format!("Expected a field, got {}", token)),
+            None => Err("Query ends early".to_string()),
+        }
+    }
+
+    fn condition(&mut self, field: &str) -> Result<QueryCondition, String> {
+        match field.to_lowercase().as_str() {
+            "perigee" => Ok(QueryCondition::PerigeeAltitude(self.bounds(field)?)),
+            "apogee" => Ok(QueryCondition::ApogeeAltitude(self.bounds(field)?)),
+            "inc" | "inclination" => Ok(QueryCondition::Inclination(self.bounds(field)?)),
+            "period" => Ok(QueryCondition::Period(self.bounds(field)?)),
+            "ecc" | "eccentricity" => Ok(QueryCondition::Eccentricity(self.bounds(field)?)),
+            "launch" | "year" => Ok(QueryCondition::LaunchYear(self.bounds(field)?)),
+            "norad" | "id" => Ok(QueryCondition::NoradId(self.bounds(field)?)),
+            "regime" => match self.next() {
+                Some(Token::Word(regime)) => Ok(QueryCondition::Regime(regime.parse()?)),
+                _ => Err("Expected leo, meo, geo or heo after regime".to_string()),
+            },
+            "name" => match self.next() {
+                Some(Token::Pattern(pattern)) | Some(Token::Word(pattern)) => {
+                    QueryCondition::name(&pattern)
+                }
+                _ => Err("Expected a pattern after name".to_string()),
+            },
+            _ => Err(format!(
+                "Unknown field {}, expected {}",
+                field, QUERY_FIELDS
+            )),
+        }
+    }
+
+    fn bounds(&mut self, field: &str) -> Result<Bounds, String> {
+        let number = |text: &str| {
+            text.parse::<f64>()
+                .map_err(|_| format!("Expected a number for {}, got '{}'", field, text))
+        };
+        match self.next() {
+            Some(Token::Operator(operator)) => {
+                let value = match self.next() {
+                    Some(Token::Word(value)) => number(&value)?,
+                    _ => return Err(format!("Expected a number after {} {}", field, operator)),
+                };
+                match operator.as_str() {
+                    "<" => Ok(Bounds::below(value, true)),
+                    "<=" => Ok(Bounds::below(value, false)),
+                    ">" => Ok(Bounds::above(value, true)),
+                    ">=" => Ok(Bounds::above(value, false)),
+                    _ => Ok(Bounds::new(Some(value), Some(value))),
+                }
+            }
+            Some(Token::Word(range)) => match range.split_once("..") {
+                Some((min, max)) => {
+                    let bound = |text: &str| match text.trim() {
+                        "" => Ok(None),
+                        text => number(text).map(Some),
+                    };
+                    Ok(Bounds::new(bound(min)?, bound(max)?))
+                }
+                None => {
+                    let value = number(&range)?;
+                    Ok(Bounds::new(Some(value), Some(value)))
+                }
+            },
+            _ => Err(format!("Expected a range after {}", field)),
+        }
+    }
+}
+
+/// Launch year from the COSPAR id, either 1998-067A or the 98067A of TLEs
+pub fn launch_year(elements: &Elements) -> Option<i32> {
+    let designator = elements.international_designator.as_ref()?.trim();
+    match designator.find('-') {
+        Some(4) => designator[..4].parse().ok(),
+        _ => Some(launch_year_of(designator.get(..2)?.parse().ok()?)),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '"' | '/' => {
+                let mut pattern = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        // a delimiter inside the pattern is escaped
+                        Some('\\') if chars.peek() == Some(&c) => {
+                            pattern.push(c);
+                            chars.next();
+                        }
+                        Some(other) => pattern.push(other),
+                        None => return Err(format!("Missing closing {}", c)),
+                    }
+                }
+                tokens.push(Token::Pattern(pattern));
+            }
+            '<' | '>' | '=' => {
+                let mut operator = c.to_string();
+                if chars.peek() == Some(&'=') {
+                    operator.push('=');
+                    chars.next();
+                }
+                tokens.push(Token::Operator(operator));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "()<>=\"".contains(*next) {
+                        break;
+                    }
+                    word.push(*next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use sgp4::Classification;
+
+    fn elements(norad_id: u64, inclination: f64) -> Elements {
+        Elements {
+            object_name: Some(format!("STARLINK-{}", norad_id)),
+            international_designator: Some("2019-074A".to_string()),
+            norad_id,
+            classification: Classification::Unclassified,
+            datetime: NaiveDate::from_ymd_opt(2024, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            mean_motion_dot: 0.0,
+            mean_motion_ddot: 0.0,
+            drag_term: 0.0,
+            element_set_number: 999,
+            inclination,
+            right_ascension: 0.0,
+            eccentricity: 0.0001,
+            argument_of_perigee: 0.0,
+            mean_anomaly: 0.0,
+            mean_motion: 15.0,
+            revolution_number: 0,
+            ephemeris_type: 0,
+        }
+    }
+
+    /// Which of the inclinations 40, 50 and 60 match
+    fn matching(query: &str) -> Vec<f64> {
+        let query = ElementQuery::parse(query).unwrap();
+        [40.0, 50.0, 60.0]
+            .into_iter()
+            .filter(|i| query.matches(&elements(1, *i)))
+            .collect()
+    }
+
+    fn orbit(mean_motion: f64, eccentricity: f64) -> Elements {
+        let mut elements = elements(1, 50.0);
+        elements.mean_motion = mean_motion;
+        elements.eccentricity = eccentricity;
+        elements
+    }
+
+    fn matches(query: &str, elements: &Elements) -> bool {
+        ElementQuery::parse(query).unwrap().matches(elements)
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(matching("inc < 50"), vec![40.0]);
+        assert_eq!(matching("inc <= 50"), vec![40.0, 50.0]);
+        assert_eq!(matching("inc > 50"), vec![60.0]);
+        assert_eq!(matching("inc >= 50"), vec![50.0, 60.0]);
+        assert_eq!(matching("inc = 50"), vec![50.0]);
+        assert_eq!(matching("inc 50"), vec![50.0]);
+    }
+
+    #[test]
+    fn ranges_include_their_ends() {
+        assert_eq!(matching("inc 40..50"), vec![40.0, 50.0]);
+        assert_eq!(matching("inc 50.."), vec![50.0, 60.0]);
+        assert_eq!(matching("inc ..50"), vec![40.0, 50.0]);
+        assert_eq!(matching("inc 41..59"), vec![50.0]);
+    }
+
+    #[test]
+    fn combined() {
+        assert_eq!(matching("inc > 40 and inc < 60"), vec![50.0]);
+        assert_eq!(matching("inc < 41 or inc > 59"), vec![40.0, 60.0]);
+        assert_eq!(matching("not inc >= 50"), vec![40.0]);
+        assert_eq!(
+            matching("name /^starlink/ and norad < 2"),
+            vec![40.0, 50.0, 60.0]
+        );
+        assert!(matching("norad > 1").is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(ElementQuery::parse("inc <").is_err());
+        assert!(ElementQuery::parse("inc < abc").is_err());
+        assert!(ElementQuery::parse("inc 1..x").is_err());
+        assert!(ElementQuery::parse("altitude 5").is_err());
+    }
+
+    #[test]
+    fn regimes() {
+        for (elements, regime) in [
+            (orbit(15.0, 0.0001), Regime::Leo),
+            // GPS
+            (orbit(2.0056, 0.01), Regime::Meo),
+            (orbit(1.0027, 0.0002), Regime::Geo),
+            // Molniya, its perigee is low but the apogee is not
+            (orbit(2.006, 0.74), Regime::Heo),
+            // graveyard and beyond
+            (orbit(0.5, 0.001), Regime::Heo),
+        ] {
+            assert_eq!(
+                OrbitParameters::from_elements(&elements).regime,
+                regime,
+                "{}",
+                elements.mean_motion
+            );
+            let query = format!("regime {}", regime.to_string().to_lowercase());
+            assert!(matches(&query, &elements), "{}", query);
+        }
+        assert!(!matches("regime geo", &orbit(15.0, 0.0001)));
+        assert!(ElementQuery::parse("regime gto").is_err());
+    }
+
+    #[test]
+    fn altitudes_and_period() {
+        // about 6945 km, 498 km above the equator at perigee and 636 km at apogee
+        let elements = orbit(15.0, 0.01);
+        let orbit = OrbitParameters::from_elements(&elements);
+        assert!(
+            (orbit.perigee_altitude - 498.0).abs() < 2.0,
+            "{}",
+            orbit.perigee_altitude
+        );
+        assert!(
+            (orbit.apogee_altitude - 636.0).abs() < 2.0,
+            "{}",
+            orbit.apogee_altitude
+        );
+        assert_eq!(orbit.period, 96.0);
+        for (query, expected) in [
+            ("perigee < 550", true),
+            ("apogee < 550", false),
+            ("perigee 450..550 and apogee 600..700", true),
+            ("apogee > 700", false),
+            ("period 95..97", true),
+            ("period = 96", true),
+            ("period > 96", false),
+            ("ecc > 0.005", true),
+            ("ecc < 0.001", false),
+            ("eccentricity 0.01", true),
+        ] {
+            assert_eq!(matches(query, &elements), expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn launch_years() {
+        let mut elements = elements(1, 50.0);
+        for (designator, expected) in [
+            (Some("1998-067A"), Some(1998)),
+            (Some("98067A"), Some(1998)),
+            (Some(" 98067A  "), Some(1998)),
+            (Some("19074A"), Some(2019)),
+            (Some("57001A"), Some(1957)),
+            (Some("56001A"), Some(2056)),
+            (Some(""), None),
+            (None, None),
+        ] {
+            elements.international_designator = designator.map(str::to_string);
+            assert_eq!(launch_year(&elements), expected, "{:?}", designator);
+            assert_eq!(
+                matches("launch 1998", &elements),
+                expected == Some(1998),
+                "{:?}",
+                designator
+            );
+        }
+        elements.international_designator = Some("98067A".to_string());
+        assert!(matches("launch 1990..2000", &elements));
+        assert!(!matches("year > 1998", &elements));
+    }
+
+    #[test]
+    fn names() {
+        let mut elements = elements(1234, 50.0);
+        for (query, expected) in [
+            ("name starlink", true),
+            ("name LINK-12", true),
+            ("name /^starlink-\\d+$/", true),
+            ("name /^link/", false),
+            ("name \"STARLINK-1234\"", true),
+            ("name \"ONEWEB\"", false),
+            ("name /a\\/b/", false),
+        ] {
+            assert_eq!(matches(query, &elements), expected, "{}", query);
+        }
+        elements.object_name = Some("A/B".to_string());
+        assert!(matches("name /a\\/b/", &elements));
+        elements.object_name = None;
+        assert!(!matches("name starlink", &elements));
+        assert!(matches("not name starlink", &elements));
+        assert!(ElementQuery::parse("name /(/").is_err());
+        assert!(ElementQuery::parse("name").is_err());
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(
+            matching("(inc < 45 or inc > 55) and norad 1"),
+            vec![40.0, 60.0]
+        );
+        assert_eq!(
+            matching("(inc < 45 or inc > 55) and norad 2"),
+            Vec::<f64>::new()
+        );
+        assert_eq!(matching("not (inc 40 or inc 60)"), vec![50.0]);
+        assert_eq!(matching("((inc 50))"), vec![50.0]);
+        assert!(ElementQuery::parse("(inc 50").is_err());
+        assert!(ElementQuery::parse("inc 50)").is_err());
+        assert!(ElementQuery::parse("()").is_err());
+    }
+
+    #[test]
+    fn and_binds_stronger_than_or() {
+        // a or (b and c), not (a or b) and c
+        assert_eq!(matching("inc 40 or inc 50 and norad 2"), vec![40.0]);
+        assert_eq!(matching("inc 40 or inc 50 and norad 1"), vec![40.0, 50.0]);
+        assert_eq!(matching("norad 2 and inc 40 or inc 60"), vec![60.0]);
+        // conditions next to each other are joined with and
+        assert_eq!(matching("inc 40 or inc 50 norad 2"), vec![40.0]);
+        assert_eq!(matching("inc > 40 inc < 60"), vec![50.0]);
+    }
+}
@@ -0,0 +1,528 @@
+// Where elements come from. The registry lives in element_sources.json in the data dir and can be edited
+// there or in the ui.
+// When several sources know the same NORAD id, the one with the higher priority wins.
+// Within the same priority, the newer epoch wins.
+
+use crate::elements::element_fetch::{SourceOutcome, SourceResult};
+use crate::elements::element_util::element_copy;
+use crate::elements::omm::{Omm, OmmFormat};
+use crate::elements::space_track::{SPACE_TRACK_SOURCE, SPACE_TRACK_URL};
+use crate::elements::tle::parse_tle;
+use crate::utility::init_dirs;
+use serde::{Deserialize, Serialize};
+use sgp4::Elements;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::path::Path;
+
+const SOURCES_FILENAME: &str = "element_sources.json";
+const CELESTRAK_GP: &str = "https://celestrak.org/NORAD/elements/gp.php";
+const CELESTRAK_SUP_GP: &str = "https://celestrak.org/NORAD/elements/supplemental/sup-gp.php";
+/// urls of imported files start with this, they are read again on updates
+pub const FILE_URL_PREFIX: &str = "file://";
+/// placeholder in url templates, replaced by the query value of the format
+pub const FORMAT_PLACEHOLDER: &str = "{format}";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ElementFormat {
+    /// OMM as json, what celestrak calls FORMAT=json
+    Json,
+    /// two or three line elements
+    Tle,
+    /// CCSDS OMM, Celestrak gives these as FORMAT=xml, kvn and csv
+    Xml,
+    Kvn,
+    Csv,
+}
+
+/// What a source delivered. OMM messages are kept whole, they hold more than Elements has room for.
+pub enum ParsedElements {
+    Elements(Vec<Elements>),
+    Messages(Vec<Omm>),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ElementSource {
+    pub name: String,
+    /// may contain {format}
+    pub url: String,
+    pub format: ElementFormat,
+    /// hours between automatic updates
+    pub refresh_interval: f64,
+    pub priority: i32,
+    pub enabled: bool,
+    /// unix time of the last successful fetch
+    #[serde(default)]
+    pub last_fetch: Option<i64>,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SourceRegistry {
+    pub sources: Vec<ElementSource>,
+}
+
+impl ElementFormat {
+    pub fn query_value(&self) -> &'static str {
+        match self {
+            ElementFormat::Json => "json",
+            ElementFormat::Tle => "tle",
+            ElementFormat::Xml => "xml",
+            ElementFormat::Kvn => "kvn",
+            ElementFormat::Csv => "csv",
+        }
+    }
+
+    /// Parses a whole answer of a source
+    pub fn parse(&self, text: &str) -> Result<Vec<Elements>, String> {
+        match self {
+            ElementFormat::Json => serde_json::from_str::<Vec<Elements>>(text)
+                .map_err(|e| format!("Invalid element json: {}", e)),
+            ElementFormat::Tle => {
+                // broken sets are skipped, only a source with nothing usable fails
+                let parsed = parse_tle(text);
+                if let Some(first) = parsed.errors.first() {
+                    if parsed.elements.is_empty() {
+                        return Err(format!("Invalid TLE, {}", first));
+                    }
+                    log::warn!(
+                        "Skipped {} broken element sets, first at {}",
+                        parsed.errors.len(),
+                        first
+                    );
+                }
+                Ok(parsed.elements)
+            }
+            ElementFormat::Xml => read_omm(OmmFormat::Xml, text),
+            ElementFormat::Kvn => read_omm(OmmFormat::Kvn, text),
+            ElementFormat::Csv => read_omm(OmmFormat::Csv, text),
+        }
+    }
+
+    /// Like parse, but OMM messages are kept
+    pub fn read(&self, text: &str) -> Result<ParsedElements, String> {
+        match self.omm_format() {
+            Some(format) => Ok(ParsedElements::Messages(format.read(text)?)),
+            None => Ok(ParsedElements::Elements(self.parse(text)?)),
+        }
+    }
+
+    pub fn omm_format(&self) -> Option<OmmFormat> {
+        match self {
+            ElementFormat::Xml => Some(OmmFormat::Xml),
+            ElementFormat::Kvn => Some(OmmFormat::Kvn),
+            ElementFormat::Csv => Some(OmmFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// OMM by file extension, TLE for everything else
+    pub fn from_path(path: &Path) -> Self {
+        match OmmFormat::from_path(path) {
+            Some(OmmFormat::Xml) => ElementFormat::Xml,
+            Some(OmmFormat::Kvn) => ElementFormat::Kvn,
+            Some(OmmFormat::Csv) => ElementFormat::Csv,
+            None => ElementFormat::Tle,
+        }
+    }
+}
+
+impl ParsedElements {
+    pub fn len(&self) -> usize {
+        match self {
+            ParsedElements::Elements(elements) => elements.len(),
+            ParsedElements::Messages(messages) => messages.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn copy(&self) -> Self {
+        match self {
+            ParsedElements::Elements(elements) => {
+                ParsedElements::Elements(elements.iter().map(element_copy).collect())
+            }
+            ParsedElements::Messages(messages) => ParsedElements::Messages(messages.clone()),
+        }
+    }
+}
+
+impl ElementSource {
+    pub fn new(name: &str, url: &str, format: ElementFormat, priority: i32) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            format,
+            refresh_interval: 24.0,
+            priority,
+            enabled: true,
+            last_fetch: None,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// An imported element file. It is not updated automatically, the file may be gone.
+    pub fn file(name: &str, path: &Path, priority: i32) -> Self {
+        let mut source = Self::new(
+            name,
+            &format!("{}{}", FILE_URL_PREFIX, path.display()),
+            ElementFormat::from_path(path),
+            priority,
+        );
+        source.enabled = false;
+        source
+    }
+
+    fn celestrak_group(name: &str, group: &str, priority: i32) -> Self {
+        Self::new(
+            name,
+            &format!(
+                "{}?GROUP={}&FORMAT={}",
+                CELESTRAK_GP, group, FORMAT_PLACEHOLDER
+            ),
+            ElementFormat::Json,
+            priority,
+        )
+    }
+
+    pub fn url(&self) -> String {
+        self.url
+            .replace(FORMAT_PLACEHOLDER, self.format.query_value())
+    }
+
+    /// Whether the refresh interval passed since the last fetch
+    pub fn is_due(&self, now: i64) -> bool {
+        match self.last_fetch {
+            Some(last) => (now - last) as f64 >= self.refresh_interval * 3600.0,
+            None => true,
+        }
+    }
+}
+
+impl SourceRegistry {
+    /// Celestrak groups that cover what the old hard coded update did, and some more
+    pub fn defaults() -> Self {
+        let mut analyst = ElementSource::celestrak_group("Celestrak analyst objects", "analyst", 0);
+        analyst.enabled = false;
+        let mut debris = vec![
+            ElementSource::celestrak_group("Celestrak Cosmos 2251 debris", "cosmos-2251-debris", 0),
+            ElementSource::celestrak_group("Celestrak Iridium 33 debris", "iridium-33-debris", 0),
+            ElementSource::celestrak_group("Celestrak Fengyun 1C debris", "fengyun-1c-debris", 0),
+        ];
+        for source in debris.iter_mut() {
+            source.enabled = false;
+        }
+        let mut supplemental = ElementSource::new(
+            "Celestrak supplemental Starlink",
+            &format!(
+                "{}?FILE=starlink&FORMAT={}",
+                CELESTRAK_SUP_GP, FORMAT_PLACEHOLDER
+            ),
+            ElementFormat::Json,
+            10,
+        );
+        supplemental.enabled = false;
+        supplemental.refresh_interval = 8.0;
+        // everything on orbit that was updated within the last month, needs an account
+        let mut space_track = ElementSource::new(
+            SPACE_TRACK_SOURCE,
+            &format!(
+                "{}/basicspacedata/query/class/gp/DECAY_DATE/null-val/EPOCH/%3Enow-30/orderby/NORAD_CAT_ID/format/3le",
+                SPACE_TRACK_URL
+            ),
+            ElementFormat::Tle,
+            5,
+        );
+        space_track.enabled = false;
+
+        let mut sources = vec![ElementSource::celestrak_group(
+            "Celestrak active",
+            "active",
+            0,
+        )];
+        sources.append(&mut debris);
+        sources.push(analyst);
+        sources.push(supplemental);
+        sources.push(space_track);
+        Self { sources }
+    }
+
+    pub fn load() -> Self {
+        init_dirs()
+            .ok()
+            .and_then(|dir| File::open(dir.join(SOURCES_FILENAME)).ok())
+            .and_then(|file| match serde_json::from_reader(file) {
+                Ok(registry) => Some(registry),
+                Err(e) => {
+                    log::error!(
+                        "Invalid {}, using the default sources: {}",
+                        SOURCES_FILENAME,
+                        e
+                    );
+                    None
+                }
+            })
+            .unwrap_or_else(Self::defaults)
+    }
+
+    pub fn save(&self) {
+        let result = init_dirs()
+            .and_then(|dir| File::create(dir.join(SOURCES_FILENAME)).map_err(|e| e.to_string()))
+            .and_then(|file| serde_json::to_writer_pretty(file, self).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::error!("Cannot save element sources: {}", e);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ElementSource> {
+        self.sources.iter().find(|s| s.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ElementSource> {
+        self.sources.iter_mut().find(|s| s.name == name)
+    }
+
+    pub fn add(&mut self, source: ElementSource) -> Result<(), String> {
+        if source.name.trim().is_empty() {
+            return Err("Sources need a name".to_string());
+        }
+        if source.url.trim().is_empty() {
+            return Err("Sources need an url".to_string());
+        }
+        if self.get(&source.name).is_some() {
+            return Err(format!("There already is a source called {}", source.name));
+        }
+        self.sources.push(source);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.sources.retain(|s| s.name != name);
+    }
+
+    /// Enabled sources to fetch, all of them or only those due for an update
+    pub fn to_fetch(&self, all: bool, now: i64) -> Vec<ElementSource> {
+        self.sources
+            .iter()
+            .filter(|s| s.enabled && (all || s.is_due(now)))
+            .cloned()
+            .collect()
+    }
+
+    /// Remembers when sources were fetched and what they said about caching
+    pub fn record(&mut self, results: &[SourceResult], now: i64) {
+        for result in results {
+            let source = match self.get_mut(&result.source) {
+                Some(source) => source,
+                None => continue,
+            };
+            match &result.outcome {
+                SourceOutcome::Updated {
+                    etag,
+                    last_modified,
+                    ..
+                } => {
+                    source.last_fetch = Some(now);
+                    source.etag = etag.clone();
+                    source.last_modified = last_modified.clone();
+                }
+                SourceOutcome::NotModified => source.last_fetch = Some(now),
+                SourceOutcome::Failed(_) => {}
+            }
+        }
+    }
+
+    /// Priority of each source, for merging
+    pub fn priorities(&self) -> HashMap<String, i32> {
+        self.sources
+            .iter()
+            .map(|s| (s.name.clone(), s.priority))
+            .collect()
+    }
+}
+
+impl Display for ElementFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ElementFormat::Json => write!(f, "OMM json"),
+            ElementFormat::Tle => write!(f, "TLE"),
+            ElementFormat::Xml => write!(f, "OMM XML"),
+            ElementFormat::Kvn => write!(f, "OMM KVN"),
+            ElementFormat::Csv => write!(f, "OMM CSV"),
+        }
+    }
+}
+
+fn read_omm(format: OmmFormat, text: &str) -> Result<Vec<Elements>, String> {
+    Ok(format.read(text)?.iter().map(Omm::to_elements).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3600;
+
+    fn registry() -> SourceRegistry {
+        let mut never = ElementSource::new("never", "https://a.test/never", ElementFormat::Tle, 0);
+        never.refresh_interval = 2.0;
+        let mut recent =
+            ElementSource::new("recent", "https://a.test/recent", ElementFormat::Tle, 5);
+        recent.refresh_interval = 2.0;
+        recent.last_fetch = Some(10 * HOUR);
+        let mut old = ElementSource::new("old", "https://a.test/old", ElementFormat::Tle, 10);
+        old.refresh_interval = 2.0;
+        old.last_fetch = Some(HOUR);
+        let mut disabled =
+            ElementSource::new("disabled", "https://a.test/off", ElementFormat::Tle, 0);
+        disabled.enabled = false;
+        SourceRegistry {
+            sources: vec![never, recent, old, disabled],
+        }
+    }
+
+    fn names(sources: &[ElementSource]) -> Vec<&str> {
+        sources.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    fn result(source: &str, outcome: SourceOutcome) -> SourceResult {
+        SourceResult {
+            source: source.to_string(),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn due_after_the_refresh_interval() {
+        let mut source = ElementSource::new("a", "https://a.test", ElementFormat::Tle, 0);
+        source.refresh_interval = 1.5;
+        assert!(source.is_due(0));
+        source.last_fetch = Some(1000);
+        assert!(!source.is_due(1000));
+        assert!(!source.is_due(1000 + 5399));
+        assert!(source.is_due(1000 + 5400));
+    }
+
+    #[test]
+    fn enabled_sources_are_fetched() {
+        let registry = registry();
+        assert_eq!(
+            names(&registry.to_fetch(true, 11 * HOUR)),
+            ["never", "recent", "old"]
+        );
+        assert_eq!(
+            names(&registry.to_fetch(false, 11 * HOUR)),
+            ["never", "old"]
+        );
+        assert_eq!(
+            names(&registry.to_fetch(false, 12 * HOUR)),
+            ["never", "recent", "old"]
+        );
+    }
+
+    #[test]
+    fn fetches_are_recorded() {
+        let mut registry = registry();
+        registry.get_mut("recent").unwrap().etag = Some("\"1\"".to_string());
+        let results = [
+            result(
+                "never",
+                SourceOutcome::Updated {
+                    fetched: 2,
+                    taken: 1,
+                    etag: Some("\"2\"".to_string()),
+                    last_modified: Some("Mon, 01 Jan 2024 00:00:00 GMT".to_string()),
+                },
+            ),
+            result("recent", SourceOutcome::NotModified),
+            result("old", SourceOutcome::Failed("Timeout".to_string())),
+            result("removed", SourceOutcome::NotModified),
+        ];
+        registry.record(&results, 20 * HOUR);
+
+        let never = registry.get("never").unwrap();
+        assert_eq!(never.last_fetch, Some(20 * HOUR));
+        assert_eq!(never.etag.as_deref(), Some("\"2\""));
+        assert_eq!(
+            never.last_modified.as_deref(),
+            Some("Mon, 01 Jan 2024 00:00:00 GMT")
+        );
+        let recent = registry.get("recent").unwrap();
+        assert_eq!(recent.last_fetch, Some(20 * HOUR));
+        assert_eq!(recent.etag.as_deref(), Some("\"1\""));
+        // failures are tried again on the next update
+        assert_eq!(registry.get("old").unwrap().last_fetch, Some(HOUR));
+        assert!(registry.get("removed").is_none());
+        assert_eq!(names(&registry.to_fetch(false, 21 * HOUR)), ["old"]);
+    }
+
+    #[test]
+    fn priorities_by_name() {
+        let priorities = registry().priorities();
+        assert_eq!(priorities.len(), 4);
+        assert_eq!(priorities["never"], 0);
+        assert_eq!(priorities["recent"], 5);
+        assert_eq!(priorities["old"], 10);
+    }
+
+    #[test]
+    fn added_sources_are_checked() {
+        let mut registry = registry();
+        for (source, expected) in [
+            (
+                ElementSource::new(" ", "https://a.test", ElementFormat::Tle, 0),
+                Err("Sources need a name".to_string()),
+            ),
+            (
+                ElementSource::new("new", "", ElementFormat::Tle, 0),
+                Err("Sources need an url".to_string()),
+            ),
+            (
+                ElementSource::new("old", "https://b.test", ElementFormat::Tle, 0),
+                Err("There already is a source called old".to_string()),
+            ),
+            (
+                ElementSource::new("new", "https://a.test", ElementFormat::Tle, 0),
+                Ok(()),
+            ),
+        ] {
+            let name = source.name.clone();
+            assert_eq!(registry.add(source), expected, "{}", name);
+        }
+        assert_eq!(registry.sources.len(), 5);
+        assert_eq!(registry.get("new").unwrap().url, "https://a.test");
+    }
+
+    #[test]
+    fn format_in_the_url() {
+        let mut source = ElementSource::celestrak_group("Stations", "stations", 0);
+        for (format, expected) in [
+            (ElementFormat::Json, "json"),
+            (ElementFormat::Tle, "tle"),
+            (ElementFormat::Xml, "xml"),
+            (ElementFormat::Kvn, "kvn"),
+            (ElementFormat::Csv, "csv"),
+        ] {
+            source.format = format;
+            assert_eq!(
+                source.url(),
+                format!("{}?GROUP=stations&FORMAT={}", CELESTRAK_GP, expected),
+                "{}",
+                format
+            );
+        }
+        let fixed = ElementSource::new("fixed", "https://a.test/3le", ElementFormat::Json, 0);
+        assert_eq!(fixed.url(), "https://a.test/3le");
+        let file = ElementSource::file("file", Path::new("/tmp/sets.xml"), 0);
+        assert_eq!(file.url(), "file:///tmp/sets.xml");
+        assert_eq!(file.format, ElementFormat::Xml);
+        assert!(!file.enabled);
+    }
+}
@@ -0,0 +1,420 @@
+// Elements and their history in an SQLite database, elements.sqlite in the data dir.
+// The schema version is the user_version of the database, MIGRATIONS bring older files up to date.
+// Saves are incremental, only what was merged since the last save is written, in one transaction.
+// With the write ahead log a crash loses at most the save it interrupted, never the file.
+// Epochs are stored in nanoseconds, as exact as the store keys its sets, so a set replaces only
+// the row of the set the store replaced.
+// Sets that came as OMM keep their message as KVN text, for what the columns have no room for.
+// The store from before, elements.json, is imported once and then renamed to elements.json.migrated.
+
+use crate::elements::element_store::{ElementSet, ElementStore};
+use crate::elements::omm::{Omm, OmmFormat};
+use crate::utility::init_dirs;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, Row, Statement, Transaction};
+use sgp4::{Classification, Elements};
+use std::path::{Path, PathBuf};
+
+const STORAGE_FILENAME: &str = "elements.sqlite";
+const LEGACY_FILENAME: &str = "elements.json";
+const LEGACY_MIGRATED_FILENAME: &str = "elements.json.migrated";
+/// ms to wait while the fetch thread writes
+const BUSY_TIMEOUT: u64 = 5000;
+
+/// Each brings the schema from its index to the next version
+const MIGRATIONS: &[fn(&Transaction) -> rusqlite::Result<()>] = &[create_tables];
+
+/// The version files written by this build have
+const STORAGE_VERSION: usize = MIGRATIONS.len();
+
+/// Columns of a set, in the order of insert_set and set_from_row
+const SET_COLUMNS: &str = "norad_id, epoch, source, object_name, international_designator, \
+    classification, mean_motion_dot, mean_motion_ddot, drag_term, element_set_number, inclination, \
+    right_ascension, eccentricity, argument_of_perigee, mean_anomaly, mean_motion, \
+    revolution_number, ephemeris_type, omm";
+
+pub(crate) struct ElementStorage {
+    connection: Connection,
+}
+
+impl ElementStorage {
+    /// The database in the data dir, created and migrated as needed
+    pub fn open() -> Result<Self, String> {
+        Self::open_at(&init_dirs()?.join(STORAGE_FILENAME))
+    }
+
+    pub fn open_at(path: &Path) -> Result<Self, String> {
+        let error = |e: rusqlite::Error| format!("{}: {}", path.display(), e);
+        let mut connection = Connection::open(path).map_err(error)?;
+        connection
+            .busy_timeout(std::time::Duration::from_millis(BUSY_TIMEOUT))
+            .map_err(error)?;
+        connection
+            .execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+            .map_err(error)?;
+        migrate(&mut connection).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self { connection })
+    }
+
+    /// Everything stored, or the old JSON store the first time
+    pub fn load(&mut self) -> Result<ElementStore, String> {
+        let mut store = self.read().map_err(|e| e.to_string())?;
+        if store.elements.is_empty() {
+            if let Some(legacy) = import_legacy()? {
+                store = legacy;
+                self.save(&mut store)?;
+                rename_legacy()?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Writes what changed since the last save and forgets about it
+    pub fn save(&mut self, store: &mut ElementStore) -> Result<(), String> {
+        self.write(store).map_err(|e| e.to_string())?;
+        store.changed.clear();
+        store.changed_sets.clear();
+        Ok(())
+    }
+
+    fn read(&self) -> rusqlite::Result<ElementStore> {
+        let mut store = ElementStore::default();
+        let mut current = self
+            .connection
+            .prepare(&format!("SELECT {} FROM current", SET_COLUMNS))?;
+        let mut rows = current.query([])?;
+        while let Some(row) = rows.next()? {
+            let set = set_from_row(row)?;
+            store
+                .sources
+                .insert(set.elements.norad_id, set.source.clone());
+            store.elements.insert(set.elements.norad_id, set.elements);
+        }
+
+        let mut history = self.connection.prepare(&format!(
+            "SELECT {} FROM element_sets ORDER BY norad_id, epoch",
+            SET_COLUMNS
+        ))?;
+        let mut rows = history.query([])?;
+        while let Some(row) = rows.next()? {
+            let set = set_from_row(row)?;
+            if let Some(omm) = message_from_row(row)? {
+                store
+                    .messages
+                    .insert((set.elements.norad_id, set.elements.datetime), omm);
+            }
+            store
+                .history
+                .entry(set.elements.norad_id)
+                .or_default()
+                .push(set);
+        }
+        Ok(store)
+    }
+
+    fn write(&mut self, store: &ElementStore) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let insert = |table: &str| {
+                transaction.prepare(&format!(
+                    "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+                    table,
+                    SET_COLUMNS,
+                    vec!["?"; SET_COLUMNS.split(',').count()].join(", ")
+                ))
+            };
+            let mut current = insert("current")?;
+            for id in store.changed.iter() {
+                if let (Some(elements), Some(source)) =
+                    (store.elements.get(id), store.sources.get(id))
+                {
+                    insert_set(&mut current, source, elements, store.message(*id))?;
+                }
+            }
+            // the store decided the precedence, what it changed replaces the row of the same epoch
+            let mut history = insert("element_sets")?;
+            for (id, epoch) in store.changed_sets.iter() {
+                let sets = store.history(*id);
+                if let Ok(i) = sets.binary_search_by(|s| s.elements.datetime.cmp(epoch)) {
+                    let omm = store.messages.get(&(*id, *epoch));
+                    insert_set(&mut history, &sets[i].source, &sets[i].elements, omm)?;
+                }
+            }
+        }
+        transaction.commit()
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), String> {
+    let version: usize = connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if version > STORAGE_VERSION {
+        return Err(format!(
+            "schema version {} is newer than {}, the version this satwatch knows",
+            version, STORAGE_VERSION
+        ));
+    }
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction().map_err(|e| e.to_string())?;
+        migration(&transaction)
+            .and_then(|_| transaction.pragma_update(None, "user_version", i + 1))
+            .and_then(|_| transaction.commit())
+            .map_err(|e| format!("migration to schema version {} failed: {}", i + 1, e))?;
+        log::info!("Element storage migrated to schema version {}", i + 1);
+    }
+    Ok(())
+}
+
+/// Version 1: the set each object is known by, and every set ever merged, with the OMM it came as
+fn create_tables(transaction: &Transaction) -> rusqlite::Result<()> {
+    let columns = "norad_id INTEGER NOT NULL, \
+        epoch INTEGER NOT NULL, \
+        source TEXT NOT NULL, \
+        object_name TEXT, \
+        international_designator TEXT, \
+        classification TEXT NOT NULL, \
+        mean_motion_dot REAL NOT NULL, \
+        mean_motion_ddot REAL NOT NULL, \
+        drag_term REAL NOT NULL, \
+        element_set_number INTEGER NOT NULL, \
+        inclination REAL NOT NULL, \
+        right_ascension REAL NOT NULL, \
+        eccentricity REAL NOT NULL, \
+        argument_of_perigee REAL NOT NULL, \
+        mean_anomaly REAL NOT NULL, \
+        mean_motion REAL NOT NULL, \
+        revolution_number INTEGER NOT NULL, \
+        ephemeris_type INTEGER NOT NULL, \
+        omm TEXT";
+    transaction.execute_batch(&format!(
+        "CREATE TABLE current ({}, PRIMARY KEY (norad_id)) WITHOUT ROWID;
+         CREATE TABLE element_sets ({}, PRIMARY KEY (norad_id, epoch)) WITHOUT ROWID;",
+        columns, columns
+    ))
+}
+
+fn insert_set(
+    statement: &mut Statement,
+    source: &str,
+    e: &Elements,
+    omm: Option<&Omm>,
+) -> rusqlite::Result<()> {
+    let classification = match e.classification {
+        Classification::Unclassified => "U",
+        Classification::Classified => "C",
+        Classification::Secret => "S",
+    };
+    statement.execute(params![
+        e.norad_id as i64,
+        epoch_to_nanos(&e.datetime)?,
+        source,
+        e.object_name,
+        e.international_designator,
+        classification,
+        e.mean_motion_dot,
+        e.mean_motion_ddot,
+        e.drag_term,
+        e.element_set_number as i64,
+        e.inclination,
+        e.right_ascension,
+        e.eccentricity,
+        e.argument_of_perigee,
+        e.mean_anomaly,
+        e.mean_motion,
+        e.revolution_number as i64,
+        e.ephemeris_type as i64,
+        omm.map(|omm| OmmFormat::Kvn.write(std::slice::from_ref(omm))),
+    ])?;
+    Ok(())
+}
+
+fn set_from_row(row: &Row) -> rusqlite::Result<ElementSet> {
+    let epoch: i64 = row.get(1)?;
+    let datetime = DateTime::<Utc>::from_timestamp_nanos(epoch).naive_utc();
+    let classification = match row.get::<_, String>(5)?.as_str() {
+        "C" => Classification::Classified,
+        "S" => Classification::Secret,
+        _ => Classification::Unclassified,
+    };
+    Ok(ElementSet {
+        source: row.get(2)?,
+        elements: Elements {
+            norad_id: row.get::<_, i64>(0)? as u64,
+            datetime,
+            object_name: row.get(3)?,
+            international_designator: row.get(4)?,
+            classification,
+            mean_motion_dot: row.get(6)?,
+            mean_motion_ddot: row.get(7)?,
+            drag_term: row.get(8)?,
+            element_set_number: row.get::<_, i64>(9)? as u64,
+            inclination: row.get(10)?,
+            right_ascension: row.get(11)?,
+            eccentricity: row.get(12)?,
+            argument_of_perigee: row.get(13)?,
+            mean_anomaly: row.get(14)?,
+            mean_motion: row.get(15)?,
+            revolution_number: row.get::<_, i64>(16)? as u64,
+            ephemeris_type: row.get::<_, i64>(17)? as u8,
+        },
+    })
+}
+
+/// A message that cannot be read anymore is dropped, the set itself is still good
+fn message_from_row(row: &Row) -> rusqlite::Result<Option<Omm>> {
+    let text = match row.get::<_, Option<String>>(18)? {
+        Some(text) => text,
+        None => return Ok(None),
+    };
+    match OmmFormat::Kvn.read(&text) {
+        Ok(mut messages) if messages.len() == 1 => Ok(messages.pop()),
+        Ok(_) => Ok(None),
+        Err(e) => {
+            log::warn!("Dropping a stored OMM: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// Works for the years 1678 to 2261, which covers every element set there is
+fn epoch_to_nanos(epoch: &NaiveDateTime) -> rusqlite::Result<i64> {
+    DateTime::<Utc>::from_naive_utc_and_offset(*epoch, Utc)
+        .timestamp_nanos_opt()
+        .ok_or_else(|| {
+            rusqlite::Error::ToSqlConversionFailure(format!("epoch {} out of range", epoch).into())
+        })
+}
+
+fn legacy_path() -> Result<PathBuf, String> {
+    Ok(init_dirs()?.join(LEGACY_FILENAME))
+}
+
+/// The JSON store of earlier versions, None if there is none
+fn import_legacy() -> Result<Option<ElementStore>, String> {
+    let path = legacy_path()?;
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
+    let mut store = serde_json::from_reader::<_, ElementStore>(std::io::BufReader::new(file))
+        .map_err(|e| format!("Cannot import {}: {}", path.display(), e))?;
+    store.seed_history();
+    store.changed = store.elements.keys().copied().collect();
+    store.changed_sets = store
+        .history
+        .iter()
+        .flat_map(|(id, sets)| sets.iter().map(move |s| (*id, s.elements.datetime)))
+        .collect();
+    log::info!(
+        "Importing {} elements from {}",
+        store.elements.len(),
+        path.display()
+    );
+    Ok(Some(store))
+}
+
+fn rename_legacy() -> Result<(), String> {
+    let path = legacy_path()?;
+    std::fs::rename(&path, path.with_file_name(LEGACY_MIGRATED_FILENAME))
+        .map_err(|e| format!("Cannot rename {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::element_source::ParsedElements;
+    use crate::elements::element_util::{test_elements_at as elements, test_epoch as epoch};
+    use crate::elements::omm::OmmCovariance;
+    use std::collections::HashMap;
+
+    /// A fresh database in the temp dir, removed on drop
+    struct TempStorage(PathBuf);
+
+    impl TempStorage {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "satwatch-storage-{}-{}.sqlite",
+                name,
+                std::process::id()
+            ));
+            std::fs::remove_file(&path).ok();
+            Self(path)
+        }
+
+        fn open(&self) -> ElementStorage {
+            ElementStorage::open_at(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempStorage {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                std::fs::remove_file(format!("{}{}", self.0.display(), suffix)).ok();
+            }
+        }
+    }
+
+    #[test]
+    fn messages_are_stored_with_their_sets() {
+        let temp = TempStorage::new("messages");
+        let mut message = Omm::from_elements(&elements(1, epoch(2)), "ORIGIN");
+        message.covariance = Some(OmmCovariance {
+            ref_frame: Some("RTN".to_string()),
+            values: [2e-4; 21],
+        });
+        message.user_defined = vec![("RCS".to_string(), "LARGE".to_string())];
+
+        let mut store = ElementStore::default();
+        let priorities = HashMap::new();
+        store.merge(vec![elements(1, epoch(1))], "tle", &priorities);
+        store.merge_parsed(
+            ParsedElements::Messages(vec![message.clone()]),
+            "omm",
+            &priorities,
+        );
+        temp.open().save(&mut store).unwrap();
+
+        let loaded = temp.open().read().unwrap();
+        assert_eq!(loaded.history(1).len(), 2);
+        assert_eq!(loaded.message(1), Some(&message));
+        assert_eq!(loaded.messages.len(), 1);
+    }
+
+    #[test]
+    fn save_load_merge() {
+        let temp = TempStorage::new("merge");
+        // a microsecond apart would be the same row if epochs were stored in microseconds
+        let first = epoch(1) + chrono::Duration::nanoseconds(400);
+        let second = epoch(1) + chrono::Duration::nanoseconds(900);
+        let priorities = HashMap::from([("low".to_string(), 0), ("high".to_string(), 10)]);
+
+        let mut store = ElementStore::default();
+        store.merge(vec![elements(1, first)], "high", &priorities);
+        store.merge(vec![elements(1, second)], "low", &priorities);
+        temp.open().save(&mut store).unwrap();
+
+        let mut loaded = temp.open().load().unwrap();
+        let epochs = loaded
+            .history(1)
+            .iter()
+            .map(|s| (s.elements.datetime, s.source.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(epochs, vec![(first, "high"), (second, "low")]);
+        assert_eq!(loaded.elements[&1].datetime, first);
+
+        // the loaded sets are found again, the lower priority does not replace the stored one
+        assert_eq!(
+            loaded.merge(vec![elements(1, first)], "low", &priorities),
+            0
+        );
+        assert!(loaded.changed_sets.is_empty());
+        loaded.merge(vec![elements(1, second)], "high", &priorities);
+        temp.open().save(&mut loaded).unwrap();
+
+        let reloaded = temp.open().load().unwrap();
+        assert_eq!(reloaded.history(1).len(), 2);
+        assert!(reloaded.history(1).iter().all(|s| s.source == "high"));
+        assert_eq!(reloaded.elements[&1].datetime, second);
+    }
+}
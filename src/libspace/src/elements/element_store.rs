@@ -1,8 +1,330 @@
+use crate::elements::element_source::ParsedElements;
+use crate::elements::element_util::{closest_epoch, element_copy};
+use crate::elements::omm::Omm;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sgp4::Elements;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+/// One published element set and where it came from
 #[derive(Serialize, Deserialize)]
+pub struct ElementSet {
+    pub source: String,
+    pub elements: Elements,
+}
+
+#[derive(Default, Serialize, Deserialize)]
 pub struct ElementStore {
+    /// the set each object is known by, following the source precedence
     pub elements: HashMap<u64, Elements>,
+    /// name of the source each element came from
+    #[serde(default)]
+    pub sources: HashMap<u64, String>,
+    /// every set ever merged, sorted by epoch, one per epoch
+    #[serde(default)]
+    pub history: HashMap<u64, Vec<ElementSet>>,
+    /// the message of each history set that came as OMM, to export it whole
+    #[serde(skip)]
+    pub messages: HashMap<(u64, NaiveDateTime), Omm>,
+    /// objects whose current set changed since the last save
+    #[serde(skip)]
+    pub changed: HashSet<u64>,
+    /// history sets added or replaced since the last save
+    #[serde(skip)]
+    pub changed_sets: HashSet<(u64, NaiveDateTime)>,
+}
+
+impl ElementSet {
+    pub fn copy(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            elements: element_copy(&self.elements),
+        }
+    }
+}
+
+impl ElementStore {
+    pub fn copy(&self) -> Self {
+        Self {
+            elements: self
+                .elements
+                .iter()
+                .map(|(id, e)| (*id, element_copy(e)))
+                .collect(),
+            sources: self.sources.clone(),
+            history: self
+                .history
+                .iter()
+                .map(|(id, sets)| (*id, sets.iter().map(ElementSet::copy).collect()))
+                .collect(),
+            messages: self.messages.clone(),
+            changed: self.changed.clone(),
+            changed_sets: self.changed_sets.clone(),
+        }
+    }
+
+    /// Stores from before the history only know the current sets
+    pub fn seed_history(&mut self) {
+        for (id, elements) in self.elements.iter() {
+            if !self.history.contains_key(id) {
+                let source = self.sources.get(id).cloned().unwrap_or_default();
+                self.history.insert(
+                    *id,
+                    vec![ElementSet {
+                        source,
+                        elements: element_copy(elements),
+                    }],
+                );
+                self.changed_sets.insert((*id, elements.datetime));
+            }
+        }
+    }
+
+    /// Merges what a source delivered, following the precedence of the registry:
+    /// higher priority wins, the newer epoch wins within the same priority.
+    /// Elements of sources the registry does not know anymore always lose.
+    /// Every set goes into the history, for the same epoch the same precedence applies.
+    /// Returns how many elements were taken.
+    pub fn merge(
+        &mut self,
+        elements: Vec<Elements>,
+        source: &str,
+        priorities: &HashMap<String, i32>,
+    ) -> usize {
+        let priority_of = |source: &str| priorities.get(source).copied().unwrap_or(i32::MIN);
+        let priority = priority_of(source);
+        let mut taken = 0;
+        for e in elements {
+            self.add_to_history(&e, source, priority, &priority_of);
+            let replace = match self.elements.get(&e.norad_id) {
+                Some(old) => {
+                    let old_priority = self
+                        .sources
+                        .get(&e.norad_id)
+                        .map(|s| priority_of(s))
+                        .unwrap_or(i32::MIN);
+                    if priority != old_priority {
+                        priority > old_priority
+                    } else {
+                        e.datetime >= old.datetime
+                    }
+                }
+                None => true,
+            };
+            if replace {
+                self.changed.insert(e.norad_id);
+                self.sources.insert(e.norad_id, source.to_string());
+                self.elements.insert(e.norad_id, e);
+                taken += 1;
+            }
+        }
+        taken
+    }
+
+    /// Like merge, OMM messages are kept for the sets they became
+    pub fn merge_parsed(
+        &mut self,
+        parsed: ParsedElements,
+        source: &str,
+        priorities: &HashMap<String, i32>,
+    ) -> usize {
+        let messages = match parsed {
+            ParsedElements::Elements(elements) => return self.merge(elements, source, priorities),
+            ParsedElements::Messages(messages) => messages,
+        };
+        let taken = self.merge(
+            messages.iter().map(Omm::to_elements).collect(),
+            source,
+            priorities,
+        );
+        for omm in messages {
+            if self
+                .set_at(omm.norad_cat_id, omm.epoch)
+                .map(|s| s.source.as_str())
+                == Some(source)
+            {
+                self.messages.insert((omm.norad_cat_id, omm.epoch), omm);
+            }
+        }
+        taken
+    }
+
+    /// The OMM the current set of an object came as, if it did
+    pub fn message(&self, id: u64) -> Option<&Omm> {
+        let epoch = self.elements.get(&id)?.datetime;
+        let set = self.set_at(id, epoch)?;
+        if self.sources.get(&id) != Some(&set.source) {
+            return None;
+        }
+        self.messages.get(&(id, epoch))
+    }
+
+    /// The set of an object with exactly the epoch
+    pub fn set_at(&self, id: u64, epoch: NaiveDateTime) -> Option<&ElementSet> {
+        let history = self.history(id);
+        history
+            .binary_search_by(|s| s.elements.datetime.cmp(&epoch))
+            .ok()
+            .map(|i| &history[i])
+    }
+
+    /// The sets of an object, oldest first
+    pub fn history(&self, id: u64) -> &[ElementSet] {
+        self.history.get(&id).map(|h| h.as_slice()).unwrap_or(&[])
+    }
+
+    /// The set with the epoch closest to the time
+    pub fn closest(&self, id: u64, time: NaiveDateTime) -> Option<&ElementSet> {
+        closest_epoch(self.history(id), time, |s| s.elements.datetime)
+    }
+
+    fn add_to_history(
+        &mut self,
+        elements: &Elements,
+        source: &str,
+        priority: i32,
+        priority_of: &dyn Fn(&str) -> i32,
+    ) {
+        let history = self.history.entry(elements.norad_id).or_default();
+        let set = ElementSet {
+            source: source.to_string(),
+            elements: element_copy(elements),
+        };
+        match history.binary_search_by(|s| s.elements.datetime.cmp(&elements.datetime)) {
+            Ok(i) => {
+                if priority >= priority_of(&history[i].source) {
+                    history[i] = set;
+                } else {
+                    return;
+                }
+            }
+            Err(i) => history.insert(i, set),
+        }
+        // a message belongs to the set it came with
+        self.messages
+            .remove(&(elements.norad_id, elements.datetime));
+        self.changed_sets
+            .insert((elements.norad_id, elements.datetime));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::element_util::{test_elements, test_epoch};
+
+    fn priorities() -> HashMap<String, i32> {
+        HashMap::from([("low".to_string(), 0), ("high".to_string(), 10)])
+    }
+
+    fn current(store: &ElementStore, id: u64) -> (&str, NaiveDateTime) {
+        (store.sources[&id].as_str(), store.elements[&id].datetime)
+    }
+
+    #[test]
+    fn priority_before_epoch() {
+        let mut store = ElementStore::default();
+        assert_eq!(
+            store.merge(vec![test_elements(1, 10)], "high", &priorities()),
+            1
+        );
+        assert_eq!(
+            store.merge(vec![test_elements(1, 20)], "low", &priorities()),
+            0
+        );
+        assert_eq!(current(&store, 1), ("high", test_epoch(10)));
+        assert_eq!(
+            store.merge(vec![test_elements(1, 5)], "high", &priorities()),
+            0
+        );
+        assert_eq!(
+            store.merge(vec![test_elements(1, 15)], "high", &priorities()),
+            1
+        );
+        assert_eq!(current(&store, 1), ("high", test_epoch(15)));
+
+        assert_eq!(
+            store.merge(vec![test_elements(2, 10)], "low", &priorities()),
+            1
+        );
+        assert_eq!(
+            store.merge(vec![test_elements(2, 5)], "low", &priorities()),
+            0
+        );
+        assert_eq!(
+            store.merge(vec![test_elements(2, 20)], "low", &priorities()),
+            1
+        );
+        assert_eq!(current(&store, 2), ("low", test_epoch(20)));
+        assert_eq!(
+            store.merge(vec![test_elements(2, 1)], "high", &priorities()),
+            1
+        );
+        assert_eq!(current(&store, 2), ("high", test_epoch(1)));
+        // every set is kept
+        assert_eq!(store.history(2).len(), 4);
+    }
+
+    #[test]
+    fn unknown_sources_lose() {
+        let mut store = ElementStore::default();
+        assert_eq!(
+            store.merge(vec![test_elements(1, 10)], "gone", &priorities()),
+            1
+        );
+        assert_eq!(
+            store.merge(vec![test_elements(1, 5)], "low", &priorities()),
+            1
+        );
+        assert_eq!(
+            store.merge(vec![test_elements(1, 20)], "gone", &priorities()),
+            0
+        );
+        assert_eq!(current(&store, 1), ("low", test_epoch(5)));
+        assert_eq!(store.set_at(1, test_epoch(20)).unwrap().source, "gone");
+        assert_eq!(
+            store.merge(vec![test_elements(1, 5)], "gone", &priorities()),
+            0
+        );
+        assert_eq!(store.set_at(1, test_epoch(5)).unwrap().source, "low");
+    }
+
+    #[test]
+    fn same_epoch_replaces_the_history_set() {
+        let mut store = ElementStore::default();
+        store.merge(vec![test_elements(1, 10)], "low", &priorities());
+        store.merge(vec![test_elements(1, 10)], "high", &priorities());
+        assert_eq!(store.history(1).len(), 1);
+        assert_eq!(store.history(1)[0].source, "high");
+        store.merge(vec![test_elements(1, 10)], "low", &priorities());
+        assert_eq!(store.history(1)[0].source, "high");
+        assert_eq!(current(&store, 1), ("high", test_epoch(10)));
+
+        let mut later = test_elements(1, 10);
+        later.revolution_number = 1001;
+        store.merge(vec![later], "high", &priorities());
+        assert_eq!(store.history(1).len(), 1);
+        assert_eq!(store.history(1)[0].elements.revolution_number, 1001);
+    }
+
+    #[test]
+    fn closest_set() {
+        let mut store = ElementStore::default();
+        let sets = [10, 12, 20].map(|day| test_elements(1, day));
+        store.merge(sets.into(), "low", &priorities());
+        let at = |day, hour| test_epoch(day) + chrono::Duration::hours(hour);
+        for (time, expected) in [
+            (at(1, 0), 10),
+            (at(10, 23), 10),
+            (at(11, 0), 10),
+            (at(11, 1), 12),
+            (at(16, 0), 12),
+            (at(16, 1), 20),
+            (at(31, 0), 20),
+        ] {
+            let set = store.closest(1, time).unwrap();
+            assert_eq!(set.elements.datetime, test_epoch(expected), "{}", time);
+        }
+        assert!(store.closest(2, at(10, 0)).is_none());
+    }
 }
@@ -1,4 +1,29 @@
 use sgp4::{Classification, Elements};
+use std::ops::Sub;
+
+/// Four digit year of the two digit ones in TLE designators, the first launch was in 1957
+pub fn launch_year_of(two_digits: i32) -> i32 {
+    if two_digits < 57 {
+        2000 + two_digits
+    } else {
+        1900 + two_digits
+    }
+}
+
+/// Of items sorted by epoch, the one with the epoch closest to the time, the earlier one on a tie
+pub fn closest_epoch<T, E, D>(sorted: &[T], time: E, epoch: impl Fn(&T) -> E) -> Option<&T>
+where
+    E: Copy + PartialOrd + Sub<Output = D>,
+    D: PartialOrd,
+{
+    let after = sorted.partition_point(|item| epoch(item) < time);
+    let before = after.checked_sub(1).and_then(|i| sorted.get(i));
+    match (before, sorted.get(after)) {
+        (Some(b), Some(a)) if epoch(a) - time < time - epoch(b) => Some(a),
+        (Some(b), _) => Some(b),
+        (None, a) => a,
+    }
+}
 
 pub fn element_copy(element: &Elements) -> Elements {
     let classification = match element.classification {
@@ -26,3 +51,41 @@ pub fn element_copy(element: &Elements) -> Elements {
         ephemeris_type: element.ephemeris_type,
     }
 }
+
+/// Noon of a day of January 2024, when the test elements are
+#[cfg(test)]
+pub fn test_epoch(day: u32) -> chrono::NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(2024, 1, day)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+}
+
+/// An ISS-like element set for tests
+#[cfg(test)]
+pub fn test_elements_at(norad_id: u64, epoch: chrono::NaiveDateTime) -> Elements {
+    Elements {
+        object_name: Some(format!("OBJECT {}", norad_id)),
+        international_designator: Some("2024-001A".to_string()),
+        norad_id,
+        classification: Classification::Unclassified,
+        datetime: epoch,
+        mean_motion_dot: 0.0,
+        mean_motion_ddot: 0.0,
+        drag_term: 0.0,
+        element_set_number: 999,
+        inclination: 51.6,
+        right_ascension: 10.0,
+        eccentricity: 0.0005,
+        argument_of_perigee: 90.0,
+        mean_anomaly: 270.0,
+        mean_motion: 15.5,
+        revolution_number: 1000,
+        ephemeris_type: 0,
+    }
+}
+
+#[cfg(test)]
+pub fn test_elements(norad_id: u64, day: u32) -> Elements {
+    test_elements_at(norad_id, test_epoch(day))
+}
@@ -0,0 +1,504 @@
+// Maneuvers and anomalies, found by comparing consecutive element sets of an object.
+// The earlier set is carried forward to the epoch of the later one with the drift it predicts itself:
+// the decay from its mean motion derivative and the J2 precession of the node. What the later set
+// disagrees with beyond the thresholds is an event. Sets are only compared within the same source,
+// sources fitting different data disagree all the time.
+// For the burn epoch, both sets are propagated across the gap and the time of closest approach of the two
+// trajectories is taken; the velocity difference there is the delta-v. The separation swings with the
+// orbital period, so the gap is scanned in fractions of the period and the smallest sample is refined by
+// golden section search. If SGP4 fails, the middle of the
+// gap and the delta-v of the element changes (Gauss, near circular) are used instead.
+
+use crate::bodies::Planet;
+use crate::elements::element_store::ElementSet;
+use chrono::{Duration, NaiveDateTime};
+use sgp4::Elements;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::sync::mpsc::{channel, Receiver};
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+/// steps per orbit when scanning the gap between two sets for the burn
+const BURN_STEPS_PER_ORBIT: f64 = 20.0;
+/// at most this many steps, for very slow orbits or very long gaps
+const MAX_BURN_STEPS: usize = 20000;
+/// minutes, the burn time is refined down to this
+const BURN_TOLERANCE: f64 = 1e-3;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EventKind {
+    SemiMajorAxis,
+    Inclination,
+    Raan,
+    /// B* jumped
+    Drag,
+    MeanMotionDerivative,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DetectionSettings {
+    /// km
+    pub semi_major_axis: f64,
+    /// deg
+    pub inclination: f64,
+    /// deg
+    pub raan: f64,
+    /// B* and mean motion derivative changes by more than this factor are anomalies
+    pub drag_factor: f64,
+    /// sets further apart than this are not compared, the predicted drift is too unsure
+    pub max_gap_days: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ElementEvent {
+    pub norad_id: u64,
+    pub object_name: Option<String>,
+    pub source: String,
+    pub kinds: Vec<EventKind>,
+    /// epochs of the sets before and after
+    pub before: NaiveDateTime,
+    pub after: NaiveDateTime,
+    /// estimated time of the event, between before and after
+    pub epoch: NaiveDateTime,
+    /// m/s
+    pub delta_v: f64,
+    /// observed minus predicted, km
+    pub delta_semi_major_axis: f64,
+    /// deg
+    pub delta_inclination: f64,
+    pub delta_raan: f64,
+}
+
+impl Default for DetectionSettings {
+    /// Above the usual noise of TLEs of low earth orbiters
+    fn default() -> Self {
+        Self {
+            semi_major_axis: 0.5,
+            inclination: 0.01,
+            raan: 0.05,
+            drag_factor: 5.0,
+            max_gap_days: 10.0,
+        }
+    }
+}
+
+impl EventKind {
+    pub fn is_maneuver(&self) -> bool {
+        matches!(
+            self,
+            EventKind::SemiMajorAxis | EventKind::Inclination | EventKind::Raan
+        )
+    }
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventKind::SemiMajorAxis => write!(f, "semi-major axis"),
+            EventKind::Inclination => write!(f, "inclination"),
+            EventKind::Raan => write!(f, "RAAN"),
+            EventKind::Drag => write!(f, "B*"),
+            EventKind::MeanMotionDerivative => write!(f, "mean motion derivative"),
+        }
+    }
+}
+
+impl ElementEvent {
+    /// A maneuver changes the orbit, anything else is only an anomaly
+    pub fn is_maneuver(&self) -> bool {
+        self.kinds.iter().any(EventKind::is_maneuver)
+    }
+
+    /// Like "semi-major axis, RAAN"
+    pub fn kinds_text(&self) -> String {
+        self.kinds
+            .iter()
+            .map(|k| k.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Events in the history of one object, oldest first
+pub fn detect_events(history: &[ElementSet], settings: &DetectionSettings) -> Vec<ElementEvent> {
+    let mut events = Vec::new();
+    let mut last_of_source: Vec<&ElementSet> = Vec::new();
+    for set in history {
+        match last_of_source.iter_mut().find(|s| s.source == set.source) {
+            Some(previous) => {
+                if let Some(event) = compare(previous, set, settings) {
+                    events.push(event);
+                }
+                *previous = set;
+            }
+            None => last_of_source.push(set),
+        }
+    }
+    events
+}
+
+/// Events of many objects, newest first
+pub fn detect_fleet_events(
+    histories: &[Vec<ElementSet>],
+    settings: &DetectionSettings,
+) -> Vec<ElementEvent> {
+    let mut events = histories
+        .iter()
+        .flat_map(|history| detect_events(history, settings))
+        .collect::<Vec<ElementEvent>>();
+    events.sort_by(|a, b| b.epoch.cmp(&a.epoch));
+    events
+}
+
+/// Scans on a separate thread, as SGP4 runs for every event found
+pub fn detect_fleet_events_in_background(
+    histories: Vec<Vec<ElementSet>>,
+    settings: DetectionSettings,
+) -> Receiver<Vec<ElementEvent>> {
+    let (tx, rx) = channel();
+    std::thread::spawn(move || {
+        tx.send(detect_fleet_events(&histories, &settings)).ok();
+    });
+    rx
+}
+
+fn compare(
+    before: &ElementSet,
+    after: &ElementSet,
+    settings: &DetectionSettings,
+) -> Option<ElementEvent> {
+    let b = &before.elements;
+    let a = &after.elements;
+    let days = (a.datetime - b.datetime).num_seconds() as f64 / SECONDS_PER_DAY;
+    if days <= 0.0 || days > settings.max_gap_days {
+        return None;
+    }
+
+    // the mean motion derivative field holds half the derivative
+    let predicted_motion = b.mean_motion + 2.0 * b.mean_motion_dot * days;
+    let delta_semi_major_axis = semi_major_axis(a.mean_motion) - semi_major_axis(predicted_motion);
+    let delta_inclination = a.inclination - b.inclination;
+    let predicted_raan = b.right_ascension + node_drift(b) * days;
+    let delta_raan = (a.right_ascension - predicted_raan + 540.0).rem_euclid(360.0) - 180.0;
+
+    let mut kinds = Vec::new();
+    if delta_semi_major_axis.abs() > settings.semi_major_axis {
+        kinds.push(EventKind::SemiMajorAxis);
+    }
+    if delta_inclination.abs() > settings.inclination {
+        kinds.push(EventKind::Inclination);
+    }
+    if delta_raan.abs() > settings.raan {
+        kinds.push(EventKind::Raan);
+    }
+    // tiny values jump around without meaning anything
+    if jumped(b.drag_term, a.drag_term, 1e-5, settings.drag_factor) {
+        kinds.push(EventKind::Drag);
+    }
+    if jumped(
+        b.mean_motion_dot,
+        a.mean_motion_dot,
+        1e-6,
+        settings.drag_factor,
+    ) {
+        kinds.push(EventKind::MeanMotionDerivative);
+    }
+    if kinds.is_empty() {
+        return None;
+    }
+
+    let (epoch, delta_v) = burn(b, a).unwrap_or_else(|| {
+        (
+            b.datetime + (a.datetime - b.datetime) / 2,
+            gauss_delta_v(b, delta_semi_major_axis, delta_inclination, delta_raan),
+        )
+    });
+    Some(ElementEvent {
+        norad_id: a.norad_id,
+        object_name: a.object_name.clone(),
+        source: after.source.clone(),
+        kinds,
+        before: b.datetime,
+        after: a.datetime,
+        epoch,
+        delta_v,
+        delta_semi_major_axis,
+        delta_inclination,
+        delta_raan,
+    })
+}
+
+fn jumped(before: f64, after: f64, floor: f64, factor: f64) -> bool {
+    let (small, large) = if before.abs() < after.abs() {
+        (before.abs(), after.abs())
+    } else {
+        (after.abs(), before.abs())
+    };
+    if large < floor {
+        return false;
+    }
+    (before.signum() != after.signum() && small >= floor) || large > small.max(floor) * factor
+}
+
+/// km, from mean motion in rev/day
+pub(crate) fn semi_major_axis(mean_motion: f64) -> f64 {
+    let n = mean_motion * 2.0 * PI / SECONDS_PER_DAY;
+    (Planet::Earth.body().gm / (n * n)).cbrt()
+}
+
+/// deg/day, the secular J2 precession of the node
+fn node_drift(elements: &Elements) -> f64 {
+    let body = Planet::Earth.body();
+    let n = elements.mean_motion * 2.0 * PI / SECONDS_PER_DAY;
+    let a = semi_major_axis(elements.mean_motion);
+    let p = a * (1.0 - elements.eccentricity * elements.eccentricity);
+    let rate = -1.5
+        * n
+        * body.j2
+        * (body.radius_equatorial / p).powi(2)
+        * elements.inclination.to_radians().cos();
+    rate.to_degrees() * SECONDS_PER_DAY
+}
+
+/// m/s, tangential for the semi-major axis and normal for the plane change
+fn gauss_delta_v(before: &Elements, delta_a: f64, delta_i: f64, delta_raan: f64) -> f64 {
+    let a = semi_major_axis(before.mean_motion);
+    let v = (Planet::Earth.body().gm / a).sqrt();
+    let tangential = v * delta_a / (2.0 * a);
+    let plane = (delta_i.to_radians().powi(2)
+        + (before.inclination.to_radians().sin() * delta_raan.to_radians()).powi(2))
+    .sqrt();
+    let normal = 2.0 * v * (plane / 2.0).sin();
+    (tangential * tangential + normal * normal).sqrt() * 1000.0
+}
+
+/// Closest approach of the two trajectories between the epochs, and the velocity difference there
+fn burn(before: &Elements, after: &Elements) -> Option<(NaiveDateTime, f64)> {
+    let before_constants = sgp4::Constants::from_elements(before).ok()?;
+    let after_constants = sgp4::Constants::from_elements(after).ok()?;
+    let gap = (after.datetime - before.datetime).num_seconds() as f64 / 60.0;
+    let states = |minutes: f64| {
+        Some((
+            before_constants.propagate(minutes).ok()?,
+            after_constants.propagate(minutes - gap).ok()?,
+        ))
+    };
+    let period = 1440.0 / before.mean_motion;
+    let minutes = closest_approach(gap, period / BURN_STEPS_PER_ORBIT, |minutes| {
+        states(minutes).map(|(b, a)| distance(&a.position, &b.position))
+    })?;
+    let (b, a) = states(minutes)?;
+    Some((
+        before.datetime + Duration::milliseconds((minutes * 60000.0) as i64),
+        distance(&a.velocity, &b.velocity) * 1000.0,
+    ))
+}
+
+/// Minutes into the gap where the separation is smallest: the smallest sample of the scan,
+/// refined by golden section search between its neighbours. None if the separation is not known somewhere.
+fn closest_approach(gap: f64, step: f64, separation: impl Fn(f64) -> Option<f64>) -> Option<f64> {
+    let steps = ((gap / step).ceil() as usize).clamp(1, MAX_BURN_STEPS);
+    let step = gap / steps as f64;
+    let mut best: Option<(f64, f64)> = None;
+    for i in 0..=steps {
+        let minutes = step * i as f64;
+        let d = separation(minutes)?;
+        if best.map(|(_, best)| d < best).unwrap_or(true) {
+            best = Some((minutes, d));
+        }
+    }
+    let (minutes, _) = best?;
+
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = ((minutes - step).max(0.0), (minutes + step).min(gap));
+    let mut left = high - ratio * (high - low);
+    let mut right = low + ratio * (high - low);
+    let (mut d_left, mut d_right) = (separation(left)?, separation(right)?);
+    while high - low > BURN_TOLERANCE {
+        if d_left < d_right {
+            high = right;
+            right = left;
+            d_right = d_left;
+            left = high - ratio * (high - low);
+            d_left = separation(left)?;
+        } else {
+            low = left;
+            left = right;
+            d_left = d_right;
+            right = low + ratio * (high - low);
+            d_right = separation(right)?;
+        }
+    }
+    Some((low + high) / 2.0)
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::element_util::test_elements;
+
+    /// The ISS-like test set of a day, as it drifts from the first of January without maneuvers
+    fn drifted(source: &str, day: u32) -> ElementSet {
+        let mut elements = test_elements(25544, 1);
+        elements.mean_motion_dot = 1e-4;
+        let days = (day - 1) as f64;
+        elements.right_ascension += node_drift(&elements) * days;
+        elements.mean_motion += 2.0 * elements.mean_motion_dot * days;
+        elements.datetime = test_elements(25544, day).datetime;
+        ElementSet {
+            source: source.to_string(),
+            elements,
+        }
+    }
+
+    /// Raised by about 15 km
+    fn raised(source: &str, day: u32) -> ElementSet {
+        let mut set = drifted(source, day);
+        set.elements.mean_motion -= 0.05;
+        set
+    }
+
+    /// Like two trajectories parting at the burn: apart a little more every orbit, and along the
+    /// orbit back and forth once per period
+    fn parting(burn: f64, period: f64) -> impl Fn(f64) -> Option<f64> {
+        move |minutes: f64| {
+            let since = minutes - burn;
+            Some(0.01 * since.abs() + 20.0 * (1.0 - (2.0 * PI * since / period).cos()))
+        }
+    }
+
+    #[test]
+    fn burns_are_found_across_long_gaps() {
+        // ten days of a low orbit, far more orbits than the old 200 steps could resolve
+        let period = 92.7;
+        let gap = 10.0 * 1440.0;
+        for burn in [5123.4, 37.25, 14321.9] {
+            let found = closest_approach(gap, period / BURN_STEPS_PER_ORBIT, parting(burn, period))
+                .unwrap();
+            assert!((found - burn).abs() < 0.01, "{} instead of {}", found, burn);
+        }
+    }
+
+    #[test]
+    fn burns_at_the_ends_of_the_gap() {
+        let period = 1436.0;
+        let gap = 2.0 * 1440.0;
+        let found =
+            closest_approach(gap, period / BURN_STEPS_PER_ORBIT, parting(0.0, period)).unwrap();
+        assert!(found < 0.01, "{}", found);
+        let found =
+            closest_approach(gap, period / BURN_STEPS_PER_ORBIT, parting(gap, period)).unwrap();
+        assert!(gap - found < 0.01, "{}", found);
+    }
+
+    #[test]
+    fn failed_propagation_finds_nothing() {
+        let separation = |minutes: f64| if minutes > 100.0 { None } else { Some(minutes) };
+        assert_eq!(closest_approach(1000.0, 5.0, separation), None);
+    }
+
+    #[test]
+    fn gauss_delta_v_of_a_raise() {
+        let before = Elements {
+            object_name: None,
+            international_designator: None,
+            norad_id: 1,
+            classification: sgp4::Classification::Unclassified,
+            datetime: chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            mean_motion_dot: 0.0,
+            mean_motion_ddot: 0.0,
+            drag_term: 0.0,
+            element_set_number: 1,
+            inclination: 51.6,
+            right_ascension: 0.0,
+            eccentricity: 0.0,
+            argument_of_perigee: 0.0,
+            mean_anomaly: 0.0,
+            mean_motion: 15.5,
+            revolution_number: 0,
+            ephemeris_type: 0,
+        };
+        // about 0.55 m/s per km of semi-major axis at 400 km
+        let delta_v = gauss_delta_v(&before, 1.0, 0.0, 0.0);
+        assert!((delta_v - 0.55).abs() < 0.02, "{}", delta_v);
+    }
+
+    #[test]
+    fn raises_are_detected() {
+        let settings = DetectionSettings::default();
+        let events = detect_events(&[drifted("a", 1), raised("a", 3)], &settings);
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.kinds, [EventKind::SemiMajorAxis]);
+        assert!(event.is_maneuver());
+        assert_eq!(event.source, "a");
+        assert_eq!(event.before, test_elements(1, 1).datetime);
+        assert_eq!(event.after, test_elements(1, 3).datetime);
+        assert!(event.before <= event.epoch && event.epoch <= event.after);
+        assert!(
+            (event.delta_semi_major_axis - 14.6).abs() < 0.2,
+            "{}",
+            event.delta_semi_major_axis
+        );
+        assert!(event.delta_raan.abs() < 1e-9, "{}", event.delta_raan);
+        assert!(event.delta_v > 0.0);
+    }
+
+    #[test]
+    fn drift_is_no_event() {
+        let settings = DetectionSettings::default();
+        let history = [1, 2, 4, 9].map(|day| drifted("a", day));
+        assert!(detect_events(&history, &settings).is_empty());
+
+        // the node moves about 5 degrees a day, a set that does not is off
+        let mut still = drifted("a", 2);
+        still.elements.right_ascension = drifted("a", 1).elements.right_ascension;
+        let events = detect_events(&[drifted("a", 1), still], &settings);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kinds, [EventKind::Raan]);
+        assert!(
+            (events[0].delta_raan - 5.0).abs() < 0.3,
+            "{}",
+            events[0].delta_raan
+        );
+    }
+
+    #[test]
+    fn sources_are_compared_separately() {
+        let settings = DetectionSettings::default();
+        let history = [drifted("a", 1), raised("b", 2)];
+        assert!(detect_events(&history, &settings).is_empty());
+
+        let history = [
+            drifted("a", 1),
+            raised("b", 2),
+            raised("b", 3),
+            raised("a", 4),
+        ];
+        let events = detect_events(&history, &settings);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source, "a");
+        assert_eq!(events[0].before, test_elements(1, 1).datetime);
+        assert_eq!(events[0].after, test_elements(1, 4).datetime);
+    }
+
+    #[test]
+    fn long_gaps_are_skipped() {
+        let mut settings = DetectionSettings::default();
+        let history = [drifted("a", 1), raised("a", 12)];
+        assert!(detect_events(&history, &settings).is_empty());
+        settings.max_gap_days = 11.0;
+        assert_eq!(detect_events(&history, &settings).len(), 1);
+    }
+}
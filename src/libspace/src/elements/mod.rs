@@ -1,10 +1,32 @@
 pub mod element_db;
 pub mod element_engine;
+pub mod element_fetch;
 pub mod element_index;
+pub mod element_query;
+pub mod element_source;
+pub mod maneuver;
+pub mod omm;
+pub mod satcat;
+pub mod space_track;
+pub mod tle;
 
 pub use element_db::*;
 pub use element_engine::*;
+pub use element_fetch::*;
 pub use element_index::*;
+pub use element_query::*;
+pub use element_source::*;
+pub use maneuver::*;
+pub use omm::*;
+pub use satcat::*;
+pub use space_track::*;
+pub use tle::*;
 
+pub use element_store::ElementSet;
+
+mod element_storage;
 mod element_store;
 mod element_util;
+mod omm_csv;
+mod omm_kvn;
+mod omm_xml;
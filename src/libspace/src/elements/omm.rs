@@ -0,0 +1,783 @@
+// CCSDS Orbit Mean-Elements Messages (CCSDS 502.0-B-3) in their XML, KVN and Celestrak's CSV form.
+// All three are read into keyword lists first, so the mapping to the message lives only here.
+// Omm keeps what sgp4::Elements has no room for, like the originator and the covariance.
+// Only messages with SGP4 mean elements (MEAN_MOTION and the TLE parameters) are supported.
+
+use crate::elements::{omm_csv, omm_kvn, omm_xml};
+use chrono::NaiveDateTime;
+use sgp4::{Classification, Elements};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// keyword and value pairs of one message, in the order they appeared
+pub(crate) type Keywords = Vec<(String, String)>;
+
+pub const OMM_VERSION: &str = "2.0";
+const EPOCH_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6f";
+pub(crate) const USER_DEFINED_PREFIX: &str = "USER_DEFINED_";
+
+/// lower triangle of the 6x6 covariance, row by row
+pub const COVARIANCE_KEYWORDS: [&str; 21] = [
+    "CX_X",
+    "CY_X",
+    "CY_Y",
+    "CZ_X",
+    "CZ_Y",
+    "CZ_Z",
+    "CX_DOT_X",
+    "CX_DOT_Y",
+    "CX_DOT_Z",
+    "CX_DOT_X_DOT",
+    "CY_DOT_X",
+    "CY_DOT_Y",
+    "CY_DOT_Z",
+    "CY_DOT_X_DOT",
+    "CY_DOT_Y_DOT",
+    "CZ_DOT_X",
+    "CZ_DOT_Y",
+    "CZ_DOT_Z",
+    "CZ_DOT_X_DOT",
+    "CZ_DOT_Y_DOT",
+    "CZ_DOT_Z_DOT",
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OmmFormat {
+    Xml,
+    Kvn,
+    /// what Celestrak gives for FORMAT=csv, one message per row with only the element columns
+    Csv,
+}
+
+/// Where a keyword goes in the message
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OmmSection {
+    Header,
+    Metadata,
+    MeanElements,
+    TleParameters,
+    Covariance,
+    UserDefined,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OmmCovariance {
+    /// the frame of the metadata if None
+    pub ref_frame: Option<String>,
+    /// in the order of COVARIANCE_KEYWORDS, km² and km²/s²
+    pub values: [f64; 21],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Omm {
+    pub version: String,
+    /// kept as written, the standard allows several time formats
+    pub creation_date: String,
+    pub originator: String,
+    pub message_id: Option<String>,
+    pub comments: Vec<String>,
+
+    pub object_name: String,
+    pub object_id: String,
+    pub center_name: String,
+    pub ref_frame: String,
+    pub time_system: String,
+    pub mean_element_theory: String,
+
+    pub epoch: NaiveDateTime,
+    /// rev/day
+    pub mean_motion: f64,
+    pub eccentricity: f64,
+    /// deg
+    pub inclination: f64,
+    pub ra_of_asc_node: f64,
+    pub arg_of_pericenter: f64,
+    pub mean_anomaly: f64,
+    /// km³/s²
+    pub gm: Option<f64>,
+
+    pub ephemeris_type: u8,
+    pub classification_type: char,
+    pub norad_cat_id: u64,
+    pub element_set_no: u64,
+    pub rev_at_epoch: u64,
+    /// 1/earth radii
+    pub bstar: f64,
+    /// rev/day²
+    pub mean_motion_dot: f64,
+    /// rev/day³
+    pub mean_motion_ddot: f64,
+
+    pub covariance: Option<OmmCovariance>,
+    /// without the USER_DEFINED_ prefix
+    pub user_defined: Vec<(String, String)>,
+}
+
+impl OmmFormat {
+    /// By file extension: .xml, .kvn or .omm, .csv
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "xml" => Some(OmmFormat::Xml),
+            "kvn" | "omm" => Some(OmmFormat::Kvn),
+            "csv" => Some(OmmFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn read(&self, text: &str) -> Result<Vec<Omm>, String> {
+        let messages = match self {
+            OmmFormat::Xml => omm_xml::read(text)?,
+            OmmFormat::Kvn => omm_kvn::read(text)?,
+            OmmFormat::Csv => omm_csv::read(text)?,
+        };
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, keywords)| {
+                Omm::from_keywords(keywords).map_err(|e| format!("Message {}: {}", i + 1, e))
+            })
+            .collect()
+    }
+
+    pub fn write(&self, messages: &[Omm]) -> String {
+        match self {
+            OmmFormat::Xml => omm_xml::write(messages),
+            OmmFormat::Kvn => omm_kvn::write(messages),
+            OmmFormat::Csv => omm_csv::write(messages),
+        }
+    }
+}
+
+impl Display for OmmFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OmmFormat::Xml => write!(f, "OMM XML"),
+            OmmFormat::Kvn => write!(f, "OMM KVN"),
+            OmmFormat::Csv => write!(f, "OMM CSV"),
+        }
+    }
+}
+
+impl Omm {
+    /// A message for elements that came from elsewhere, with the usual SGP4 metadata
+    pub fn from_elements(elements: &Elements, originator: &str) -> Self {
+        Self {
+            version: OMM_VERSION.to_string(),
+            creation_date: chrono::Utc::now()
+                .naive_utc()
+                .format(EPOCH_FORMAT)
+                .to_string(),
+            originator: originator.to_string(),
+            message_id: None,
+            comments: Vec::new(),
+            object_name: elements
+                .object_name
+                .clone()
+                .unwrap_or_else(|| elements.norad_id.to_string()),
+            object_id: elements
+                .international_designator
+                .clone()
+                .unwrap_or_else(|| "UNKNOWN".to_string()),
+            center_name: "EARTH".to_string(),
+            ref_frame: "TEME".to_string(),
+            time_system: "UTC".to_string(),
+            mean_element_theory: "SGP4".to_string(),
+            epoch: elements.datetime,
+            mean_motion: elements.mean_motion,
+            eccentricity: elements.eccentricity,
+            inclination: elements.inclination,
+            ra_of_asc_node: elements.right_ascension,
+            arg_of_pericenter: elements.argument_of_perigee,
+            mean_anomaly: elements.mean_anomaly,
+            gm: None,
+            ephemeris_type: elements.ephemeris_type,
+            classification_type: match elements.classification {
+                Classification::Unclassified => 'U',
+                Classification::Classified => 'C',
+                Classification::Secret => 'S',
+            },
+            norad_cat_id: elements.norad_id,
+            element_set_no: elements.element_set_number,
+            rev_at_epoch: elements.revolution_number,
+            bstar: elements.drag_term,
+            mean_motion_dot: elements.mean_motion_dot,
+            mean_motion_ddot: elements.mean_motion_ddot,
+            covariance: None,
+            user_defined: Vec::new(),
+        }
+    }
+
+    pub fn to_elements(&self) -> Elements {
+        Elements {
+            object_name: Some(self.object_name.clone()).filter(|n| !n.is_empty()),
+            international_designator: Some(self.object_id.clone())
+                .filter(|id| !id.is_empty() && id != "UNKNOWN"),
+            norad_id: self.norad_cat_id,
+            classification: match self.classification_type {
+                'C' => Classification::Classified,
+                'S' => Classification::Secret,
+                _ => Classification::Unclassified,
+            },
+            datetime: self.epoch,
+            mean_motion_dot: self.mean_motion_dot,
+            mean_motion_ddot: self.mean_motion_ddot,
+            drag_term: self.bstar,
+            element_set_number: self.element_set_no,
+            inclination: self.inclination,
+            right_ascension: self.ra_of_asc_node,
+            eccentricity: self.eccentricity,
+            argument_of_perigee: self.arg_of_pericenter,
+            mean_anomaly: self.mean_anomaly,
+            mean_motion: self.mean_motion,
+            revolution_number: self.rev_at_epoch,
+            ephemeris_type: self.ephemeris_type,
+        }
+    }
+
+    pub(crate) fn from_keywords(keywords: &[(String, String)]) -> Result<Self, String> {
+        let map = keywords
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.trim()))
+            .collect::<HashMap<&str, &str>>();
+        let optional = |keyword: &str| map.get(keyword).map(|v| v.to_string());
+        let required = |keyword: &str| {
+            map.get(keyword)
+                .map(|v| v.to_string())
+                .ok_or_else(|| format!("No {}", keyword))
+        };
+        let number = |keyword: &str| {
+            required(keyword)?
+                .parse::<f64>()
+                .map_err(|_| format!("{} is not a number", keyword))
+        };
+        let integer = |keyword: &str| {
+            required(keyword)?
+                .parse::<u64>()
+                .map_err(|_| format!("{} is not a whole number", keyword))
+        };
+
+        let covariance = if map.contains_key(COVARIANCE_KEYWORDS[0]) {
+            let mut values = [0.0; 21];
+            for (value, keyword) in values.iter_mut().zip(COVARIANCE_KEYWORDS.iter()) {
+                *value = number(keyword)?;
+            }
+            Some(OmmCovariance {
+                ref_frame: optional("COV_REF_FRAME"),
+                values,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            version: optional("CCSDS_OMM_VERS").unwrap_or_else(|| OMM_VERSION.to_string()),
+            creation_date: optional("CREATION_DATE").unwrap_or_default(),
+            originator: optional("ORIGINATOR").unwrap_or_default(),
+            message_id: optional("MESSAGE_ID"),
+            comments: keywords
+                .iter()
+                .filter(|(k, _)| k == "COMMENT")
+                .map(|(_, v)| v.trim().to_string())
+                .collect(),
+            object_name: optional("OBJECT_NAME").unwrap_or_default(),
+            object_id: optional("OBJECT_ID").unwrap_or_default(),
+            center_name: optional("CENTER_NAME").unwrap_or_else(|| "EARTH".to_string()),
+            ref_frame: optional("REF_FRAME").unwrap_or_else(|| "TEME".to_string()),
+            time_system: optional("TIME_SYSTEM").unwrap_or_else(|| "UTC".to_string()),
+            mean_element_theory: optional("MEAN_ELEMENT_THEORY")
+                .unwrap_or_else(|| "SGP4".to_string()),
+            epoch: parse_epoch(&required("EPOCH")?)?,
+            mean_motion: number("MEAN_MOTION")?,
+            eccentricity: number("ECCENTRICITY")?,
+            inclination: number("INCLINATION")?,
+            ra_of_asc_node: number("RA_OF_ASC_NODE")?,
+            arg_of_pericenter: number("ARG_OF_PERICENTER")?,
+            mean_anomaly: number("MEAN_ANOMALY")?,
+            gm: match map.contains_key("GM") {
+                true => Some(number("GM")?),
+                false => None,
+            },
+            ephemeris_type: optional("EPHEMERIS_TYPE")
+                .map(|t| t.parse::<u8>())
+                .unwrap_or(Ok(0))
+                .map_err(|_| "EPHEMERIS_TYPE is not a number".to_string())?,
+            classification_type: optional("CLASSIFICATION_TYPE")
+                .and_then(|c| c.chars().next())
+                .unwrap_or('U'),
+            norad_cat_id: integer("NORAD_CAT_ID")?,
+            element_set_no: optional("ELEMENT_SET_NO")
+                .map(|_| integer("ELEMENT_SET_NO"))
+                .unwrap_or(Ok(0))?,
+            rev_at_epoch: optional("REV_AT_EPOCH")
+                .map(|_| integer("REV_AT_EPOCH"))
+                .unwrap_or(Ok(0))?,
+            bstar: number("BSTAR")?,
+            mean_motion_dot: number("MEAN_MOTION_DOT")?,
+            mean_motion_ddot: number("MEAN_MOTION_DDOT")?,
+            covariance,
+            user_defined: keywords
+                .iter()
+                .filter_map(|(k, v)| {
+                    k.strip_prefix(USER_DEFINED_PREFIX)
+                        .map(|k| (k.to_string(), v.trim().to_string()))
+                })
+                .collect(),
+        })
+    }
+
+    /// Everything but the version and comments, in the order of the standard
+    pub(crate) fn keywords(&self) -> Vec<(OmmSection, String, String)> {
+        let mut keywords = Vec::new();
+        let mut add = |section: OmmSection, keyword: &str, value: String| {
+            keywords.push((section, keyword.to_string(), value))
+        };
+        add(
+            OmmSection::Header,
+            "CREATION_DATE",
+            self.creation_date.clone(),
+        );
+        add(OmmSection::Header, "ORIGINATOR", self.originator.clone());
+        if let Some(message_id) = &self.message_id {
+            add(OmmSection::Header, "MESSAGE_ID", message_id.clone());
+        }
+
+        add(
+            OmmSection::Metadata,
+            "OBJECT_NAME",
+            self.object_name.clone(),
+        );
+        add(OmmSection::Metadata, "OBJECT_ID", self.object_id.clone());
+        add(
+            OmmSection::Metadata,
+            "CENTER_NAME",
+            self.center_name.clone(),
+        );
+        add(OmmSection::Metadata, "REF_FRAME", self.ref_frame.clone());
+        add(
+            OmmSection::Metadata,
+            "TIME_SYSTEM",
+            self.time_system.clone(),
+        );
+        add(
+            OmmSection::Metadata,
+            "MEAN_ELEMENT_THEORY",
+            self.mean_element_theory.clone(),
+        );
+
+        let mean = OmmSection::MeanElements;
+        add(mean, "EPOCH", self.epoch.format(EPOCH_FORMAT).to_string());
+        add(mean, "MEAN_MOTION", self.mean_motion.to_string());
+        add(mean, "ECCENTRICITY", self.eccentricity.to_string());
+        add(mean, "INCLINATION", self.inclination.to_string());
+        add(mean, "RA_OF_ASC_NODE", self.ra_of_asc_node.to_string());
+        add(
+            mean,
+            "ARG_OF_PERICENTER",
+            self.arg_of_pericenter.to_string(),
+        );
+        add(mean, "MEAN_ANOMALY", self.mean_anomaly.to_string());
+        if let Some(gm) = self.gm {
+            add(mean, "GM", gm.to_string());
+        }
+
+        let tle = OmmSection::TleParameters;
+        add(tle, "EPHEMERIS_TYPE", self.ephemeris_type.to_string());
+        add(
+            tle,
+            "CLASSIFICATION_TYPE",
+            self.classification_type.to_string(),
+        );
+        add(tle, "NORAD_CAT_ID", self.norad_cat_id.to_string());
+        add(tle, "ELEMENT_SET_NO", self.element_set_no.to_string());
+        add(tle, "REV_AT_EPOCH", self.rev_at_epoch.to_string());
+        add(tle, "BSTAR", self.bstar.to_string());
+        add(tle, "MEAN_MOTION_DOT", self.mean_motion_dot.to_string());
+        add(tle, "MEAN_MOTION_DDOT", self.mean_motion_ddot.to_string());
+
+        if let Some(covariance) = &self.covariance {
+            if let Some(frame) = &covariance.ref_frame {
+                add(OmmSection::Covariance, "COV_REF_FRAME", frame.clone());
+            }
+            for (keyword, value) in COVARIANCE_KEYWORDS.iter().zip(covariance.values.iter()) {
+                add(OmmSection::Covariance, keyword, value.to_string());
+            }
+        }
+
+        for (parameter, value) in self.user_defined.iter() {
+            add(
+                OmmSection::UserDefined,
+                &format!("{}{}", USER_DEFINED_PREFIX, parameter),
+                value.clone(),
+            );
+        }
+        keywords
+    }
+}
+
+/// Calendar or day of year form, with or without a trailing Z
+fn parse_epoch(epoch: &str) -> Result<NaiveDateTime, String> {
+    let epoch = epoch.trim().trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(epoch, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(epoch, "%Y-%jT%H:%M:%S%.f"))
+        .map_err(|_| format!("Invalid EPOCH {}", epoch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn message() -> Omm {
+        let mut values = [0.0; 21];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = (i as f64 + 1.0) * 1.25e-4;
+        }
+        Omm {
+            version: OMM_VERSION.to_string(),
+            creation_date: "2024-03-01T12:00:00".to_string(),
+            originator: "TEST".to_string(),
+            message_id: Some("M-1".to_string()),
+            comments: vec!["from a test".to_string()],
+            object_name: "ISS (ZARYA)".to_string(),
+            object_id: "1998-067A".to_string(),
+            center_name: "EARTH".to_string(),
+            ref_frame: "TEME".to_string(),
+            time_system: "UTC".to_string(),
+            mean_element_theory: "SGP4".to_string(),
+            epoch: NaiveDate::from_ymd_opt(2024, 3, 1)
+                .unwrap()
+                .and_hms_micro_opt(6, 30, 15, 123456)
+                .unwrap(),
+            mean_motion: 15.50103472,
+            eccentricity: 0.0006703,
+            inclination: 51.6416,
+            ra_of_asc_node: 247.4627,
+            arg_of_pericenter: 130.536,
+            mean_anomaly: 325.0288,
+            gm: Some(398600.8),
+            ephemeris_type: 0,
+            classification_type: 'U',
+            norad_cat_id: 25544,
+            element_set_no: 999,
+            rev_at_epoch: 44150,
+            bstar: -1.1606e-5,
+            mean_motion_dot: -2.182e-5,
+            mean_motion_ddot: 0.0,
+            covariance: Some(OmmCovariance {
+                ref_frame: Some("TEME".to_string()),
+                values,
+            }),
+            user_defined: vec![
+                ("SOURCE".to_string(), "unit test".to_string()),
+                ("RCS".to_string(), "LARGE".to_string()),
+            ],
+        }
+    }
+
+    #[test]
+    fn xml_round_trip() {
+        let messages = vec![message(), message()];
+        let read = OmmFormat::Xml
+            .read(&OmmFormat::Xml.write(&messages))
+            .unwrap();
+        assert_eq!(read, messages);
+    }
+
+    #[test]
+    fn kvn_round_trip() {
+        let mut without = message();
+        without.covariance = None;
+        without.user_defined.clear();
+        let messages = vec![message(), without];
+        let read = OmmFormat::Kvn
+            .read(&OmmFormat::Kvn.write(&messages))
+            .unwrap();
+        assert_eq!(read, messages);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        // the header does not fit the columns
+        let mut full = message();
+        full.creation_date = String::new();
+        full.originator = String::new();
+        full.message_id = None;
+        full.comments.clear();
+        let mut without = full.clone();
+        without.covariance = None;
+        without.user_defined.clear();
+        without.gm = None;
+        let messages = vec![without, full];
+
+        let text = OmmFormat::Csv.write(&messages);
+        let header = text.lines().next().unwrap();
+        assert!(header.starts_with("OBJECT_NAME,OBJECT_ID,EPOCH,"));
+        assert!(header.ends_with("CZ_DOT_Z_DOT,USER_DEFINED_SOURCE,USER_DEFINED_RCS"));
+        assert_eq!(OmmFormat::Csv.read(&text).unwrap(), messages);
+    }
+
+    /// The GOES 9 example of CCSDS 502.0-B-3, with its covariance
+    const CCSDS_KVN: &str = "CCSDS_OMM_VERS = 3.0
+COMMENT this is a comment
+COMMENT here is another one
+CREATION_DATE = 2007-065T16:00:00
+ORIGINATOR = NOAA/USA
+MESSAGE_ID = OMM 201113719185
+
+COMMENT this comment describes the metadata
+OBJECT_NAME = GOES 9
+OBJECT_ID = 1995-025A
+CENTER_NAME = EARTH
+REF_FRAME = TEME
+TIME_SYSTEM = UTC
+MEAN_ELEMENT_THEORY = SGP/SGP4
+
+COMMENT this comment describes the mean elements
+EPOCH = 2007-064T10:34:41.4264
+MEAN_MOTION = 1.00273272 [rev/day]
+ECCENTRICITY = 0.0005013
+INCLINATION = 3.0539 [deg]
+RA_OF_ASC_NODE = 81.7939 [deg]
+ARG_OF_PERICENTER = 249.2363 [deg]
+MEAN_ANOMALY = 150.1602 [deg]
+GM = 398600.8 [km**3/s**2]
+
+EPHEMERIS_TYPE = 0
+CLASSIFICATION_TYPE = U
+NORAD_CAT_ID = 23581
+ELEMENT_SET_NO = 0925
+REV_AT_EPOCH = 4316
+BSTAR = 0.0001 [1/ER]
+MEAN_MOTION_DOT = -0.00000113 [rev/day**2]
+MEAN_MOTION_DDOT = 0.0 [rev/day**3]
+
+COV_REF_FRAME = TEME
+CX_X = 3.331349476038534e-04
+CY_X = 4.618927349220216e-04
+CY_Y = 6.782421679971363e-04
+CZ_X = -3.070007847730449e-04
+CZ_Y = -4.221234189514228e-04
+CZ_Z = 3.231931992380369e-04
+CX_DOT_X = -3.349365033922630e-07
+CX_DOT_Y = -4.686084221046758e-07
+CX_DOT_Z = 2.484949578400095e-07
+CX_DOT_X_DOT = 4.296022805587290e-10
+CY_DOT_X = -2.211832501084875e-07
+CY_DOT_Y = -2.864186892102733e-07
+CY_DOT_Z = 1.798098699846038e-07
+CY_DOT_X_DOT = 2.608899201686016e-10
+CY_DOT_Y_DOT = 1.767514756338532e-10
+CZ_DOT_X = -3.041346050686871e-07
+CZ_DOT_Y = -4.989496988610662e-07
+CZ_DOT_Z = 3.540310904497689e-07
+CZ_DOT_X_DOT = 1.869263192954590e-10
+CZ_DOT_Y_DOT = 1.008862586240695e-10
+CZ_DOT_Z_DOT = 6.224444338635500e-10
+
+USER_DEFINED_EARTH_MODEL = WGS-84
+";
+
+    /// The XML version of the GOES 9 example, in the default namespace of the qualified schema
+    const CCSDS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- OMM as in CCSDS 505.0-B-3 -->
+<omm xmlns="urn:ccsds:schema:ndmxml"
+     xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+     xsi:schemaLocation="urn:ccsds:schema:ndmxml https://sanaregistry.org/r/ndmxml_qualified/ndmxml-3.0.0-master-3.0.xsd"
+     id="CCSDS_OMM_VERS" version="3.0">
+  <header>
+    <COMMENT>THIS IS AN XML VERSION OF THE OMM</COMMENT>
+    <CREATION_DATE>2007-065T16:00:00</CREATION_DATE>
+    <ORIGINATOR>NOAA</ORIGINATOR>
+    <MESSAGE_ID>OMM 201113719185</MESSAGE_ID>
+  </header>
+  <body>
+    <segment>
+      <metadata>
+        <OBJECT_NAME>GOES-9</OBJECT_NAME>
+        <OBJECT_ID>1995-025A</OBJECT_ID>
+        <CENTER_NAME>EARTH</CENTER_NAME>
+        <REF_FRAME>TEME</REF_FRAME>
+        <TIME_SYSTEM>UTC</TIME_SYSTEM>
+        <MEAN_ELEMENT_THEORY>SGP/SGP4</MEAN_ELEMENT_THEORY>
+      </metadata>
+      <data>
+        <meanElements>
+          <EPOCH>2007-064T10:34:41.4264</EPOCH>
+          <MEAN_MOTION units="rev/day">1.00273272</MEAN_MOTION>
+          <ECCENTRICITY>0.0005013</ECCENTRICITY>
+          <INCLINATION units="deg">3.0539</INCLINATION>
+          <RA_OF_ASC_NODE units="deg">81.7939</RA_OF_ASC_NODE>
+          <ARG_OF_PERICENTER units="deg">249.2363</ARG_OF_PERICENTER>
+          <MEAN_ANOMALY units="deg">150.1602</MEAN_ANOMALY>
+          <GM units="km**3/s**2">398600.8</GM>
+        </meanElements>
+        <tleParameters>
+          <EPHEMERIS_TYPE>0</EPHEMERIS_TYPE>
+          <CLASSIFICATION_TYPE>U</CLASSIFICATION_TYPE>
+          <NORAD_CAT_ID>23581</NORAD_CAT_ID>
+          <ELEMENT_SET_NO>0925</ELEMENT_SET_NO>
+          <REV_AT_EPOCH>4316</REV_AT_EPOCH>
+          <BSTAR units="1/ER">0.0001</BSTAR>
+          <MEAN_MOTION_DOT units="rev/day**2">-0.00000113</MEAN_MOTION_DOT>
+          <MEAN_MOTION_DDOT units="rev/day**3">0.0</MEAN_MOTION_DDOT>
+        </tleParameters>
+        <userDefinedParameters>
+          <USER_DEFINED parameter="EARTH_MODEL">WGS-84</USER_DEFINED>
+        </userDefinedParameters>
+      </data>
+    </segment>
+  </body>
+</omm>
+"#;
+
+    /// As Celestrak serves FORMAT=xml, with empty header fields
+    const CELESTRAK_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ndm xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="https://sanaregistry.org/r/ndmxml_unqualified/ndmxml-2.0.0-master-2.0.xsd">
+<omm xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="https://sanaregistry.org/r/ndmxml_unqualified/ndmxml-2.0.0-master-2.0.xsd" id="CCSDS_OMM_VERS" version="2.0">
+<header><CREATION_DATE/><ORIGINATOR/></header><body><segment><metadata><OBJECT_NAME>ISS (ZARYA)</OBJECT_NAME><OBJECT_ID>1998-067A</OBJECT_ID><CENTER_NAME>EARTH</CENTER_NAME><REF_FRAME>TEME</REF_FRAME><TIME_SYSTEM>UTC</TIME_SYSTEM><MEAN_ELEMENT_THEORY>SGP4</MEAN_ELEMENT_THEORY></metadata><data><meanElements><EPOCH>2024-02-29T12:26:39.963360</EPOCH><MEAN_MOTION>15.49910918</MEAN_MOTION><ECCENTRICITY>.0005776</ECCENTRICITY><INCLINATION>51.6405</INCLINATION><RA_OF_ASC_NODE>149.5213</RA_OF_ASC_NODE><ARG_OF_PERICENTER>322.9563</ARG_OF_PERICENTER><MEAN_ANOMALY>147.0936</MEAN_ANOMALY></meanElements><tleParameters><EPHEMERIS_TYPE>0</EPHEMERIS_TYPE><CLASSIFICATION_TYPE>U</CLASSIFICATION_TYPE><NORAD_CAT_ID>25544</NORAD_CAT_ID><ELEMENT_SET_NO>999</ELEMENT_SET_NO><REV_AT_EPOCH>44150</REV_AT_EPOCH><BSTAR>.46158E-3</BSTAR><MEAN_MOTION_DOT>.00024961</MEAN_MOTION_DOT><MEAN_MOTION_DDOT>0</MEAN_MOTION_DDOT></tleParameters></data></segment></body></omm>
+</ndm>
+"#;
+
+    /// As Celestrak serves FORMAT=csv
+    const CELESTRAK_CSV: &str = "OBJECT_NAME,OBJECT_ID,EPOCH,MEAN_MOTION,ECCENTRICITY,INCLINATION,RA_OF_ASC_NODE,ARG_OF_PERICENTER,MEAN_ANOMALY,EPHEMERIS_TYPE,CLASSIFICATION_TYPE,NORAD_CAT_ID,ELEMENT_SET_NO,REV_AT_EPOCH,BSTAR,MEAN_MOTION_DOT,MEAN_MOTION_DDOT\r
+ISS (ZARYA),1998-067A,2024-02-29T12:26:39.963360,15.49910918,.0005776,51.6405,149.5213,322.9563,147.0936,0,U,25544,999,44150,.46158E-3,.00024961,0\r
+\"FLOCK 4P-1, 2\",2021-006DG,2024-02-29T09:46:37.117536,15.36453478,.0008642,97.3894,93.8147,103.4568,256.7627,0,U,47454,999,17183,.55419E-3,.00028497,0\r
+";
+
+    fn goes_9_epoch() -> NaiveDateTime {
+        NaiveDate::from_yo_opt(2007, 64)
+            .unwrap()
+            .and_hms_micro_opt(10, 34, 41, 426400)
+            .unwrap()
+    }
+
+    #[test]
+    fn ccsds_kvn_example() {
+        let messages = OmmFormat::Kvn.read(CCSDS_KVN).unwrap();
+        assert_eq!(messages.len(), 1);
+        let omm = &messages[0];
+        assert_eq!(omm.version, "3.0");
+        assert_eq!(omm.creation_date, "2007-065T16:00:00");
+        assert_eq!(omm.originator, "NOAA/USA");
+        assert_eq!(omm.message_id.as_deref(), Some("OMM 201113719185"));
+        assert_eq!(
+            omm.comments,
+            vec![
+                "this is a comment",
+                "here is another one",
+                "this comment describes the metadata",
+                "this comment describes the mean elements",
+            ]
+        );
+        assert_eq!(omm.object_name, "GOES 9");
+        assert_eq!(omm.object_id, "1995-025A");
+        assert_eq!(omm.mean_element_theory, "SGP/SGP4");
+        assert_eq!(omm.epoch, goes_9_epoch());
+        assert_eq!(omm.mean_motion, 1.00273272);
+        assert_eq!(omm.eccentricity, 0.0005013);
+        assert_eq!(omm.inclination, 3.0539);
+        assert_eq!(omm.ra_of_asc_node, 81.7939);
+        assert_eq!(omm.arg_of_pericenter, 249.2363);
+        assert_eq!(omm.mean_anomaly, 150.1602);
+        assert_eq!(omm.gm, Some(398600.8));
+        assert_eq!(omm.norad_cat_id, 23581);
+        assert_eq!(omm.element_set_no, 925);
+        assert_eq!(omm.rev_at_epoch, 4316);
+        assert_eq!(omm.bstar, 0.0001);
+        assert_eq!(omm.mean_motion_dot, -0.00000113);
+        assert_eq!(omm.mean_motion_ddot, 0.0);
+        let covariance = omm.covariance.as_ref().unwrap();
+        assert_eq!(covariance.ref_frame.as_deref(), Some("TEME"));
+        assert_eq!(covariance.values[0], 3.331349476038534e-04);
+        assert_eq!(covariance.values[20], 6.2244443386355e-10);
+        assert_eq!(
+            omm.user_defined,
+            vec![("EARTH_MODEL".to_string(), "WGS-84".to_string())]
+        );
+    }
+
+    #[test]
+    fn ccsds_xml_example() {
+        let messages = OmmFormat::Xml.read(CCSDS_XML).unwrap();
+        assert_eq!(messages.len(), 1);
+        let omm = &messages[0];
+        assert_eq!(omm.version, "3.0");
+        assert_eq!(omm.comments, vec!["THIS IS AN XML VERSION OF THE OMM"]);
+        assert_eq!(omm.originator, "NOAA");
+        assert_eq!(omm.object_name, "GOES-9");
+        assert_eq!(omm.epoch, goes_9_epoch());
+        assert_eq!(omm.mean_motion, 1.00273272);
+        assert_eq!(omm.gm, Some(398600.8));
+        assert_eq!(omm.norad_cat_id, 23581);
+        assert_eq!(omm.mean_motion_dot, -0.00000113);
+        assert_eq!(omm.covariance, None);
+        assert_eq!(
+            omm.user_defined,
+            vec![("EARTH_MODEL".to_string(), "WGS-84".to_string())]
+        );
+    }
+
+    #[test]
+    fn celestrak_xml() {
+        let messages = OmmFormat::Xml.read(CELESTRAK_XML).unwrap();
+        assert_eq!(messages.len(), 1);
+        let omm = &messages[0];
+        assert_eq!(omm.version, "2.0");
+        assert_eq!(omm.creation_date, "");
+        assert_eq!(omm.originator, "");
+        assert_eq!(omm.object_name, "ISS (ZARYA)");
+        assert_eq!(omm.object_id, "1998-067A");
+        assert_eq!(
+            omm.epoch,
+            NaiveDate::from_ymd_opt(2024, 2, 29)
+                .unwrap()
+                .and_hms_micro_opt(12, 26, 39, 963360)
+                .unwrap()
+        );
+        assert_eq!(omm.eccentricity, 0.0005776);
+        assert_eq!(omm.norad_cat_id, 25544);
+        assert_eq!(omm.rev_at_epoch, 44150);
+        assert_eq!(omm.bstar, 0.46158e-3);
+        assert_eq!(omm.mean_motion_dot, 0.00024961);
+        assert_eq!(omm.gm, None);
+    }
+
+    #[test]
+    fn celestrak_csv() {
+        let messages = OmmFormat::Csv.read(CELESTRAK_CSV).unwrap();
+        assert_eq!(messages.len(), 2);
+        let iss = &messages[0];
+        assert_eq!(iss.object_name, "ISS (ZARYA)");
+        assert_eq!(iss.mean_motion, 15.49910918);
+        assert_eq!(iss.eccentricity, 0.0005776);
+        assert_eq!(iss.inclination, 51.6405);
+        assert_eq!(iss.classification_type, 'U');
+        assert_eq!(iss.bstar, 0.46158e-3);
+        assert_eq!(iss.mean_motion_ddot, 0.0);
+        // what the columns do not have
+        assert_eq!(iss.center_name, "EARTH");
+        assert_eq!(iss.mean_element_theory, "SGP4");
+        let flock = &messages[1];
+        assert_eq!(flock.object_name, "FLOCK 4P-1, 2");
+        assert_eq!(flock.object_id, "2021-006DG");
+        assert_eq!(flock.norad_cat_id, 47454);
+        assert_eq!(flock.rev_at_epoch, 17183);
+    }
+
+    #[test]
+    fn elements_keep_what_they_can() {
+        let omm = message();
+        let elements = omm.to_elements();
+        assert_eq!(elements.norad_id, 25544);
+        assert_eq!(elements.datetime, omm.epoch);
+        assert_eq!(
+            elements.international_designator.as_deref(),
+            Some("1998-067A")
+        );
+        let back = Omm::from_elements(&elements, "TEST");
+        assert_eq!(back.mean_motion, omm.mean_motion);
+        assert_eq!(back.covariance, None);
+    }
+}
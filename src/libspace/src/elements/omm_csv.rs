@@ -0,0 +1,122 @@
+// OMM as Celestrak writes it for FORMAT=csv: a header row naming the keywords, then one message per row.
+// Writing uses the columns of Celestrak, followed by GM, the covariance and user defined parameters of
+// the messages that have them. The header and comments are lost. Empty fields count as not given.
+
+use crate::elements::omm::{Keywords, Omm, OmmSection};
+
+const COLUMNS: [&str; 17] = [
+    "OBJECT_NAME",
+    "OBJECT_ID",
+    "EPOCH",
+    "MEAN_MOTION",
+    "ECCENTRICITY",
+    "INCLINATION",
+    "RA_OF_ASC_NODE",
+    "ARG_OF_PERICENTER",
+    "MEAN_ANOMALY",
+    "EPHEMERIS_TYPE",
+    "CLASSIFICATION_TYPE",
+    "NORAD_CAT_ID",
+    "ELEMENT_SET_NO",
+    "REV_AT_EPOCH",
+    "BSTAR",
+    "MEAN_MOTION_DOT",
+    "MEAN_MOTION_DDOT",
+];
+
+pub(crate) fn read(text: &str) -> Result<Vec<Keywords>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    let header = match lines.next() {
+        Some((_, header)) => split_row(header)
+            .into_iter()
+            .map(|h| h.trim().to_uppercase())
+            .collect::<Vec<String>>(),
+        None => return Err("Empty CSV".to_string()),
+    };
+
+    let mut messages = Vec::new();
+    for (i, line) in lines {
+        let fields = split_row(line);
+        if fields.len() != header.len() {
+            return Err(format!(
+                "Line {}: {} fields, but {} columns",
+                i + 1,
+                fields.len(),
+                header.len()
+            ));
+        }
+        messages.push(
+            header
+                .iter()
+                .cloned()
+                .zip(fields)
+                .filter(|(_, value)| !value.trim().is_empty())
+                .collect(),
+        );
+    }
+    Ok(messages)
+}
+
+pub(crate) fn write(messages: &[Omm]) -> String {
+    let keywords = messages.iter().map(Omm::keywords).collect::<Vec<_>>();
+    let mut columns = COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<String>>();
+    for (section, keyword, _) in keywords.iter().flatten() {
+        let extra =
+            matches!(section, OmmSection::Covariance | OmmSection::UserDefined) || keyword == "GM";
+        if extra && !columns.contains(keyword) {
+            columns.push(keyword.clone());
+        }
+    }
+
+    let mut text = columns.join(",") + "\n";
+    for keywords in keywords.iter() {
+        let row = columns
+            .iter()
+            .map(|column| {
+                keywords
+                    .iter()
+                    .find(|(_, k, _)| k == column)
+                    .map(|(_, _, v)| quote(v))
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<String>>();
+        text += &row.join(",");
+        text += "\n";
+    }
+    text
+}
+
+/// Fields may be quoted, with doubled quotes inside
+pub(crate) fn split_row(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn quote(value: &str) -> String {
+    if value.contains(',') || value.contains('"') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
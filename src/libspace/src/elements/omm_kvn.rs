@@ -0,0 +1,62 @@
+// OMM in keyword = value notation. A file may hold several messages, each starting with CCSDS_OMM_VERS.
+
+use crate::elements::omm::{Keywords, Omm, OmmSection};
+
+pub(crate) fn read(text: &str) -> Result<Vec<Keywords>, String> {
+    let mut messages = Vec::new();
+    let mut current: Keywords = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix("COMMENT") {
+            current.push(("COMMENT".to_string(), comment.trim().to_string()));
+            continue;
+        }
+        let (keyword, value) = match line.split_once('=') {
+            Some((keyword, value)) => (keyword.trim(), value.trim()),
+            // block markers carry no values
+            None if line.ends_with("_START") || line.ends_with("_STOP") => continue,
+            None => return Err(format!("Line {}: expected KEYWORD = value", i + 1)),
+        };
+        if keyword == "CCSDS_OMM_VERS" && !current.is_empty() {
+            messages.push(std::mem::take(&mut current));
+        }
+        // units in brackets are informative only
+        let value = match value.rfind('[') {
+            Some(unit) if value.ends_with(']') => value[..unit].trim(),
+            _ => value,
+        };
+        current.push((keyword.to_string(), value.to_string()));
+    }
+    if !current.is_empty() {
+        messages.push(current);
+    }
+    if messages.is_empty() {
+        return Err("No OMM in the text".to_string());
+    }
+    Ok(messages)
+}
+
+pub(crate) fn write(messages: &[Omm]) -> String {
+    let mut text = String::new();
+    for omm in messages {
+        text += &format!("{:<20} = {}\n", "CCSDS_OMM_VERS", omm.version);
+        let mut section = OmmSection::Header;
+        for (keyword_section, keyword, value) in omm.keywords() {
+            if keyword_section != section {
+                text += "\n";
+                if section == OmmSection::Header {
+                    for comment in omm.comments.iter() {
+                        text += &format!("COMMENT {}\n", comment);
+                    }
+                }
+                section = keyword_section;
+            }
+            text += &format!("{:<20} = {}\n", keyword, value);
+        }
+        text += "\n";
+    }
+    text
+}
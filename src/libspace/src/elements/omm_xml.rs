@@ -0,0 +1,310 @@
+// OMM in the CCSDS NDM/XML schema, one or more <omm> inside an <ndm> or on their own.
+// The reader knows just enough XML for these messages: elements, attributes, text, comments and CDATA.
+// Namespace prefixes are dropped, the schema puts the keywords in no namespace anyway.
+
+use crate::elements::omm::{Keywords, Omm, OmmSection, USER_DEFINED_PREFIX};
+
+struct Node {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            text: String::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn find_all<'a>(&'a self, name: &str, found: &mut Vec<&'a Node>) {
+        for child in self.children.iter() {
+            if child.name == name {
+                found.push(child);
+            } else {
+                child.find_all(name, found);
+            }
+        }
+    }
+
+    /// Leaves become keywords, the sections around them do not matter
+    fn keywords(&self, keywords: &mut Keywords) {
+        for child in self.children.iter() {
+            if !child.children.is_empty() {
+                child.keywords(keywords);
+            } else if child.name == "USER_DEFINED" {
+                let parameter = child.attribute("parameter").unwrap_or_default();
+                keywords.push((
+                    format!("{}{}", USER_DEFINED_PREFIX, parameter),
+                    child.text.trim().to_string(),
+                ));
+            } else {
+                keywords.push((child.name.clone(), child.text.trim().to_string()));
+            }
+        }
+    }
+}
+
+pub(crate) fn read(text: &str) -> Result<Vec<Keywords>, String> {
+    let document = parse(text)?;
+    let mut messages = Vec::new();
+    document.find_all("omm", &mut messages);
+    if messages.is_empty() {
+        return Err("No omm element in the XML".to_string());
+    }
+    Ok(messages
+        .into_iter()
+        .map(|omm| {
+            let mut keywords = Vec::new();
+            if let Some(version) = omm.attribute("version") {
+                keywords.push(("CCSDS_OMM_VERS".to_string(), version.to_string()));
+            }
+            omm.keywords(&mut keywords);
+            keywords
+        })
+        .collect())
+}
+
+pub(crate) fn write(messages: &[Omm]) -> String {
+    let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    text += "<ndm xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+             xsi:noNamespaceSchemaLocation=\"https://sanaregistry.org/r/ndmxml_unqualified/ndmxml-2.0.0-master-2.0.xsd\">\n";
+    for omm in messages {
+        text += &format!(
+            "  <omm id=\"CCSDS_OMM_VERS\" version=\"{}\">\n",
+            escape(&omm.version)
+        );
+        let keywords = omm.keywords();
+        let section = |section: OmmSection| {
+            keywords
+                .iter()
+                .filter(move |(s, _, _)| *s == section)
+                .map(|(_, k, v)| (k.as_str(), v.as_str()))
+        };
+
+        text += "    <header>\n";
+        for (keyword, value) in section(OmmSection::Header) {
+            text += &leaf(3, keyword, value);
+        }
+        text += "    </header>\n    <body>\n      <segment>\n        <metadata>\n";
+        for comment in omm.comments.iter() {
+            text += &leaf(5, "COMMENT", comment);
+        }
+        for (keyword, value) in section(OmmSection::Metadata) {
+            text += &leaf(5, keyword, value);
+        }
+        text += "        </metadata>\n        <data>\n";
+        let blocks = [
+            (OmmSection::MeanElements, "meanElements"),
+            (OmmSection::TleParameters, "tleParameters"),
+            (OmmSection::Covariance, "covarianceMatrix"),
+        ];
+        for (block, name) in blocks {
+            let mut values = section(block).peekable();
+            if values.peek().is_none() {
+                continue;
+            }
+            text += &format!("          <{}>\n", name);
+            for (keyword, value) in values {
+                text += &leaf(6, keyword, value);
+            }
+            text += &format!("          </{}>\n", name);
+        }
+        if !omm.user_defined.is_empty() {
+            text += "          <userDefinedParameters>\n";
+            for (parameter, value) in omm.user_defined.iter() {
+                text += &format!(
+                    "            <USER_DEFINED parameter=\"{}\">{}</USER_DEFINED>\n",
+                    escape(parameter),
+                    escape(value)
+                );
+            }
+            text += "          </userDefinedParameters>\n";
+        }
+        text += "        </data>\n      </segment>\n    </body>\n  </omm>\n";
+    }
+    text + "</ndm>\n"
+}
+
+fn leaf(depth: usize, keyword: &str, value: &str) -> String {
+    format!(
+        "{}<{}>{}</{}>\n",
+        "  ".repeat(depth),
+        keyword,
+        escape(value),
+        keyword
+    )
+}
+
+/// The document as a node without name holding the root element
+fn parse(text: &str) -> Result<Node, String> {
+    let mut stack = vec![Node::new("")];
+    let mut rest = text;
+    let line = |rest: &str| text[..text.len() - rest.len()].lines().count().max(1);
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<?") {
+            rest = skip_past(after, "?>").ok_or_else(|| format!("Line {}: open <?", line(rest)))?;
+        } else if let Some(after) = rest.strip_prefix("<!--") {
+            rest = skip_past(after, "-->")
+                .ok_or_else(|| format!("Line {}: open comment", line(rest)))?;
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after
+                .find("]]>")
+                .ok_or_else(|| format!("Line {}: open CDATA", line(rest)))?;
+            if let Some(node) = stack.last_mut() {
+                node.text += &after[..end];
+            }
+            rest = &after[end + 3..];
+        } else if let Some(after) = rest.strip_prefix("<!") {
+            rest = skip_past(after, ">").ok_or_else(|| format!("Line {}: open <!", line(rest)))?;
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after
+                .find('>')
+                .ok_or_else(|| format!("Line {}: open end tag", line(rest)))?;
+            let name = local_name(after[..end].trim());
+            let node = match stack.pop() {
+                Some(node) if node.name == name && !stack.is_empty() => node,
+                _ => return Err(format!("Line {}: unexpected </{}>", line(rest), name)),
+            };
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(node);
+            }
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = tag_end(after).ok_or_else(|| format!("Line {}: open tag", line(rest)))?;
+            let tag = &after[..end];
+            let (tag, closed) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let node = parse_tag(tag).map_err(|e| format!("Line {}: {}", line(rest), e))?;
+            if closed {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node);
+                }
+            } else {
+                stack.push(node);
+            }
+            rest = &after[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            if let Some(node) = stack.last_mut() {
+                node.text += &unescape(&rest[..end]);
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    let document = stack.pop().unwrap_or_else(|| Node::new(""));
+    if !stack.is_empty() {
+        return Err(format!("<{}> is never closed", document.name));
+    }
+    Ok(document)
+}
+
+fn skip_past<'a>(text: &'a str, end: &str) -> Option<&'a str> {
+    text.find(end).map(|i| &text[i + end.len()..])
+}
+
+/// The closing > of a tag, skipping those in quoted attribute values
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (c, quote) {
+            ('"', None) | ('\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('>', None) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_tag(tag: &str) -> Result<Node, String> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut node = Node::new(local_name(&tag[..name_end]));
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let equals = rest
+            .find('=')
+            .ok_or_else(|| format!("attribute without value in <{}>", node.name))?;
+        let name = local_name(rest[..equals].trim()).to_string();
+        let value = rest[equals + 1..].trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| format!("unquoted attribute {} in <{}>", name, node.name))?;
+        let end = value[1..]
+            .find(quote)
+            .ok_or_else(|| format!("open attribute {} in <{}>", name, node.name))?;
+        node.attributes.push((name, unescape(&value[1..end + 1])));
+        rest = value[end + 2..].trim_start();
+    }
+    Ok(node)
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result += &rest[..start];
+        let after = &rest[start + 1..];
+        let end = match after.find(';') {
+            Some(end) => end,
+            None => {
+                result.push('&');
+                rest = after;
+                continue;
+            }
+        };
+        let entity = &after[..end];
+        let replacement = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match replacement {
+            Some(c) => {
+                result.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = after;
+            }
+        }
+    }
+    result + rest
+}
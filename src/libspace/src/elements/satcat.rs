@@ -0,0 +1,593 @@
+// The Celestrak satellite catalogue: what an object is, who owns it, when it launched and decayed.
+// Joined to the elements by NORAD id. Read from the CSV or JSON Celestrak serves, kept in satcat.json
+// in the data dir. Both the current RCS column (m²) and the older RCS_SIZE column are understood.
+// Records that cannot be read are skipped, the catalogue has tens of thousands and one should not lose them all.
+
+use crate::elements::omm_csv::split_row;
+use crate::utility::init_dirs;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::sync::mpsc::{channel, Receiver};
+
+pub const CELESTRAK_SATCAT_CSV: &str = "https://celestrak.org/pub/satcat.csv";
+const SATCAT_FILENAME: &str = "satcat.json";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ObjectType {
+    Payload,
+    RocketBody,
+    Debris,
+    Unknown,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum OpsStatus {
+    Operational,
+    Nonoperational,
+    PartiallyOperational,
+    Backup,
+    Spare,
+    ExtendedMission,
+    Decayed,
+    Unknown,
+}
+
+/// Radar cross section classes, as the catalogue used to give them
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum RcsSize {
+    /// below 0.1 m²
+    Small,
+    /// below 1 m²
+    Medium,
+    Large,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SatcatEntry {
+    pub norad_id: u64,
+    pub name: String,
+    /// COSPAR id
+    pub object_id: String,
+    pub object_type: ObjectType,
+    pub ops_status: OpsStatus,
+    /// owner or country code, like US or PRC
+    pub owner: String,
+    pub launch_date: Option<NaiveDate>,
+    /// site code, like AFETR
+    pub launch_site: String,
+    pub decay_date: Option<NaiveDate>,
+    /// m²
+    pub rcs: Option<f64>,
+    pub rcs_size: Option<RcsSize>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Satcat {
+    pub entries: HashMap<u64, SatcatEntry>,
+    /// records the parse could not read, with why
+    #[serde(skip)]
+    pub skipped: Vec<String>,
+}
+
+/// What to show of the catalogue, None and empty match everything.
+/// Objects without an entry only pass if nothing is filtered.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SatcatFilter {
+    pub object_type: Option<ObjectType>,
+    pub ops_status: Option<OpsStatus>,
+    pub rcs_size: Option<RcsSize>,
+    /// case insensitive prefix
+    pub owner: String,
+    pub launch_site: String,
+    /// launch years, inclusive
+    pub launched_from: Option<i32>,
+    pub launched_to: Option<i32>,
+    pub hide_decayed: bool,
+}
+
+impl ObjectType {
+    pub const ALL: [ObjectType; 4] = [
+        ObjectType::Payload,
+        ObjectType::RocketBody,
+        ObjectType::Debris,
+        ObjectType::Unknown,
+    ];
+
+    fn from_code(code: &str) -> Self {
+        match code.trim() {
+            "PAY" => ObjectType::Payload,
+            "R/B" => ObjectType::RocketBody,
+            "DEB" => ObjectType::Debris,
+            _ => ObjectType::Unknown,
+        }
+    }
+}
+
+impl OpsStatus {
+    pub const ALL: [OpsStatus; 8] = [
+        OpsStatus::Operational,
+        OpsStatus::Nonoperational,
+        OpsStatus::PartiallyOperational,
+        OpsStatus::Backup,
+        OpsStatus::Spare,
+        OpsStatus::ExtendedMission,
+        OpsStatus::Decayed,
+        OpsStatus::Unknown,
+    ];
+
+    fn from_code(code: &str) -> Self {
+        match code.trim() {
+            "+" => OpsStatus::Operational,
+            "-" => OpsStatus::Nonoperational,
+            "P" => OpsStatus::PartiallyOperational,
+            "B" => OpsStatus::Backup,
+            "S" => OpsStatus::Spare,
+            "X" => OpsStatus::ExtendedMission,
+            "D" => OpsStatus::Decayed,
+            _ => OpsStatus::Unknown,
+        }
+    }
+}
+
+impl RcsSize {
+    pub const ALL: [RcsSize; 3] = [RcsSize::Small, RcsSize::Medium, RcsSize::Large];
+
+    pub fn from_rcs(rcs: f64) -> Self {
+        if rcs < 0.1 {
+            RcsSize::Small
+        } else if rcs < 1.0 {
+            RcsSize::Medium
+        } else {
+            RcsSize::Large
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_uppercase().as_str() {
+            "SMALL" => Some(RcsSize::Small),
+            "MEDIUM" => Some(RcsSize::Medium),
+            "LARGE" => Some(RcsSize::Large),
+            _ => None,
+        }
+    }
+}
+
+impl SatcatEntry {
+    /// From the columns of one record, by their Celestrak names
+    fn from_fields(field: &dyn Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let norad_id = field("NORAD_CAT_ID")
+            .ok_or_else(|| "No NORAD_CAT_ID".to_string())?
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| "Invalid NORAD_CAT_ID".to_string())?;
+        let text = |name: &str| {
+            field(name)
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+        let date = |name: &str| {
+            field(name).and_then(|d| NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").ok())
+        };
+        let rcs = field("RCS").and_then(|r| r.trim().parse::<f64>().ok());
+        Ok(Self {
+            norad_id,
+            name: text("OBJECT_NAME"),
+            object_id: text("OBJECT_ID"),
+            object_type: ObjectType::from_code(&text("OBJECT_TYPE")),
+            ops_status: OpsStatus::from_code(&text("OPS_STATUS_CODE")),
+            owner: text("OWNER"),
+            launch_date: date("LAUNCH_DATE"),
+            launch_site: text("LAUNCH_SITE"),
+            decay_date: date("DECAY_DATE"),
+            rcs,
+            rcs_size: rcs
+                .map(RcsSize::from_rcs)
+                .or_else(|| RcsSize::from_name(&text("RCS_SIZE"))),
+        })
+    }
+
+    pub fn is_decayed(&self) -> bool {
+        self.decay_date.is_some() || self.ops_status == OpsStatus::Decayed
+    }
+}
+
+impl Satcat {
+    pub fn load() -> Self {
+        init_dirs()
+            .ok()
+            .and_then(|dir| File::open(dir.join(SATCAT_FILENAME)).ok())
+            .and_then(|file| match serde_json::from_reader(file) {
+                Ok(satcat) => Some(satcat),
+                Err(e) => {
+                    log::error!("Invalid {}: {}", SATCAT_FILENAME, e);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let file = File::create(init_dirs()?.join(SATCAT_FILENAME)).map_err(|e| e.to_string())?;
+        serde_json::to_writer(file, self).map_err(|e| e.to_string())
+    }
+
+    /// The CSV or JSON of Celestrak, told apart by the first character
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.trim_start().starts_with('[') {
+            Self::parse_json(text)
+        } else {
+            Self::parse_csv(text)
+        }
+    }
+
+    pub fn parse_csv(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let header = match lines.next() {
+            Some((_, header)) => split_row(header)
+                .into_iter()
+                .map(|h| h.trim().to_uppercase())
+                .collect::<Vec<String>>(),
+            None => return Err("Empty SATCAT".to_string()),
+        };
+        let mut satcat = Self::default();
+        for (i, line) in lines {
+            let fields = split_row(line);
+            let field = |name: &str| {
+                header
+                    .iter()
+                    .position(|h| h == name)
+                    .and_then(|p| fields.get(p).cloned())
+            };
+            satcat.add(
+                SatcatEntry::from_fields(&field).map_err(|e| format!("Line {}: {}", i + 1, e)),
+            );
+        }
+        satcat.checked()
+    }
+
+    pub fn parse_json(text: &str) -> Result<Self, String> {
+        let records = serde_json::from_str::<Vec<serde_json::Value>>(text)
+            .map_err(|e| format!("Invalid SATCAT json: {}", e))?;
+        let mut satcat = Self::default();
+        for (i, record) in records.iter().enumerate() {
+            // numbers and strings alike
+            let field = |name: &str| match record.get(name) {
+                Some(serde_json::Value::String(s)) => Some(s.clone()),
+                Some(serde_json::Value::Null) | None => None,
+                Some(value) => Some(value.to_string()),
+            };
+            satcat.add(
+                SatcatEntry::from_fields(&field).map_err(|e| format!("Record {}: {}", i + 1, e)),
+            );
+        }
+        satcat.checked()
+    }
+
+    fn add(&mut self, entry: Result<SatcatEntry, String>) {
+        match entry {
+            Ok(entry) => {
+                self.entries.insert(entry.norad_id, entry);
+            }
+            Err(e) => self.skipped.push(e),
+        }
+    }
+
+    /// Some skipped records are fine, nothing but skipped records is not a catalogue
+    fn checked(self) -> Result<Self, String> {
+        match self.skipped.first() {
+            Some(first) if self.entries.is_empty() => {
+                Err(format!("No valid SATCAT records. {}", first))
+            }
+            _ => Ok(self),
+        }
+    }
+
+    /// How many entries and skipped records, with the first skipped one
+    pub fn report(&self) -> String {
+        match self.skipped.first() {
+            None => format!("{} catalogue entries", self.entries.len()),
+            Some(first) => format!(
+                "{} catalogue entries, {} records skipped. {}",
+                self.entries.len(),
+                self.skipped.len(),
+                first
+            ),
+        }
+    }
+
+    /// Downloads and parses on a separate thread, the full catalogue is some megabytes
+    pub fn fetch_in_background(url: &str) -> Receiver<Result<Satcat, String>> {
+        let (tx, rx) = channel();
+        let url = url.to_string();
+        std::thread::spawn(move || {
+            let result = ureq::get(&url)
+                .call()
+                .map_err(|e| format!("SATCAT download failed: {}", e))
+                .and_then(|r| r.into_string().map_err(|e| e.to_string()))
+                .and_then(|text| Satcat::parse(&text));
+            tx.send(result).ok();
+        });
+        rx
+    }
+
+    pub fn get(&self, id: u64) -> Option<&SatcatEntry> {
+        self.entries.get(&id)
+    }
+}
+
+impl SatcatFilter {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn matches(&self, entry: Option<&SatcatEntry>) -> bool {
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return self.is_empty(),
+        };
+        let prefix = |value: &str, prefix: &str| {
+            value
+                .to_lowercase()
+                .starts_with(&prefix.trim().to_lowercase())
+        };
+        let year = entry.launch_date.map(|d| chrono::Datelike::year(&d));
+        self.object_type.map_or(true, |t| entry.object_type == t)
+            && self.ops_status.map_or(true, |s| entry.ops_status == s)
+            && self.rcs_size.map_or(true, |s| entry.rcs_size == Some(s))
+            && prefix(&entry.owner, &self.owner)
+            && prefix(&entry.launch_site, &self.launch_site)
+            && self
+                .launched_from
+                .map_or(true, |from| year.map_or(false, |y| y >= from))
+            && self
+                .launched_to
+                .map_or(true, |to| year.map_or(false, |y| y <= to))
+            && !(self.hide_decayed && entry.is_decayed())
+    }
+}
+
+impl Display for ObjectType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectType::Payload => write!(f, "Payload"),
+            ObjectType::RocketBody => write!(f, "Rocket body"),
+            ObjectType::Debris => write!(f, "Debris"),
+            ObjectType::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+impl Display for OpsStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpsStatus::Operational => write!(f, "Operational"),
+            OpsStatus::Nonoperational => write!(f, "Nonoperational"),
+            OpsStatus::PartiallyOperational => write!(f, "Partially operational"),
+            OpsStatus::Backup => write!(f, "Backup"),
+            OpsStatus::Spare => write!(f, "Spare"),
+            OpsStatus::ExtendedMission => write!(f, "Extended mission"),
+            OpsStatus::Decayed => write!(f, "Decayed"),
+            OpsStatus::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+impl Display for RcsSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RcsSize::Small => write!(f, "Small"),
+            RcsSize::Medium => write!(f, "Medium"),
+            RcsSize::Large => write!(f, "Large"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "OBJECT_NAME,OBJECT_ID,NORAD_CAT_ID,OBJECT_TYPE,OPS_STATUS_CODE,OWNER,LAUNCH_DATE,LAUNCH_SITE,DECAY_DATE,PERIOD,INCLINATION,APOGEE,PERIGEE,RCS,DATA_STATUS_CODE,ORBIT_CENTER,ORBIT_TYPE
+SL-1 R/B,1957-001A,1,R/B,D,CIS,1957-10-04,TYMSC,1957-12-01,96.19,65.10,938,214,20.4200,,EA,IMP
+ISS (ZARYA),1998-067A,25544,PAY,+,ISS,1998-11-20,TYMSC,,92.90,51.64,422,417,399.0524,,EA,ORB
+\"BROKEN, RECORD\",2000-001A,25X44,PAY,+,US,2000-01-01,AFETR,,,,,,,,EA,ORB
+FENGYUN 1C DEB,1999-025DZS,31142,DEB,,PRC,1999-05-10,TSC,,101.66,98.72,843,796,0.0120,,EA,ORB
+";
+
+    /// The older records with RCS_SIZE, numbers as numbers
+    const JSON: &str = r#"[
+        {"OBJECT_NAME": "VANGUARD 1", "OBJECT_ID": "1958-002B", "NORAD_CAT_ID": 5, "OBJECT_TYPE": "PAY",
+         "OPS_STATUS_CODE": "-", "OWNER": "US", "LAUNCH_DATE": "1958-03-17", "LAUNCH_SITE": "AFETR",
+         "DECAY_DATE": null, "RCS_SIZE": "SMALL"},
+        {"OBJECT_NAME": "HST", "OBJECT_ID": "1990-037B", "NORAD_CAT_ID": "20580", "OBJECT_TYPE": "PAY",
+         "OPS_STATUS_CODE": "P", "OWNER": "US", "LAUNCH_DATE": "1990-04-24", "LAUNCH_SITE": "AFETR",
+         "DECAY_DATE": "", "RCS": 0.9, "RCS_SIZE": "LARGE"},
+        {"OBJECT_NAME": "NO ID", "OBJECT_TYPE": "PAY"},
+        {"OBJECT_NAME": "ODD DATES", "NORAD_CAT_ID": 7, "OBJECT_TYPE": "UNK", "OPS_STATUS_CODE": "?",
+         "LAUNCH_DATE": "1958-3", "RCS": "n/a", "RCS_SIZE": "TINY"}
+    ]"#;
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn csv_records() {
+        let satcat = Satcat::parse(CSV).unwrap();
+        assert_eq!(satcat.entries.len(), 3);
+        assert_eq!(satcat.skipped, vec!["Line 4: Invalid NORAD_CAT_ID"]);
+        assert_eq!(
+            satcat.report(),
+            "3 catalogue entries, 1 records skipped. Line 4: Invalid NORAD_CAT_ID"
+        );
+
+        let iss = satcat.get(25544).unwrap();
+        assert_eq!(iss.name, "ISS (ZARYA)");
+        assert_eq!(iss.object_id, "1998-067A");
+        assert_eq!(iss.object_type, ObjectType::Payload);
+        assert_eq!(iss.ops_status, OpsStatus::Operational);
+        assert_eq!(iss.owner, "ISS");
+        assert_eq!(iss.launch_date, date(1998, 11, 20));
+        assert_eq!(iss.launch_site, "TYMSC");
+        assert_eq!(iss.decay_date, None);
+        assert_eq!(iss.rcs, Some(399.0524));
+        assert_eq!(iss.rcs_size, Some(RcsSize::Large));
+        assert!(!iss.is_decayed());
+
+        let rocket = satcat.get(1).unwrap();
+        assert_eq!(rocket.object_type, ObjectType::RocketBody);
+        assert_eq!(rocket.ops_status, OpsStatus::Decayed);
+        assert_eq!(rocket.decay_date, date(1957, 12, 1));
+        assert!(rocket.is_decayed());
+
+        let debris = satcat.get(31142).unwrap();
+        assert_eq!(debris.object_type, ObjectType::Debris);
+        assert_eq!(debris.ops_status, OpsStatus::Unknown);
+        assert_eq!(debris.rcs_size, Some(RcsSize::Small));
+    }
+
+    #[test]
+    fn json_records() {
+        let satcat = Satcat::parse(JSON).unwrap();
+        assert_eq!(satcat.entries.len(), 3);
+        assert_eq!(satcat.skipped, vec!["Record 3: No NORAD_CAT_ID"]);
+
+        let vanguard = satcat.get(5).unwrap();
+        assert_eq!(vanguard.ops_status, OpsStatus::Nonoperational);
+        assert_eq!(vanguard.launch_date, date(1958, 3, 17));
+        assert_eq!(vanguard.decay_date, None);
+        assert_eq!(vanguard.rcs, None);
+        assert_eq!(vanguard.rcs_size, Some(RcsSize::Small));
+
+        // the m² win over the class
+        let hst = satcat.get(20580).unwrap();
+        assert_eq!(hst.ops_status, OpsStatus::PartiallyOperational);
+        assert_eq!(hst.decay_date, None);
+        assert_eq!(hst.rcs, Some(0.9));
+        assert_eq!(hst.rcs_size, Some(RcsSize::Medium));
+
+        let odd = satcat.get(7).unwrap();
+        assert_eq!(odd.object_type, ObjectType::Unknown);
+        assert_eq!(odd.ops_status, OpsStatus::Unknown);
+        assert_eq!(odd.launch_date, None);
+        assert_eq!(odd.rcs, None);
+        assert_eq!(odd.rcs_size, None);
+        assert_eq!(odd.owner, "");
+    }
+
+    #[test]
+    fn status_codes() {
+        for (code, status) in [
+            ("+", OpsStatus::Operational),
+            ("-", OpsStatus::Nonoperational),
+            ("P", OpsStatus::PartiallyOperational),
+            ("B", OpsStatus::Backup),
+            ("S", OpsStatus::Spare),
+            ("X", OpsStatus::ExtendedMission),
+            ("D", OpsStatus::Decayed),
+            ("?", OpsStatus::Unknown),
+            ("", OpsStatus::Unknown),
+        ] {
+            assert_eq!(OpsStatus::from_code(code), status, "{}", code);
+        }
+    }
+
+    #[test]
+    fn only_bad_records_fail() {
+        let error = Satcat::parse("NORAD_CAT_ID,OBJECT_NAME\nX,A\nY,B\n").err();
+        assert_eq!(
+            error.as_deref(),
+            Some("No valid SATCAT records. Line 2: Invalid NORAD_CAT_ID")
+        );
+        assert!(Satcat::parse("").is_err());
+        assert!(Satcat::parse("[{").is_err());
+    }
+
+    #[test]
+    fn filter_criteria() {
+        let satcat = Satcat::parse(CSV).unwrap();
+        let matching = |filter: SatcatFilter| {
+            let mut ids = [1, 25544, 31142, 99999]
+                .into_iter()
+                .filter(|id| filter.matches(satcat.get(*id)))
+                .collect::<Vec<u64>>();
+            ids.sort_unstable();
+            ids
+        };
+        let filter = SatcatFilter::default;
+        for (name, criteria, expected) in [
+            ("nothing", filter(), vec![1, 25544, 31142, 99999]),
+            (
+                "object type",
+                SatcatFilter {
+                    object_type: Some(ObjectType::Debris),
+                    ..filter()
+                },
+                vec![31142],
+            ),
+            (
+                "ops status",
+                SatcatFilter {
+                    ops_status: Some(OpsStatus::Operational),
+                    ..filter()
+                },
+                vec![25544],
+            ),
+            (
+                "rcs size",
+                SatcatFilter {
+                    rcs_size: Some(RcsSize::Large),
+                    ..filter()
+                },
+                vec![1, 25544],
+            ),
+            (
+                "owner prefix",
+                SatcatFilter {
+                    owner: " ci".to_string(),
+                    ..filter()
+                },
+                vec![1],
+            ),
+            (
+                "launch site prefix",
+                SatcatFilter {
+                    launch_site: "TYM".to_string(),
+                    ..filter()
+                },
+                vec![1, 25544],
+            ),
+            (
+                "launched from",
+                SatcatFilter {
+                    launched_from: Some(1998),
+                    ..filter()
+                },
+                vec![25544, 31142],
+            ),
+            (
+                "launched to",
+                SatcatFilter {
+                    launched_to: Some(1998),
+                    ..filter()
+                },
+                vec![1, 25544],
+            ),
+            (
+                "hide decayed",
+                SatcatFilter {
+                    hide_decayed: true,
+                    ..filter()
+                },
+                vec![25544, 31142],
+            ),
+        ] {
+            assert_eq!(matching(criteria), expected, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,553 @@
+// Client for the GP classes of space-track.org, which unlike Celestrak has the element history
+// and the decayed objects too. It needs an account, the session is a cookie set by the login.
+// Space-Track allows 30 requests per minute and 300 per hour; every client in the process shares one limit
+// and waits for a free slot instead of getting the account suspended.
+// The credentials are stored in plain text in the data dir, if the user wants them remembered.
+
+use crate::elements::element_source::ElementFormat;
+use crate::utility::init_dirs;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sgp4::Elements;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const SPACE_TRACK_URL: &str = "https://www.space-track.org";
+/// source name of elements queried from Space-Track, as known to the registry
+pub const SPACE_TRACK_SOURCE: &str = "Space-Track";
+const CREDENTIALS_FILENAME: &str = "space_track.json";
+const MINUTE_LIMIT: usize = 30;
+const HOUR_LIMIT: usize = 300;
+
+/// what a query delivers, element sets in the order of NORAD id and epoch
+pub type GpResult = Result<Vec<Elements>, String>;
+
+/// when the last requests to Space-Track were sent
+static REQUESTS: Mutex<VecDeque<Instant>> = Mutex::new(VecDeque::new());
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SpaceTrackCredentials {
+    pub identity: String,
+    pub password: String,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GpClass {
+    /// the newest element set of each object
+    Gp,
+    /// every element set ever published
+    GpHistory,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpQuery {
+    pub class: GpClass,
+    /// empty for all objects
+    pub norad_ids: Vec<u64>,
+    /// inclusive epoch range
+    pub epoch: Option<(NaiveDate, NaiveDate)>,
+}
+
+/// A logged in session with Space-Track. Clones share the session.
+#[derive(Clone)]
+pub struct SpaceTrackClient {
+    /// SPACE_TRACK_URL, without the trailing slash
+    pub base_url: String,
+    credentials: SpaceTrackCredentials,
+    /// the session cookies, None until logged in
+    cookie: Arc<Mutex<Option<String>>>,
+}
+
+impl SpaceTrackCredentials {
+    pub fn load() -> Option<Self> {
+        let file = File::open(init_dirs().ok()?.join(CREDENTIALS_FILENAME)).ok()?;
+        match serde_json::from_reader(file) {
+            Ok(credentials) => Some(credentials),
+            Err(e) => {
+                log::error!("Invalid {}: {}", CREDENTIALS_FILENAME, e);
+                None
+            }
+        }
+    }
+
+    /// Stored in plain text, readable only by the user where the system allows it
+    pub fn save(&self) -> Result<(), String> {
+        self.save_at(&init_dirs()?.join(CREDENTIALS_FILENAME))
+    }
+
+    /// The file is created readable only by the user, a file from before is made so
+    fn save_at(&self, path: &Path) -> Result<(), String> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(path).map_err(|e| e.to_string())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| e.to_string())?;
+        }
+        serde_json::to_writer_pretty(file, self).map_err(|e| e.to_string())
+    }
+
+    pub fn forget() -> Result<(), String> {
+        let path = init_dirs()?.join(CREDENTIALS_FILENAME);
+        if path.exists() {
+            std::fs::remove_file(path).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.identity.trim().is_empty() || self.password.is_empty()
+    }
+}
+
+impl GpQuery {
+    pub fn latest(norad_ids: Vec<u64>) -> Self {
+        Self {
+            class: GpClass::Gp,
+            norad_ids,
+            epoch: None,
+        }
+    }
+
+    pub fn history(norad_ids: Vec<u64>, from: NaiveDate, to: NaiveDate) -> Self {
+        Self {
+            class: GpClass::GpHistory,
+            norad_ids,
+            epoch: Some((from, to)),
+        }
+    }
+
+    /// The history of everything is far too much to ask for at once
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some((from, to)) = self.epoch {
+            if from > to {
+                return Err("The epoch range ends before it starts".to_string());
+            }
+        }
+        if self.class == GpClass::GpHistory && self.norad_ids.is_empty() && self.epoch.is_none() {
+            return Err("History queries need NORAD ids or an epoch range".to_string());
+        }
+        Ok(())
+    }
+
+    /// Path of the query below the base url, asking for three line elements to keep the names
+    pub fn path(&self) -> String {
+        let mut path = String::from("/basicspacedata/query/class/");
+        path += match self.class {
+            GpClass::Gp => "gp",
+            GpClass::GpHistory => "gp_history",
+        };
+        if !self.norad_ids.is_empty() {
+            let ids = self
+                .norad_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+            path += &format!("/NORAD_CAT_ID/{}", ids);
+        }
+        if let Some((from, to)) = self.epoch {
+            // the range end is exclusive on Space-Track
+            path += &format!(
+                "/EPOCH/{}--{}",
+                from.format("%Y-%m-%d"),
+                to.succ_opt().unwrap_or(to).format("%Y-%m-%d")
+            );
+        }
+        path + "/orderby/NORAD_CAT_ID,EPOCH/format/3le"
+    }
+}
+
+impl SpaceTrackClient {
+    pub fn new(credentials: SpaceTrackCredentials) -> Self {
+        Self::with_base_url(SPACE_TRACK_URL, credentials)
+    }
+
+    pub fn with_base_url(base_url: &str, credentials: SpaceTrackCredentials) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            credentials,
+            cookie: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Client with the stored credentials
+    pub fn from_stored() -> Result<Self, String> {
+        SpaceTrackCredentials::load()
+            .filter(|c| !c.is_empty())
+            .map(Self::new)
+            .ok_or_else(|| "No Space-Track credentials stored".to_string())
+    }
+
+    pub fn identity(&self) -> &str {
+        &self.credentials.identity
+    }
+
+    pub fn logged_in(&self) -> bool {
+        self.cookie.lock().map(|c| c.is_some()).unwrap_or(false)
+    }
+
+    pub fn login(&self) -> Result<(), String> {
+        if self.credentials.is_empty() {
+            return Err("Space-Track needs a user name and password".to_string());
+        }
+        wait_for_slot();
+        let response = ureq::post(&format!("{}/ajaxauth/login", self.base_url))
+            .send_form(&[
+                ("identity", &self.credentials.identity),
+                ("password", &self.credentials.password),
+            ])
+            .map_err(|e| format!("Space-Track login failed: {}", e))?;
+        let cookies = response
+            .all("Set-Cookie")
+            .iter()
+            .filter_map(|c| c.split(';').next())
+            .map(|c| c.trim().to_string())
+            .collect::<Vec<String>>()
+            .join("; ");
+        // a wrong password is a 200 too, with a json complaint
+        let body = response.into_string().unwrap_or_default();
+        if body.contains("\"Failed\"") || cookies.is_empty() {
+            return Err(format!(
+                "Space-Track login failed for {}",
+                self.credentials.identity
+            ));
+        }
+        *self.cookie.lock().map_err(|e| e.to_string())? = Some(cookies);
+        Ok(())
+    }
+
+    pub fn logout(&self) {
+        let cookie = match self.cookie.lock().ok().and_then(|mut c| c.take()) {
+            Some(cookie) => cookie,
+            None => return,
+        };
+        wait_for_slot();
+        if let Err(e) = ureq::get(&format!("{}/ajaxauth/logout", self.base_url))
+            .set("Cookie", &cookie)
+            .call()
+        {
+            log::warn!("Space-Track logout failed: {}", e);
+        }
+    }
+
+    pub fn gp(&self, query: &GpQuery) -> GpResult {
+        query.validate()?;
+        ElementFormat::Tle.parse(&self.get(&query.path())?)
+    }
+
+    /// Queries on a separate thread, as the rate limit may make it wait for minutes
+    pub fn gp_in_background(&self, query: GpQuery) -> Receiver<GpResult> {
+        let (tx, rx) = channel();
+        let client = self.clone();
+        std::thread::spawn(move || {
+            tx.send(client.gp(&query)).ok();
+        });
+        rx
+    }
+
+    /// Answer text of a path or full url on Space-Track, logging in first if needed.
+    /// An expired session is renewed once.
+    pub fn get(&self, path: &str) -> Result<String, String> {
+        let url = if path.starts_with("http") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.base_url, path)
+        };
+        let mut renewed = false;
+        loop {
+            let cookie = self.session()?;
+            wait_for_slot();
+            match ureq::get(&url).set("Cookie", &cookie).call() {
+                Ok(response) => {
+                    return response
+                        .into_string()
+                        .map_err(|e| format!("Space-Track download failed: {}", e))
+                }
+                Err(ureq::Error::Status(401, _)) if !renewed => {
+                    renewed = true;
+                    *self.cookie.lock().map_err(|e| e.to_string())? = None;
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    return Err(format!(
+                        "Space-Track error {}: {}",
+                        status,
+                        response.into_string().unwrap_or_default()
+                    ))
+                }
+                Err(e) => return Err(format!("Space-Track network error: {}", e)),
+            }
+        }
+    }
+
+    fn session(&self) -> Result<String, String> {
+        if let Some(cookie) = self.cookie.lock().map_err(|e| e.to_string())?.clone() {
+            return Ok(cookie);
+        }
+        self.login()?;
+        self.cookie
+            .lock()
+            .map_err(|e| e.to_string())?
+            .clone()
+            .ok_or_else(|| "Space-Track login failed".to_string())
+    }
+}
+
+/// Blocks until another request fits into the rate limit, then counts it
+fn wait_for_slot() {
+    loop {
+        let wait = {
+            let mut requests = match REQUESTS.lock() {
+                Ok(requests) => requests,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            match take_slot(&mut requests, Instant::now()) {
+                Some(wait) => wait,
+                None => return,
+            }
+        };
+        log::info!(
+            "Space-Track rate limit reached, waiting {} s",
+            wait.as_secs() + 1
+        );
+        std::thread::sleep(wait + Duration::from_millis(100));
+    }
+}
+
+/// Counts a request sent now if it fits into the limits, else how long until one does
+fn take_slot(requests: &mut VecDeque<Instant>, now: Instant) -> Option<Duration> {
+    let minute = Duration::from_secs(60);
+    let hour = Duration::from_secs(3600);
+    while requests
+        .front()
+        .map(|t| now.duration_since(*t) > hour)
+        .unwrap_or(false)
+    {
+        requests.pop_front();
+    }
+    let last_minute = requests
+        .iter()
+        .filter(|t| now.duration_since(**t) <= minute)
+        .count();
+    if last_minute < MINUTE_LIMIT && requests.len() < HOUR_LIMIT {
+        requests.push_back(now);
+        return None;
+    }
+    // until the oldest request that counts falls out of its window
+    if requests.len() >= HOUR_LIMIT {
+        Some(hour - now.duration_since(requests[0]))
+    } else {
+        Some(minute - now.duration_since(requests[requests.len() - last_minute]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::test_server::{serve, Response};
+
+    fn login(set_cookie: &str) -> Response {
+        Response::new(200, "\"\"").header("Set-Cookie", set_cookie)
+    }
+
+    fn client(url: &str) -> SpaceTrackClient {
+        SpaceTrackClient::with_base_url(
+            url,
+            SpaceTrackCredentials {
+                identity: "user@example.com".to_string(),
+                password: "secret".to_string(),
+            },
+        )
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    #[test]
+    fn logs_in_and_sends_the_cookie() {
+        let (url, requests) = serve(vec![
+            login("chocolatechip=abc; path=/; HttpOnly"),
+            Response::new(200, "first"),
+            Response::new(200, "second"),
+        ]);
+        let client = client(&url);
+        assert!(!client.logged_in());
+        assert_eq!(client.get("/first").unwrap(), "first");
+        assert!(client.logged_in());
+        // the session is kept
+        assert_eq!(client.get("/second").unwrap(), "second");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].line.starts_with("POST /ajaxauth/login "));
+        assert_eq!(
+            requests[0].body,
+            "identity=user%40example.com&password=secret"
+        );
+        assert!(requests[1].line.starts_with("GET /first "));
+        assert_eq!(requests[1].cookie, "chocolatechip=abc");
+        assert_eq!(requests[2].cookie, "chocolatechip=abc");
+    }
+
+    #[test]
+    fn an_expired_session_is_renewed_once() {
+        let (url, requests) = serve(vec![
+            login("chocolatechip=old"),
+            Response::new(401, "expired"),
+            login("chocolatechip=new"),
+            Response::new(200, "data"),
+        ]);
+        assert_eq!(client(&url).get("/data").unwrap(), "data");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[1].cookie, "chocolatechip=old");
+        assert!(requests[2].line.starts_with("POST /ajaxauth/login "));
+        assert!(requests[3].line.starts_with("GET /data "));
+        assert_eq!(requests[3].cookie, "chocolatechip=new");
+
+        let (url, requests) = serve(vec![
+            login("chocolatechip=a"),
+            Response::new(401, "expired"),
+            login("chocolatechip=b"),
+            Response::new(401, "still expired"),
+        ]);
+        assert_eq!(
+            client(&url).get("/data").unwrap_err(),
+            "Space-Track error 401: still expired"
+        );
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn a_wrong_password_fails_the_login() {
+        let (url, requests) =
+            serve(vec![Response::new(200, "{\"Login\":\"Failed\"}")
+                .header("Set-Cookie", "chocolatechip=abc")]);
+        let client = client(&url);
+        assert_eq!(
+            client.get("/data").unwrap_err(),
+            "Space-Track login failed for user@example.com"
+        );
+        assert!(!client.logged_in());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let empty = SpaceTrackClient::with_base_url(&url, SpaceTrackCredentials::default());
+        assert!(empty.login().is_err());
+    }
+
+    #[test]
+    fn query_paths() {
+        assert_eq!(
+            GpQuery::latest(vec![25544, 43013]).path(),
+            "/basicspacedata/query/class/gp/NORAD_CAT_ID/25544,43013/orderby/NORAD_CAT_ID,EPOCH/format/3le"
+        );
+        assert_eq!(
+            GpQuery::latest(vec![]).path(),
+            "/basicspacedata/query/class/gp/orderby/NORAD_CAT_ID,EPOCH/format/3le"
+        );
+        // the end of the range is asked for inclusively
+        assert_eq!(
+            GpQuery::history(vec![25544], date(1, 1), date(1, 31)).path(),
+            "/basicspacedata/query/class/gp_history/NORAD_CAT_ID/25544/EPOCH/2024-01-01--2024-02-01/orderby/NORAD_CAT_ID,EPOCH/format/3le"
+        );
+
+        assert!(GpQuery::history(vec![25544], date(2, 1), date(1, 1))
+            .validate()
+            .is_err());
+        let everything = GpQuery {
+            class: GpClass::GpHistory,
+            norad_ids: vec![],
+            epoch: None,
+        };
+        assert!(everything.validate().is_err());
+        assert!(GpQuery::latest(vec![]).validate().is_ok());
+    }
+
+    #[test]
+    fn gp_asks_for_the_query_path() {
+        let (url, requests) = serve(vec![
+            login("chocolatechip=abc"),
+            Response::new(200, ""),
+            Response::new(200, ""),
+        ]);
+        let client = client(&url);
+        let latest = GpQuery::latest(vec![25544]);
+        let history = GpQuery::history(vec![25544], date(3, 1), date(3, 2));
+        assert_eq!(client.gp(&latest).unwrap().len(), 0);
+        assert_eq!(
+            client
+                .gp_in_background(history.clone())
+                .recv()
+                .unwrap()
+                .unwrap()
+                .len(),
+            0
+        );
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[1].line, format!("GET {} HTTP/1.1", latest.path()));
+        assert_eq!(requests[2].line, format!("GET {} HTTP/1.1", history.path()));
+    }
+
+    #[test]
+    fn rate_limit() {
+        let start = Instant::now();
+        let mut requests = VecDeque::new();
+        for _ in 0..MINUTE_LIMIT {
+            assert_eq!(take_slot(&mut requests, start), None);
+        }
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            take_slot(&mut requests, start + 10 * second),
+            Some(50 * second)
+        );
+        assert_eq!(requests.len(), MINUTE_LIMIT);
+        assert_eq!(take_slot(&mut requests, start + 61 * second), None);
+
+        // spread out to stay below the minute limit
+        let mut requests = VecDeque::new();
+        for i in 0..HOUR_LIMIT as u32 {
+            assert_eq!(take_slot(&mut requests, start + i * 12 * second), None);
+        }
+        assert_eq!(
+            take_slot(&mut requests, start + 3590 * second),
+            Some(10 * second)
+        );
+        // the first one is more than an hour old then
+        assert_eq!(take_slot(&mut requests, start + 3601 * second), None);
+        assert_eq!(requests.len(), HOUR_LIMIT);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn credentials_are_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+        let path =
+            std::env::temp_dir().join(format!("satwatch-space-track-{}.json", std::process::id()));
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let credentials = SpaceTrackCredentials {
+            identity: "user".to_string(),
+            password: "secret".to_string(),
+        };
+        credentials.save_at(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        let stored: SpaceTrackCredentials =
+            serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(stored.password, "secret");
+    }
+}
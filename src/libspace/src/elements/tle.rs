@@ -0,0 +1,462 @@
+// Classic two and three line element text.
+// Reading goes on past broken sets, so one bad line in a partner file does not lose the rest;
+// every problem is reported with the line it was found on.
+// See https://celestrak.org/columns/v04n03/ for the column layout.
+
+use chrono::{Datelike, Timelike};
+use sgp4::{Classification, Elements};
+use std::fmt::{Display, Formatter};
+
+const LINE_LENGTH: usize = 69;
+/// alpha-5 letters for NORAD ids above 99999, I and O are left out
+const ALPHA5: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TleError {
+    /// starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+pub struct TleParse {
+    pub elements: Vec<Elements>,
+    pub errors: Vec<TleError>,
+}
+
+impl Display for TleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Modulo 10 sum of the digits, minus signs count as 1
+pub fn tle_checksum(line: &str) -> u8 {
+    let sum: u32 = line
+        .chars()
+        .take(LINE_LENGTH - 1)
+        .map(|c| match c {
+            '-' => 1,
+            c => c.to_digit(10).unwrap_or(0),
+        })
+        .sum();
+    (sum % 10) as u8
+}
+
+/// Two or three line elements, the name lines may start with "0 "
+pub fn parse_tle(text: &str) -> TleParse {
+    let mut result = TleParse {
+        elements: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut name: Option<(usize, String)> = None;
+    let mut first: Option<(usize, &str)> = None;
+    let mut error = |line: usize, message: String| result.errors.push(TleError { line, message });
+    let mut elements = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if is_element_line(line, '1') {
+            if let Some((l, _)) = first.take() {
+                error(l, "Line 1 without line 2".to_string());
+            }
+            match check_line(line) {
+                Ok(()) => first = Some((number, line)),
+                Err(e) => {
+                    error(number, e);
+                    name = None;
+                }
+            }
+        } else if is_element_line(line, '2') {
+            let (first_number, first_line) = match first.take() {
+                Some(first) => first,
+                None => {
+                    error(number, "Line 2 without line 1".to_string());
+                    name = None;
+                    continue;
+                }
+            };
+            let object_name = name.take().map(|(_, n)| n);
+            if let Err(e) = check_line(line) {
+                error(number, e);
+                continue;
+            }
+            if first_line[2..7] != line[2..7] {
+                error(
+                    number,
+                    format!(
+                        "Catalog number {} does not match {} of line {}",
+                        line[2..7].trim(),
+                        first_line[2..7].trim(),
+                        first_number
+                    ),
+                );
+                continue;
+            }
+            match Elements::from_tle(object_name, first_line.as_bytes(), line.as_bytes()) {
+                Ok(e) => elements.push(e),
+                Err(e) => error(first_number, format!("Invalid elements: {:?}", e)),
+            }
+        } else {
+            if let Some((l, _)) = first.take() {
+                error(l, "Line 1 without line 2".to_string());
+            }
+            if let Some((l, n)) = name.take() {
+                error(l, format!("Name {} without elements", n));
+            }
+            let trimmed = line.trim();
+            let trimmed = trimmed.strip_prefix("0 ").unwrap_or(trimmed);
+            name = Some((number, trimmed.trim().to_string()));
+        }
+    }
+    if let Some((l, _)) = first {
+        error(l, "Line 1 without line 2".to_string());
+    }
+    if let Some((l, n)) = name {
+        error(l, format!("Name {} without elements", n));
+    }
+
+    result.elements = elements;
+    result
+}
+
+/// The element lines, with a name line in front for three line elements
+pub fn write_tle(elements: &Elements, three_line: bool) -> Result<String, String> {
+    let id = catalog_number(elements.norad_id)?;
+    let classification = match elements.classification {
+        Classification::Unclassified => 'U',
+        Classification::Classified => 'C',
+        Classification::Secret => 'S',
+    };
+    let epoch = elements.datetime;
+    let day = epoch.ordinal() as f64
+        + (epoch.num_seconds_from_midnight() as f64 + epoch.nanosecond() as f64 * 1e-9) / 86400.0;
+
+    let line1 = format!(
+        "1 {}{} {:<8} {:02}{:012.8} {} {} {} {} {:>4}",
+        id,
+        classification,
+        designator(elements.international_designator.as_deref()),
+        epoch.year() % 100,
+        day,
+        decimal_field(elements.mean_motion_dot)?,
+        exponent_field(elements.mean_motion_ddot),
+        exponent_field(elements.drag_term),
+        elements.ephemeris_type,
+        elements.element_set_number % 10000,
+    );
+    let eccentricity = (elements.eccentricity * 1e7).round();
+    if !(0.0..1e7).contains(&eccentricity) {
+        return Err(format!(
+            "Eccentricity {} does not fit a TLE",
+            elements.eccentricity
+        ));
+    }
+    let line2 = format!(
+        "2 {} {:>8.4} {:>8.4} {:07} {:>8.4} {:>8.4} {:>11.8}{:>5}",
+        id,
+        elements.inclination,
+        elements.right_ascension,
+        eccentricity as u64,
+        elements.argument_of_perigee,
+        elements.mean_anomaly,
+        elements.mean_motion,
+        elements.revolution_number % 100000,
+    );
+    if line1.len() != LINE_LENGTH - 1 || line2.len() != LINE_LENGTH - 1 {
+        return Err(format!(
+            "Elements of {} do not fit a TLE",
+            elements.norad_id
+        ));
+    }
+
+    let mut text = String::new();
+    if three_line {
+        let name = elements
+            .object_name
+            .clone()
+            .unwrap_or_else(|| elements.norad_id.to_string());
+        text += &format!("{}\n", name);
+    }
+    text += &format!("{}{}\n", line1, tle_checksum(&line1));
+    text += &format!("{}{}\n", line2, tle_checksum(&line2));
+    Ok(text)
+}
+
+fn is_element_line(line: &str, number: char) -> bool {
+    let mut chars = line.chars();
+    chars.next() == Some(number) && chars.next() == Some(' ') && line.len() >= LINE_LENGTH - 1
+}
+
+fn check_line(line: &str) -> Result<(), String> {
+    if !line.is_ascii() {
+        return Err("Element lines must be plain ascii".to_string());
+    }
+    if line.len() != LINE_LENGTH {
+        return Err(format!(
+            "Element lines have {} characters, this one {}",
+            LINE_LENGTH,
+            line.len()
+        ));
+    }
+    let expected = tle_checksum(line);
+    match line[LINE_LENGTH - 1..].parse::<u8>() {
+        Ok(checksum) if checksum == expected => Ok(()),
+        Ok(checksum) => Err(format!(
+            "Checksum is {}, but should be {}",
+            checksum, expected
+        )),
+        Err(_) => Err("The checksum is not a digit".to_string()),
+    }
+}
+
+/// Five characters, in alpha-5 above 99999
+fn catalog_number(id: u64) -> Result<String, String> {
+    if id <= 99999 {
+        return Ok(format!("{:05}", id));
+    }
+    let letter = ALPHA5
+        .chars()
+        .nth((id / 10000 - 10) as usize)
+        .ok_or_else(|| format!("NORAD id {} does not fit a TLE", id))?;
+    Ok(format!("{}{:04}", letter, id % 10000))
+}
+
+/// Accepts both 1998-067A and 98067A. What is not ascii or does not fit the eight columns is left blank.
+fn designator(designator: Option<&str>) -> String {
+    let d = match designator.map(str::trim) {
+        Some(d) if d.is_ascii() => d,
+        _ => return String::new(),
+    };
+    let compact = match (d.get(2..4), d.get(4..5), d.get(5..)) {
+        (Some(year), Some("-"), Some(piece)) if !piece.is_empty() => format!("{}{}", year, piece),
+        _ => d.to_string(),
+    };
+    if compact.len() > 8 {
+        String::new()
+    } else {
+        compact
+    }
+}
+
+/// Like " .00001234" or "-.00001234"
+fn decimal_field(value: f64) -> Result<String, String> {
+    if value.abs() >= 1.0 {
+        return Err(format!(
+            "Mean motion derivative {} does not fit a TLE",
+            value
+        ));
+    }
+    let digits = format!("{:.8}", value.abs());
+    let sign = if value < 0.0 { '-' } else { ' ' };
+    Ok(format!("{}{}", sign, digits.trim_start_matches('0')))
+}
+
+/// Assumed leading decimal point and an exponent, like " 12345-3" for 0.12345e-3
+fn exponent_field(value: f64) -> String {
+    if value == 0.0 {
+        return " 00000+0".to_string();
+    }
+    let sign = if value < 0.0 { '-' } else { ' ' };
+    let mut exponent = value.abs().log10().floor() as i32 + 1;
+    let mut mantissa = (value.abs() / 10f64.powi(exponent) * 1e5).round() as u32;
+    if mantissa >= 100000 {
+        mantissa /= 10;
+        exponent += 1;
+    }
+    if exponent < -9 {
+        return " 00000+0".to_string();
+    }
+    let exponent = exponent.min(9);
+    let exponent_sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}{:05}{}{}", sign, mantissa, exponent_sign, exponent.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::element_util::test_elements;
+    use chrono::NaiveDate;
+
+    const ISS_1: &str = "1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927";
+    const ISS_2: &str = "2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537";
+
+    /// Sets that passed the text checks, whether or not sgp4 made elements of them
+    fn checked_sets(parse: &TleParse) -> usize {
+        parse.elements.len() + parse.errors.len() - problems(parse).len()
+    }
+
+    /// The errors found in the text
+    fn problems(parse: &TleParse) -> Vec<TleError> {
+        parse
+            .errors
+            .iter()
+            .filter(|e| !e.message.starts_with("Invalid elements"))
+            .cloned()
+            .collect()
+    }
+
+    fn error(line: usize, message: &str) -> TleError {
+        TleError {
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let text = format!("ISS (ZARYA)\n{}\n{}8\n", ISS_1, &ISS_2[..68]);
+        let parse = parse_tle(&text);
+        assert_eq!(
+            problems(&parse),
+            vec![error(3, "Checksum is 8, but should be 7")]
+        );
+        assert_eq!(checked_sets(&parse), 0);
+
+        // a bad line 1 leaves its line 2 alone, the next set is still read
+        let text = format!("{}8\n{}\n{}\n{}\n", &ISS_1[..68], ISS_2, ISS_1, ISS_2);
+        let parse = parse_tle(&text);
+        assert_eq!(
+            problems(&parse),
+            vec![
+                error(1, "Checksum is 8, but should be 7"),
+                error(2, "Line 2 without line 1"),
+            ]
+        );
+        assert_eq!(checked_sets(&parse), 1);
+    }
+
+    #[test]
+    fn line_1_without_line_2() {
+        let parse = parse_tle(&format!("{}\n{}\n{}\n{}\n", ISS_1, ISS_1, ISS_2, ISS_1));
+        assert_eq!(
+            problems(&parse),
+            vec![
+                error(1, "Line 1 without line 2"),
+                error(4, "Line 1 without line 2"),
+            ]
+        );
+        assert_eq!(checked_sets(&parse), 1);
+    }
+
+    #[test]
+    fn catalog_numbers_must_match() {
+        let line2 = "2 25545  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563538";
+        let parse = parse_tle(&format!("{}\n{}\n", ISS_1, line2));
+        assert_eq!(
+            problems(&parse),
+            vec![error(
+                2,
+                "Catalog number 25545 does not match 25544 of line 1"
+            )]
+        );
+        assert_eq!(checked_sets(&parse), 0);
+    }
+
+    #[test]
+    fn three_line_names() {
+        let parse = parse_tle(&format!("0 ISS (ZARYA)\n{}\n{}\n", ISS_1, ISS_2));
+        assert_eq!(problems(&parse), vec![]);
+        assert_eq!(checked_sets(&parse), 1);
+        for elements in parse.elements.iter() {
+            assert_eq!(elements.object_name.as_deref(), Some("ISS (ZARYA)"));
+        }
+
+        // the prefix is not part of the name
+        let parse = parse_tle("0 ISS (ZARYA)\n\n0 HST\n");
+        assert_eq!(
+            problems(&parse),
+            vec![
+                error(1, "Name ISS (ZARYA) without elements"),
+                error(3, "Name HST without elements"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_write_parse() {
+        let text = format!("ISS (ZARYA)\n{}\n{}\n", ISS_1, ISS_2);
+        let parse = parse_tle(&text);
+        assert_eq!(problems(&parse), vec![]);
+        assert_eq!(checked_sets(&parse), 1);
+        for elements in parse.elements.iter() {
+            assert_eq!(elements.norad_id, 25544);
+            assert_eq!(
+                elements.international_designator.as_deref(),
+                Some("1998-067A")
+            );
+            assert_eq!(elements.inclination, 51.6416);
+            assert_eq!(elements.mean_motion, 15.72125391);
+
+            let written = write_tle(elements, true).unwrap();
+            let lines = written.lines().collect::<Vec<&str>>();
+            assert_eq!(lines[0], "ISS (ZARYA)");
+            // the zero second derivative is written as 00000+0
+            assert_eq!(lines[1][..44], ISS_1[..44]);
+            assert_eq!(lines[1][53..68], ISS_1[53..68]);
+            assert_eq!(lines[2], ISS_2);
+
+            let again = parse_tle(&written);
+            assert_eq!(again.errors, vec![]);
+            assert_eq!(again.elements[0].datetime, elements.datetime);
+            assert_eq!(again.elements[0].drag_term, elements.drag_term);
+        }
+
+        // what is written passes the checks
+        let written = write_tle(&test_elements(25544, 1), true).unwrap();
+        let parse = parse_tle(&written);
+        assert_eq!(problems(&parse), vec![]);
+        assert_eq!(checked_sets(&parse), 1);
+    }
+
+    #[test]
+    fn designators() {
+        assert_eq!(designator(Some("1998-067A")), "98067A");
+        assert_eq!(designator(Some("98067A")), "98067A");
+        assert_eq!(designator(Some(" 2024-123ABC ")), "24123ABC");
+        assert_eq!(designator(None), "");
+        // these used to panic or break the columns
+        assert_eq!(designator(Some("19é-067A")), "");
+        assert_eq!(designator(Some("1998-0ü7A")), "");
+        assert_eq!(designator(Some("ABCD-")), "ABCD-");
+        assert_eq!(designator(Some("1998-067ABCDEF")), "");
+    }
+
+    #[test]
+    fn written_lines_keep_their_columns() {
+        let elements = Elements {
+            object_name: Some("ISS (ZARYA)".to_string()),
+            international_designator: Some("1998-0ü7A".to_string()),
+            norad_id: 25544,
+            classification: Classification::Unclassified,
+            datetime: NaiveDate::from_ymd_opt(2024, 3, 1)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+            mean_motion_dot: 0.00016717,
+            mean_motion_ddot: 0.0,
+            drag_term: 0.00010270,
+            element_set_number: 999,
+            inclination: 51.6416,
+            right_ascension: 247.4627,
+            eccentricity: 0.0006703,
+            argument_of_perigee: 130.5360,
+            mean_anomaly: 325.0288,
+            mean_motion: 15.72125391,
+            revolution_number: 56353,
+            ephemeris_type: 0,
+        };
+        let text = write_tle(&elements, true).unwrap();
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "ISS (ZARYA)");
+        for line in &lines[1..] {
+            assert_eq!(line.len(), LINE_LENGTH);
+            assert_eq!(check_line(line), Ok(()));
+        }
+        assert_eq!(&lines[1][9..17], "        ");
+    }
+}
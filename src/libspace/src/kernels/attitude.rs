@@ -0,0 +1,211 @@
+// Orientation of spacecraft and instruments. Either through the frame system (pxform_c, needs the FK,
+// the CK and the SCLK kernel of the mission) or straight from the CK pointing (ckgp_c, needs CK and SCLK only).
+
+use crate::kernels::coverage::body_name;
+use glam::f64::{DMat3, DQuat, DVec3};
+use spice::SpiceLock;
+use std::ffi::{CStr, CString};
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_char;
+
+const NAME_LENGTH: usize = 33;
+/// CK ids of spacecraft structures are the spacecraft id times this
+const CK_ID_FACTOR: i32 = 1000;
+/// seconds, how far ckgp may look for pointing
+const DEFAULT_CK_TOLERANCE: f64 = 1.0;
+const MAX_FOV_BOUNDS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Attitude {
+    /// any frame the frame system knows, like "JUNO_SPACECRAFT"
+    Frame(String),
+    /// CK structure id and how far to look for pointing, seconds
+    Ck { id: i32, tolerance: f64 },
+}
+
+impl Attitude {
+    /// The body frame the FK assigns to the object, or the CK of the spacecraft bus if there is none
+    pub fn for_object(id: i32) -> Self {
+        match object_frame(id) {
+            Some(frame) => Self::Frame(frame),
+            None => Self::Ck {
+                id: id * CK_ID_FACTOR,
+                tolerance: DEFAULT_CK_TOLERANCE,
+            },
+        }
+    }
+
+    /// Rotation taking vectors of the body frame to J2000/ICRF
+    pub fn to_icrf(&self, et: f64) -> Result<DQuat, String> {
+        match self {
+            Attitude::Frame(frame) => frame_to_j2000(frame, et),
+            Attitude::Ck { id, tolerance } => ck_pointing(*id, et, *tolerance),
+        }
+    }
+}
+
+impl Display for Attitude {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Attitude::Frame(frame) => write!(f, "frame {}", frame),
+            Attitude::Ck { id, tolerance } => write!(f, "CK {} (+-{} s)", id, tolerance),
+        }
+    }
+}
+
+/// Field of view of an instrument, from its IK
+#[derive(Clone, Debug)]
+pub struct Instrument {
+    pub id: i32,
+    pub name: String,
+    pub frame: String,
+    /// in the instrument frame
+    pub boresight: DVec3,
+    /// edges of the field of view in the instrument frame, empty for circles and ellipses
+    pub bounds: Vec<DVec3>,
+}
+
+impl Instrument {
+    /// A NAIF id or name, like "NH_LORRI"
+    pub fn find(name_or_id: &str) -> Result<Self, String> {
+        let name_or_id = name_or_id.trim();
+        let _lock = SpiceLock::acquire().map_err(|_| "spice lock poisoned".to_string())?;
+        let id = match name_or_id.parse::<i32>() {
+            Ok(id) => id,
+            Err(_) => {
+                let name_c = CString::new(name_or_id).map_err(|e| e.to_string())?;
+                let mut code: spice::c::SpiceInt = 0;
+                let mut found: spice::c::SpiceBoolean = 0;
+                unsafe { spice::c::bodn2c_c(name_c.as_ptr(), &mut code, &mut found) };
+                if found == 0 {
+                    return Err(format!("Spice does not know {}", name_or_id));
+                }
+                code
+            }
+        };
+
+        let mut shape = vec![0 as c_char; NAME_LENGTH];
+        let mut frame = vec![0 as c_char; NAME_LENGTH];
+        let mut boresight = [0.0f64; 3];
+        let mut count: spice::c::SpiceInt = 0;
+        let mut bounds = [[0.0f64; 3]; MAX_FOV_BOUNDS];
+        unsafe {
+            spice::c::getfov_c(
+                id,
+                MAX_FOV_BOUNDS as i32,
+                NAME_LENGTH as i32,
+                NAME_LENGTH as i32,
+                shape.as_mut_ptr(),
+                frame.as_mut_ptr(),
+                boresight.as_mut_ptr(),
+                &mut count,
+                bounds.as_mut_ptr(),
+            );
+            if spice::c::failed_c() != 0 {
+                spice::c::reset_c();
+                return Err(format!("No field of view for {}, is the IK loaded?", id));
+            }
+            Ok(Self {
+                id,
+                name: body_name(id).unwrap_or_else(|| id.to_string()),
+                frame: CStr::from_ptr(frame.as_ptr()).to_string_lossy().to_string(),
+                boresight: DVec3::from(boresight).normalize_or_zero(),
+                bounds: bounds[..count.max(0) as usize]
+                    .iter()
+                    .map(|b| DVec3::from(*b))
+                    .collect(),
+            })
+        }
+    }
+
+    /// Boresight direction in J2000/ICRF
+    pub fn boresight_icrf(&self, et: f64) -> Result<DVec3, String> {
+        Ok(frame_to_j2000(&self.frame, et)? * self.boresight)
+    }
+}
+
+/// Rotation from a frame to J2000 at a time, pxform_c
+pub fn frame_to_j2000(frame: &str, et: f64) -> Result<DQuat, String> {
+    let from = CString::new(frame).map_err(|e| e.to_string())?;
+    let to = CString::new("J2000").unwrap();
+    let mut rotation = [[0.0f64; 3]; 3];
+    let _lock = SpiceLock::acquire().map_err(|_| "spice lock poisoned".to_string())?;
+    unsafe {
+        spice::c::pxform_c(from.as_ptr(), to.as_ptr(), et, rotation.as_mut_ptr());
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!("No orientation of {} at this time", frame));
+        }
+    }
+    Ok(quat_from_rows(&rotation))
+}
+
+/// Pointing of a CK structure, ckgp_c. The CK gives J2000 to structure, this returns the inverse.
+pub fn ck_pointing(id: i32, et: f64, tolerance: f64) -> Result<DQuat, String> {
+    let reference = CString::new("J2000").unwrap();
+    let spacecraft = id / CK_ID_FACTOR;
+    let mut sclk = 0.0f64;
+    let mut sclk_tolerance = 0.0f64;
+    let mut matrix = [[0.0f64; 3]; 3];
+    let mut clock_out = 0.0f64;
+    let mut found: spice::c::SpiceBoolean = 0;
+    let _lock = SpiceLock::acquire().map_err(|_| "spice lock poisoned".to_string())?;
+    unsafe {
+        spice::c::sce2c_c(spacecraft, et, &mut sclk);
+        spice::c::sce2c_c(spacecraft, et + tolerance, &mut sclk_tolerance);
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!(
+                "No spacecraft clock for {}, is the SCLK loaded?",
+                spacecraft
+            ));
+        }
+        spice::c::ckgp_c(
+            id,
+            sclk,
+            sclk_tolerance - sclk,
+            reference.as_ptr(),
+            matrix.as_mut_ptr(),
+            &mut clock_out,
+            &mut found,
+        );
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!("Cannot read pointing of {}", id));
+        }
+    }
+    if found == 0 {
+        return Err(format!("No pointing of {} at this time", id));
+    }
+    Ok(quat_from_rows(&matrix).inverse())
+}
+
+/// Name of the frame the FK assigns to an object, cidfrm_c
+fn object_frame(id: i32) -> Option<String> {
+    let mut frame_code: spice::c::SpiceInt = 0;
+    let mut frame = vec![0 as c_char; NAME_LENGTH];
+    let mut found: spice::c::SpiceBoolean = 0;
+    let _lock = SpiceLock::acquire().ok()?;
+    unsafe {
+        spice::c::cidfrm_c(
+            id,
+            NAME_LENGTH as i32,
+            &mut frame_code,
+            frame.as_mut_ptr(),
+            &mut found,
+        );
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return None;
+        }
+        if found == 0 {
+            return None;
+        }
+        Some(CStr::from_ptr(frame.as_ptr()).to_string_lossy().to_string())
+    }
+}
+
+/// Spice matrices are row major
+fn quat_from_rows(rows: &[[f64; 3]; 3]) -> DQuat {
+    DQuat::from_mat3(&DMat3::from_cols_array_2d(rows).transpose())
+}
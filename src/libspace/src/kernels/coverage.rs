@@ -0,0 +1,244 @@
+// Time coverage of the objects in binary SPK, CK and PCK files (spkcov_c, ckcov_c, pckcov_c).
+// The window functions want SPICE cells, which are built by hand here since the
+// SPICEDOUBLE_CELL/SPICEINT_CELL macros do not make it through to rust.
+
+use spice::SpiceLock;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+
+/// Control area in front of the data of every cell
+const CELL_CONTROL_SIZE: usize = 6;
+/// Objects per file and intervals per object this can report
+const MAX_OBJECTS: usize = 1000;
+const MAX_INTERVALS: usize = 10000;
+
+#[derive(Clone, Debug)]
+pub struct ObjectCoverage {
+    /// NAIF id of the body, spacecraft structure or frame
+    pub id: i32,
+    pub name: Option<String>,
+    /// start and end, ET
+    pub intervals: Vec<(f64, f64)>,
+}
+
+impl ObjectCoverage {
+    pub fn start(&self) -> Option<f64> {
+        self.intervals.first().map(|i| i.0)
+    }
+
+    pub fn end(&self) -> Option<f64> {
+        self.intervals.last().map(|i| i.1)
+    }
+
+    pub fn covers(&self, et: f64) -> bool {
+        self.intervals.iter().any(|(s, e)| *s <= et && et <= *e)
+    }
+
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", name, self.id),
+            None => self.id.to_string(),
+        }
+    }
+}
+
+pub fn spk_coverage(path: &Path) -> Result<Vec<ObjectCoverage>, String> {
+    coverage(
+        path,
+        |file, ids| unsafe { spice::c::spkobj_c(file, ids) },
+        |file, id, cover| unsafe { spice::c::spkcov_c(file, id, cover) },
+    )
+    .map(|objects| with_names(objects, body_name))
+}
+
+/// Coverage of CK structures at interval level, without requiring angular velocity
+pub fn ck_coverage(path: &Path) -> Result<Vec<ObjectCoverage>, String> {
+    let level = CString::new("INTERVAL").unwrap();
+    let time_system = CString::new("TDB").unwrap();
+    coverage(
+        path,
+        |file, ids| unsafe { spice::c::ckobj_c(file, ids) },
+        |file, id, cover| unsafe {
+            spice::c::ckcov_c(
+                file,
+                id,
+                0,
+                level.as_ptr(),
+                0.0,
+                time_system.as_ptr(),
+                cover,
+            )
+        },
+    )
+    .map(|objects| with_names(objects, body_name))
+}
+
+/// Coverage of the frames in a binary PCK
+pub fn pck_coverage(path: &Path) -> Result<Vec<ObjectCoverage>, String> {
+    coverage(
+        path,
+        |file, ids| unsafe { spice::c::pckfrm_c(file, ids) },
+        |file, id, cover| unsafe { spice::c::pckcov_c(file, id, cover) },
+    )
+    .map(|objects| with_names(objects, frame_name))
+}
+
+fn coverage<O, C>(path: &Path, objects: O, cover: C) -> Result<Vec<ObjectCoverage>, String>
+where
+    O: Fn(*const c_char, *mut spice::c::SpiceCell),
+    C: Fn(*const c_char, spice::c::SpiceInt, *mut spice::c::SpiceCell),
+{
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| format!("Invalid path {}", path.display()))?;
+    let file = CString::new(path_str).map_err(|e| e.to_string())?;
+
+    let _lock = SpiceLock::acquire().map_err(|_| "spice lock poisoned".to_string())?;
+    let mut ids = IntCell::new(MAX_OBJECTS);
+    objects(file.as_ptr(), &mut ids.cell);
+    check_failed(path)?;
+
+    let mut result = Vec::new();
+    for id in ids.values() {
+        let mut window = DoubleCell::new(MAX_INTERVALS * 2);
+        cover(file.as_ptr(), id, &mut window.cell);
+        check_failed(path)?;
+        result.push(ObjectCoverage {
+            id,
+            name: None,
+            intervals: window.values().chunks(2).map(|i| (i[0], i[1])).collect(),
+        });
+    }
+    Ok(result)
+}
+
+fn with_names(
+    mut objects: Vec<ObjectCoverage>,
+    name: fn(i32) -> Option<String>,
+) -> Vec<ObjectCoverage> {
+    if let Ok(_lock) = SpiceLock::acquire() {
+        for object in objects.iter_mut() {
+            object.name = name(object.id);
+        }
+    }
+    objects
+}
+
+/// Needs the spice lock to be held
+fn check_failed(path: &Path) -> Result<(), String> {
+    unsafe {
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!("Cannot read coverage of {}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Needs the spice lock to be held
+pub(crate) fn body_name(id: i32) -> Option<String> {
+    let mut name = vec![0 as c_char; 37];
+    let mut found: spice::c::SpiceBoolean = 0;
+    unsafe {
+        spice::c::bodc2n_c(id, name.len() as i32, name.as_mut_ptr(), &mut found);
+        if found == 0 {
+            return None;
+        }
+        CStr::from_ptr(name.as_ptr())
+            .to_str()
+            .ok()
+            .map(|s| s.to_owned())
+    }
+}
+
+/// Needs the spice lock to be held
+fn frame_name(id: i32) -> Option<String> {
+    let mut name = vec![0 as c_char; 33];
+    unsafe {
+        spice::c::frmnam_c(id, name.len() as i32, name.as_mut_ptr());
+        let name = CStr::from_ptr(name.as_ptr()).to_str().ok()?.to_owned();
+        if name.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+}
+
+/// Cell of doubles, the buffer never moves so the cell can point into it
+struct DoubleCell {
+    _buffer: Vec<f64>,
+    cell: spice::c::SpiceCell,
+}
+
+impl DoubleCell {
+    fn new(size: usize) -> Self {
+        let mut buffer = vec![0.0; CELL_CONTROL_SIZE + size];
+        let cell = new_cell(
+            spice::c::_SpiceDataType_SPICE_DP,
+            size,
+            buffer.as_mut_ptr() as *mut c_void,
+            unsafe { buffer.as_mut_ptr().add(CELL_CONTROL_SIZE) } as *mut c_void,
+        );
+        Self {
+            _buffer: buffer,
+            cell,
+        }
+    }
+
+    fn values(&self) -> Vec<f64> {
+        let data = self.cell.data as *const f64;
+        (0..self.cell.card as usize)
+            .map(|i| unsafe { *data.add(i) })
+            .collect()
+    }
+}
+
+struct IntCell {
+    _buffer: Vec<spice::c::SpiceInt>,
+    cell: spice::c::SpiceCell,
+}
+
+impl IntCell {
+    fn new(size: usize) -> Self {
+        let mut buffer = vec![0; CELL_CONTROL_SIZE + size];
+        let cell = new_cell(
+            spice::c::_SpiceDataType_SPICE_INT,
+            size,
+            buffer.as_mut_ptr() as *mut c_void,
+            unsafe { buffer.as_mut_ptr().add(CELL_CONTROL_SIZE) } as *mut c_void,
+        );
+        Self {
+            _buffer: buffer,
+            cell,
+        }
+    }
+
+    fn values(&self) -> Vec<spice::c::SpiceInt> {
+        let data = self.cell.data as *const spice::c::SpiceInt;
+        (0..self.cell.card as usize)
+            .map(|i| unsafe { *data.add(i) })
+            .collect()
+    }
+}
+
+/// What SPICEDOUBLE_CELL and SPICEINT_CELL expand to
+fn new_cell(
+    dtype: spice::c::SpiceDataType,
+    size: usize,
+    base: *mut c_void,
+    data: *mut c_void,
+) -> spice::c::SpiceCell {
+    spice::c::SpiceCell {
+        dtype,
+        length: 0,
+        size: size as spice::c::SpiceInt,
+        card: 0,
+        isSet: 1,
+        adjust: 0,
+        init: 0,
+        base,
+        data,
+    }
+}
@@ -0,0 +1,419 @@
+use crate::kernels::coverage::*;
+use crate::kernels::meta_kernel::MetaKernel;
+use crate::utility::init_dirs;
+use spice::SpiceLock;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+const KERNEL_LIST_FILENAME: &str = "kernels.json";
+const PATH_LENGTH: usize = 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KernelType {
+    Spk,
+    Ck,
+    Pck,
+    Dsk,
+    Ek,
+    Lsk,
+    Fk,
+    Ik,
+    Sclk,
+    TextPck,
+    Meta,
+    Unknown,
+}
+
+impl KernelType {
+    /// From the id word of the file, or the extension if there is none
+    pub fn detect(path: &Path) -> Self {
+        let from_file = SpiceLock::acquire()
+            .ok()
+            .and_then(|_lock| file_architecture(path));
+        match from_file {
+            Some((arch, kind)) => match (arch.as_str(), kind.as_str()) {
+                (_, "SPK") => Self::Spk,
+                (_, "CK") => Self::Ck,
+                ("KPL", "PCK") => Self::TextPck,
+                (_, "PCK") => Self::Pck,
+                (_, "DSK") => Self::Dsk,
+                (_, "EK") => Self::Ek,
+                (_, "LSK") => Self::Lsk,
+                (_, "FK") => Self::Fk,
+                (_, "IK") => Self::Ik,
+                (_, "SCLK") => Self::Sclk,
+                (_, "MK") => Self::Meta,
+                _ => Self::from_extension(path),
+            },
+            None => Self::from_extension(path),
+        }
+    }
+
+    /// NAIF naming conventions
+    pub fn from_extension(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match extension.as_str() {
+            "bsp" => Self::Spk,
+            "bc" => Self::Ck,
+            "bpc" => Self::Pck,
+            "bds" | "dsk" => Self::Dsk,
+            "bes" => Self::Ek,
+            "tls" => Self::Lsk,
+            "tf" => Self::Fk,
+            "ti" => Self::Ik,
+            "tsc" => Self::Sclk,
+            "tpc" => Self::TextPck,
+            "tm" => Self::Meta,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn has_coverage(&self) -> bool {
+        matches!(self, Self::Spk | Self::Ck | Self::Pck)
+    }
+}
+
+impl Display for KernelType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Spk => "SPK",
+            Self::Ck => "CK",
+            Self::Pck => "PCK",
+            Self::Dsk => "DSK",
+            Self::Ek => "EK",
+            Self::Lsk => "LSK",
+            Self::Fk => "FK",
+            Self::Ik => "IK",
+            Self::Sclk => "SCLK",
+            Self::TextPck => "Text PCK",
+            Self::Meta => "Meta",
+            Self::Unknown => "?",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FurnishedKernel {
+    pub path: PathBuf,
+    pub kernel_type: KernelType,
+    /// meta-kernel that loaded this one
+    pub source: Option<PathBuf>,
+}
+
+/// Loads and unloads kernels and remembers what was loaded between sessions.
+/// Kernels loaded by other means (like the LSK of the Timebase) are listed, but not managed.
+pub struct KernelManager {
+    /// kernels and meta-kernels in load order
+    loaded: Vec<PathBuf>,
+    /// kernels loaded through each meta-kernel
+    meta_kernels: HashMap<PathBuf, Vec<PathBuf>>,
+    coverage: HashMap<PathBuf, Result<Vec<ObjectCoverage>, String>>,
+}
+
+impl KernelManager {
+    /// Also makes spice return on errors, everything loaded through the manager is checked with failed_c
+    pub fn new() -> Self {
+        spice_return_on_error();
+        Self {
+            loaded: Vec::new(),
+            meta_kernels: HashMap::new(),
+            coverage: HashMap::new(),
+        }
+    }
+
+    /// Loads whatever was loaded last session. Kernels that went missing are dropped.
+    pub fn restore() -> Self {
+        let mut manager = Self::new();
+        let saved = init_dirs()
+            .ok()
+            .and_then(|dir| File::open(dir.join(KERNEL_LIST_FILENAME)).ok())
+            .and_then(|file| serde_json::from_reader::<_, Vec<PathBuf>>(file).ok())
+            .unwrap_or_default();
+        for path in saved {
+            if let Err(e) = manager.load_unsaved(&path) {
+                log::warn!("Not restoring kernel: {}", e);
+            }
+        }
+        manager.save();
+        manager
+    }
+
+    pub fn save(&self) {
+        let data_dir = init_dirs().expect("Something is wrong, you cant write home!");
+        match File::create(data_dir.join(KERNEL_LIST_FILENAME)) {
+            Ok(f) => {
+                if let Err(e) = serde_json::to_writer(f, &self.loaded) {
+                    log::error!("Cannot save kernel list: {}", e);
+                }
+            }
+            Err(e) => log::error!("Cannot save kernel list: {}", e),
+        }
+    }
+
+    /// Kernels and meta-kernels loaded through the manager
+    pub fn loaded(&self) -> &[PathBuf] {
+        &self.loaded
+    }
+
+    pub fn is_meta_kernel(&self, path: &Path) -> bool {
+        self.meta_kernels.contains_key(path)
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        self.load_unsaved(path)?;
+        self.save();
+        Ok(())
+    }
+
+    fn load_unsaved(&mut self, path: &Path) -> Result<(), String> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if self.loaded.contains(&path) {
+            return Err(format!("{} is already loaded", path.display()));
+        }
+
+        if KernelType::detect(&path) == KernelType::Meta {
+            let meta_kernel = MetaKernel::from_file(&path)?;
+            let missing = meta_kernel.missing();
+            if !missing.is_empty() {
+                return Err(format!(
+                    "{} lists missing kernels: {}",
+                    path.display(),
+                    missing
+                        .iter()
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ));
+            }
+            let mut kernels = Vec::new();
+            for kernel in meta_kernel.kernels.iter() {
+                match furnish(kernel) {
+                    Ok(()) => kernels.push(kernel.clone()),
+                    Err(e) => {
+                        // all or nothing
+                        for loaded in kernels.iter().rev() {
+                            unload(loaded);
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+            self.meta_kernels.insert(path.clone(), kernels);
+        } else {
+            furnish(&path)?;
+        }
+
+        self.loaded.push(path);
+        Ok(())
+    }
+
+    pub fn unload(&mut self, path: &Path) {
+        if let Some(kernels) = self.meta_kernels.remove(path) {
+            for kernel in kernels.iter().rev() {
+                unload(kernel);
+                self.coverage.remove(kernel);
+            }
+        } else {
+            unload(path);
+        }
+        self.coverage.remove(path);
+        self.loaded.retain(|p| p != path);
+        self.save();
+    }
+
+    pub fn unload_all(&mut self) {
+        for path in self.loaded.clone().iter().rev() {
+            self.unload(path);
+        }
+    }
+
+    /// Everything spice has loaded right now, in load order
+    pub fn furnished(&self) -> Vec<FurnishedKernel> {
+        let mut kernels = furnished_files("ALL")
+            .into_iter()
+            .map(|(path, source)| FurnishedKernel {
+                kernel_type: KernelType::detect(&path),
+                source,
+                path,
+            })
+            .collect::<Vec<FurnishedKernel>>();
+        for kernel in kernels.iter_mut().filter(|k| k.source.is_none()) {
+            kernel.source = self
+                .meta_kernels
+                .iter()
+                .find(|(_, list)| list.contains(&kernel.path))
+                .map(|(meta, _)| meta.clone());
+        }
+        kernels
+    }
+
+    /// Objects in a binary SPK, CK or PCK and the times they are covered, cached
+    pub fn coverage(&mut self, path: &Path) -> &Result<Vec<ObjectCoverage>, String> {
+        self.coverage
+            .entry(path.to_path_buf())
+            .or_insert_with(|| match KernelType::detect(path) {
+                KernelType::Spk => spk_coverage(path),
+                KernelType::Ck => ck_coverage(path),
+                KernelType::Pck => pck_coverage(path),
+                other => Err(format!("{} files have no coverage", other)),
+            })
+    }
+}
+
+/// Makes spice return from errors instead of aborting the program, so calls can be checked with failed_c.
+/// The messages are not printed, callers report errors themselves.
+pub fn spice_return_on_error() {
+    if let Ok(_lock) = SpiceLock::acquire() {
+        let set = CString::new("SET").unwrap();
+        let mut action = CString::new("RETURN").unwrap().into_bytes_with_nul();
+        let mut device = CString::new("NONE").unwrap().into_bytes_with_nul();
+        unsafe {
+            spice::c::erract_c(set.as_ptr(), 0, action.as_mut_ptr() as *mut c_char);
+            spice::c::errprt_c(set.as_ptr(), 0, device.as_mut_ptr() as *mut c_char);
+        }
+    }
+}
+
+/// All loaded DSK files
+pub fn furnished_dsks() -> Vec<PathBuf> {
+    furnished_files("DSK")
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Times an object is covered by all loaded SPKs together, merged and sorted, ET
+pub fn spk_object_coverage(id: i32) -> Vec<(f64, f64)> {
+    let mut intervals = furnished_files("SPK")
+        .iter()
+        .filter_map(|(path, _)| spk_coverage(path).ok())
+        .flatten()
+        .filter(|object| object.id == id)
+        .flat_map(|object| object.intervals)
+        .collect::<Vec<(f64, f64)>>();
+    intervals.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut merged: Vec<(f64, f64)> = Vec::new();
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn furnish(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    let file = CString::new(path.to_str().ok_or("Invalid path")?).map_err(|e| e.to_string())?;
+    let _lock = SpiceLock::acquire().map_err(|_| "spice lock poisoned".to_string())?;
+    unsafe {
+        spice::c::furnsh_c(file.as_ptr());
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!("Cannot load {}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+fn unload(path: &Path) {
+    if let (Some(path_str), Ok(_lock)) = (path.to_str(), SpiceLock::acquire()) {
+        let file = CString::new(path_str).unwrap();
+        unsafe {
+            spice::c::unload_c(file.as_ptr());
+            if spice::c::failed_c() != 0 {
+                spice::c::reset_c();
+            }
+        }
+    }
+}
+
+/// Files of a kind ("ALL", "SPK", "CK", ...) and the meta-kernels spice loaded them from
+fn furnished_files(kind: &str) -> Vec<(PathBuf, Option<PathBuf>)> {
+    let _lock = match SpiceLock::acquire() {
+        Ok(lock) => lock,
+        Err(_) => return Vec::new(),
+    };
+    let kind = CString::new(kind).unwrap();
+    let mut result = Vec::new();
+    unsafe {
+        let mut count: spice::c::SpiceInt = 0;
+        spice::c::ktotal_c(kind.as_ptr(), &mut count);
+        for i in 0..count {
+            let mut file = vec![0 as c_char; PATH_LENGTH];
+            let mut file_type = vec![0 as c_char; 32];
+            let mut source = vec![0 as c_char; PATH_LENGTH];
+            let mut handle: spice::c::SpiceInt = 0;
+            let mut found: spice::c::SpiceBoolean = 0;
+            spice::c::kdata_c(
+                i,
+                kind.as_ptr(),
+                PATH_LENGTH as i32,
+                file_type.len() as i32,
+                PATH_LENGTH as i32,
+                file.as_mut_ptr(),
+                file_type.as_mut_ptr(),
+                source.as_mut_ptr(),
+                &mut handle,
+                &mut found,
+            );
+            if found == 0 {
+                continue;
+            }
+            let file = CStr::from_ptr(file.as_ptr()).to_string_lossy().to_string();
+            let source = CStr::from_ptr(source.as_ptr())
+                .to_string_lossy()
+                .to_string();
+            result.push((
+                PathBuf::from(file),
+                if source.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(source))
+                },
+            ));
+        }
+    }
+    result
+}
+
+/// Architecture and type, like ("DAF", "SPK") or ("KPL", "FK"). Needs the spice lock to be held.
+fn file_architecture(path: &Path) -> Option<(String, String)> {
+    if !path.is_file() {
+        return None;
+    }
+    let file = CString::new(path.to_str()?).ok()?;
+    let mut arch = vec![0 as c_char; 8];
+    let mut kind = vec![0 as c_char; 8];
+    unsafe {
+        spice::c::getfat_c(
+            file.as_ptr(),
+            arch.len() as i32,
+            kind.len() as i32,
+            arch.as_mut_ptr(),
+            kind.as_mut_ptr(),
+        );
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return None;
+        }
+        Some((
+            CStr::from_ptr(arch.as_ptr()).to_string_lossy().to_string(),
+            CStr::from_ptr(kind.as_ptr()).to_string_lossy().to_string(),
+        ))
+    }
+}
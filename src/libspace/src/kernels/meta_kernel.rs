@@ -0,0 +1,348 @@
+// Meta-kernels (.tm) are text kernels listing other kernels in KERNELS_TO_LOAD,
+// optionally with $SYMBOL path abbreviations from PATH_SYMBOLS and PATH_VALUES.
+// See the NAIF "Kernel Required Reading" for the format.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Strings ending in this are continued by the next string
+const CONTINUATION: char = '+';
+
+#[derive(Clone, Debug)]
+pub struct MetaKernel {
+    pub path: PathBuf,
+    /// in load order, with symbols substituted
+    pub kernels: Vec<PathBuf>,
+}
+
+impl MetaKernel {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let mut meta_kernel =
+            Self::parse(&content).map_err(|e| format!("Cannot parse {}: {}", path.display(), e))?;
+        meta_kernel.path = path.to_path_buf();
+
+        // spice takes relative paths relative to the working directory, which is rarely what was meant.
+        // use the directory of the meta-kernel for anything that does not exist that way
+        if let Some(dir) = path.parent() {
+            for kernel in meta_kernel.kernels.iter_mut() {
+                if kernel.is_relative() && !kernel.exists() && dir.join(&kernel).exists() {
+                    *kernel = dir.join(&kernel);
+                }
+            }
+        }
+        Ok(meta_kernel)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let variables = parse_data(content)?;
+        let symbols = variables.get("PATH_SYMBOLS").cloned().unwrap_or_default();
+        let values = variables.get("PATH_VALUES").cloned().unwrap_or_default();
+        if symbols.len() != values.len() {
+            return Err(format!(
+                "{} PATH_SYMBOLS but {} PATH_VALUES",
+                symbols.len(),
+                values.len()
+            ));
+        }
+
+        let kernels = join_continued(variables.get("KERNELS_TO_LOAD").unwrap_or(&Vec::new()))
+            .iter()
+            .map(|name| {
+                let mut name = name.clone();
+                for (symbol, value) in symbols.iter().zip(values.iter()) {
+                    name = name.replace(&format!("${}", symbol), value);
+                }
+                PathBuf::from(name)
+            })
+            .collect();
+
+        Ok(Self {
+            path: PathBuf::new(),
+            kernels,
+        })
+    }
+
+    /// Kernels that cannot be found
+    pub fn missing(&self) -> Vec<&PathBuf> {
+        self.kernels.iter().filter(|k| !k.exists()).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Assign,
+    Append,
+    Open,
+    Close,
+    Value(String),
+}
+
+/// All variables assigned in \begindata sections, values as strings
+fn parse_data(content: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    let tokens = tokenize(content)?;
+    let mut variables: HashMap<String, Vec<String>> = HashMap::new();
+    let mut iter = tokens.into_iter();
+    while let Some((line, token)) = iter.next() {
+        let name = match token {
+            Token::Name(name) => name,
+            other => return Err(format!("line {}: expected a name, got {:?}", line, other)),
+        };
+        let append = match iter.next() {
+            Some((_, Token::Assign)) => false,
+            Some((_, Token::Append)) => true,
+            _ => return Err(format!("line {}: expected = or += after {}", line, name)),
+        };
+
+        let mut values = Vec::new();
+        match iter.next() {
+            Some((_, Token::Value(v))) => values.push(v),
+            Some((_, Token::Open)) => loop {
+                match iter.next() {
+                    Some((_, Token::Value(v))) => values.push(v),
+                    Some((_, Token::Close)) => break,
+                    Some((line, other)) => {
+                        return Err(format!("line {}: unexpected {:?} in list", line, other))
+                    }
+                    None => return Err(format!("line {}: unterminated list for {}", line, name)),
+                }
+            },
+            _ => return Err(format!("line {}: missing value for {}", line, name)),
+        }
+
+        let entry = variables.entry(name).or_default();
+        if !append {
+            entry.clear();
+        }
+        entry.extend(values);
+    }
+    Ok(variables)
+}
+
+/// Tokens of the data sections with their line numbers
+fn tokenize(content: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = Vec::new();
+    let mut in_data = false;
+    for (line_number, line) in content.lines().enumerate() {
+        let line_number = line_number + 1;
+        let trimmed = line.trim();
+        if trimmed.starts_with("\\begindata") {
+            in_data = true;
+            continue;
+        }
+        if trimmed.starts_with("\\begintext") {
+            in_data = false;
+            continue;
+        }
+        if !in_data {
+            continue;
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                c if c.is_whitespace() || c == ',' => {}
+                '(' => tokens.push((line_number, Token::Open)),
+                ')' => tokens.push((line_number, Token::Close)),
+                '=' => tokens.push((line_number, Token::Assign)),
+                '+' if chars.peek() == Some(&'=') => {
+                    chars.next();
+                    tokens.push((line_number, Token::Append));
+                }
+                '\'' => {
+                    // '' is a quote inside a string
+                    let mut s = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\'') if chars.peek() == Some(&'\'') => {
+                                chars.next();
+                                s.push('\'');
+                            }
+                            Some('\'') => break,
+                            Some(c) => s.push(c),
+                            None => {
+                                return Err(format!("line {}: unterminated string", line_number))
+                            }
+                        }
+                    }
+                    tokens.push((line_number, Token::Value(s)));
+                }
+                c => {
+                    let mut word = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || "(),='".contains(next) {
+                            break;
+                        }
+                        if next == '+' {
+                            let mut ahead = chars.clone();
+                            ahead.next();
+                            if ahead.peek() == Some(&'=') {
+                                break;
+                            }
+                        }
+                        word.push(next);
+                        chars.next();
+                    }
+                    // a bare word right after = or inside a list is a value (number or @date)
+                    let expects_name = !in_list(&tokens)
+                        && !matches!(
+                            tokens.last(),
+                            Some((_, Token::Assign)) | Some((_, Token::Append))
+                        );
+                    if expects_name {
+                        tokens.push((line_number, Token::Name(word)));
+                    } else {
+                        tokens.push((line_number, Token::Value(word)));
+                    }
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Whether the last token is inside an open list
+fn in_list(tokens: &[(usize, Token)]) -> bool {
+    for (_, token) in tokens.iter().rev() {
+        match token {
+            Token::Open => return true,
+            Token::Close | Token::Assign | Token::Append => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn join_continued(values: &[String]) -> Vec<String> {
+    let mut joined = Vec::new();
+    let mut current = String::new();
+    for value in values {
+        match value.strip_suffix(CONTINUATION) {
+            Some(start) => current.push_str(start),
+            None => {
+                current.push_str(value);
+                joined.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        joined.push(current);
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kernels(content: &str) -> Vec<String> {
+        MetaKernel::parse(content)
+            .unwrap()
+            .kernels
+            .iter()
+            .map(|k| k.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn path_symbols() {
+        let content = r"
+Text before the data is ignored, KERNELS_TO_LOAD = ( 'not.bsp' )
+\begindata
+   PATH_VALUES  = ( '/data/naif', '/data/naif/spk' )
+   PATH_SYMBOLS = ( 'NAIF', 'SPK' )
+   KERNELS_TO_LOAD = ( '$NAIF/lsk/naif0012.tls',
+                       '$SPK/de440s.bsp'
+                       '$NAIF/pck/pck00011.tpc', 'plain.bsp' )
+\begintext
+";
+        assert_eq!(
+            kernels(content),
+            vec![
+                "/data/naif/lsk/naif0012.tls",
+                "/data/naif/spk/de440s.bsp",
+                "/data/naif/pck/pck00011.tpc",
+                "plain.bsp"
+            ]
+        );
+
+        let unequal = "\\begindata\nPATH_VALUES = ( 'a' )\nPATH_SYMBOLS = ( 'A', 'B' )\n";
+        assert_eq!(
+            MetaKernel::parse(unequal).unwrap_err(),
+            "2 PATH_SYMBOLS but 1 PATH_VALUES"
+        );
+    }
+
+    #[test]
+    fn appended_and_replaced() {
+        let content = r"\begindata
+KERNELS_TO_LOAD = ( 'gone.bsp' )
+KERNELS_TO_LOAD = ( 'a.bsp' )
+\begintext
+later on
+\begindata
+KERNELS_TO_LOAD += ( 'b.bsp', 'c.bsp' )
+KERNELS_TO_LOAD += 'd.bsp'
+";
+        assert_eq!(kernels(content), vec!["a.bsp", "b.bsp", "c.bsp", "d.bsp"]);
+    }
+
+    #[test]
+    fn continued_strings() {
+        let content = r"\begindata
+KERNELS_TO_LOAD = ( '/a/very/long/+'
+                    'path/to/+', 'the.bsp'
+                    'short.bsp' )
+";
+        assert_eq!(
+            kernels(content),
+            vec!["/a/very/long/path/to/the.bsp", "short.bsp"]
+        );
+    }
+
+    #[test]
+    fn doubled_quotes() {
+        let content = "\\begindata\nKERNELS_TO_LOAD = ( 'it''s.bsp', '''quoted''.bsp' )\n";
+        assert_eq!(kernels(content), vec!["it's.bsp", "'quoted'.bsp"]);
+    }
+
+    #[test]
+    fn other_variables() {
+        let content = r"\begindata
+BODY399_RADII = ( 6378.1366 6378.1366 6356.7519 )
+START = @2024-JAN-01
+KERNELS_TO_LOAD = 'a.bsp'
+";
+        let variables = parse_data(content).unwrap();
+        assert_eq!(
+            variables["BODY399_RADII"],
+            vec!["6378.1366", "6378.1366", "6356.7519"]
+        );
+        assert_eq!(variables["START"], vec!["@2024-JAN-01"]);
+        assert!(parse_data("\\begindata\nA = ( 'b'\n").is_err());
+        assert!(parse_data("\\begindata\nA = 'b\n").is_err());
+        assert!(parse_data("\\begindata\nA 'b'\n").is_err());
+    }
+
+    #[test]
+    fn relative_to_the_meta_kernel() {
+        let dir = std::env::temp_dir().join(format!("satwatch-meta-kernel-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("spk")).unwrap();
+        std::fs::write(dir.join("spk").join("here.bsp"), "").unwrap();
+        let path = dir.join("test.tm");
+        std::fs::write(
+            &path,
+            "\\begindata\nKERNELS_TO_LOAD = ( 'spk/here.bsp', 'spk/missing.bsp' )\n",
+        )
+        .unwrap();
+
+        let meta_kernel = MetaKernel::from_file(&path).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(meta_kernel.path, path);
+        assert_eq!(meta_kernel.kernels[0], dir.join("spk").join("here.bsp"));
+        // what is nowhere stays as written
+        assert_eq!(meta_kernel.kernels[1], PathBuf::from("spk/missing.bsp"));
+    }
+}
@@ -0,0 +1,11 @@
+pub mod attitude;
+pub mod coverage;
+pub mod kernel_manager;
+pub mod meta_kernel;
+pub mod spice_object;
+
+pub use attitude::*;
+pub use coverage::*;
+pub use kernel_manager::*;
+pub use meta_kernel::*;
+pub use spice_object::*;
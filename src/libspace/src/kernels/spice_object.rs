@@ -0,0 +1,210 @@
+// Any object the loaded SPKs know about, like spacecraft, moons or asteroids.
+// The analytic planet positions of satwatch differ from the SPICE ones, so states are computed
+// relative to a planet (spkezr with the planet as observer) and added to its analytic position.
+// That way a spacecraft around Jupiter sits right next to the Jupiter that is drawn.
+
+use crate::bodies::Planet;
+use crate::coordinate::{CoordinateUnit, IcrfStateVector};
+use crate::kernels::coverage::body_name;
+use crate::kernels::kernel_manager::spk_object_coverage;
+use crate::timebase::Timebase;
+use glam::f64::DVec3;
+use spice::SpiceLock;
+use std::ffi::CString;
+
+const ALL_PLANETS: [Planet; 10] = [
+    Planet::Sun,
+    Planet::Mercury,
+    Planet::Venus,
+    Planet::Earth,
+    Planet::Moon,
+    Planet::Mars,
+    Planet::Jupiter,
+    Planet::Saturn,
+    Planet::Uranus,
+    Planet::Neptune,
+];
+
+#[derive(Clone, Debug)]
+pub struct SpiceObject {
+    pub id: i32,
+    pub name: String,
+    /// planet the object is placed relative to, when the preferred one does not work
+    pub center: Planet,
+    /// planets and the NAIF id spice can compute the object relative to
+    placements: Vec<(Planet, i32)>,
+    /// ET
+    pub coverage: Vec<(f64, f64)>,
+}
+
+impl SpiceObject {
+    /// A NAIF id or a name spice knows, like "-98" or "NEW HORIZONS"
+    pub fn find(name_or_id: &str, timebase: &Timebase) -> Result<Self, String> {
+        let name_or_id = name_or_id.trim();
+        let id = match name_or_id.parse::<i32>() {
+            Ok(id) => id,
+            Err(_) => body_code(name_or_id)
+                .ok_or_else(|| format!("Spice does not know {}", name_or_id))?,
+        };
+        let name = SpiceLock::acquire()
+            .ok()
+            .and_then(|_lock| body_name(id))
+            .unwrap_or_else(|| id.to_string());
+        let coverage = spk_object_coverage(id);
+        if coverage.is_empty() {
+            return Err(format!("No loaded SPK covers {}", name));
+        }
+
+        // checked once, a failing spkezr every frame would be wasteful
+        let et = if coverage
+            .iter()
+            .any(|(s, e)| *s <= timebase.now() && timebase.now() <= *e)
+        {
+            timebase.now()
+        } else {
+            coverage[0].0
+        };
+        let placements = ALL_PLANETS
+            .iter()
+            .filter_map(|p| {
+                [p.naif_id(), p.naif_barycenter_id()]
+                    .into_iter()
+                    .find(|observer| relative_state(id, *observer, et).is_ok())
+                    .map(|observer| (*p, observer))
+            })
+            .collect::<Vec<(Planet, i32)>>();
+        // the sun if possible, as that is where all the analytic positions are relative to
+        let center = placements
+            .first()
+            .map(|p| p.0)
+            .ok_or_else(|| format!("{} cannot be placed relative to any planet", name))?;
+
+        Ok(Self {
+            id,
+            name,
+            center,
+            placements,
+            coverage,
+        })
+    }
+
+    pub fn start(&self) -> f64 {
+        self.coverage.first().map(|c| c.0).unwrap_or(0.0)
+    }
+
+    pub fn end(&self) -> f64 {
+        self.coverage.last().map(|c| c.1).unwrap_or(0.0)
+    }
+
+    pub fn covers(&self, et: f64) -> bool {
+        self.coverage.iter().any(|(s, e)| *s <= et && et <= *e)
+    }
+
+    /// Heliocentric state, placed relative to the preferred planet if spice can do that
+    pub fn state_icrf(
+        &self,
+        prefer: Planet,
+        timebase: &Timebase,
+    ) -> Result<IcrfStateVector, String> {
+        let et = timebase.now();
+        if !self.covers(et) {
+            return Err(format!("{} is not covered at this time", self.name));
+        }
+        let (planet, observer) = self
+            .placements
+            .iter()
+            .find(|p| p.0 == prefer)
+            .or_else(|| self.placements.iter().find(|p| p.0 == self.center))
+            .copied()
+            .ok_or_else(|| format!("Cannot place {}", self.name))?;
+        let (position, velocity) = relative_state(self.id, observer, et)?;
+        let planet_pos = planet.pos_icrf(timebase).as_unit(CoordinateUnit::KiloMeter);
+        Ok(IcrfStateVector {
+            unit: CoordinateUnit::KiloMeter,
+            position: planet_pos.position + position,
+            velocity: planet_pos.velocity + velocity,
+        })
+    }
+
+    /// Points between two times (ET) where the object is covered, placed like state_icrf does
+    pub fn trajectory(
+        &self,
+        from: f64,
+        to: f64,
+        points: usize,
+        prefer: Planet,
+        timebase: &Timebase,
+    ) -> Vec<IcrfStateVector> {
+        let intervals = self
+            .coverage
+            .iter()
+            .map(|(s, e)| (s.max(from), e.min(to)))
+            .filter(|(s, e)| s < e)
+            .collect::<Vec<(f64, f64)>>();
+        let total: f64 = intervals.iter().map(|(s, e)| e - s).sum();
+
+        let mut at = *timebase;
+        let mut result = Vec::new();
+        for (start, end) in intervals {
+            // share the points between the intervals by their length
+            let n = ((points as f64 * (end - start) / total).ceil() as usize).max(2);
+            for i in 0..n {
+                at.set_now(start + (end - start) * i as f64 / (n - 1) as f64);
+                if let Ok(state) = self.state_icrf(prefer, &at) {
+                    result.push(state);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Position and velocity of target relative to observer in km and km/s, J2000 axes, geometric
+pub(crate) fn relative_state(
+    target: i32,
+    observer: i32,
+    et: f64,
+) -> Result<(DVec3, DVec3), String> {
+    let target_c = CString::new(target.to_string()).unwrap();
+    let observer_c = CString::new(observer.to_string()).unwrap();
+    let frame = CString::new("J2000").unwrap();
+    let correction = CString::new("NONE").unwrap();
+    let mut state = [0.0f64; 6];
+    let mut light_time = 0.0f64;
+
+    let _lock = SpiceLock::acquire().map_err(|_| "spice lock poisoned".to_string())?;
+    unsafe {
+        spice::c::spkezr_c(
+            target_c.as_ptr(),
+            et,
+            frame.as_ptr(),
+            correction.as_ptr(),
+            observer_c.as_ptr(),
+            state.as_mut_ptr(),
+            &mut light_time,
+        );
+        if spice::c::failed_c() != 0 {
+            spice::c::reset_c();
+            return Err(format!("No state for {} relative to {}", target, observer));
+        }
+    }
+    Ok((
+        DVec3::new(state[0], state[1], state[2]),
+        DVec3::new(state[3], state[4], state[5]),
+    ))
+}
+
+pub(crate) fn body_code(name: &str) -> Option<i32> {
+    let name_c = CString::new(name).ok()?;
+    let mut code: spice::c::SpiceInt = 0;
+    let mut found: spice::c::SpiceBoolean = 0;
+    let _lock = SpiceLock::acquire().ok()?;
+    unsafe {
+        spice::c::bodn2c_c(name_c.as_ptr(), &mut code, &mut found);
+    }
+    if found != 0 {
+        Some(code)
+    } else {
+        None
+    }
+}
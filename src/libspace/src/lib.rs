@@ -1,7 +1,10 @@
+pub mod accuracy;
 pub mod bodies;
 pub mod coordinate;
 pub mod elements;
+pub mod kernels;
 pub mod nasa_horizons;
+pub mod small_bodies;
 pub mod timebase;
 
 mod utility;
@@ -0,0 +1,269 @@
+use crate::nasa_horizons::horizons_request::{HorizonsRequest, TableType};
+use crate::nasa_horizons::horizons_table::{ObserverTable, VectorTable};
+use crate::utility::init_dirs;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+
+pub const HORIZONS_API_URL: &str = "https://ssd.jpl.nasa.gov/api/horizons.api";
+
+pub enum HorizonsTable {
+    Vectors(VectorTable),
+    Observer(ObserverTable),
+}
+
+/// Talks to the Horizons API. Answers are cached on disk, the same request is only sent once.
+#[derive(Clone, Debug)]
+pub struct HorizonsClient {
+    /// the API endpoint, HORIZONS_API_URL unless testing
+    pub base_url: String,
+    /// None disables the cache
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl HorizonsClient {
+    /// Client for the real API, caching into the satwatch data dir
+    pub fn new() -> Self {
+        Self {
+            base_url: HORIZONS_API_URL.to_string(),
+            cache_dir: init_dirs().ok().map(|dir| dir.join("horizons")),
+        }
+    }
+
+    pub fn with_base_url(base_url: &str, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            cache_dir,
+        }
+    }
+
+    pub fn vectors(&self, request: &HorizonsRequest, refresh: bool) -> Result<VectorTable, String> {
+        VectorTable::parse(&self.fetch_text(request, refresh)?, request.center)
+    }
+
+    pub fn observer(
+        &self,
+        request: &HorizonsRequest,
+        refresh: bool,
+    ) -> Result<ObserverTable, String> {
+        ObserverTable::parse(&self.fetch_text(request, refresh)?, request.center)
+    }
+
+    pub fn table(&self, request: &HorizonsRequest, refresh: bool) -> Result<HorizonsTable, String> {
+        match request.table_type {
+            TableType::Vectors => Ok(HorizonsTable::Vectors(self.vectors(request, refresh)?)),
+            TableType::Observer => Ok(HorizonsTable::Observer(self.observer(request, refresh)?)),
+        }
+    }
+
+    /// Fetches on a separate thread, as Horizons takes a few seconds to answer
+    pub fn table_in_background(
+        &self,
+        request: HorizonsRequest,
+        refresh: bool,
+    ) -> Receiver<Result<HorizonsTable, String>> {
+        let (tx, rx) = channel();
+        let client = self.clone();
+        std::thread::spawn(move || {
+            tx.send(client.table(&request, refresh)).ok();
+        });
+        rx
+    }
+
+    /// The result text of the API, from the cache if it is there and refresh is not set
+    pub fn fetch_text(&self, request: &HorizonsRequest, refresh: bool) -> Result<String, String> {
+        request.validate()?;
+        if !refresh {
+            if let Some(text) = self.cached(request) {
+                return Ok(text);
+            }
+        }
+
+        let mut call = ureq::get(&self.base_url);
+        for (name, value) in request.parameters() {
+            call = call.query(name, &value);
+        }
+        let text = match call.call() {
+            Ok(response) => parse_response(
+                response
+                    .into_json::<serde_json::Value>()
+                    .map_err(|e| format!("Invalid Horizons response: {}", e))?,
+            )?,
+            // errors come with a json body explaining them too
+            Err(ureq::Error::Status(status, response)) => {
+                let explanation = response
+                    .into_json::<serde_json::Value>()
+                    .map_err(|e| e.to_string())
+                    .and_then(parse_response)
+                    .unwrap_or_else(|e| e);
+                return Err(format!("Horizons error {}: {}", status, explanation));
+            }
+            Err(e) => return Err(format!("Horizons network error: {}", e)),
+        };
+
+        self.store(request, &text);
+        Ok(text)
+    }
+
+    pub fn clear_cache(&self) -> Result<(), String> {
+        match &self.cache_dir {
+            Some(dir) if dir.exists() => std::fs::remove_dir_all(dir).map_err(|e| e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    fn cache_file(&self, request: &HorizonsRequest) -> Option<PathBuf> {
+        self.cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.txt", request.cache_key())))
+    }
+
+    fn cached(&self, request: &HorizonsRequest) -> Option<String> {
+        let mut text = String::new();
+        File::open(self.cache_file(request)?)
+            .ok()?
+            .read_to_string(&mut text)
+            .ok()?;
+        Some(text)
+    }
+
+    /// Only results with an ephemeris are cached, errors should be asked again
+    fn store(&self, request: &HorizonsRequest, text: &str) {
+        let path = match self.cache_file(request) {
+            Some(path) if text.contains("$$SOE") => path,
+            _ => return,
+        };
+        let result = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .unwrap_or(Ok(()))
+            .and_then(|_| File::create(&path))
+            .and_then(|mut f| f.write_all(text.as_bytes()));
+        if let Err(e) = result {
+            log::warn!("Cannot cache Horizons result: {}", e);
+        }
+    }
+}
+
+/// The API puts the table into "result", or an explanation into "error"
+fn parse_response(json: serde_json::Value) -> Result<String, String> {
+    if let Some(error) = json.get("error").and_then(|e| e.as_str()) {
+        return Err(error.trim().to_string());
+    }
+    json.get("result")
+        .and_then(|r| r.as_str())
+        .map(|r| r.to_string())
+        .ok_or_else(|| "Horizons response has no result".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bodies::Planet;
+    use crate::utility::test_server::{serve, Response};
+
+    const TABLE: &str = "Target body name: Mars (499)\n$$SOE\n\
+        2460000.5, A.D. 2023-Feb-25 00:00:00.0000, 1.0, 2.0, 3.0, 0.1, 0.2, 0.3,\n\
+        2460001.5, A.D. 2023-Feb-26 00:00:00.0000, 4.0, 5.0, 6.0, 0.1, 0.2, 0.3,\n\
+        $$EOE\n";
+
+    fn json(status: u16, body: String) -> Response {
+        Response::new(status, body).header("Content-Type", "application/json")
+    }
+
+    /// The stand-in answers at the path of the real API
+    fn client(url: &str, cache_dir: Option<PathBuf>) -> HorizonsClient {
+        HorizonsClient::with_base_url(&format!("{}/api/horizons.api", url), cache_dir)
+    }
+
+    fn result(text: &str) -> String {
+        serde_json::json!({ "result": text, "signature": { "version": "1.2" } }).to_string()
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("satwatch-horizons-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn request() -> HorizonsRequest {
+        HorizonsRequest::vectors("499", Planet::Sun, 0.0, 86400.0, "1d")
+    }
+
+    #[test]
+    fn fetches_and_parses() {
+        let (url, requests) = serve(vec![json(200, result(TABLE))]);
+        let client = client(&url, None);
+        let table = client.vectors(&request(), false).unwrap();
+        assert_eq!(table.target, "Mars (499)");
+        assert_eq!(table.states.len(), 2);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0]
+            .line
+            .starts_with("GET /api/horizons.api?format=json"));
+        assert!(
+            requests[0].line.contains("COMMAND=%27499%27"),
+            "{}",
+            requests[0].line
+        );
+        assert!(requests[0].line.contains("EPHEM_TYPE=%27VECTORS%27"));
+    }
+
+    #[test]
+    fn answers_come_from_the_cache() {
+        let dir = cache_dir("cache");
+        let (url, requests) = serve(vec![json(200, result(TABLE)), json(200, result(TABLE))]);
+        let client = client(&url, Some(dir.clone()));
+        client.vectors(&request(), false).unwrap();
+        client.vectors(&request(), false).unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        // refresh asks again
+        client.vectors(&request(), true).unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        client.clear_cache().unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn errors_are_reported_and_not_cached() {
+        let dir = cache_dir("errors");
+        let no_ephemeris = "No ephemeris for target \"Ceres\" prior to A.D. 1550-JAN-01";
+        let (url, requests) = serve(vec![
+            json(
+                400,
+                serde_json::json!({ "error": "Cannot interpret date. Type \"?!\" for help." })
+                    .to_string(),
+            ),
+            json(200, result(no_ephemeris)),
+            json(200, result(no_ephemeris)),
+        ]);
+        let client = client(&url, Some(dir.clone()));
+
+        let error = client.vectors(&request(), false).unwrap_err();
+        assert_eq!(
+            error,
+            "Horizons error 400: Cannot interpret date. Type \"?!\" for help."
+        );
+        for _ in 0..2 {
+            let error = client.vectors(&request(), false).unwrap_err();
+            assert!(error.contains("No ephemeris for target"), "{}", error);
+        }
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn invalid_requests_are_not_sent() {
+        let client = HorizonsClient::with_base_url("http://127.0.0.1:1/", None);
+        let mut request = request();
+        request.stop = request.start;
+        assert_eq!(
+            client.fetch_text(&request, false).unwrap_err(),
+            "Stop time must be after start time"
+        );
+    }
+}
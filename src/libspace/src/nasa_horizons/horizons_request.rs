@@ -0,0 +1,135 @@
+use crate::bodies::Planet;
+use crate::timebase::et_to_jd;
+use std::fmt::{Display, Formatter};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TableType {
+    /// cartesian states, ICRF axes, km and km/s
+    Vectors,
+    /// astrometric right ascension, declination and range as seen from the center
+    Observer,
+}
+
+/// What to ask Horizons for. Times are ET, which Horizons calls TDB.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HorizonsRequest {
+    pub table_type: TableType,
+    /// anything the COMMAND parameter takes, like "499", "Ceres;" or "DES=2000433;"
+    pub target: String,
+    pub center: Planet,
+    pub start: f64,
+    pub stop: f64,
+    /// like "1d", "6h", "10m" or "100" for a number of equal steps
+    pub step: String,
+}
+
+impl HorizonsRequest {
+    pub fn vectors(target: &str, center: Planet, start: f64, stop: f64, step: &str) -> Self {
+        Self {
+            table_type: TableType::Vectors,
+            target: target.trim().to_string(),
+            center,
+            start,
+            stop,
+            step: step.trim().to_string(),
+        }
+    }
+
+    pub fn observer(target: &str, center: Planet, start: f64, stop: f64, step: &str) -> Self {
+        Self {
+            table_type: TableType::Observer,
+            ..Self::vectors(target, center, start, stop, step)
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target.is_empty() {
+            return Err("No target given".to_string());
+        }
+        if self.step.is_empty() {
+            return Err("No step size given".to_string());
+        }
+        if self.stop <= self.start {
+            return Err("Stop time must be after start time".to_string());
+        }
+        Ok(())
+    }
+
+    /// Query parameters of the API, quoted the way Horizons wants them
+    pub fn parameters(&self) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![
+            ("format", "json".to_string()),
+            ("MAKE_EPHEM", "'YES'".to_string()),
+            ("OBJ_DATA", "'NO'".to_string()),
+            ("COMMAND", quoted(&self.target)),
+            ("CENTER", quoted(&format!("500@{}", self.center.naif_id()))),
+            (
+                "START_TIME",
+                quoted(&format!("JD {:.9}", et_to_jd(self.start))),
+            ),
+            (
+                "STOP_TIME",
+                quoted(&format!("JD {:.9}", et_to_jd(self.stop))),
+            ),
+            ("STEP_SIZE", quoted(&self.step)),
+            ("CSV_FORMAT", "'YES'".to_string()),
+        ];
+        match self.table_type {
+            TableType::Vectors => parameters.extend([
+                ("EPHEM_TYPE", "'VECTORS'".to_string()),
+                ("REF_SYSTEM", "'ICRF'".to_string()),
+                // equatorial, which is what satwatch calls ICRF
+                ("REF_PLANE", "'FRAME'".to_string()),
+                ("VEC_TABLE", "'2'".to_string()),
+                ("VEC_LABELS", "'NO'".to_string()),
+                ("VEC_CORR", "'NONE'".to_string()),
+                ("OUT_UNITS", "'KM-S'".to_string()),
+            ]),
+            TableType::Observer => parameters.extend([
+                ("EPHEM_TYPE", "'OBSERVER'".to_string()),
+                ("QUANTITIES", "'1,20'".to_string()),
+                ("ANG_FORMAT", "'DEG'".to_string()),
+                ("CAL_FORMAT", "'JD'".to_string()),
+                // TT is within 2ms of TDB, close enough for an observer table
+                ("TIME_TYPE", "'TT'".to_string()),
+            ]),
+        }
+        parameters
+    }
+
+    /// Name of the cache file, the same request always gets the same one
+    pub fn cache_key(&self) -> String {
+        // FNV-1a, as the std hasher is not guaranteed to be stable between releases
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for (name, value) in self.parameters() {
+            for byte in name.bytes().chain(value.bytes()).chain([0u8]) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+fn quoted(value: &str) -> String {
+    format!("'{}'", value.replace('\'', ""))
+}
+
+impl Display for TableType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableType::Vectors => write!(f, "Vectors"),
+            TableType::Observer => write!(f, "Observer"),
+        }
+    }
+}
+
+impl Display for HorizonsRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} from {}, step {}",
+            self.table_type, self.target, self.center, self.step
+        )
+    }
+}
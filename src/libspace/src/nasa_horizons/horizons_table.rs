@@ -0,0 +1,370 @@
+// Parsers for the text tables Horizons returns, requested in CSV form.
+// The data sits between the $$SOE and $$EOE markers, the header above tells what the target is.
+
+use crate::bodies::Planet;
+use crate::coordinate::{
+    CoordinateUnit, IcrfStateVector, PlanetaryReferenceFrame, PlanetaryStateVector,
+};
+use crate::timebase::{jd_to_et, Timebase};
+use glam::f64::DVec3;
+
+const START_OF_EPHEMERIS: &str = "$$SOE";
+const END_OF_EPHEMERIS: &str = "$$EOE";
+
+/// Cartesian states of a target relative to a planet, sorted by time
+#[derive(Clone, Debug)]
+pub struct VectorTable {
+    pub target: String,
+    pub center: Planet,
+    /// ET and the inertial, planet centered state in km and km/s
+    pub states: Vec<(f64, PlanetaryStateVector)>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ObserverRow {
+    /// ET
+    pub time: f64,
+    /// degree
+    pub right_ascension: f64,
+    pub declination: f64,
+    /// au
+    pub range: f64,
+    /// km/s
+    pub range_rate: f64,
+}
+
+/// Where a target shows up in the sky of a planet, sorted by time
+#[derive(Clone, Debug)]
+pub struct ObserverTable {
+    pub target: String,
+    pub center: Planet,
+    pub rows: Vec<ObserverRow>,
+}
+
+impl VectorTable {
+    pub fn parse(text: &str, center: Planet) -> Result<Self, String> {
+        let mut states = Vec::new();
+        for (line_number, line) in ephemeris_lines(text)? {
+            // JDTDB, calendar date, X, Y, Z, VX, VY, VZ
+            let fields = csv_fields(line);
+            if fields.len() < 8 {
+                return Err(format!("Vector table line {} is too short", line_number));
+            }
+            let numbers = [0, 2, 3, 4, 5, 6, 7]
+                .iter()
+                .map(|i| parse_number(fields[*i], line_number))
+                .collect::<Result<Vec<f64>, String>>()?;
+            states.push((
+                jd_to_et(numbers[0]),
+                PlanetaryStateVector {
+                    planet: center,
+                    reference_frame: PlanetaryReferenceFrame::Inertial,
+                    unit: CoordinateUnit::KiloMeter,
+                    position: DVec3::new(numbers[1], numbers[2], numbers[3]),
+                    velocity: DVec3::new(numbers[4], numbers[5], numbers[6]),
+                },
+            ));
+        }
+        if states.is_empty() {
+            return Err("Vector table is empty".to_string());
+        }
+        states.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(Self {
+            target: target_name(text),
+            center,
+            states,
+        })
+    }
+
+    pub fn start(&self) -> f64 {
+        self.states.first().map(|s| s.0).unwrap_or(0.0)
+    }
+
+    pub fn end(&self) -> f64 {
+        self.states.last().map(|s| s.0).unwrap_or(0.0)
+    }
+
+    pub fn covers(&self, et: f64) -> bool {
+        self.start() <= et && et <= self.end()
+    }
+
+    /// Cubic hermite interpolation between the two neighbouring states, none outside the table
+    pub fn state_at(&self, et: f64) -> Option<PlanetaryStateVector> {
+        if !self.covers(et) {
+            return None;
+        }
+        if self.states.len() == 1 {
+            return Some(self.states[0].1);
+        }
+        let next = self.states.partition_point(|s| s.0 < et).max(1);
+        let (t0, s0) = self.states[next - 1];
+        let (t1, s1) = self.states[next];
+        let h = t1 - t0;
+        if h <= 0.0 {
+            return Some(s0);
+        }
+        let s = (et - t0) / h;
+        let (s2, s3) = (s * s, s * s * s);
+
+        let position = s0.position * (2.0 * s3 - 3.0 * s2 + 1.0)
+            + s0.velocity * h * (s3 - 2.0 * s2 + s)
+            + s1.position * (-2.0 * s3 + 3.0 * s2)
+            + s1.velocity * h * (s3 - s2);
+        let velocity = s0.position * ((6.0 * s2 - 6.0 * s) / h)
+            + s0.velocity * (3.0 * s2 - 4.0 * s + 1.0)
+            + s1.position * ((-6.0 * s2 + 6.0 * s) / h)
+            + s1.velocity * (3.0 * s2 - 2.0 * s);
+        Some(PlanetaryStateVector {
+            position,
+            velocity,
+            ..s0
+        })
+    }
+
+    /// Heliocentric state, placed relative to the analytic position of the center planet
+    pub fn state_icrf(&self, timebase: &Timebase) -> Option<IcrfStateVector> {
+        self.state_at(timebase.now())
+            .map(|state| state.to_icrf(timebase))
+    }
+}
+
+impl ObserverTable {
+    pub fn parse(text: &str, center: Planet) -> Result<Self, String> {
+        let lines = ephemeris_lines(text)?;
+        // the columns depend on the quantities, so they are looked up by name
+        let header = text
+            .lines()
+            .take_while(|l| l.trim() != START_OF_EPHEMERIS)
+            .filter(|l| l.contains("R.A."))
+            .last()
+            .ok_or_else(|| "Observer table has no column header".to_string())?;
+        let columns = csv_fields(header);
+        let column = |matches: &dyn Fn(&str) -> bool, name: &str| {
+            columns
+                .iter()
+                .position(|c| matches(c))
+                .ok_or_else(|| format!("Observer table has no {} column", name))
+        };
+        let ra_column = column(&|c| c.starts_with("R.A."), "right ascension")?;
+        let dec_column = column(&|c| c.starts_with("DEC"), "declination")?;
+        let range_column = column(&|c| c == "delta", "range")?;
+        let rate_column = column(&|c| c == "deldot", "range rate")?;
+
+        let mut rows = Vec::new();
+        for (line_number, line) in lines {
+            let fields = csv_fields(line);
+            let field = |i: usize| {
+                fields
+                    .get(i)
+                    .ok_or_else(|| format!("Observer table line {} is too short", line_number))
+                    .and_then(|f| parse_number(f, line_number))
+            };
+            rows.push(ObserverRow {
+                time: jd_to_et(field(0)?),
+                right_ascension: field(ra_column)?,
+                declination: field(dec_column)?,
+                range: field(range_column)?,
+                range_rate: field(rate_column)?,
+            });
+        }
+        if rows.is_empty() {
+            return Err("Observer table is empty".to_string());
+        }
+        rows.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self {
+            target: target_name(text),
+            center,
+            rows,
+        })
+    }
+}
+
+/// Lines between the markers, with their line number for errors
+fn ephemeris_lines(text: &str) -> Result<Vec<(usize, &str)>, String> {
+    let start = text
+        .lines()
+        .position(|l| l.trim() == START_OF_EPHEMERIS)
+        .ok_or_else(|| no_ephemeris_error(text))?;
+    Ok(text
+        .lines()
+        .enumerate()
+        .skip(start + 1)
+        .take_while(|(_, l)| l.trim() != END_OF_EPHEMERIS)
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| (i + 1, l))
+        .collect())
+}
+
+/// Horizons answers ambiguous targets and bad parameters with a plain text explanation
+fn no_ephemeris_error(text: &str) -> String {
+    let explanation = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('*'))
+        .take(8)
+        .collect::<Vec<&str>>()
+        .join(" ");
+    format!("Horizons returned no ephemeris: {}", explanation)
+}
+
+fn csv_fields(line: &str) -> Vec<&str> {
+    line.split(',').map(str::trim).collect()
+}
+
+fn parse_number(field: &str, line_number: usize) -> Result<f64, String> {
+    field
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid number '{}' in line {}", field, line_number))
+}
+
+/// "Target body name: Mars (499)    {source: mar097}" becomes "Mars (499)"
+fn target_name(text: &str) -> String {
+    text.lines()
+        .find_map(|l| l.trim().strip_prefix("Target body name:"))
+        .map(|name| name.split('{').next().unwrap_or(name).trim().to_string())
+        .unwrap_or_else(|| "Unknown target".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VECTORS: &str = "\
+*******************************************************************************
+Ephemeris / API_USER Mon Feb 27 05:11:38 2023 Pasadena, USA      / Horizons
+*******************************************************************************
+Target body name: Mars (499)                      {source: mar097}
+Center body name: Sun (10)                        {source: DE441}
+Center-site name: BODY CENTER
+*******************************************************************************
+            JDTDB,            Calendar Date (TDB),                      X,                      Y,                      Z,                     VX,                     VY,                     VZ,
+**************************************************************************************************************************************************************************************************
+$$SOE
+2460000.500000000, A.D. 2023-Feb-25 00:00:00.0000, -2.046337384296029E+07,  2.152081587838990E+08,  9.926470413469614E+07, -2.474003283497727E+01, -7.706014862217713E-01,  3.129089689069131E-01,
+2460001.500000000, A.D. 2023-Feb-26 00:00:00.0000, -2.259954926349627E+07,  2.151200869346958E+08,  9.928827689219698E+07, -2.470661117225093E+01, -1.268458452722447E+00,  1.031669001418733E-01,
+2460000.000000000, A.D. 2023-Feb-24 12:00:00.0000, -1.939436718434117E+07,  2.152402839474470E+08,  9.924840416432500E+07, -2.475549867541367E+01, -5.215073107052806E-01,  4.177016474669476E-01,
+$$EOE
+**************************************************************************************************************************************************************************************************
+";
+
+    const OBSERVER: &str = "\
+Target body name: Ceres (1)                       {source: JPL#48}
+Center body name: Earth (399)                     {source: DE441}
+*******************************************************************************
+ Date_________JDTT, , , R.A.___(ICRF), DEC____(ICRF),             delta,     deldot,
+*******************************************************************************
+$$SOE
+2460001.500000000, , ,  97.10652, 27.36022, 1.84839612545413,  19.6451440,
+2460000.500000000, , ,  97.07233, 27.40329, 1.83707131232541,  19.5638227,
+$$EOE
+*******************************************************************************
+";
+
+    const AMBIGUOUS: &str = "\
+*******************************************************************************
+JPL/DASTCOM            Small-body Index Search Results     2023-Feb-27 05:14:12
+
+ Comet AND asteroid index search:
+
+    NAME = CERES;
+
+ Matching small-bodies:
+
+    Record #  Epoch-yr  >MATCH NAME<   Primary Desig  Name
+    --------  --------  -------------  -------------  -------------------------
+           1            Ceres          A899 OF        Ceres
+    No ephemeris for target \"Ceres\" prior to A.D. 1550-JAN-01 12:00:00.0000 TDB
+";
+
+    #[test]
+    fn vector_table_fixture() {
+        let table = VectorTable::parse(VECTORS, Planet::Sun).unwrap();
+        assert_eq!(table.target, "Mars (499)");
+        assert_eq!(table.center, Planet::Sun);
+        assert_eq!(table.states.len(), 3);
+        // sorted by time
+        assert_eq!(table.start(), jd_to_et(2460000.0));
+        assert_eq!(table.end(), jd_to_et(2460001.5));
+        let (time, state) = table.states[1];
+        assert_eq!(time, jd_to_et(2460000.5));
+        assert_eq!(state.planet, Planet::Sun);
+        assert!(matches!(state.unit, CoordinateUnit::KiloMeter));
+        assert_eq!(state.position.x, -2.046337384296029E+07);
+        assert_eq!(state.velocity.z, 3.129089689069131E-01);
+    }
+
+    #[test]
+    fn vector_table_interpolates() {
+        // moving in a straight line, which the cubic interpolation has to give back exactly
+        let text = "$$SOE\n\
+            2460000.0, A.D. 2023-Feb-24 12:00:00.0000, 1000.0, 0.0, -50.0, 2.0, 0.5, 0.0,\n\
+            2460001.0, A.D. 2023-Feb-25 12:00:00.0000, 173800.0, 43200.0, -50.0, 2.0, 0.5, 0.0,\n\
+            $$EOE\n";
+        let table = VectorTable::parse(text, Planet::Earth).unwrap();
+        assert_eq!(table.target, "Unknown target");
+        let start = table.start();
+        for seconds in [0.0, 1.0, 20000.0, 43200.0, 86399.0, 86400.0] {
+            let state = table.state_at(start + seconds).unwrap();
+            let expected = DVec3::new(1000.0 + 2.0 * seconds, 0.5 * seconds, -50.0);
+            assert!((state.position - expected).length() < 1e-6, "{}", seconds);
+            assert!((state.velocity - DVec3::new(2.0, 0.5, 0.0)).length() < 1e-9);
+        }
+        assert!(table.state_at(start - 1.0).is_none());
+        assert!(table.state_at(table.end() + 1.0).is_none());
+    }
+
+    #[test]
+    fn observer_table_fixture() {
+        let table = ObserverTable::parse(OBSERVER, Planet::Earth).unwrap();
+        assert_eq!(table.target, "Ceres (1)");
+        assert_eq!(table.rows.len(), 2);
+        let row = table.rows[0];
+        assert_eq!(row.time, jd_to_et(2460000.5));
+        assert_eq!(row.right_ascension, 97.07233);
+        assert_eq!(row.declination, 27.40329);
+        assert_eq!(row.range, 1.83707131232541);
+        assert_eq!(row.range_rate, 19.5638227);
+    }
+
+    #[test]
+    fn no_ephemeris_is_an_error() {
+        for parsed in [
+            VectorTable::parse(AMBIGUOUS, Planet::Sun).map(|_| ()),
+            ObserverTable::parse(AMBIGUOUS, Planet::Earth).map(|_| ()),
+        ] {
+            let error = parsed.unwrap_err();
+            assert!(
+                error.starts_with("Horizons returned no ephemeris"),
+                "{}",
+                error
+            );
+            assert!(error.contains("No ephemeris for target"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn empty_and_broken_tables_are_errors() {
+        let empty = "Target body name: Mars (499)\n$$SOE\n$$EOE\n";
+        assert_eq!(
+            VectorTable::parse(empty, Planet::Sun).unwrap_err(),
+            "Vector table is empty"
+        );
+
+        let short = VECTORS.replace(
+            ", -2.474003283497727E+01, -7.706014862217713E-01,  3.129089689069131E-01,",
+            "",
+        );
+        let error = VectorTable::parse(&short, Planet::Sun).unwrap_err();
+        assert_eq!(error, "Vector table line 11 is too short");
+
+        let invalid = VECTORS.replace("-2.046337384296029E+07", "n.a.");
+        let error = VectorTable::parse(&invalid, Planet::Sun).unwrap_err();
+        assert_eq!(error, "Invalid number 'n.a.' in line 11");
+
+        let no_header = OBSERVER.replace("R.A.___(ICRF)", "RA");
+        let error = ObserverTable::parse(&no_header, Planet::Earth).unwrap_err();
+        assert_eq!(error, "Observer table has no column header");
+    }
+}
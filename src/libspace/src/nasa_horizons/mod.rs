@@ -1 +1,7 @@
+pub mod horizons_client;
+pub mod horizons_request;
+pub mod horizons_table;
 
+pub use horizons_client::*;
+pub use horizons_request::*;
+pub use horizons_table::*;
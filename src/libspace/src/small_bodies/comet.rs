@@ -0,0 +1,76 @@
+use crate::bodies::two_body::*;
+use crate::coordinate::*;
+use glam::f64::DMat3;
+
+/// Perihelion elements, referred to the J2000 ecliptic, as the MPC publishes them for comets
+#[derive(Copy, Clone, Debug)]
+pub struct CometElements {
+    /// julian date (TT) of perihelion passage
+    pub perihelion_jd: f64,
+    /// au
+    pub perihelion_distance: f64,
+    pub eccentricity: f64,
+    /// degree
+    pub inclination: f64,
+    /// degree
+    pub long_ascending: f64,
+    /// degree
+    pub argument_perihelion: f64,
+    /// julian date (TT) the elements osculate at, if they are perturbed elements
+    pub epoch_jd: Option<f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Comet {
+    /// designation and name, like "1P/Halley" or "C/2020 F3 (NEOWISE)"
+    pub designation: String,
+    /// C, P, D, X, I or A
+    pub orbit_type: char,
+    /// total absolute magnitude
+    pub magnitude_g: f64,
+    /// slope parameter
+    pub slope_k: f64,
+    pub elements: CometElements,
+}
+
+impl CometElements {
+    pub fn to_two_body(&self) -> TwoBodyOrbit {
+        TwoBodyOrbit::from_perihelion_elements(
+            self.perihelion_jd,
+            self.perihelion_distance,
+            self.eccentricity,
+            self.inclination,
+            self.long_ascending,
+            self.argument_perihelion,
+        )
+    }
+
+    pub fn is_bound(&self) -> bool {
+        self.eccentricity < 1.0
+    }
+
+    /// Points along the conic, evenly spaced in true anomaly so the perihelion passage looks right.
+    /// Open or very large orbits are cut off at max_distance (au).
+    pub fn trajectory(&self, points: usize, max_distance: f64) -> Vec<IcrfStateVector> {
+        let e = self.eccentricity;
+        let p = self.perihelion_distance * (1.0 + e);
+        // largest true anomaly that stays within max_distance
+        let cos_limit = ((p / max_distance - 1.0) / e).clamp(-1.0, 1.0);
+        let nu_max = cos_limit.acos();
+
+        let rot = DMat3::from_rotation_z(self.long_ascending.to_radians())
+            * DMat3::from_rotation_x(self.inclination.to_radians())
+            * DMat3::from_rotation_z(self.argument_perihelion.to_radians());
+        (0..=points)
+            .map(|i| {
+                let nu = -nu_max + 2.0 * nu_max * i as f64 / points as f64;
+                let r = p / (1.0 + e * nu.cos());
+                IcrfStateVector {
+                    unit: CoordinateUnit::Au,
+                    position: ecliptic_to_icrf(rot * DVec3::new(r * nu.cos(), r * nu.sin(), 0.0)),
+                    velocity: DVec3::default(),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,156 @@
+// Format description: https://minorplanetcenter.net/iau/info/CometOrbitFormat.html
+
+use crate::small_bodies::comet::*;
+use crate::timebase::julian_date;
+use crate::utility::init_dirs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+
+pub const COMET_ELS_URL: &'static str = "https://minorplanetcenter.net/iau/MPCORB/CometEls.txt";
+pub const COMET_ELS_FILENAME: &'static str = "CometEls.txt";
+
+pub fn comet_els_file() -> PathBuf {
+    init_dirs()
+        .expect("Cannot read from home?")
+        .join(COMET_ELS_FILENAME)
+}
+
+fn field<'a>(line: &'a str, from: usize, to: usize) -> &'a str {
+    line.get(from - 1..to.min(line.len())).unwrap_or("").trim()
+}
+
+fn parse_field<T: std::str::FromStr>(
+    line: &str,
+    from: usize,
+    to: usize,
+    what: &str,
+) -> Result<T, String> {
+    field(line, from, to)
+        .parse()
+        .map_err(|_| format!("Invalid {} '{}'", what, field(line, from, to)))
+}
+
+/// Parses a single line of CometEls.txt
+pub fn parse_comet_line(line: &str) -> Result<Comet, String> {
+    if line.len() < 103 {
+        return Err("Line too short".to_string());
+    }
+
+    let perihelion_jd = julian_date(
+        parse_field(line, 15, 18, "perihelion year")?,
+        parse_field(line, 20, 21, "perihelion month")?,
+        parse_field(line, 23, 29, "perihelion day")?,
+    );
+
+    // perturbed solutions have an epoch, others leave it blank
+    let epoch_jd = match (
+        parse_field::<i32>(line, 82, 85, "").ok(),
+        parse_field::<u32>(line, 86, 87, "").ok(),
+        parse_field::<f64>(line, 88, 89, "").ok(),
+    ) {
+        (Some(year), Some(month), Some(day)) => Some(julian_date(year, month, day)),
+        _ => None,
+    };
+
+    let elements = CometElements {
+        perihelion_jd,
+        perihelion_distance: parse_field(line, 31, 39, "perihelion distance")?,
+        eccentricity: parse_field(line, 42, 49, "eccentricity")?,
+        argument_perihelion: parse_field(line, 52, 59, "argument of perihelion")?,
+        long_ascending: parse_field(line, 62, 69, "ascending node")?,
+        inclination: parse_field(line, 72, 79, "inclination")?,
+        epoch_jd,
+    };
+
+    Ok(Comet {
+        designation: field(line, 103, 158).to_string(),
+        orbit_type: field(line, 5, 5).chars().next().unwrap_or('C'),
+        magnitude_g: parse_field(line, 92, 95, "magnitude").unwrap_or(f64::NAN),
+        slope_k: parse_field(line, 97, 100, "slope").unwrap_or(f64::NAN),
+        elements,
+    })
+}
+
+/// Reads a whole CometEls.txt. Broken lines are logged and skipped.
+pub fn parse_comet_els<R: Read>(reader: R) -> Result<Vec<Comet>, String> {
+    let mut result = Vec::new();
+    for (line_number, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_comet_line(&line) {
+            Ok(comet) => result.push(comet),
+            Err(e) => log::warn!("CometEls line {}: {}", line_number + 1, e),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEOWISE: &str = "    C2020F03  2020 07  3.6796  0.294510  0.999178   37.2776   61.0104  128.9375  20200812   7.0  3.2  C/2020 F3 (NEOWISE)";
+    const BORISOV: &str = "    C2019Q04  2019 12  8.5546  2.006548  3.356633  209.1246  308.1488   44.0527            11.0  4.0  2I/Borisov";
+    const HALLEY: &str = "0001P         1986 02  9.4589  0.574636  0.967143  112.2414   59.8560  162.1951  19940217   5.5  8.0  1P/Halley";
+
+    #[test]
+    fn perturbed_line() {
+        let comet = parse_comet_line(NEOWISE).unwrap();
+        assert_eq!(comet.designation, "C/2020 F3 (NEOWISE)");
+        assert_eq!(comet.orbit_type, 'C');
+        assert_eq!(comet.magnitude_g, 7.0);
+        assert_eq!(comet.slope_k, 3.2);
+        let e = comet.elements;
+        assert_eq!(e.perihelion_jd, julian_date(2020, 7, 3.6796));
+        assert_eq!(e.perihelion_distance, 0.29451);
+        assert_eq!(e.eccentricity, 0.999178);
+        assert_eq!(e.argument_perihelion, 37.2776);
+        assert_eq!(e.long_ascending, 61.0104);
+        assert_eq!(e.inclination, 128.9375);
+        assert_eq!(e.epoch_jd, Some(julian_date(2020, 8, 12.0)));
+        assert!(e.is_bound());
+    }
+
+    #[test]
+    fn unperturbed_line() {
+        let comet = parse_comet_line(BORISOV).unwrap();
+        assert_eq!(comet.designation, "2I/Borisov");
+        assert_eq!(comet.elements.epoch_jd, None);
+        assert!(!comet.elements.is_bound());
+    }
+
+    #[test]
+    fn numbered_periodic_line() {
+        let comet = parse_comet_line(HALLEY).unwrap();
+        assert_eq!(comet.designation, "1P/Halley");
+        assert_eq!(comet.orbit_type, 'P');
+        assert_eq!(comet.elements.perihelion_jd, julian_date(1986, 2, 9.4589));
+    }
+
+    #[test]
+    fn whole_file_skips_broken_lines() {
+        let els = format!(
+            "{}\n\n{}\nnot a comet\n{}\n",
+            NEOWISE,
+            BORISOV,
+            HALLEY.replace("0.967143", "0.96x143")
+        );
+        let comets = parse_comet_els(els.as_bytes()).unwrap();
+        assert_eq!(comets.len(), 2);
+        assert!(parse_comet_line(&NEOWISE[..90]).is_err());
+    }
+
+    #[test]
+    fn trajectory_stays_within_distance() {
+        let comet = parse_comet_line(BORISOV).unwrap();
+        let points = comet.elements.trajectory(64, 10.0);
+        assert_eq!(points.len(), 65);
+        for p in points.iter() {
+            let r = p.position.length();
+            assert!(r >= comet.elements.perihelion_distance - 1e-12 && r <= 10.0 + 1e-9);
+        }
+        assert!((points[32].position.length() - comet.elements.perihelion_distance).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::bodies::kepler_equation::eccentric_anomaly;
+use crate::bodies::two_body::TwoBodyOrbit;
+use crate::coordinate::*;
+use crate::timebase::Timebase;
+use glam::f64::DMat3;
+use std::fmt::{Display, Formatter};
+
+/// Osculating heliocentric elements, referred to the J2000 ecliptic, as published by the MPC
+#[derive(Copy, Clone, Debug)]
+pub struct HeliocentricElements {
+    /// julian date (TT) the elements osculate at
+    pub epoch_jd: f64,
+    /// au
+    pub semi_mayor: f64,
+    pub eccentricity: f64,
+    /// degree
+    pub inclination: f64,
+    /// degree
+    pub long_ascending: f64,
+    /// degree
+    pub argument_perihelion: f64,
+    /// degree, at epoch
+    pub mean_anomaly: f64,
+    /// degree/day
+    pub mean_motion: f64,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum OrbitClass {
+    NearEarth,
+    MarsCrosser,
+    MainBelt,
+    Hilda,
+    JupiterTrojan,
+    Centaur,
+    TransNeptunian,
+    Other,
+}
+
+pub const ALL_ORBIT_CLASSES: [OrbitClass; 8] = [
+    OrbitClass::NearEarth,
+    OrbitClass::MarsCrosser,
+    OrbitClass::MainBelt,
+    OrbitClass::Hilda,
+    OrbitClass::JupiterTrojan,
+    OrbitClass::Centaur,
+    OrbitClass::TransNeptunian,
+    OrbitClass::Other,
+];
+
+#[derive(Clone, Debug)]
+pub struct MinorPlanet {
+    pub number: Option<u64>,
+    pub name: Option<String>,
+    /// principal provisional designation, like "2004 MN4"
+    pub designation: Option<String>,
+    /// absolute magnitude
+    pub magnitude_h: f64,
+    /// slope parameter
+    pub slope_g: f64,
+    pub elements: HeliocentricElements,
+    pub orbit_class: OrbitClass,
+}
+
+impl HeliocentricElements {
+    pub fn perihelion_distance(&self) -> f64 {
+        self.semi_mayor * (1.0 - self.eccentricity)
+    }
+
+    pub fn aphelion_distance(&self) -> f64 {
+        self.semi_mayor * (1.0 + self.eccentricity)
+    }
+
+    /// Rotation from the orbital plane (x towards perihelion) into the J2000 ecliptic
+    pub fn orbit_to_ecliptic(&self) -> DMat3 {
+        DMat3::from_rotation_z(self.long_ascending.to_radians())
+            * DMat3::from_rotation_x(self.inclination.to_radians())
+            * DMat3::from_rotation_z(self.argument_perihelion.to_radians())
+    }
+
+    pub fn state_icrf_since_j2000(&self, time: f64) -> IcrfStateVector {
+        let dt = time + 2451545.0 - self.epoch_jd;
+        let n = self.mean_motion.to_radians();
+        let e = self.eccentricity;
+        let a = self.semi_mayor;
+        let mean_anomaly =
+            (self.mean_anomaly.to_radians() + n * dt).rem_euclid(2.0 * std::f64::consts::PI);
+        let eccentric_anomaly = eccentric_anomaly(mean_anomaly, e);
+
+        let (sin_ea, cos_ea) = eccentric_anomaly.sin_cos();
+        let b = a * (1.0 - e * e).sqrt();
+        // au/day
+        let ea_dot = n / (1.0 - e * cos_ea);
+        let position = DVec3::new(a * (cos_ea - e), b * sin_ea, 0.0);
+        let velocity = DVec3::new(-a * sin_ea * ea_dot, b * cos_ea * ea_dot, 0.0);
+
+        let rot = self.orbit_to_ecliptic();
+        IcrfStateVector {
+            unit: CoordinateUnit::Au,
+            position: ecliptic_to_icrf(rot * position),
+            velocity: ecliptic_to_icrf(rot * velocity) / 86400.0,
+        }
+    }
+
+    pub fn state_icrf(&self, timebase: &Timebase) -> IcrfStateVector {
+        self.state_icrf_since_j2000(timebase.now_jd_j2000())
+    }
+
+    pub fn to_two_body(&self) -> TwoBodyOrbit {
+        let mut orbit = TwoBodyOrbit::from_icrf_state(
+            &self.state_icrf_since_j2000(self.epoch_jd - 2451545.0),
+            self.epoch_jd,
+        );
+        // the published mean motion and semi-mayor axis are not exactly consistent with the sun's GM,
+        // so take the GM that matches them to keep the mean motion the MPC gives us
+        let n = self.mean_motion.to_radians();
+        orbit.mu = n * n * self.semi_mayor.powi(3);
+        orbit
+    }
+
+    /// Samples one revolution, starting at the given time
+    pub fn rough_pos_list(&self, time: f64, points: usize) -> Vec<IcrfStateVector> {
+        let period = 360.0 / self.mean_motion;
+        (0..=points)
+            .map(|i| self.state_icrf_since_j2000(time + period * i as f64 / points as f64))
+            .collect()
+    }
+}
+
+impl OrbitClass {
+    /// Rough classification following the usual MPC/JPL boundaries
+    pub fn from_elements(elements: &HeliocentricElements) -> Self {
+        let a = elements.semi_mayor;
+        let q = elements.perihelion_distance();
+        if q < 1.3 {
+            OrbitClass::NearEarth
+        } else if q < 1.666 && a < 3.2 {
+            OrbitClass::MarsCrosser
+        } else if (1.78..3.3).contains(&a) {
+            OrbitClass::MainBelt
+        } else if (3.7..4.2).contains(&a) {
+            OrbitClass::Hilda
+        } else if (5.05..5.35).contains(&a) && elements.eccentricity < 0.3 {
+            OrbitClass::JupiterTrojan
+        } else if (5.5..30.1).contains(&a) {
+            OrbitClass::Centaur
+        } else if a >= 30.1 {
+            OrbitClass::TransNeptunian
+        } else {
+            OrbitClass::Other
+        }
+    }
+}
+
+impl MinorPlanet {
+    pub fn display_name(&self) -> String {
+        match (&self.number, &self.name, &self.designation) {
+            (Some(number), Some(name), _) => format!("({}) {}", number, name),
+            (Some(number), None, Some(designation)) => format!("({}) {}", number, designation),
+            (None, Some(name), _) => name.clone(),
+            (None, None, Some(designation)) => designation.clone(),
+            (Some(number), None, None) => format!("({})", number),
+            (None, None, None) => "Unknown".to_string(),
+        }
+    }
+}
+
+impl Display for OrbitClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrbitClass::NearEarth => write!(f, "NEO"),
+            OrbitClass::MarsCrosser => write!(f, "Mars-crosser"),
+            OrbitClass::MainBelt => write!(f, "Main belt"),
+            OrbitClass::Hilda => write!(f, "Hilda"),
+            OrbitClass::JupiterTrojan => write!(f, "Jupiter Trojan"),
+            OrbitClass::Centaur => write!(f, "Centaur"),
+            OrbitClass::TransNeptunian => write!(f, "TNO"),
+            OrbitClass::Other => write!(f, "Other"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ceres() -> HeliocentricElements {
+        HeliocentricElements {
+            epoch_jd: 2460800.5,
+            semi_mayor: 2.7660512,
+            eccentricity: 0.0794013,
+            inclination: 10.5878,
+            long_ascending: 80.25221,
+            argument_perihelion: 73.27343,
+            mean_anomaly: 188.70269,
+            mean_motion: 0.21424651,
+        }
+    }
+
+    #[test]
+    fn distance_follows_kepler() {
+        let elements = ceres();
+        let state = elements.state_icrf_since_j2000(elements.epoch_jd - 2451545.0);
+        let e = elements.eccentricity;
+        let ea = eccentric_anomaly(elements.mean_anomaly.to_radians(), e);
+        let r = elements.semi_mayor * (1.0 - e * ea.cos());
+        assert!((state.position.length() - r).abs() < 1e-12);
+    }
+
+    #[test]
+    fn perihelion_and_aphelion() {
+        let mut elements = ceres();
+        elements.mean_anomaly = 0.0;
+        let t = elements.epoch_jd - 2451545.0;
+        let r = elements.state_icrf_since_j2000(t).position.length();
+        assert!((r - elements.perihelion_distance()).abs() < 1e-12);
+        let half_period = 180.0 / elements.mean_motion;
+        let r = elements
+            .state_icrf_since_j2000(t + half_period)
+            .position
+            .length();
+        assert!((r - elements.aphelion_distance()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn two_body_agrees() {
+        let elements = ceres();
+        let orbit = elements.to_two_body();
+        for days in [-3000.0, -10.0, 0.0, 500.0, 5000.0] {
+            let t = elements.epoch_jd - 2451545.0 + days;
+            let kepler = elements.state_icrf_since_j2000(t);
+            let universal = orbit.state_icrf_since_j2000(t).unwrap();
+            assert!((kepler.position - universal.position).length() < 1e-9);
+            assert!((kepler.velocity - universal.velocity).length() < 1e-14);
+        }
+    }
+}
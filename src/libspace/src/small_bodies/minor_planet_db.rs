@@ -0,0 +1,145 @@
+use crate::small_bodies::minor_planet::*;
+use crate::small_bodies::mpcorb::MPCORB_FILENAME;
+use crate::utility::init_dirs;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub struct MinorPlanetDb {
+    minor_planets: Vec<MinorPlanet>,
+    by_number: HashMap<u64, usize>,
+    /// upper case
+    by_designation: HashMap<String, usize>,
+    search_names: Vec<String>,
+}
+
+impl MinorPlanetDb {
+    pub fn new() -> Self {
+        Self::from_minor_planets(Vec::new())
+    }
+
+    pub fn from_minor_planets(minor_planets: Vec<MinorPlanet>) -> Self {
+        let mut by_number = HashMap::new();
+        let mut by_designation = HashMap::new();
+        let mut search_names = Vec::with_capacity(minor_planets.len());
+        for (index, minor_planet) in minor_planets.iter().enumerate() {
+            if let Some(number) = minor_planet.number {
+                by_number.insert(number, index);
+            }
+            if let Some(designation) = &minor_planet.designation {
+                by_designation.insert(designation.to_uppercase(), index);
+            }
+            search_names.push(
+                format!(
+                    "{} {}",
+                    minor_planet.name.as_deref().unwrap_or(""),
+                    minor_planet.designation.as_deref().unwrap_or("")
+                )
+                .to_uppercase(),
+            );
+        }
+
+        Self {
+            minor_planets,
+            by_number,
+            by_designation,
+            search_names,
+        }
+    }
+
+    pub fn mpcorb_file() -> PathBuf {
+        init_dirs()
+            .expect("Cannot read from home?")
+            .join(MPCORB_FILENAME)
+    }
+
+    pub fn len(&self) -> usize {
+        self.minor_planets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minor_planets.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&MinorPlanet> {
+        self.minor_planets.get(index)
+    }
+
+    pub fn get_by_number(&self, number: u64) -> Option<&MinorPlanet> {
+        self.by_number
+            .get(&number)
+            .and_then(|index| self.minor_planets.get(*index))
+    }
+
+    pub fn all(&self) -> &[MinorPlanet] {
+        &self.minor_planets
+    }
+
+    /// Searches by number, name or designation. An empty search term returns everything of the given class.
+    /// A whole number or designation only finds that one.
+    pub fn find(&self, term: &str, class: Option<OrbitClass>) -> Vec<usize> {
+        let term = term.trim().to_uppercase();
+        let class_matches = |index: &usize| {
+            class
+                .map(|c| self.minor_planets[*index].orbit_class == c)
+                .unwrap_or(true)
+        };
+
+        let exact = match term.trim_matches(|c| c == '(' || c == ')').parse::<u64>() {
+            Ok(number) => self.by_number.get(&number),
+            Err(_) => self.by_designation.get(&term),
+        };
+        if let Some(index) = exact.filter(|index| class_matches(index)) {
+            return vec![*index];
+        }
+
+        (0..self.minor_planets.len())
+            .filter(|index| term.is_empty() || self.search_names[*index].contains(&term))
+            .filter(class_matches)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::small_bodies::mpcorb::parse_mpcorb_json;
+
+    fn db() -> MinorPlanetDb {
+        let json = r#"[
+            {"Number": "(433)", "Name": "Eros", "Epoch": 2460800.5, "M": 310.5, "Peri": 178.9,
+             "Node": 304.3, "i": 10.83, "e": 0.2228, "n": 0.5598, "a": 1.4579},
+            {"Number": "(4330)", "Name": "Vivaldi", "Epoch": 2460800.5, "M": 10.1, "Peri": 20.1,
+             "Node": 30.1, "i": 2.1, "e": 0.11, "n": 0.229, "a": 2.7},
+            {"Principal_desig": "2024 AA", "Epoch": 2460800.5, "M": 10.1, "Peri": 200.1,
+             "Node": 300.1, "i": 5.1, "e": 0.41, "n": 0.789, "a": 1.2346},
+            {"Principal_desig": "2024 AA1", "Epoch": 2460800.5, "M": 10.1, "Peri": 200.1,
+             "Node": 300.1, "i": 5.1, "e": 0.11, "n": 0.229, "a": 2.7}
+        ]"#;
+        MinorPlanetDb::from_minor_planets(parse_mpcorb_json(json.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn find_by_number_and_designation() {
+        let db = db();
+        for (term, class, expected) in [
+            ("433", None, vec![0]),
+            ("(433)", None, vec![0]),
+            ("4330", None, vec![1]),
+            ("433", Some(OrbitClass::MainBelt), vec![]),
+            ("2024 aa", None, vec![2]),
+            ("2024 AA1", None, vec![3]),
+            ("2024 AA", Some(OrbitClass::MainBelt), vec![3]),
+            ("2024", None, vec![2, 3]),
+            ("ival", None, vec![1]),
+            ("", Some(OrbitClass::MainBelt), vec![1, 3]),
+            ("1", None, vec![3]),
+        ] {
+            assert_eq!(db.find(term, class), expected, "{} {:?}", term, class);
+        }
+        assert_eq!(
+            db.get_by_number(4330).unwrap().display_name(),
+            "(4330) Vivaldi"
+        );
+        assert_eq!(db.all().len(), 4);
+    }
+}
@@ -0,0 +1,15 @@
+pub mod comet;
+pub mod comet_els;
+pub mod minor_planet;
+pub mod minor_planet_db;
+pub mod mpc_import;
+pub mod mpcorb;
+pub mod small_body_engine;
+
+pub use comet::*;
+pub use comet_els::*;
+pub use minor_planet::*;
+pub use minor_planet_db::*;
+pub use mpc_import::*;
+pub use mpcorb::*;
+pub use small_body_engine::*;
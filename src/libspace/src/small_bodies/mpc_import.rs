@@ -0,0 +1,174 @@
+use crate::small_bodies::minor_planet::MinorPlanet;
+use crate::small_bodies::mpcorb::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::mpsc::*;
+
+const PROGRESS_STEP: u64 = 4 * 1024 * 1024;
+
+pub enum ImportStatus {
+    Invalid,
+    Progress { bytes_read: u64, bytes_total: u64 },
+    Error(String),
+    Complete(Vec<MinorPlanet>),
+}
+
+/// Reads MPCORB.DAT or its json export on a separate thread, as the full file is rather large
+pub struct MpcImport {
+    rx: Receiver<ImportStatus>,
+    last_status: ImportStatus,
+}
+
+struct ProgressReader<R: Read> {
+    inner: R,
+    bytes_read: u64,
+    bytes_total: u64,
+    last_report: u64,
+    tx: SyncSender<ImportStatus>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.bytes_read += count as u64;
+        if self.bytes_read - self.last_report > PROGRESS_STEP {
+            self.last_report = self.bytes_read;
+            // if the ui is busy, it just misses a progress update
+            let _ = self.tx.try_send(ImportStatus::Progress {
+                bytes_read: self.bytes_read,
+                bytes_total: self.bytes_total,
+            });
+        }
+        Ok(count)
+    }
+}
+
+fn read_dat<R: Read>(reader: R) -> Result<Vec<MinorPlanet>, String> {
+    let mut result = Vec::new();
+    let mut error_count = 0usize;
+    // the header is optional, but if its there, it ends with a line of dashes
+    let mut in_header = true;
+    for (line_number, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with("-----") {
+            in_header = false;
+            continue;
+        }
+
+        match parse_mpcorb_line(&line) {
+            Ok(minor_planet) => {
+                in_header = false;
+                result.push(minor_planet);
+            }
+            Err(e) => {
+                if !in_header {
+                    error_count += 1;
+                    if error_count <= 10 {
+                        log::warn!("MPCORB line {}: {}", line_number + 1, e);
+                    }
+                }
+            }
+        }
+    }
+
+    if error_count > 0 {
+        log::warn!("MPCORB import skipped {} broken lines", error_count);
+    }
+    Ok(result)
+}
+
+impl MpcImport {
+    pub fn import_file(path: PathBuf) -> Self {
+        let (tx, rx) = sync_channel(10);
+        std::thread::spawn(move || {
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    tx.send(ImportStatus::Error(format!("{}: {}", path.display(), e)))
+                        .ok();
+                    return;
+                }
+            };
+            let bytes_total = file.metadata().map(|m| m.len()).unwrap_or(0);
+            let reader = ProgressReader {
+                inner: file,
+                bytes_read: 0,
+                bytes_total,
+                last_report: 0,
+                tx: tx.clone(),
+            };
+
+            let is_json = path
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("json"))
+                .unwrap_or(false);
+            let result = if is_json {
+                parse_mpcorb_json(BufReader::new(reader))
+            } else {
+                read_dat(reader)
+            };
+
+            let status = match result {
+                Ok(minor_planets) => ImportStatus::Complete(minor_planets),
+                Err(e) => ImportStatus::Error(e),
+            };
+            tx.send(status).ok();
+        });
+
+        Self {
+            rx,
+            last_status: ImportStatus::Invalid,
+        }
+    }
+
+    pub fn update_status(&mut self) {
+        'status_loop: loop {
+            match self.rx.try_recv() {
+                Ok(new_status) => self.last_status = new_status,
+                Err(_) => {
+                    break 'status_loop;
+                }
+            }
+        }
+    }
+
+    pub fn get_status(&self) -> &ImportStatus {
+        &self.last_status
+    }
+
+    /// Takes the result out once the import is done, leaving the importer invalid
+    pub fn take_result(&mut self) -> Option<Result<Vec<MinorPlanet>, String>> {
+        match std::mem::replace(&mut self.last_status, ImportStatus::Invalid) {
+            ImportStatus::Complete(minor_planets) => Some(Ok(minor_planets)),
+            ImportStatus::Error(e) => Some(Err(e)),
+            other => {
+                self.last_status = other;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dat_with_header() {
+        let dat = "MINOR PLANET CENTER ORBIT DATABASE (MPCORB)\n\
+            \n\
+            Des'n     H     G   Epoch     M        Peri.      Node       Incl.       e            n           a        Reference #Obs #Opp    Arc    rms  Perts   Computer\n\
+            ----------------------------------------------------------------------------------------------------------------------------------------------------------------\n\
+            00001    3.34  0.15 K2555 188.70269   73.27343   80.25221   10.58780  0.0794013  0.21424651   2.7660512  0 E2024-V47  7330 125 1801-2024 0.80 M-v 30k MPCLINUX   4000 (1) Ceres                   20241101\n\
+            this line is broken\n\
+            K24A00A  25.6  0.15 K2555  10.12345  200.12345  300.12345    5.12345  0.4123456  0.78901234   1.2345678  0 E2024-V47  7330 125 1801-2024 0.80 M-v 30k MPCLINUX   4000 2024 AA                     20241101\n";
+        let planets = read_dat(dat.as_bytes()).unwrap();
+        assert_eq!(planets.len(), 2);
+        assert_eq!(planets[0].display_name(), "(1) Ceres");
+        assert_eq!(planets[1].display_name(), "2024 AA");
+    }
+}
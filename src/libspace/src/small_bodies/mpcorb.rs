@@ -0,0 +1,329 @@
+// Format description: https://minorplanetcenter.net/iau/info/MPOrbitFormat.html
+// The JSON variant is the "extended" export from https://minorplanetcenter.net/data
+
+use crate::small_bodies::minor_planet::*;
+use crate::timebase::julian_date;
+use serde::Deserialize;
+
+pub const MPCORB_URL: &'static str = "https://minorplanetcenter.net/iau/MPCORB/MPCORB.DAT";
+pub const MPCORB_FILENAME: &'static str = "MPCORB.DAT";
+
+#[derive(Deserialize)]
+struct MpcJsonRecord {
+    #[serde(rename = "Number")]
+    number: Option<String>,
+    #[serde(rename = "Name")]
+    name: Option<String>,
+    #[serde(rename = "Principal_desig")]
+    principal_desig: Option<String>,
+    #[serde(rename = "H")]
+    h: Option<f64>,
+    #[serde(rename = "G")]
+    g: Option<f64>,
+    #[serde(rename = "Epoch")]
+    epoch: f64,
+    #[serde(rename = "M")]
+    m: f64,
+    #[serde(rename = "Peri")]
+    peri: f64,
+    #[serde(rename = "Node")]
+    node: f64,
+    i: f64,
+    e: f64,
+    n: f64,
+    a: f64,
+}
+
+/// Unpacks a single character of the MPC packed formats. 0-9, then A-Z, then a-z
+fn unpack_char(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => Some(c as u32 - '0' as u32),
+        'A'..='Z' => Some(c as u32 - 'A' as u32 + 10),
+        'a'..='z' => Some(c as u32 - 'a' as u32 + 36),
+        _ => None,
+    }
+}
+
+pub fn unpack_number(packed: &str) -> Option<u64> {
+    let packed = packed.trim();
+    if packed.len() != 5 || !packed.is_ascii() {
+        return None;
+    }
+
+    let mut chars = packed.chars();
+    let first = chars.next()?;
+    if first == '~' {
+        // base 62 beyond 619999
+        let mut value = 0u64;
+        for c in chars {
+            value = value * 62 + unpack_char(c)? as u64;
+        }
+        Some(value + 620000)
+    } else {
+        let rest: u64 = packed.get(1..)?.parse().ok()?;
+        Some(unpack_char(first)? as u64 * 10000 + rest)
+    }
+}
+
+/// Packed epochs look like K24AM: century, year, month and day
+pub fn unpack_epoch(packed: &str) -> Option<f64> {
+    let chars = packed.trim().chars().collect::<Vec<char>>();
+    if chars.len() != 5 {
+        return None;
+    }
+
+    let century = unpack_char(chars[0])? as i32;
+    let year: i32 = chars[1..3].iter().collect::<String>().parse().ok()?;
+    let month = unpack_char(chars[3])?;
+    let day = unpack_char(chars[4])?;
+    Some(julian_date(century * 100 + year, month, day as f64))
+}
+
+fn field<'a>(line: &'a str, from: usize, to: usize) -> &'a str {
+    line.get(from - 1..to.min(line.len())).unwrap_or("").trim()
+}
+
+fn parse_field(line: &str, from: usize, to: usize, what: &str) -> Result<f64, String> {
+    field(line, from, to)
+        .parse()
+        .map_err(|_| format!("Invalid {} '{}'", what, field(line, from, to)))
+}
+
+/// Splits "(433) Eros" or "(3708) 1974 FV1" into number, name and designation
+fn split_readable(readable: &str) -> (Option<u64>, Option<String>, Option<String>) {
+    let readable = readable.trim();
+    let (number, rest) = if readable.starts_with('(') {
+        match readable.find(')') {
+            Some(end) => (readable[1..end].parse().ok(), readable[end + 1..].trim()),
+            None => (None, readable),
+        }
+    } else {
+        (None, readable)
+    };
+
+    if rest.is_empty() {
+        return (number, None, None);
+    }
+
+    let looks_provisional = rest.len() > 5
+        && rest.bytes().take(4).all(|c| c.is_ascii_digit())
+        && rest.as_bytes()[4] == b' ';
+    if looks_provisional {
+        (number, None, Some(rest.to_string()))
+    } else {
+        (number, Some(rest.to_string()), None)
+    }
+}
+
+fn make_minor_planet(
+    number: Option<u64>,
+    name: Option<String>,
+    designation: Option<String>,
+    magnitude_h: f64,
+    slope_g: f64,
+    elements: HeliocentricElements,
+) -> MinorPlanet {
+    MinorPlanet {
+        number,
+        name,
+        designation,
+        magnitude_h,
+        slope_g,
+        orbit_class: OrbitClass::from_elements(&elements),
+        elements,
+    }
+}
+
+/// Parses a single line of MPCORB.DAT. Header lines are rejected as well.
+pub fn parse_mpcorb_line(line: &str) -> Result<MinorPlanet, String> {
+    if line.len() < 103 {
+        return Err("Line too short".to_string());
+    }
+    // the columns count bytes
+    if !line.is_ascii() {
+        return Err("Line is not ASCII".to_string());
+    }
+
+    let epoch_jd = unpack_epoch(field(line, 21, 25))
+        .ok_or_else(|| format!("Invalid epoch '{}'", field(line, 21, 25)))?;
+    let elements = HeliocentricElements {
+        epoch_jd,
+        mean_anomaly: parse_field(line, 27, 35, "mean anomaly")?,
+        argument_perihelion: parse_field(line, 38, 46, "argument of perihelion")?,
+        long_ascending: parse_field(line, 49, 57, "ascending node")?,
+        inclination: parse_field(line, 60, 68, "inclination")?,
+        eccentricity: parse_field(line, 71, 79, "eccentricity")?,
+        mean_motion: parse_field(line, 81, 91, "mean motion")?,
+        semi_mayor: parse_field(line, 93, 103, "semi-mayor axis")?,
+    };
+
+    // H and G are blank for some objects, G defaults to 0.15 then
+    let magnitude_h = parse_field(line, 9, 13, "H").unwrap_or(f64::NAN);
+    let slope_g = parse_field(line, 15, 19, "G").unwrap_or(0.15);
+
+    let (mut number, name, mut designation) = split_readable(field(line, 167, 194));
+    if number.is_none() {
+        number = unpack_number(field(line, 1, 7));
+    }
+    if number.is_none() && designation.is_none() && name.is_none() {
+        designation = Some(field(line, 1, 7).to_string());
+    }
+
+    Ok(make_minor_planet(
+        number,
+        name,
+        designation,
+        magnitude_h,
+        slope_g,
+        elements,
+    ))
+}
+
+fn convert_json_record(record: MpcJsonRecord) -> MinorPlanet {
+    let number = record
+        .number
+        .and_then(|n| n.trim_matches(|c| c == '(' || c == ')').parse().ok());
+    make_minor_planet(
+        number,
+        record.name,
+        record.principal_desig,
+        record.h.unwrap_or(f64::NAN),
+        record.g.unwrap_or(0.15),
+        HeliocentricElements {
+            epoch_jd: record.epoch,
+            semi_mayor: record.a,
+            eccentricity: record.e,
+            inclination: record.i,
+            long_ascending: record.node,
+            argument_perihelion: record.peri,
+            mean_anomaly: record.m,
+            mean_motion: record.n,
+        },
+    )
+}
+
+/// Parses the extended JSON export, which is one big array of records
+pub fn parse_mpcorb_json<R: std::io::Read>(reader: R) -> Result<Vec<MinorPlanet>, String> {
+    let records: Vec<MpcJsonRecord> =
+        serde_json::from_reader(reader).map_err(|e| format!("Invalid MPCORB json: {}", e))?;
+    Ok(records.into_iter().map(convert_json_record).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERES: &str = "00001    3.34  0.15 K2555 188.70269   73.27343   80.25221   10.58780  0.0794013  0.21424651   2.7660512  0 E2024-V47  7330 125 1801-2024 0.80 M-v 30k MPCLINUX   4000 (1) Ceres                   20241101";
+    const PROVISIONAL: &str = "K24A00A  25.6  0.15 K2555  10.12345  200.12345  300.12345    5.12345  0.4123456  0.78901234   1.2345678  0 E2024-V47  7330 125 1801-2024 0.80 M-v 30k MPCLINUX   4000 2024 AA                     20241101";
+
+    #[test]
+    fn unpack() {
+        assert_eq!(unpack_number("00001"), Some(1));
+        assert_eq!(unpack_number("A0345"), Some(100345));
+        assert_eq!(unpack_number("a0017"), Some(360017));
+        assert_eq!(unpack_number("~0000"), Some(620000));
+        assert_eq!(unpack_number("~000z"), Some(620061));
+        assert_eq!(unpack_number("K24A00A"), None);
+        assert_eq!(unpack_number("é123"), None);
+        assert_eq!(unpack_number("0é12"), None);
+        assert_eq!(unpack_number("~00é"), None);
+        assert_eq!(unpack_epoch("K2555"), Some(2460800.5));
+        assert_eq!(unpack_epoch("J9611"), Some(julian_date(1996, 1, 1.0)));
+        assert_eq!(unpack_epoch("K25"), None);
+    }
+
+    #[test]
+    fn numbered_line() {
+        let ceres = parse_mpcorb_line(CERES).unwrap();
+        assert_eq!(ceres.number, Some(1));
+        assert_eq!(ceres.name.as_deref(), Some("Ceres"));
+        assert_eq!(ceres.designation, None);
+        assert_eq!(ceres.magnitude_h, 3.34);
+        assert_eq!(ceres.slope_g, 0.15);
+        assert_eq!(ceres.orbit_class, OrbitClass::MainBelt);
+        let e = ceres.elements;
+        assert_eq!(e.epoch_jd, 2460800.5);
+        assert_eq!(e.mean_anomaly, 188.70269);
+        assert_eq!(e.argument_perihelion, 73.27343);
+        assert_eq!(e.long_ascending, 80.25221);
+        assert_eq!(e.inclination, 10.5878);
+        assert_eq!(e.eccentricity, 0.0794013);
+        assert_eq!(e.mean_motion, 0.21424651);
+        assert_eq!(e.semi_mayor, 2.7660512);
+    }
+
+    #[test]
+    fn provisional_line() {
+        let planet = parse_mpcorb_line(PROVISIONAL).unwrap();
+        assert_eq!(planet.number, None);
+        assert_eq!(planet.name, None);
+        assert_eq!(planet.designation.as_deref(), Some("2024 AA"));
+        assert_eq!(planet.orbit_class, OrbitClass::NearEarth);
+        assert_eq!(planet.display_name(), "2024 AA");
+    }
+
+    #[test]
+    fn rejects_header_and_short_lines() {
+        assert!(parse_mpcorb_line(&"-".repeat(202)).is_err());
+        assert!(parse_mpcorb_line(&CERES[..80]).is_err());
+        assert!(parse_mpcorb_line(&CERES.replace("0.0794013", "0.07x4013")).is_err());
+    }
+
+    #[test]
+    fn readable_designations() {
+        for (readable, expected) in [
+            ("(433) Eros", (Some(433), Some("Eros"), None)),
+            ("(3708) 1974 FV1", (Some(3708), None, Some("1974 FV1"))),
+            ("2024 AA", (None, None, Some("2024 AA"))),
+            ("(1)", (Some(1), None, None)),
+            ("(2) ", (Some(2), None, None)),
+            ("(12 Broken", (None, Some("(12 Broken"), None)),
+            ("Čapek", (None, Some("Čapek"), None)),
+            ("19ö4 FV1", (None, Some("19ö4 FV1"), None)),
+            (
+                "(1980) Tezcatlipoca",
+                (Some(1980), Some("Tezcatlipoca"), None),
+            ),
+        ] {
+            let (number, name, designation) = split_readable(readable);
+            assert_eq!(
+                (number, name.as_deref(), designation.as_deref()),
+                expected,
+                "{}",
+                readable
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_non_ascii_lines() {
+        let line = CERES.replace("(1) Ceres ", "(1) Cérès");
+        assert_eq!(
+            parse_mpcorb_line(&line).err().as_deref(),
+            Some("Line is not ASCII")
+        );
+        let line = CERES.replacen("00001", "0000é", 1);
+        assert!(parse_mpcorb_line(&line).is_err());
+    }
+
+    #[test]
+    fn json_records() {
+        let json = r#"[
+            {"Number": "(433)", "Name": "Eros", "Principal_desig": "A898 PA", "H": 10.38, "G": 0.46,
+             "Epoch": 2460800.5, "M": 310.5, "Peri": 178.9, "Node": 304.3, "i": 10.83, "e": 0.2228,
+             "n": 0.5598, "a": 1.4579},
+            {"Principal_desig": "2024 AA", "Epoch": 2460800.5, "M": 10.1, "Peri": 200.1, "Node": 300.1,
+             "i": 5.1, "e": 0.41, "n": 0.789, "a": 1.2346}
+        ]"#;
+        let planets = parse_mpcorb_json(json.as_bytes()).unwrap();
+        assert_eq!(planets.len(), 2);
+        assert_eq!(planets[0].number, Some(433));
+        assert_eq!(planets[0].display_name(), "(433) Eros");
+        assert_eq!(planets[0].slope_g, 0.46);
+        assert_eq!(planets[0].elements.semi_mayor, 1.4579);
+        assert_eq!(planets[1].number, None);
+        assert!(planets[1].magnitude_h.is_nan());
+        assert_eq!(planets[1].slope_g, 0.15);
+        assert!(parse_mpcorb_json("{}".as_bytes()).is_err());
+    }
+}
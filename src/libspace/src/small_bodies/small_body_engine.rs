@@ -0,0 +1,143 @@
+use crate::bodies::two_body::TwoBodyOrbit;
+use crate::coordinate::*;
+use crate::timebase::*;
+use log::{error, warn};
+use std::collections::HashMap;
+use std::ops::Sub;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct SmallBodyUpdate {
+    pub id: usize,
+    pub state: IcrfStateVector,
+}
+
+/// Propagates small bodies on a worker thread, the same way ElementEngine does it for TLEs
+pub struct SmallBodyEngine {
+    update_rx: Receiver<SmallBodyUpdate>,
+    add_tx: Sender<(usize, TwoBodyOrbit)>,
+    remove_tx: Sender<usize>,
+    timebase_tx: Sender<Timebase>,
+}
+
+struct WorkerData {
+    bodies: HashMap<usize, TwoBodyOrbit>,
+    timebase: Timebase,
+    update_tx: Sender<SmallBodyUpdate>,
+    add_rx: Receiver<(usize, TwoBodyOrbit)>,
+    remove_rx: Receiver<usize>,
+    timebase_rx: Receiver<Timebase>,
+}
+
+impl SmallBodyEngine {
+    pub fn new() -> Self {
+        let (update_tx, update_rx) = channel();
+        let (add_tx, add_rx) = channel();
+        let (remove_tx, remove_rx) = channel();
+        let (timebase_tx, timebase_rx) = channel();
+        thread::spawn(|| {
+            let mut data = WorkerData {
+                bodies: HashMap::new(),
+                timebase: Timebase::new(),
+                update_tx,
+                add_rx,
+                remove_rx,
+                timebase_rx,
+            };
+            data.run();
+        });
+
+        Self {
+            update_rx,
+            add_tx,
+            remove_tx,
+            timebase_tx,
+        }
+    }
+
+    pub fn update_timebase(&self, timebase: Timebase) {
+        self.timebase_tx.send(timebase).unwrap();
+    }
+
+    pub fn add(&self, id: usize, orbit: TwoBodyOrbit) {
+        self.add_tx.send((id, orbit)).unwrap();
+    }
+
+    pub fn remove(&self, id: usize) {
+        self.remove_tx.send(id).unwrap();
+    }
+
+    pub fn get_more(&self) -> Option<SmallBodyUpdate> {
+        match self.update_rx.try_recv() {
+            Ok(update) => Some(update),
+            Err(_) => None,
+        }
+    }
+}
+
+impl WorkerData {
+    fn run(&mut self) {
+        loop {
+            if !self.wait_for_new_timebase() {
+                break;
+            }
+
+            while let Ok((id, orbit)) = self.add_rx.try_recv() {
+                self.bodies.insert(id, orbit);
+            }
+            while let Ok(id) = self.remove_rx.try_recv() {
+                self.bodies.remove(&id);
+            }
+            self.update();
+        }
+    }
+
+    fn wait_for_new_timebase(&mut self) -> bool {
+        let mut got_any = false;
+        loop {
+            match self.timebase_rx.try_recv() {
+                Ok(timebase) => {
+                    self.timebase = timebase;
+                    got_any = true;
+                }
+                Err(TryRecvError::Empty) => {
+                    if got_any {
+                        return true;
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        let start = Instant::now();
+        let time = self.timebase.now_jd_j2000();
+        let mut failed = Vec::new();
+        for (id, orbit) in &self.bodies {
+            match orbit.state_icrf_since_j2000(time) {
+                Ok(state) => {
+                    if let Err(e) = self.update_tx.send(SmallBodyUpdate { id: *id, state }) {
+                        error!(
+                            "Something is going very wrong the the small body thread: {}",
+                            e
+                        );
+                    }
+                }
+                Err(e) => {
+                    warn!("Dropping small body {}: {}", id, e);
+                    failed.push(*id);
+                }
+            }
+        }
+        for id in failed {
+            self.bodies.remove(&id);
+        }
+        let sim_time = Instant::now().sub(start);
+        if sim_time > Duration::from_secs_f64(1.0 / 60.0) {
+            warn!("Small body sim took to long. {}ms", sim_time.as_millis())
+        }
+    }
+}
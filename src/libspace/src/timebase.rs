@@ -5,6 +5,7 @@ use std::ffi::{CStr, CString};
 
 use std::os::raw::c_char;
 use std::path::PathBuf;
+use std::sync::Once;
 
 use std::time::*;
 
@@ -19,6 +20,10 @@ pub struct Timebase {
 pub const SPICE_LSK_URL: &'static str =
     "https://naif.jpl.nasa.gov/pub/naif/generic_kernels/lsk/latest_leapseconds.tls";
 pub const SPICE_LSK_FILENAME: &'static str = "latest_leapseconds.tls";
+/// s, TT - UTC since the leap second at the end of 2016
+const TT_MINUS_UTC: f64 = 69.184;
+
+static MISSING_LSK: Once = Once::new();
 
 pub fn date_time_to_spice_str(t: DateTime<Utc>) -> String {
     t.format("%F %T.%f").to_string()
@@ -30,7 +35,22 @@ fn parse_spice_utc(s: &str) -> DateTime<Utc> {
 
 pub fn date_time_to_et(t: DateTime<Utc>) -> f64 {
     let sl = SpiceLock::acquire().expect("mutex poisoned. im sad now");
-    sl.str2et(&date_time_to_spice_str(t))
+    str2et(&sl, t)
+}
+
+/// Spice returning on errors is left failed by str2et without an LSK, which is reset here.
+/// The time is then taken as UTC with today's offset, off by the leap seconds before 2017.
+fn str2et(lock: &SpiceLock, t: DateTime<Utc>) -> f64 {
+    let et = lock.str2et(&date_time_to_spice_str(t));
+    unsafe {
+        if spice::c::failed_c() == 0 {
+            return et;
+        }
+        spice::c::reset_c();
+    }
+    MISSING_LSK.call_once(|| log::error!("Cannot convert UTC to ET, is the LSK loaded?"));
+    let j2000 = NaiveDateTime::parse_from_str("2000-01-01T12:00:00.0", "%FT%T%.f").unwrap();
+    (t.naive_utc() - j2000).num_milliseconds() as f64 / 1000.0 + TT_MINUS_UTC
 }
 
 pub fn now_utc_str() -> String {
@@ -40,7 +60,47 @@ pub fn now_utc_str() -> String {
 
 pub fn now_et() -> f64 {
     let lock = SpiceLock::acquire().unwrap();
-    lock.str2et(&now_utc_str())
+    str2et(&lock, SystemTime::now().into())
+}
+
+/// Julian date of a (proleptic gregorian) calendar date, day may contain a fraction
+pub fn julian_date(year: i32, month: u32, day: f64) -> f64 {
+    let (y, m) = if month <= 2 {
+        (year as f64 - 1.0, month as f64 + 12.0)
+    } else {
+        (year as f64, month as f64)
+    };
+    let a = (y / 100.0).floor();
+    let b = 2.0 - a + (a / 4.0).floor();
+    (365.25 * (y + 4716.0)).floor() + (30.6001 * (m + 1.0)).floor() + day + b - 1524.5
+}
+
+pub fn jd_to_et(jd: f64) -> f64 {
+    (jd - 2451545.0) * 86400.0
+}
+
+pub fn et_to_jd(et: f64) -> f64 {
+    et / 86400.0 + 2451545.0
+}
+
+pub fn et_to_utc(et: f64) -> DateTime<Utc> {
+    let iso_date = unsafe {
+        let lock = SpiceLock::acquire().unwrap();
+        let format_c = CString::new("ISOC").unwrap().into_raw();
+        let mut dst_bytes = Vec::<c_char>::with_capacity(128);
+        spice::c::et2utc_c(
+            et as spice::c::SpiceDouble,
+            format_c as *mut ConstSpiceChar,
+            5,
+            128,
+            dst_bytes.as_mut_ptr() as *mut spice::c::SpiceChar,
+        );
+        drop(CString::from_raw(format_c));
+        let iso_str_c = CStr::from_ptr(dst_bytes.as_ptr());
+        iso_str_c.to_str().unwrap().to_owned()
+    };
+
+    parse_spice_utc(&iso_date)
 }
 
 impl Timebase {
@@ -49,7 +109,7 @@ impl Timebase {
         Self {
             running: true,
             realtime: true,
-            now: lock.str2et(&now_utc_str()),
+            now: str2et(&lock, SystemTime::now().into()),
             acceleration: 1.0,
         }
     }
@@ -59,23 +119,7 @@ impl Timebase {
     }
 
     pub fn now_utc(&self) -> DateTime<Utc> {
-        let iso_date = unsafe {
-            let lock = SpiceLock::acquire().unwrap();
-            let format_c = CString::new("ISOC").unwrap().into_raw();
-            let mut dst_bytes = Vec::<c_char>::with_capacity(128);
-            spice::c::et2utc_c(
-                self.now as spice::c::SpiceDouble,
-                format_c as *mut ConstSpiceChar,
-                5,
-                128,
-                dst_bytes.as_mut_ptr() as *mut spice::c::SpiceChar,
-            );
-            drop(CString::from_raw(format_c));
-            let iso_str_c = CStr::from_ptr(dst_bytes.as_ptr());
-            iso_str_c.to_str().unwrap().to_owned()
-        };
-
-        parse_spice_utc(&iso_date)
+        et_to_utc(self.now)
     }
 
     pub fn now_jd_j2000(&self) -> f64 {
@@ -1,3 +1,6 @@
+#[cfg(test)]
+pub mod test_server;
+
 use std::path::{Path, PathBuf};
 
 pub fn init_dirs() -> Result<PathBuf, String> {
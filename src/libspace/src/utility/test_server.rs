@@ -0,0 +1,92 @@
+// A stand-in for the web services in tests. It answers one connection after the other with canned
+// responses and keeps what it was asked.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+pub struct SeenRequest {
+    /// like "GET /path HTTP/1.1"
+    pub line: String,
+    /// empty if there was none
+    pub cookie: String,
+    pub body: String,
+}
+
+pub type Seen = Arc<Mutex<Vec<SeenRequest>>>;
+
+pub struct Response {
+    pub status: u16,
+    /// whole header lines, like "Set-Cookie: a=b"
+    pub headers: Vec<String>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push(format!("{}: {}", name, value));
+        self
+    }
+}
+
+/// Answers with the responses in order, then stops.
+/// Returns the base url, like http://127.0.0.1:1234, and the requests it got.
+pub fn serve(responses: Vec<Response>) -> (String, Seen) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    std::thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let mut cookie = String::new();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let (name, value) = header.split_once(':').unwrap();
+                match name.trim().to_lowercase().as_str() {
+                    "cookie" => cookie = value.trim().to_string(),
+                    "content-length" => length = value.trim().parse().unwrap(),
+                    _ => {}
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            seen.lock().unwrap().push(SeenRequest {
+                line: line.trim().to_string(),
+                cookie,
+                body: String::from_utf8(body).unwrap(),
+            });
+            let headers: String = response
+                .headers
+                .iter()
+                .map(|h| format!("{}\r\n", h))
+                .collect();
+            write!(
+                stream,
+                "HTTP/1.1 {} X\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.status,
+                headers,
+                response.body.len(),
+                response.body
+            )
+            .unwrap();
+        }
+    });
+    (url, requests)
+}
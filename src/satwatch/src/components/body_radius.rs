@@ -0,0 +1,4 @@
+/// Radius in km of a body drawn with a unit sized shape. The view scales it along with the world,
+/// but never smaller than a marker.
+#[derive(Copy, Clone, Debug)]
+pub struct BodyRadius(pub f64);
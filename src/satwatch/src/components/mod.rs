@@ -1,11 +1,17 @@
+pub use body_radius::*;
 pub use camera::*;
 pub use directional_light::*;
 pub use material::*;
+pub use spice_trail::*;
+pub use trajectory::*;
 pub use transform::*;
 pub use vertex_list::*;
 
+pub mod body_radius;
 pub mod camera;
 pub mod directional_light;
 pub mod material;
+pub mod spice_trail;
+pub mod trajectory;
 pub mod transform;
 pub mod vertex_list;
@@ -0,0 +1,15 @@
+use libspace::bodies::Planet;
+use libspace::kernels::SpiceObject;
+
+/// Goes along with a Trajectory. The view fills in the points, relative to the planet it is centered on,
+/// so the trail lines up with the analytic planet positions.
+#[derive(Clone, Debug)]
+pub struct SpiceTrail {
+    pub object: SpiceObject,
+    /// ET
+    pub from: f64,
+    pub to: f64,
+    pub points: usize,
+    /// planet the points were computed relative to. None means they need to be (re)computed.
+    pub anchor: Option<Planet>,
+}
@@ -0,0 +1,47 @@
+use libspace::bodies::Planet;
+use libspace::coordinate::IcrfStateVector;
+
+/// A line through ICRF points. The view turns it into vertices, relative to the gl origin at that time,
+/// and afterwards only moves it along with the origin.
+#[derive(Clone, Debug)]
+pub struct Trajectory {
+    pub points: Vec<IcrfStateVector>,
+    /// if set, the points are relative to this planet and the line moves along with it
+    pub centre: Option<Planet>,
+    /// if set, the points are relative to the IcrfStateVector of the entity, which places the line,
+    /// and its WorldTransform rotation turns it
+    pub attached: bool,
+    /// gl origin the vertices were generated against. None means they need to be (re)generated.
+    pub origin: Option<IcrfStateVector>,
+}
+
+impl Trajectory {
+    pub fn new(points: Vec<IcrfStateVector>) -> Self {
+        Self {
+            points,
+            centre: None,
+            attached: false,
+            origin: None,
+        }
+    }
+
+    /// Points relative to the planet, in ICRF axes
+    pub fn around(centre: Planet, points: Vec<IcrfStateVector>) -> Self {
+        Self {
+            points,
+            centre: Some(centre),
+            attached: false,
+            origin: None,
+        }
+    }
+
+    /// Points relative to where the entity is, in its own axes
+    pub fn attached(points: Vec<IcrfStateVector>) -> Self {
+        Self {
+            points,
+            centre: None,
+            attached: true,
+            origin: None,
+        }
+    }
+}
@@ -2,6 +2,10 @@ use glam::f32::*;
 use glow::{Buffer, Context, HasContext, VertexArray};
 
 use crate::util::evil_unsafe::u8_slice_from_any;
+use std::sync::Mutex;
+
+/// GL objects of dropped lists. Drop has no context to delete them with, so the renderer does.
+static DROPPED: Mutex<Vec<(VertexArray, Vec<Buffer>)>> = Mutex::new(Vec::new());
 
 #[derive(Debug)]
 pub struct VertexList {
@@ -144,4 +148,32 @@ impl VertexList {
             }
         }
     }
+
+    /// Deletes the GL objects of all lists dropped since the last call
+    pub fn delete_dropped(gl: &Context) {
+        let dropped = match DROPPED.lock() {
+            Ok(mut dropped) => std::mem::take(&mut *dropped),
+            Err(_) => return,
+        };
+        for (vao, buffers) in dropped {
+            unsafe {
+                gl.delete_vertex_array(vao);
+                for buffer in buffers {
+                    gl.delete_buffer(buffer);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for VertexList {
+    fn drop(&mut self) {
+        let buffers = [self._vbo, self._indices, self._normals]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<Buffer>>();
+        if let (Some(vao), Ok(mut dropped)) = (self.vao, DROPPED.lock()) {
+            dropped.push((vao, buffers));
+        }
+    }
 }
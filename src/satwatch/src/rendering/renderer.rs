@@ -29,6 +29,7 @@ impl Renderer {
     }
 
     pub fn draw(&self, gl: &Context, world: &mut World, aspect: f32) {
+        VertexList::delete_dropped(gl);
         let mut cam_query = <(&WorldTransform, &Camera)>::query();
         let mut object_query = <(&WorldTransform, &VertexList, &MaterialComponent)>::query();
         let lights: Vec<DirectionalLight> = Read::<DirectionalLight>::query()
@@ -2,4 +2,5 @@ pub mod asset_file;
 pub mod evil_unsafe;
 pub mod imgui_logger;
 pub mod input_events;
+pub mod orbit_elements_table;
 pub mod vertex_tools;
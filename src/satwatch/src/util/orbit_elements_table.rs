@@ -0,0 +1,98 @@
+use imgui::*;
+use libspace::bodies::osculating_elements::*;
+use libspace::coordinate::{CoordinateUnit, PlanetaryStateVector};
+use libspace::timebase::Timebase;
+
+fn row(ui: &Ui, name: &str, value: String) {
+    ui.text(name);
+    ui.next_column();
+    ui.text(value);
+    ui.next_column();
+}
+
+fn distance(km: f64) -> String {
+    if km.abs() > 1e7 {
+        format!(
+            "{:.6} au",
+            CoordinateUnit::KiloMeter.to(CoordinateUnit::Au, &km)
+        )
+    } else {
+        format!("{:.3} km", km)
+    }
+}
+
+fn duration(seconds: f64) -> String {
+    if seconds > 2.0 * 86400.0 {
+        format!("{:.4} days", seconds / 86400.0)
+    } else {
+        format!("{:.2} min", seconds / 60.0)
+    }
+}
+
+/// Classical and equinoctial osculating elements of the given state, around its planet
+pub fn orbit_elements_table(ui: &Ui, state: &PlanetaryStateVector, timebase: &Timebase) {
+    ui.text(format!(
+        "Osculating elements around {}, referred to the ICRF axes",
+        state.planet
+    ));
+    match OsculatingElements::from_state(state, timebase) {
+        Ok(elements) => {
+            ui.columns(2, "Classical Elements", true);
+            row(ui, "Semi-mayor axis", distance(elements.semi_mayor));
+            row(ui, "Eccentricity", format!("{:.8}", elements.eccentricity));
+            row(
+                ui,
+                "Inclination",
+                format!("{:.6} deg", elements.inclination),
+            );
+            row(
+                ui,
+                "Ascending node",
+                format!("{:.6} deg", elements.long_ascending),
+            );
+            row(
+                ui,
+                "Argument of periapsis",
+                format!("{:.6} deg", elements.argument_periapsis),
+            );
+            row(
+                ui,
+                "True anomaly",
+                format!("{:.6} deg", elements.true_anomaly),
+            );
+            row(
+                ui,
+                "Mean anomaly",
+                format!("{:.6} deg", elements.mean_anomaly()),
+            );
+            row(ui, "Periapsis", distance(elements.periapsis()));
+            if let Some(apoapsis) = elements.apoapsis() {
+                row(ui, "Apoapsis", distance(apoapsis));
+            }
+            if let Some(period) = elements.period() {
+                row(ui, "Period", duration(period));
+            }
+            ui.columns(1, "Classical Elements", false);
+        }
+        Err(e) => ui.text(format!("No classical elements: {}", e)),
+    }
+
+    ui.separator();
+    match EquinoctialElements::from_state(state, timebase) {
+        Ok(elements) => {
+            ui.columns(2, "Equinoctial Elements", true);
+            row(ui, "p", distance(elements.p));
+            row(ui, "f", format!("{:.8}", elements.f));
+            row(ui, "g", format!("{:.8}", elements.g));
+            row(ui, "h", format!("{:.8}", elements.h));
+            row(ui, "k", format!("{:.8}", elements.k));
+            row(
+                ui,
+                "True longitude",
+                format!("{:.6} deg", elements.true_longitude),
+            );
+            ui.columns(1, "Equinoctial Elements", false);
+        }
+        Err(e) => ui.text(format!("No equinoctial elements: {}", e)),
+    }
+}
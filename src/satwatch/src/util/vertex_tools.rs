@@ -1,5 +1,6 @@
 use glam::f32::*;
 
+use libspace::bodies::shape_model::ShapeModel;
 use libspace::bodies::Planet;
 use libspace::coordinate::*;
 use libspace::timebase::Timebase;
@@ -18,7 +19,7 @@ pub fn gen_orbit_points(
         let gl_coord: DVec3 = points.get(i as usize).unwrap().to_icrf(time).to_gl_coord(
             world_scale,
             CoordinateUnit::KiloMeter,
-            &Planet::Earth.orbit().elements_short.position_icrf(time),
+            &Planet::Earth.pos_icrf(time),
         );
         results.push(Vec3::new(
             (gl_coord.x) as f32,
@@ -222,3 +223,16 @@ pub fn gen_icosphere(r: f32, recursion: u32) -> (Vec<Vec3>, Vec<u32>, Vec<Vec3>)
 
     (vertices, indices, normals)
 }
+
+/// Vertices of a shape model in gl axes, divided by `radius` (km) so it can be scaled like a unit sphere
+pub fn gen_shape_model(model: &ShapeModel, radius: f64) -> (Vec<Vec3>, Vec<u32>, Vec<Vec3>) {
+    let to_gl = |v: &DVec3| Vec3::new(v.y as f32, v.z as f32, v.x as f32);
+    let vertices = model
+        .vertices
+        .iter()
+        .map(|v| to_gl(&(*v / radius)))
+        .collect();
+    let indices = model.plates.iter().flatten().copied().collect();
+    let normals = model.vertex_normals().iter().map(to_gl).collect();
+    (vertices, indices, normals)
+}
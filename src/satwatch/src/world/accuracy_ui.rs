@@ -0,0 +1,308 @@
+use crate::util::input_events::Event;
+use crate::world::world_ui::WorldUi;
+use imgui::*;
+use legion::*;
+use libspace::accuracy::*;
+use libspace::bodies::Planet;
+use libspace::elements::ElementDb;
+use libspace::timebase::{et_to_utc, Timebase};
+
+const PLANETS: [Planet; 10] = [
+    Planet::Sun,
+    Planet::Mercury,
+    Planet::Venus,
+    Planet::Earth,
+    Planet::Moon,
+    Planet::Mars,
+    Planet::Jupiter,
+    Planet::Saturn,
+    Planet::Uranus,
+    Planet::Neptune,
+];
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum SourceKind {
+    Analytic,
+    Spice,
+    Sgp4,
+}
+
+/// The form for one side of the comparison
+struct SourceForm {
+    kind: SourceKind,
+    /// index into PLANETS
+    planet: usize,
+    spice_object: String,
+    norad_id: i32,
+}
+
+impl SourceForm {
+    fn new(kind: SourceKind) -> Self {
+        Self {
+            kind,
+            planet: 5,
+            spice_object: "4".to_string(),
+            norad_id: 25544,
+        }
+    }
+
+    fn source(&self) -> Result<EphemerisSource, String> {
+        match self.kind {
+            SourceKind::Analytic => Ok(EphemerisSource::Analytic(PLANETS[self.planet])),
+            SourceKind::Spice => EphemerisSource::spice(&self.spice_object),
+            SourceKind::Sgp4 => ElementDb::new()
+                .get_copy(self.norad_id.max(0) as u64)
+                .map(EphemerisSource::Sgp4)
+                .ok_or_else(|| format!("No elements of {}, fetch them first", self.norad_id)),
+        }
+    }
+
+    fn ui(&mut self, ui: &Ui, label: &str) {
+        let _id_scope = ui.push_id(label);
+        ui.text(label);
+        ui.radio_button("Analytic", &mut self.kind, SourceKind::Analytic);
+        ui.same_line();
+        ui.radio_button("SPICE", &mut self.kind, SourceKind::Spice);
+        ui.same_line();
+        ui.radio_button("SGP4", &mut self.kind, SourceKind::Sgp4);
+        match self.kind {
+            SourceKind::Analytic => {
+                let names = PLANETS
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>();
+                ui.combo_simple_string("Body", &mut self.planet, &names);
+            }
+            SourceKind::Spice => {
+                ui.input_text("NAIF id or name", &mut self.spice_object)
+                    .build();
+            }
+            SourceKind::Sgp4 => {
+                ui.input_int("NORAD id", &mut self.norad_id).build();
+            }
+        }
+    }
+}
+
+pub struct AccuracyUi {
+    visible: bool,
+    tested: SourceForm,
+    reference: SourceForm,
+    /// index into PLANETS
+    center: usize,
+    days_before: f64,
+    days_after: f64,
+    steps: i32,
+    report: Option<ComparisonReport>,
+    comparison: Option<EphemerisComparison>,
+    last_error: Option<String>,
+    now: f64,
+}
+
+impl AccuracyUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            tested: SourceForm::new(SourceKind::Analytic),
+            reference: SourceForm::new(SourceKind::Spice),
+            center: 0,
+            days_before: 365.0,
+            days_after: 365.0,
+            steps: 500,
+            report: None,
+            comparison: None,
+            last_error: None,
+            now: 0.0,
+        }
+    }
+
+    fn compare(&mut self) -> Result<EphemerisComparison, String> {
+        let tested = self.tested.source()?;
+        let reference = self.reference.source()?;
+        Ok(EphemerisComparison::start(
+            tested,
+            reference,
+            PLANETS[self.center],
+            self.now - self.days_before.max(0.0) * 86400.0,
+            self.now + self.days_after.max(0.0) * 86400.0,
+            self.steps.max(2) as usize,
+        ))
+    }
+
+    fn update_comparison(&mut self) {
+        let comparison = match &mut self.comparison {
+            Some(comparison) => comparison,
+            None => return,
+        };
+        comparison.update_status();
+        match comparison.take_result() {
+            Some(Ok(report)) => {
+                log::info!("{}", report.summary());
+                self.report = Some(report);
+                self.last_error = None;
+                self.comparison = None;
+            }
+            Some(Err(e)) => {
+                self.last_error = Some(e);
+                self.comparison = None;
+            }
+            None => {
+                if let ComparisonStatus::Cancelled = comparison.get_status() {
+                    self.comparison = None;
+                }
+            }
+        }
+    }
+
+    fn report_ui(ui: &Ui, report: &ComparisonReport) {
+        ui.text(format!(
+            "{} against {}, relative to {}",
+            report.tested, report.reference, report.center
+        ));
+        ui.text(format!(
+            "{} samples from {} to {}, {} skipped",
+            report.samples.len(),
+            format_et(report.samples.first().map(|s| s.time).unwrap_or(0.0)),
+            format_et(report.samples.last().map(|s| s.time).unwrap_or(0.0)),
+            report.skipped
+        ));
+
+        ui.columns(3, "Error Statistics", true);
+        ui.text("Error");
+        ui.next_column();
+        ui.text("Max");
+        ui.next_column();
+        ui.text("RMS");
+        ui.next_column();
+        ui.separator();
+        for (name, statistics, unit) in [
+            ("Position", &report.position, "km"),
+            ("Velocity", &report.velocity, "km/s"),
+            ("Radial", &report.radial, "km"),
+            ("Along track", &report.along, "km"),
+            ("Cross track", &report.cross, "km"),
+        ] {
+            ui.text(name);
+            ui.next_column();
+            ui.text(format!("{:.6} {}", statistics.max, unit));
+            ui.next_column();
+            ui.text(format!("{:.6} {}", statistics.rms, unit));
+            ui.next_column();
+        }
+        ui.columns(1, "", false);
+        ui.separator();
+
+        let width = ui.content_region_avail()[0];
+        for (name, values) in [
+            ("Position (km)", report.series(|s| s.position_error)),
+            ("Velocity (km/s)", report.series(|s| s.velocity_error)),
+            ("Radial (km)", report.series(|s| s.radial)),
+            ("Along track (km)", report.series(|s| s.along)),
+            ("Cross track (km)", report.series(|s| s.cross)),
+        ] {
+            ui.text(name);
+            ui.plot_lines(format!("##{}", name), &values)
+                .graph_size([width, 80.0])
+                .build();
+        }
+    }
+}
+
+fn format_et(et: f64) -> String {
+    et_to_utc(et).format("%F").to_string()
+}
+
+impl WorldUi for AccuracyUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Ephemeris Accuracy") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, _gl: &glow::Context, _world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        self.update_comparison();
+        if !self.visible {
+            return Ok(());
+        }
+
+        let mut do_compare = false;
+        let mut copy_summary = false;
+        let mut visible = self.visible;
+        ui.window("Ephemeris Accuracy")
+            .opened(&mut visible)
+            .size([550.0, 700.0], Condition::Appearing)
+            .build(|| {
+                self.tested.ui(ui, "Tested");
+                ui.separator();
+                self.reference.ui(ui, "Reference");
+                ui.separator();
+                let names = PLANETS
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>();
+                ui.combo_simple_string("Relative to", &mut self.center, &names);
+                ui.input_scalar("Days before now", &mut self.days_before)
+                    .build();
+                ui.input_scalar("Days after now", &mut self.days_after)
+                    .build();
+                ui.input_int("Samples", &mut self.steps).build();
+                match &self.comparison {
+                    Some(comparison) => {
+                        if let ComparisonStatus::Progress { done, total } = comparison.get_status()
+                        {
+                            ui.text(format!("Comparing... {} of {}", done, total));
+                        } else {
+                            ui.text("Comparing...");
+                        }
+                        ui.same_line();
+                        if ui.button("Cancel") {
+                            comparison.cancel();
+                        }
+                    }
+                    None => do_compare = ui.button("Compare"),
+                }
+                if self.report.is_some() {
+                    ui.same_line();
+                    if ui.button("Copy summary") {
+                        copy_summary = true;
+                    }
+                }
+                if let Some(e) = &self.last_error {
+                    ui.text_wrapped(e);
+                }
+                ui.separator();
+
+                if let Some(report) = &self.report {
+                    Self::report_ui(ui, report);
+                }
+            });
+        self.visible = visible;
+
+        if do_compare {
+            match self.compare() {
+                Ok(comparison) => self.comparison = Some(comparison),
+                Err(e) => self.last_error = Some(e),
+            }
+        }
+        if copy_summary {
+            if let Some(report) = &self.report {
+                ui.set_clipboard_text(report.summary());
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        _world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.now = timebase.now();
+        Ok(())
+    }
+}
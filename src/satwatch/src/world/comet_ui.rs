@@ -0,0 +1,257 @@
+use crate::components::{MaterialComponent, Trajectory, VertexList, WorldTransform};
+use crate::download::{DownloadStatus, Downloader};
+use crate::util::input_events::Event;
+use crate::util::vertex_tools::gen_icosphere;
+use crate::world::world_ui::WorldUi;
+use imgui::*;
+use legion::*;
+use libspace::coordinate::{CoordinateUnit, IcrfStateVector};
+use libspace::small_bodies::*;
+use libspace::timebase::Timebase;
+use std::collections::HashMap;
+use std::fs::File;
+
+/// Open orbits are drawn out to this distance from the sun, in au
+const TRAJECTORY_MAX_DISTANCE: f64 = 50.0;
+const TRAJECTORY_POINTS: usize = 512;
+/// each comet brings a trajectory, the whole catalogue would be too much to draw
+const MAX_ADD_ALL: usize = 500;
+
+struct TrackedComet {
+    marker: Entity,
+    trajectory: Entity,
+}
+
+pub struct CometUi {
+    visible: bool,
+    comets: Vec<Comet>,
+    download: Option<Downloader>,
+    search_term: String,
+    only_unbound: bool,
+    engine: SmallBodyEngine,
+    tracked_items: HashMap<usize, TrackedComet>,
+}
+
+fn load_comets() -> Vec<Comet> {
+    match File::open(comet_els_file()) {
+        Ok(file) => match parse_comet_els(file) {
+            Ok(comets) => {
+                log::info!("Loaded {} comets", comets.len());
+                comets
+            }
+            Err(e) => {
+                log::error!("Reading comet elements failed: {}", e);
+                Vec::new()
+            }
+        },
+        Err(_) => Vec::new(),
+    }
+}
+
+impl CometUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            comets: load_comets(),
+            download: None,
+            search_term: String::new(),
+            only_unbound: false,
+            engine: SmallBodyEngine::new(),
+            tracked_items: HashMap::new(),
+        }
+    }
+
+    fn add_new(&mut self, gl: &glow::Context, world: &mut World, id: usize) -> Result<(), String> {
+        if self.tracked_items.contains_key(&id) {
+            return Ok(());
+        }
+
+        if let Some(comet) = self.comets.get(id) {
+            let (verts, index, normal) = gen_icosphere(0.01, 2);
+            let marker = world.push((
+                IcrfStateVector {
+                    unit: CoordinateUnit::Au,
+                    position: Default::default(),
+                    velocity: Default::default(),
+                },
+                WorldTransform::default(),
+                VertexList::create_triangles(gl, &verts, Some(&index), Some(&normal))?,
+                MaterialComponent("material/sats.toml".to_string()),
+            ));
+            let trajectory = world.push((
+                Trajectory::new(
+                    comet
+                        .elements
+                        .trajectory(TRAJECTORY_POINTS, TRAJECTORY_MAX_DISTANCE),
+                ),
+                WorldTransform::default(),
+                VertexList::create_lines(gl, &[], None, None)?,
+                MaterialComponent("material/colored_orbit.toml".to_string()),
+            ));
+            self.tracked_items
+                .insert(id, TrackedComet { marker, trajectory });
+            self.engine.add(id, comet.elements.to_two_body());
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, world: &mut World, id: usize) {
+        if let Some(item) = self.tracked_items.remove(&id) {
+            world.remove(item.marker);
+            world.remove(item.trajectory);
+            self.engine.remove(id);
+        }
+    }
+
+    fn update_download(&mut self, world: &mut World) {
+        if let Some(download) = self.download.as_mut() {
+            download.update_status();
+            match download.get_status() {
+                DownloadStatus::FileComplete(_) => {
+                    log::info!("Comet elements download complete");
+                    self.download = None;
+                    // indices change with new elements, so nothing tracked survives this
+                    for id in self.tracked_items.keys().copied().collect::<Vec<usize>>() {
+                        self.remove(world, id);
+                    }
+                    self.comets = load_comets();
+                }
+                DownloadStatus::Error(e) => {
+                    log::error!("Comet elements download failed: {}", e);
+                    self.download = None;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl WorldUi for CometUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Comet Database") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        self.update_download(world);
+
+        let mut to_add = Vec::new();
+        let mut to_remove = Vec::new();
+
+        if self.visible {
+            ui.window("Comet Database")
+                .opened(&mut self.visible)
+                .size([400.0, 400.0], Condition::Appearing)
+                .build(|| {
+                    if self.download.is_some() {
+                        ui.text("Downloading comet elements...");
+                    } else if ui.button("Update from MPC") {
+                        self.download = Some(Downloader::download_to_file(
+                            COMET_ELS_URL,
+                            comet_els_file(),
+                        ));
+                    }
+                    ui.separator();
+                    ui.input_text("Search Comets", &mut self.search_term)
+                        .build();
+                    ui.same_line();
+                    ui.checkbox("Only e >= 1", &mut self.only_unbound);
+
+                    let term = self.search_term.to_uppercase();
+                    let items = self
+                        .comets
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| !self.only_unbound || !c.elements.is_bound())
+                        .filter(|(_, c)| {
+                            term.is_empty() || c.designation.to_uppercase().contains(&term)
+                        })
+                        .map(|(id, _)| id)
+                        .collect::<Vec<usize>>();
+
+                    ui.text(format!("Found {} comets.", items.len()));
+                    ui.same_line();
+                    if items.len() <= MAX_ADD_ALL {
+                        if ui.button("Add all results") {
+                            to_add.extend(items.iter().copied());
+                        }
+                        ui.same_line();
+                    }
+                    if ui.button("Remove All") {
+                        to_remove.extend(self.tracked_items.keys());
+                    }
+
+                    ui.child_window("Comet Table")
+                        .always_vertical_scrollbar(true)
+                        .build(|| {
+                            ui.columns(4, "Comet Table", true);
+                            ui.text("Selected");
+                            ui.next_column();
+                            ui.text("Designation");
+                            ui.next_column();
+                            ui.text("q [au]");
+                            ui.next_column();
+                            ui.text("e");
+                            ui.next_column();
+                            for id in items {
+                                let _id_scope = ui.push_id_usize(id);
+                                let comet = &self.comets[id];
+                                let mut tracked = self.tracked_items.contains_key(&id);
+                                if ui.checkbox("Select", &mut tracked) {
+                                    if tracked {
+                                        to_add.push(id);
+                                    } else {
+                                        to_remove.push(id);
+                                    }
+                                }
+                                ui.next_column();
+                                ui.text(&comet.designation);
+                                ui.next_column();
+                                ui.text(format!("{:.4}", comet.elements.perihelion_distance));
+                                ui.next_column();
+                                ui.text(format!("{:.6}", comet.elements.eccentricity));
+                                ui.next_column();
+                            }
+                        });
+                });
+        }
+
+        for remove in to_remove {
+            self.remove(world, remove);
+        }
+
+        for add in to_add {
+            self.add_new(gl, world, add)
+                .unwrap_or_else(|e| log::error!("Adding comet failed: {}", e));
+        }
+
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.engine.update_timebase(timebase.clone());
+
+        while let Some(update) = self.engine.get_more() {
+            if let Some(item) = self.tracked_items.get(&update.id) {
+                if let Ok(mut entry) = world.entry_mut(item.marker) {
+                    if let Ok(state_vec) = entry.get_component_mut::<IcrfStateVector>() {
+                        *state_vec = update.state;
+                    }
+                }
+            } else {
+                self.engine.remove(update.id);
+            }
+        }
+        Ok(())
+    }
+}
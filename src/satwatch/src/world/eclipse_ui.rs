@@ -0,0 +1,339 @@
+use crate::components::{MaterialComponent, Trajectory, VertexList, WorldTransform};
+use crate::util::input_events::Event;
+use crate::world::world_ui::WorldUi;
+use chrono::{Datelike, Utc};
+use imgui::*;
+use legion::*;
+use libspace::bodies::eclipse::*;
+use libspace::bodies::Planet;
+use libspace::coordinate::{CoordinateUnit, IcrfStateVector};
+use libspace::timebase::{et_to_utc, julian_date, Timebase};
+
+/// km, a bit beyond the moon
+const CONE_LENGTH: f64 = 420000.0;
+const CONE_SEGMENTS: usize = 32;
+/// days. In between the cones only move along with their casters, they turn by about a degree a day
+const CONE_REFRESH: f64 = 1.0 / 24.0;
+
+/// Casting body and umbra (false) or penumbra (true) for each drawn cone
+const CONES: [(Planet, bool); 4] = [
+    (Planet::Moon, false),
+    (Planet::Moon, true),
+    (Planet::Earth, false),
+    (Planet::Earth, true),
+];
+
+pub struct EclipseUi {
+    visible: bool,
+    from_year: i32,
+    years: i32,
+    show_solar: bool,
+    show_lunar: bool,
+    results: Vec<Eclipse>,
+    search: Option<EclipseSearch>,
+    selected: Option<usize>,
+    cones: Vec<Entity>,
+    /// when the cone points were made
+    cones_time: Option<f64>,
+    jump_to: Option<f64>,
+}
+
+impl EclipseUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            from_year: Utc::now().year(),
+            years: 2,
+            show_solar: true,
+            show_lunar: true,
+            results: Vec::new(),
+            search: None,
+            selected: None,
+            cones: Vec::new(),
+            cones_time: None,
+            jump_to: None,
+        }
+    }
+
+    fn search(&mut self) {
+        let from = julian_date(self.from_year, 1, 1.0) - 2451545.0;
+        let to = julian_date(self.from_year + self.years.max(1), 1, 1.0) - 2451545.0;
+        self.search = Some(EclipseSearch::start(from, to));
+    }
+
+    fn update_search(&mut self) {
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return,
+        };
+        search.update_status();
+        if let Some(results) = search.take_result() {
+            self.results = results
+                .into_iter()
+                .filter(|e| match e.kind {
+                    EclipseKind::Solar => self.show_solar,
+                    EclipseKind::Lunar => self.show_lunar,
+                })
+                .collect();
+            self.selected = None;
+            self.search = None;
+            log::info!("Found {} eclipses", self.results.len());
+        } else if let EclipseSearchStatus::Cancelled = search.get_status() {
+            self.search = None;
+        }
+    }
+
+    fn add_cones(&mut self, gl: &glow::Context, world: &mut World) -> Result<(), String> {
+        for cone in CONES {
+            self.cones.push(world.push((
+                Trajectory::around(cone.0, Vec::new()),
+                WorldTransform::default(),
+                VertexList::create_lines(gl, &[], None, None)?,
+                MaterialComponent("material/colored_orbit.toml".to_string()),
+            )));
+        }
+        Ok(())
+    }
+
+    fn remove_cones(&mut self, world: &mut World) {
+        for entity in self.cones.drain(..) {
+            world.remove(entity);
+        }
+        self.cones_time = None;
+    }
+
+    fn details_ui(&mut self, ui: &Ui, id: usize) {
+        let eclipse = &self.results[id];
+        ui.text(format!(
+            "{} {} eclipse, gamma {:.4}, magnitude {:.4}",
+            eclipse.eclipse_type, eclipse.kind, eclipse.gamma, eclipse.magnitude
+        ));
+        let contacts = [
+            ("P1", Some(eclipse.contacts.p1)),
+            ("U1", eclipse.contacts.u1),
+            ("U2", eclipse.contacts.u2),
+            ("Greatest", Some(eclipse.greatest)),
+            ("U3", eclipse.contacts.u3),
+            ("U4", eclipse.contacts.u4),
+            ("P4", Some(eclipse.contacts.p4)),
+        ];
+        for (i, (name, time)) in contacts.iter().enumerate() {
+            if let Some(time) = time {
+                let _id_scope = ui.push_id_usize(i);
+                ui.text(format!("{:>8}: {}", name, format_time(*time)));
+                ui.same_line();
+                if ui.small_button("Jump to") {
+                    self.jump_to = Some(*time * 86400.0);
+                }
+            }
+        }
+
+        if eclipse.path.is_empty() {
+            return;
+        }
+        ui.separator();
+        ui.text("Center line");
+        ui.child_window("Eclipse Path")
+            .size([0.0, 150.0])
+            .always_vertical_scrollbar(true)
+            .build(|| {
+                ui.columns(4, "Eclipse Path Table", true);
+                ui.text("Time (UTC)");
+                ui.next_column();
+                ui.text("Latitude");
+                ui.next_column();
+                ui.text("Longitude");
+                ui.next_column();
+                ui.text("Width (km)");
+                ui.next_column();
+                let mut clipper = ListClipper::new(eclipse.path.len() as i32).begin(ui);
+                while clipper.step() {
+                    for row in clipper.display_start()..clipper.display_end() {
+                        let point = &eclipse.path[row as usize];
+                        ui.text(format_time(point.time));
+                        ui.next_column();
+                        ui.text(format!("{:.2}", point.latitude));
+                        ui.next_column();
+                        ui.text(format!("{:.2}", point.longitude));
+                        ui.next_column();
+                        ui.text(format!("{:.0}", point.width));
+                        ui.next_column();
+                    }
+                }
+            });
+    }
+}
+
+/// days since J2000 to a UTC string
+fn format_time(time: f64) -> String {
+    et_to_utc(time * 86400.0).format("%F %H:%M:%S").to_string()
+}
+
+fn duration(eclipse: &Eclipse) -> String {
+    let minutes = |a: f64, b: f64| (b - a) * 1440.0;
+    let total = minutes(eclipse.contacts.p1, eclipse.contacts.p4);
+    match (eclipse.contacts.u2, eclipse.contacts.u3) {
+        (Some(u2), Some(u3)) if eclipse.kind == EclipseKind::Lunar => {
+            format!("{:.0} min, totality {:.0} min", total, minutes(u2, u3))
+        }
+        _ => format!("{:.0} min", total),
+    }
+}
+
+impl WorldUi for EclipseUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Eclipses") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        self.update_search();
+        if !self.visible {
+            return Ok(());
+        }
+
+        let mut do_search = false;
+        let mut show_cones = !self.cones.is_empty();
+        let mut toggle_cones = false;
+        let mut visible = self.visible;
+        ui.window("Eclipses")
+            .opened(&mut visible)
+            .size([600.0, 500.0], Condition::Appearing)
+            .build(|| {
+                ui.input_int("From year", &mut self.from_year).build();
+                ui.input_int("Years", &mut self.years).build();
+                ui.checkbox("Solar", &mut self.show_solar);
+                ui.same_line();
+                ui.checkbox("Lunar", &mut self.show_lunar);
+                match self.search.as_ref().map(|s| s.get_status()) {
+                    Some(EclipseSearchStatus::Progress { done, total }) => {
+                        ui.text(format!("Searching... {} of {} years", done, total));
+                    }
+                    Some(_) => ui.text("Searching..."),
+                    None => do_search = ui.button("Search"),
+                }
+                if let Some(search) = &self.search {
+                    ui.same_line();
+                    if ui.button("Cancel") {
+                        search.cancel();
+                    }
+                }
+                ui.same_line();
+                toggle_cones = ui.checkbox("Draw shadow cones", &mut show_cones);
+                ui.text(
+                    "Times are from the approximate elements, expect a minute or two of error.",
+                );
+                ui.separator();
+
+                ui.child_window("Eclipse Table")
+                    .size([0.0, 200.0])
+                    .always_vertical_scrollbar(true)
+                    .build(|| {
+                        ui.columns(6, "Eclipse Table", true);
+                        ui.text("Greatest (UTC)");
+                        ui.next_column();
+                        ui.text("Kind");
+                        ui.next_column();
+                        ui.text("Type");
+                        ui.next_column();
+                        ui.text("Magnitude");
+                        ui.next_column();
+                        ui.text("Duration");
+                        ui.next_column();
+                        ui.next_column();
+                        let mut clipper = ListClipper::new(self.results.len() as i32).begin(ui);
+                        while clipper.step() {
+                            for row in clipper.display_start()..clipper.display_end() {
+                                let _id_scope = ui.push_id_usize(row as usize);
+                                let eclipse = &self.results[row as usize];
+                                if ui
+                                    .selectable_config(format_time(eclipse.greatest))
+                                    .selected(self.selected == Some(row as usize))
+                                    .build()
+                                {
+                                    self.selected = Some(row as usize);
+                                }
+                                ui.next_column();
+                                ui.text(eclipse.kind.to_string());
+                                ui.next_column();
+                                ui.text(eclipse.eclipse_type.to_string());
+                                ui.next_column();
+                                ui.text(format!("{:.3}", eclipse.magnitude));
+                                ui.next_column();
+                                ui.text(duration(eclipse));
+                                ui.next_column();
+                                if ui.button("Jump to") {
+                                    self.jump_to = Some(eclipse.greatest * 86400.0);
+                                }
+                                ui.next_column();
+                            }
+                        }
+                    });
+
+                if let Some(id) = self.selected {
+                    ui.separator();
+                    self.details_ui(ui, id);
+                }
+            });
+        self.visible = visible;
+
+        if do_search {
+            self.search();
+        }
+        if toggle_cones {
+            if show_cones {
+                self.add_cones(gl, world)?;
+            } else {
+                self.remove_cones(world);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        if let Some(et) = self.jump_to.take() {
+            timebase.set_realtime(false);
+            timebase.set_now(et);
+        }
+
+        let now = timebase.now_jd_j2000();
+        let refresh = self
+            .cones_time
+            .map_or(true, |time| (now - time).abs() > CONE_REFRESH);
+        if !refresh || self.cones.is_empty() {
+            return Ok(());
+        }
+        self.cones_time = Some(now);
+        for (entity, (caster, penumbra)) in self.cones.iter().zip(CONES) {
+            if let Ok(mut entry) = world.entry_mut(*entity) {
+                if let Ok(trajectory) = entry.get_component_mut::<Trajectory>() {
+                    let centre = caster
+                        .pos_icrf(timebase)
+                        .as_unit(CoordinateUnit::KiloMeter)
+                        .position;
+                    trajectory.points =
+                        shadow_cone(caster, penumbra, CONE_LENGTH, CONE_SEGMENTS, timebase)
+                            .into_iter()
+                            .map(|p| IcrfStateVector {
+                                position: p.as_unit(CoordinateUnit::KiloMeter).position - centre,
+                                unit: CoordinateUnit::KiloMeter,
+                                ..p
+                            })
+                            .collect();
+                    trajectory.origin = None;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use crate::components::{MaterialComponent, VertexList, WorldTransform};
 use crate::util::input_events::Event;
+use crate::util::orbit_elements_table::orbit_elements_table;
 use crate::util::vertex_tools::gen_icosphere;
 use crate::world::world_ui::WorldUi;
+use chrono::{NaiveDate, TimeZone, Utc};
 use imgui::*;
 use legion::*;
 use libspace::bodies::Planet;
@@ -10,6 +12,11 @@ use libspace::elements::*;
 use libspace::timebase::Timebase;
 use log::error;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+
+/// seconds between automatic updates, if a source keeps failing
+const AUTO_UPDATE_RETRY: i64 = 600;
 
 pub struct DbUi {
     visible: bool,
@@ -17,8 +24,98 @@ pub struct DbUi {
     db: ElementDb,
     search_term: String,
     search_exact: bool,
+    /// ranked, only searched again when the term or the elements change
+    search_results: Vec<u64>,
+    search_dirty: bool,
+    query_text: String,
+    /// parsed from query_text, None if it is empty or broken
+    query: Option<ElementQuery>,
+    query_error: String,
+    /// what the table lists: the search results or all, through the SATCAT filter and the query.
+    /// Only filtered again when one of them or the elements change.
+    shown: Vec<u64>,
+    shown_dirty: bool,
+    /// the SATCAT filter shown was filtered with
+    shown_filter: SatcatFilter,
     engine: ElementEngine,
     tracked_items: HashMap<u64, Entity>,
+    inspected: Option<u64>,
+    timebase: Timebase,
+    fetch: Option<ElementFetch>,
+    /// download even if the sources say nothing changed
+    force_update: bool,
+    registry: SourceRegistry,
+    sources_visible: bool,
+    /// fetch sources once their refresh interval passed
+    auto_update: bool,
+    /// unix time, failing sources are not retried every frame
+    last_auto_update: i64,
+    new_source: ElementSource,
+    space_track: SpaceTrackForm,
+    import: ImportForm,
+    export: ExportForm,
+    maneuvers: ManeuverForm,
+    satcat: SatcatForm,
+}
+
+struct SatcatForm {
+    download: Option<Receiver<Result<Satcat, String>>>,
+    path: String,
+    status: String,
+    filter: SatcatFilter,
+    /// combo indices, 0 is any
+    object_type: usize,
+    ops_status: usize,
+    rcs_size: usize,
+}
+
+struct ManeuverForm {
+    visible: bool,
+    settings: DetectionSettings,
+    scan: Option<Receiver<Vec<ElementEvent>>>,
+    /// fleet wide, newest first
+    events: Vec<ElementEvent>,
+    maneuvers_only: bool,
+    /// events of the inspected sat, with the id and history length they were found for
+    inspected: Option<(u64, usize, DetectionSettings, Vec<ElementEvent>)>,
+}
+
+struct ImportForm {
+    visible: bool,
+    path: String,
+    /// tag of the imported sets, the file name if empty
+    source: String,
+    priority: i32,
+    status: String,
+    errors: Vec<TleError>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ExportFormat {
+    Tle { three_line: bool },
+    Omm(OmmFormat),
+}
+
+struct ExportForm {
+    visible: bool,
+    path: String,
+    format: ExportFormat,
+    status: String,
+}
+
+struct SpaceTrackForm {
+    visible: bool,
+    credentials: SpaceTrackCredentials,
+    remember: bool,
+    /// kept to reuse the session, dropped when the credentials change
+    client: Option<SpaceTrackClient>,
+    class: GpClass,
+    ids: String,
+    use_epoch: bool,
+    from: String,
+    to: String,
+    query: Option<Receiver<GpResult>>,
+    status: String,
 }
 
 impl DbUi {
@@ -29,8 +126,57 @@ impl DbUi {
             db: ElementDb::new(),
             search_term: String::new(),
             search_exact: false,
+            search_results: Vec::new(),
+            search_dirty: false,
+            query_text: String::new(),
+            query: None,
+            query_error: String::new(),
+            shown: Vec::new(),
+            shown_dirty: true,
+            shown_filter: SatcatFilter::default(),
             engine: ElementEngine::new(),
             tracked_items: HashMap::new(),
+            inspected: None,
+            timebase: Timebase::new(),
+            fetch: None,
+            force_update: false,
+            registry: SourceRegistry::load(),
+            sources_visible: false,
+            auto_update: false,
+            last_auto_update: 0,
+            new_source: ElementSource::new("", "", ElementFormat::Json, 0),
+            space_track: SpaceTrackForm::new(),
+            import: ImportForm {
+                visible: false,
+                path: String::new(),
+                source: String::new(),
+                priority: 0,
+                status: String::new(),
+                errors: Vec::new(),
+            },
+            export: ExportForm {
+                visible: false,
+                path: String::new(),
+                format: ExportFormat::Tle { three_line: true },
+                status: String::new(),
+            },
+            maneuvers: ManeuverForm {
+                visible: false,
+                settings: DetectionSettings::default(),
+                scan: None,
+                events: Vec::new(),
+                maneuvers_only: false,
+                inspected: None,
+            },
+            satcat: SatcatForm {
+                download: None,
+                path: String::new(),
+                status: String::new(),
+                filter: SatcatFilter::default(),
+                object_type: 0,
+                ops_status: 0,
+                rcs_size: 0,
+            },
         }
     }
 
@@ -39,7 +185,7 @@ impl DbUi {
             return Ok(());
         }
 
-        if let Some(elements) = self.db.get(id) {
+        if self.db.get(id).is_some() {
             let (verts, index, normal) = gen_icosphere(0.005, 2);
             let entity = world.push((
                 PlanetaryStateVector {
@@ -54,7 +200,7 @@ impl DbUi {
                 MaterialComponent("material/sats.toml".to_string()),
             ));
             self.tracked_items.insert(id, entity);
-            self.engine.add(elements);
+            self.engine.add_history(self.db.history(id));
         }
         Ok(())
     }
@@ -66,6 +212,933 @@ impl DbUi {
             self.engine.remove(id);
         }
     }
+
+    /// Hands the current history of the tracked sats to the engine, after the elements changed
+    fn refresh_tracked(&mut self) {
+        self.search_dirty = true;
+        for id in self.tracked_items.keys() {
+            self.engine.add_history(self.db.history(*id));
+        }
+    }
+
+    fn update_search(&mut self) {
+        self.search_dirty = false;
+        self.shown_dirty = true;
+        self.search_results = if self.search_term.trim().is_empty() {
+            Vec::new()
+        } else {
+            self.db
+                .index()
+                .find_str(&self.search_term, self.search_exact)
+        };
+    }
+
+    fn update_query(&mut self) {
+        self.shown_dirty = true;
+        if self.query_text.trim().is_empty() {
+            self.query = None;
+            self.query_error.clear();
+            return;
+        }
+        match ElementQuery::parse(&self.query_text) {
+            Ok(query) => {
+                self.query = Some(query);
+                self.query_error.clear();
+            }
+            Err(e) => {
+                self.query = None;
+                self.query_error = e;
+            }
+        }
+    }
+
+    fn update_shown(&mut self) {
+        self.shown_dirty = false;
+        self.shown_filter = self.satcat.filter.clone();
+        let items = if self.search_term.trim().is_empty() {
+            self.db.index().get_by(self.order)
+        } else {
+            &self.search_results
+        };
+        self.shown = items
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.satcat.filter.matches(self.db.satcat(*id))
+                    && self.query.as_ref().map_or(true, |query| {
+                        self.db.get(*id).map_or(false, |e| query.matches(e))
+                    })
+            })
+            .collect();
+    }
+
+    fn start_fetch(&mut self, all: bool) {
+        let fetch = self.db.start_fetch(&self.registry, all, self.force_update);
+        self.fetch = Some(fetch);
+    }
+
+    /// A Space-Track query is running, its elements are merged when it is done
+    fn merge_pending(&self) -> bool {
+        self.space_track.query.is_some()
+    }
+
+    fn update_fetch(&mut self) {
+        let fetch = match self.fetch.as_mut() {
+            Some(fetch) => fetch,
+            None => {
+                let now = Utc::now().timestamp();
+                // a fetch works on a copy of the elements, so it waits for manual merges
+                if self.auto_update
+                    && !self.merge_pending()
+                    && now - self.last_auto_update > AUTO_UPDATE_RETRY
+                    && !self.registry.to_fetch(false, now).is_empty()
+                {
+                    self.last_auto_update = now;
+                    self.start_fetch(false);
+                }
+                return;
+            }
+        };
+        fetch.update_status();
+        if let Some(mut fetched) = fetch.take_result() {
+            for result in fetched.results.iter() {
+                match &result.outcome {
+                    SourceOutcome::Updated { fetched, taken, .. } => log::info!(
+                        "Fetched {} elements from {}, {} taken",
+                        fetched,
+                        result.source,
+                        taken
+                    ),
+                    SourceOutcome::NotModified => {
+                        log::info!("Elements of {} did not change", result.source)
+                    }
+                    SourceOutcome::Failed(e) => {
+                        error!("Something went wrong updating {}: {}", result.source, e)
+                    }
+                }
+            }
+            self.registry
+                .record(&fetched.results, Utc::now().timestamp());
+            self.registry.save();
+            self.db.swap_in(&mut fetched);
+            self.refresh_tracked();
+            self.fetch = None;
+        } else if let FetchStatus::Cancelled = fetch.get_status() {
+            log::info!("Element update cancelled");
+            self.db.fetch_cancelled();
+            self.fetch = None;
+        }
+    }
+
+    fn fetch_ui(&mut self, ui: &Ui) {
+        match &self.fetch {
+            Some(fetch) => {
+                match fetch.get_status() {
+                    FetchStatus::Progress {
+                        source,
+                        bytes_read,
+                        bytes_total: Some(bytes_total),
+                    } => ProgressBar::new(*bytes_read as f32 / (*bytes_total).max(1) as f32)
+                        .overlay_text(format!("Downloading {}", source))
+                        .build(ui),
+                    FetchStatus::Progress {
+                        source, bytes_read, ..
+                    } => ui.text(format!(
+                        "Downloading {}, {:.1} MB",
+                        source,
+                        *bytes_read as f64 / 1e6
+                    )),
+                    FetchStatus::Connecting(source) => {
+                        ui.text(format!("Connecting to {}...", source))
+                    }
+                    FetchStatus::Indexing => ui.text("Indexing elements..."),
+                    _ => ui.text("Updating..."),
+                }
+                if ui.button("Cancel") {
+                    fetch.cancel();
+                }
+            }
+            None => {
+                if ui.button("Full update all") {
+                    self.start_fetch(true);
+                }
+                ui.same_line();
+                if ui.button("Update due") {
+                    self.start_fetch(false);
+                }
+                ui.same_line();
+                if ui.button("Sources") {
+                    self.sources_visible = true;
+                }
+                ui.same_line();
+                if ui.button("Space-Track") {
+                    self.space_track.visible = true;
+                }
+                ui.same_line();
+                if ui.button("Maneuvers") {
+                    self.maneuvers.visible = true;
+                }
+                ui.checkbox("Even if unchanged", &mut self.force_update);
+                ui.same_line();
+                ui.checkbox("Auto update", &mut self.auto_update);
+            }
+        }
+    }
+
+    fn sources_ui(&mut self, ui: &Ui) {
+        if !self.sources_visible {
+            return;
+        }
+
+        let mut to_remove = None;
+        let mut do_add = false;
+        let mut changed = false;
+        let mut visible = self.sources_visible;
+        ui.window("Element Sources")
+            .opened(&mut visible)
+            .size([600.0, 400.0], Condition::Appearing)
+            .build(|| {
+                ui.text("Higher priority wins, the newer epoch wins within the same priority.");
+                ui.columns(6, "Source Table", true);
+                ui.text("On");
+                ui.next_column();
+                ui.text("Name");
+                ui.next_column();
+                ui.text("Priority");
+                ui.next_column();
+                ui.text("Refresh (h)");
+                ui.next_column();
+                ui.text("Last fetch");
+                ui.next_column();
+                ui.next_column();
+                ui.separator();
+                for (i, source) in self.registry.sources.iter_mut().enumerate() {
+                    let _id_scope = ui.push_id_usize(i);
+                    changed |= ui.checkbox("##enabled", &mut source.enabled);
+                    ui.next_column();
+                    ui.text(&source.name);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(format!("{}\n{}", source.url(), source.format));
+                    }
+                    ui.next_column();
+                    changed |= ui.input_int("##priority", &mut source.priority).build();
+                    ui.next_column();
+                    changed |= ui
+                        .input_scalar("##refresh", &mut source.refresh_interval)
+                        .build();
+                    ui.next_column();
+                    match source
+                        .last_fetch
+                        .and_then(|t| Utc.timestamp_opt(t, 0).single())
+                    {
+                        Some(t) => ui.text(t.format("%F %R").to_string()),
+                        None => ui.text("never"),
+                    }
+                    ui.next_column();
+                    if ui.small_button("Remove") {
+                        to_remove = Some(source.name.clone());
+                    }
+                    ui.next_column();
+                }
+                ui.columns(1, "", false);
+                ui.separator();
+
+                ui.text("New source");
+                ui.input_text("Name", &mut self.new_source.name).build();
+                ui.input_text("URL", &mut self.new_source.url).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("{format} is replaced by json, tle, xml, kvn or csv");
+                }
+                ui.radio_button("OMM json", &mut self.new_source.format, ElementFormat::Json);
+                ui.same_line();
+                ui.radio_button("TLE", &mut self.new_source.format, ElementFormat::Tle);
+                ui.same_line();
+                ui.radio_button("OMM XML", &mut self.new_source.format, ElementFormat::Xml);
+                ui.same_line();
+                ui.radio_button("OMM KVN", &mut self.new_source.format, ElementFormat::Kvn);
+                ui.same_line();
+                ui.radio_button("OMM CSV", &mut self.new_source.format, ElementFormat::Csv);
+                ui.input_int("Priority", &mut self.new_source.priority)
+                    .build();
+                ui.input_scalar(
+                    "Refresh interval (h)",
+                    &mut self.new_source.refresh_interval,
+                )
+                .build();
+                if ui.button("Add source") {
+                    do_add = true;
+                }
+            });
+        self.sources_visible = visible;
+
+        if let Some(name) = to_remove {
+            self.registry.remove(&name);
+            changed = true;
+        }
+        if do_add {
+            let mut source = self.new_source.clone();
+            source.name = source.name.trim().to_string();
+            source.url = source.url.trim().to_string();
+            match self.registry.add(source) {
+                Ok(()) => {
+                    self.new_source = ElementSource::new("", "", ElementFormat::Json, 0);
+                    changed = true;
+                }
+                Err(e) => error!("Cannot add source: {}", e),
+            }
+        }
+        if changed {
+            self.registry.save();
+        }
+    }
+
+    fn update_satcat(&mut self) {
+        let result = match self.satcat.download.as_ref().map(|rx| rx.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(std::sync::mpsc::TryRecvError::Empty)) | None => return,
+            Some(Err(std::sync::mpsc::TryRecvError::Disconnected)) => {
+                Err("SATCAT download died".to_string())
+            }
+        };
+        self.satcat.download = None;
+        self.satcat.status = match result {
+            Ok(satcat) => {
+                let report = satcat.report();
+                self.db.set_satcat(satcat);
+                self.shown_dirty = true;
+                format!("Got {}", report)
+            }
+            Err(e) => {
+                error!("{}", e);
+                e
+            }
+        };
+    }
+
+    /// Downloading the catalogue and what of it to show
+    fn satcat_ui(&mut self, ui: &Ui) {
+        if !ui.collapsing_header(
+            format!("SATCAT, {} entries###SATCAT", self.db.satcat_len()),
+            TreeNodeFlags::empty(),
+        ) {
+            return;
+        }
+
+        let form = &mut self.satcat;
+        let mut do_import = false;
+        if form.download.is_some() {
+            ui.text("Downloading the SATCAT...");
+        } else if ui.button("Download from Celestrak") {
+            form.download = Some(Satcat::fetch_in_background(CELESTRAK_SATCAT_CSV));
+            form.status.clear();
+        }
+        ui.input_text("SATCAT file", &mut form.path).build();
+        if ui.is_item_hovered() {
+            ui.tooltip_text("CSV or JSON as Celestrak serves it");
+        }
+        ui.same_line();
+        if ui.button("Import") {
+            do_import = true;
+        }
+        ui.text_wrapped(&form.status);
+
+        let mut type_names = vec!["Any".to_string()];
+        type_names.extend(ObjectType::ALL.iter().map(|t| t.to_string()));
+        ui.combo_simple_string("Type", &mut form.object_type, &type_names);
+        let mut status_names = vec!["Any".to_string()];
+        status_names.extend(OpsStatus::ALL.iter().map(|s| s.to_string()));
+        ui.combo_simple_string("Status", &mut form.ops_status, &status_names);
+        let mut size_names = vec!["Any".to_string()];
+        size_names.extend(RcsSize::ALL.iter().map(|s| s.to_string()));
+        ui.combo_simple_string("RCS size", &mut form.rcs_size, &size_names);
+        ui.input_text("Owner", &mut form.filter.owner).build();
+        ui.input_text("Launch site", &mut form.filter.launch_site)
+            .build();
+        let mut from_year = form.filter.launched_from.unwrap_or(0);
+        ui.input_int("Launched from", &mut from_year).build();
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Year, 0 for any");
+        }
+        let mut to_year = form.filter.launched_to.unwrap_or(0);
+        ui.input_int("Launched until", &mut to_year).build();
+        if ui.is_item_hovered() {
+            ui.tooltip_text("Year, 0 for any");
+        }
+        ui.checkbox("Hide decayed", &mut form.filter.hide_decayed);
+        if ui.button("Clear filter") {
+            *form = SatcatForm {
+                download: form.download.take(),
+                path: std::mem::take(&mut form.path),
+                status: std::mem::take(&mut form.status),
+                filter: SatcatFilter::default(),
+                object_type: 0,
+                ops_status: 0,
+                rcs_size: 0,
+            };
+        } else {
+            form.filter.launched_from = Some(from_year).filter(|y| *y != 0);
+            form.filter.launched_to = Some(to_year).filter(|y| *y != 0);
+            form.filter.object_type = form
+                .object_type
+                .checked_sub(1)
+                .and_then(|i| ObjectType::ALL.get(i).copied());
+            form.filter.ops_status = form
+                .ops_status
+                .checked_sub(1)
+                .and_then(|i| OpsStatus::ALL.get(i).copied());
+            form.filter.rcs_size = form
+                .rcs_size
+                .checked_sub(1)
+                .and_then(|i| RcsSize::ALL.get(i).copied());
+        }
+
+        if do_import {
+            form.status = match self.db.import_satcat(&PathBuf::from(form.path.trim())) {
+                Ok(report) => {
+                    self.shown_dirty = true;
+                    format!("Imported {}", report)
+                }
+                Err(e) => e,
+            };
+        }
+    }
+
+    fn update_space_track(&mut self) {
+        let result = match self.space_track.query.as_ref().map(|rx| rx.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(std::sync::mpsc::TryRecvError::Empty)) | None => return,
+            Some(Err(std::sync::mpsc::TryRecvError::Disconnected)) => {
+                Err("Space-Track query died".to_string())
+            }
+        };
+        self.space_track.query = None;
+        self.space_track.status = match result {
+            Ok(elements) => {
+                let fetched = elements.len();
+                // merge remembers what it took while a fetch runs, the swap in keeps it
+                let taken = self.db.merge(elements, SPACE_TRACK_SOURCE, &self.registry);
+                self.refresh_tracked();
+                format!("Got {} element sets, {} taken", fetched, taken)
+            }
+            Err(e) => {
+                error!("{}", e);
+                e
+            }
+        };
+    }
+
+    fn space_track_ui(&mut self, ui: &Ui) {
+        if !self.space_track.visible {
+            return;
+        }
+
+        let form = &mut self.space_track;
+        let mut do_query = false;
+        let mut do_forget = false;
+        let mut visible = form.visible;
+        ui.window("Space-Track")
+            .opened(&mut visible)
+            .size([400.0, 330.0], Condition::Appearing)
+            .build(|| {
+                let mut changed = ui
+                    .input_text("User", &mut form.credentials.identity)
+                    .build();
+                changed |= ui
+                    .input_text("Password", &mut form.credentials.password)
+                    .password(true)
+                    .build();
+                if changed {
+                    form.client = None;
+                }
+                ui.checkbox("Remember", &mut form.remember);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Stored in plain text in the satwatch data dir");
+                }
+                ui.same_line();
+                if ui.button("Forget stored") {
+                    do_forget = true;
+                }
+                ui.separator();
+
+                ui.radio_button("Latest", &mut form.class, GpClass::Gp);
+                ui.same_line();
+                ui.radio_button("History", &mut form.class, GpClass::GpHistory);
+                ui.input_text("NORAD ids", &mut form.ids).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Separated by commas, empty for all objects");
+                }
+                ui.checkbox("Epoch range", &mut form.use_epoch);
+                if form.use_epoch {
+                    ui.input_text("From", &mut form.from).build();
+                    ui.input_text("To", &mut form.to).build();
+                }
+                ui.text(
+                    "Elements are merged as source \"Space-Track\", with its priority in Sources.",
+                );
+                if form.query.is_some() {
+                    ui.text("Querying Space-Track...");
+                } else if ui.button("Query") {
+                    do_query = true;
+                }
+                ui.text_wrapped(&form.status);
+            });
+        form.visible = visible;
+
+        if do_forget {
+            match SpaceTrackCredentials::forget() {
+                Ok(()) => form.status = "Stored credentials removed".to_string(),
+                Err(e) => error!("Cannot remove Space-Track credentials: {}", e),
+            }
+        }
+        if do_query {
+            match form.build_query() {
+                Ok(query) => {
+                    if form.remember {
+                        if let Err(e) = form.credentials.save() {
+                            error!("Cannot store Space-Track credentials: {}", e);
+                        }
+                    }
+                    let client = form
+                        .client
+                        .get_or_insert_with(|| SpaceTrackClient::new(form.credentials.clone()));
+                    form.query = Some(client.gp_in_background(query));
+                    form.status.clear();
+                }
+                Err(e) => form.status = e,
+            }
+        }
+    }
+
+    fn import_ui(&mut self, ui: &Ui) {
+        if !self.import.visible {
+            return;
+        }
+
+        let form = &mut self.import;
+        let mut do_import = false;
+        let mut visible = form.visible;
+        ui.window("Import Elements")
+            .opened(&mut visible)
+            .size([400.0, 300.0], Condition::Appearing)
+            .build(|| {
+                ui.input_text("File", &mut form.path).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("OMM if it ends in .xml, .kvn or .csv, else TLE/3LE");
+                }
+                ui.input_text("Source", &mut form.source).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Tag of the imported elements, the file name if empty");
+                }
+                ui.input_int("Priority", &mut form.priority).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Only used if the source is new, see Sources");
+                }
+                if ui.button("Import") {
+                    do_import = true;
+                }
+                ui.text_wrapped(&form.status);
+                if !form.errors.is_empty() {
+                    ui.child_window("Import Errors").build(|| {
+                        for error in form.errors.iter() {
+                            ui.text(error.to_string());
+                        }
+                    });
+                }
+            });
+        form.visible = visible;
+
+        if !do_import {
+            return;
+        }
+        let path = PathBuf::from(form.path.trim());
+        let source = match form.source.trim() {
+            "" => path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| form.path.trim().to_string()),
+            source => source.to_string(),
+        };
+        if self.registry.get(&source).is_none() {
+            if let Err(e) = self
+                .registry
+                .add(ElementSource::file(&source, &path, form.priority))
+            {
+                form.status = e;
+                return;
+            }
+            self.registry.save();
+        }
+        match self.db.import_file(&path, &source, &self.registry) {
+            Ok(import) => {
+                form.status = format!(
+                    "Read {} element sets as {}, {} taken, {} problems",
+                    import.read,
+                    source,
+                    import.taken,
+                    import.errors.len()
+                );
+                form.errors = import.errors;
+                self.refresh_tracked();
+            }
+            Err(e) => {
+                form.status = e;
+                form.errors.clear();
+            }
+        }
+    }
+
+    fn export_ui(&mut self, ui: &Ui) {
+        if !self.export.visible {
+            return;
+        }
+
+        let form = &mut self.export;
+        let mut do_export = false;
+        let mut visible = form.visible;
+        let selected = self.tracked_items.len();
+        ui.window("Export Elements")
+            .opened(&mut visible)
+            .size([480.0, 150.0], Condition::Appearing)
+            .build(|| {
+                ui.text(format!("{} selected sats", selected));
+                ui.input_text("File", &mut form.path).build();
+                ui.radio_button(
+                    "3LE",
+                    &mut form.format,
+                    ExportFormat::Tle { three_line: true },
+                );
+                ui.same_line();
+                ui.radio_button(
+                    "2LE",
+                    &mut form.format,
+                    ExportFormat::Tle { three_line: false },
+                );
+                ui.same_line();
+                ui.radio_button(
+                    "OMM XML",
+                    &mut form.format,
+                    ExportFormat::Omm(OmmFormat::Xml),
+                );
+                ui.same_line();
+                ui.radio_button(
+                    "OMM KVN",
+                    &mut form.format,
+                    ExportFormat::Omm(OmmFormat::Kvn),
+                );
+                ui.same_line();
+                ui.radio_button(
+                    "OMM CSV",
+                    &mut form.format,
+                    ExportFormat::Omm(OmmFormat::Csv),
+                );
+                if ui.button("Export") {
+                    do_export = true;
+                }
+                ui.text_wrapped(&form.status);
+            });
+        form.visible = visible;
+
+        if do_export {
+            let mut ids = self.tracked_items.keys().copied().collect::<Vec<u64>>();
+            ids.sort_unstable();
+            let path = PathBuf::from(form.path.trim());
+            let written = match form.format {
+                ExportFormat::Tle { three_line } => self.db.export_tle(&path, &ids, three_line),
+                ExportFormat::Omm(format) => self.db.export_omm(&path, &ids, format, "SATWATCH"),
+            };
+            form.status = match written {
+                Ok(count) => format!("Wrote {} element sets to {}", count, path.display()),
+                Err(e) => e,
+            };
+        }
+    }
+
+    fn start_maneuver_scan(&mut self, selected_only: bool) {
+        let histories = self
+            .db
+            .all()
+            .keys()
+            .filter(|id| !selected_only || self.tracked_items.contains_key(id))
+            .map(|id| self.db.history(*id))
+            .filter(|history| history.len() > 1)
+            .map(|history| history.iter().map(ElementSet::copy).collect())
+            .collect();
+        self.maneuvers.scan = Some(detect_fleet_events_in_background(
+            histories,
+            self.maneuvers.settings,
+        ));
+    }
+
+    fn maneuver_ui(&mut self, ui: &Ui) {
+        if let Some(Ok(events)) = self.maneuvers.scan.as_ref().map(|rx| rx.try_recv()) {
+            log::info!("Found {} maneuvers and anomalies", events.len());
+            self.maneuvers.events = events;
+            self.maneuvers.scan = None;
+        }
+        if !self.maneuvers.visible {
+            return;
+        }
+
+        let mut scan = None;
+        let mut inspect = None;
+        let form = &mut self.maneuvers;
+        let mut visible = form.visible;
+        ui.window("Maneuvers and Anomalies")
+            .opened(&mut visible)
+            .size([600.0, 400.0], Condition::Appearing)
+            .build(|| {
+                ui.text("Thresholds for the difference to the predicted drift");
+                ui.input_scalar("Semi-major axis (km)", &mut form.settings.semi_major_axis)
+                    .build();
+                ui.input_scalar("Inclination (deg)", &mut form.settings.inclination)
+                    .build();
+                ui.input_scalar("RAAN (deg)", &mut form.settings.raan)
+                    .build();
+                ui.input_scalar("B*, dn/dt factor", &mut form.settings.drag_factor)
+                    .build();
+                ui.input_scalar("Max gap (days)", &mut form.settings.max_gap_days)
+                    .build();
+                if form.scan.is_some() {
+                    ui.text("Scanning element history...");
+                } else {
+                    if ui.button("Scan all") {
+                        scan = Some(false);
+                    }
+                    ui.same_line();
+                    if ui.button("Scan selected") {
+                        scan = Some(true);
+                    }
+                }
+                ui.same_line();
+                ui.checkbox("Maneuvers only", &mut form.maneuvers_only);
+                ui.separator();
+
+                ui.child_window("Event Feed").build(|| {
+                    ui.columns(5, "Event Table", true);
+                    ui.text("Epoch (est.)");
+                    ui.next_column();
+                    ui.text("Object");
+                    ui.next_column();
+                    ui.text("Changed");
+                    ui.next_column();
+                    ui.text("Delta-v (m/s)");
+                    ui.next_column();
+                    ui.text("Source");
+                    ui.next_column();
+                    ui.separator();
+                    for (i, event) in form
+                        .events
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, e)| !form.maneuvers_only || e.is_maneuver())
+                    {
+                        let _id_scope = ui.push_id_usize(i);
+                        ui.text(event.epoch.format("%F %R").to_string());
+                        ui.next_column();
+                        let name = event
+                            .object_name
+                            .clone()
+                            .unwrap_or_else(|| event.norad_id.to_string());
+                        if ui.selectable(name) {
+                            inspect = Some(event.norad_id);
+                        }
+                        ui.next_column();
+                        ui.text(event.kinds_text());
+                        ui.next_column();
+                        ui.text(format!("{:.2}", event.delta_v));
+                        ui.next_column();
+                        ui.text(&event.source);
+                        ui.next_column();
+                    }
+                    ui.columns(1, "", false);
+                });
+            });
+        form.visible = visible;
+
+        if let Some(selected_only) = scan {
+            self.start_maneuver_scan(selected_only);
+        }
+        if inspect.is_some() {
+            self.inspected = inspect;
+        }
+    }
+
+    /// Events of one sat, only looked for again when its history or the settings change
+    fn inspected_events(&mut self, id: u64) -> &[ElementEvent] {
+        let history = self.db.history(id);
+        let settings = self.maneuvers.settings;
+        let current = matches!(
+            &self.maneuvers.inspected,
+            Some((i, len, s, _)) if *i == id && *len == history.len() && *s == settings
+        );
+        if !current {
+            self.maneuvers.inspected = Some((
+                id,
+                history.len(),
+                settings,
+                detect_events(history, &settings),
+            ));
+        }
+        match &self.maneuvers.inspected {
+            Some((_, _, _, events)) => events,
+            None => &[],
+        }
+    }
+
+    fn inspection_ui(&mut self, world: &mut World, ui: &Ui) {
+        let id = match self.inspected {
+            Some(id) => id,
+            None => return,
+        };
+
+        let mut open = true;
+        let name = self
+            .db
+            .get_copy(id)
+            .and_then(|e| e.object_name)
+            .unwrap_or_else(|| id.to_string());
+        let events = self.inspected_events(id).to_vec();
+        ui.window(format!("Orbital Elements: {}###Orbital Elements", name))
+            .opened(&mut open)
+            .size([350.0, 350.0], Condition::Appearing)
+            .build(|| {
+                ui.text(format!(
+                    "Source: {}",
+                    self.db.source_of(id).unwrap_or("unknown")
+                ));
+                if let Some(entry) = self.db.satcat(id) {
+                    ui.text(format!(
+                        "{} of {}, {}, {}",
+                        entry.object_type, entry.owner, entry.object_id, entry.ops_status
+                    ));
+                    ui.text(format!(
+                        "Launched {} from {}",
+                        date_text(entry.launch_date),
+                        entry.launch_site
+                    ));
+                    if let Some(decay) = entry.decay_date {
+                        ui.text(format!("Decayed {}", decay));
+                    }
+                    if let Some(rcs) = entry.rcs {
+                        ui.text(format!("RCS {:.4} m²", rcs));
+                    }
+                }
+                let now = self.timebase.now_utc().naive_utc();
+                let history = self.db.history(id);
+                let used = self.db.closest(id, now);
+                if let Some(set) = used {
+                    ui.text(format!(
+                        "Propagating the set of {} from {}",
+                        set.elements.datetime.format("%F %T"),
+                        set.source
+                    ));
+                    ui.text("Age:");
+                    ui.same_line();
+                    age_text(ui, element_age_days(&set.elements, now));
+                }
+                if ui.collapsing_header(
+                    format!("History, {} sets###History", history.len()),
+                    TreeNodeFlags::empty(),
+                ) {
+                    for set in history.iter().rev() {
+                        let marker = match used {
+                            Some(used) if std::ptr::eq(used, set) => ">",
+                            _ => " ",
+                        };
+                        ui.text(format!(
+                            "{} {}  {}",
+                            marker,
+                            set.elements.datetime.format("%F %T"),
+                            set.source
+                        ));
+                    }
+                }
+                if ui.collapsing_header(
+                    format!("Maneuvers and anomalies, {}###Events", events.len()),
+                    TreeNodeFlags::empty(),
+                ) {
+                    for event in events.iter().rev() {
+                        ui.text(format!(
+                            "{}  {}, {:.2} m/s",
+                            event.epoch.format("%F %R"),
+                            event.kinds_text(),
+                            event.delta_v
+                        ));
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text(format!(
+                                "Between the sets of {} and {}\nSemi-major axis {:+.3} km\nInclination {:+.4} deg\nRAAN {:+.4} deg",
+                                event.before.format("%F %R"),
+                                event.after.format("%F %R"),
+                                event.delta_semi_major_axis,
+                                event.delta_inclination,
+                                event.delta_raan
+                            ));
+                        }
+                    }
+                }
+                ui.separator();
+                let state = self
+                    .tracked_items
+                    .get(&id)
+                    .and_then(|entity| world.entry_mut(*entity).ok())
+                    .and_then(|entry| entry.get_component::<PlanetaryStateVector>().ok().copied());
+                match state {
+                    Some(state) => orbit_elements_table(ui, &state, &self.timebase),
+                    None => ui.text("Select the satellite to propagate it first."),
+                }
+            });
+        if !open {
+            self.inspected = None;
+        }
+    }
+}
+
+impl SpaceTrackForm {
+    fn new() -> Self {
+        let stored = SpaceTrackCredentials::load();
+        let today = Utc::now().date_naive();
+        Self {
+            visible: false,
+            remember: stored.is_some(),
+            credentials: stored.unwrap_or_default(),
+            client: None,
+            class: GpClass::Gp,
+            ids: String::new(),
+            use_epoch: false,
+            from: (today - chrono::Duration::days(30)).to_string(),
+            to: today.to_string(),
+            query: None,
+            status: String::new(),
+        }
+    }
+
+    fn build_query(&self) -> Result<GpQuery, String> {
+        let norad_ids = self
+            .ids
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<u64>()
+                    .map_err(|_| format!("Invalid NORAD id: {}", s))
+            })
+            .collect::<Result<Vec<u64>, String>>()?;
+        let epoch = if self.use_epoch {
+            let parse = |s: &str| {
+                NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+                    .map_err(|_| format!("Invalid date, expected YYYY-MM-DD: {}", s))
+            };
+            Some((parse(&self.from)?, parse(&self.to)?))
+        } else {
+            None
+        };
+        let query = GpQuery {
+            class: self.class,
+            norad_ids,
+            epoch,
+        };
+        query.validate()?;
+        Ok(query)
+    }
 }
 
 impl WorldUi for DbUi {
@@ -78,37 +1151,55 @@ impl WorldUi for DbUi {
     }
 
     fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
-        let serach_results = if self.search_term.is_empty() {
-            Vec::new()
-        } else {
-            self.db
-                .index()
-                .find_str(&self.search_term, self.search_exact)
-        };
+        self.update_fetch();
+        self.update_space_track();
+        self.update_satcat();
         let mut to_add = Vec::new();
         let mut to_remove = Vec::new();
 
         if self.visible {
+            let mut visible = self.visible;
             ui.window("TLE Database")
-                .opened(&mut self.visible)
-                .size([300.0, 300.0], Condition::Appearing)
+                .opened(&mut visible)
+                .size([800.0, 400.0], Condition::Appearing)
                 .build(|| {
-                    if ui.button("Full update all") {
-                        if let Err(e) = self.db.fetch_full_celestrak() {
-                            error!("Something went wrong during update: {}", e);
-                        } else {
-                            self.db.save();
-                        }
+                    if let Some(e) = self.db.storage_error() {
+                        ui.text_colored(
+                            [1.0, 0.3, 0.3, 1.0],
+                            format!("Elements are not saved: {}", e),
+                        );
                     }
+                    self.fetch_ui(ui);
+                    self.satcat_ui(ui);
                     ui.separator();
-                    ui.input_text("Search Sats", &mut self.search_term).build();
+                    self.search_dirty |=
+                        ui.input_text("Search Sats", &mut self.search_term).build();
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Name, part of it, NORAD id or COSPAR id");
+                    }
                     ui.same_line();
-                    ui.checkbox("Exact Match", &mut self.search_exact);
-                    let items = if self.search_term.is_empty() {
-                        self.db.index().get_by(self.order)
-                    } else {
-                        &serach_results
-                    };
+                    self.search_dirty |= ui.checkbox("Exact Match", &mut self.search_exact);
+                    if self.search_dirty {
+                        self.update_search();
+                    }
+                    if ui.input_text("Query", &mut self.query_text).build() {
+                        self.update_query();
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(format!(
+                            "Like: regime leo and inc 50..60 and (name /^STARLINK/ or norad < 30000)\n\
+                             Fields: {}\n\
+                             Numbers take a..b, a.., ..b, < > = or a value, and/or/not combine",
+                            QUERY_FIELDS
+                        ));
+                    }
+                    if !self.query_error.is_empty() {
+                        ui.text_colored([1.0, 0.3, 0.3, 1.0], &self.query_error);
+                    }
+                    if self.shown_dirty || self.shown_filter != self.satcat.filter {
+                        self.update_shown();
+                    }
+                    let items = &self.shown;
 
                     ui.text(format!("Found {} sats.", items.len()));
                     ui.same_line();
@@ -119,19 +1210,38 @@ impl WorldUi for DbUi {
                     if ui.button("Remove All") {
                         to_remove.extend(self.tracked_items.keys());
                     }
+                    if ui.button("Import file...") {
+                        self.import.visible = true;
+                    }
+                    ui.same_line();
+                    if ui.button("Export selected...") {
+                        self.export.visible = true;
+                    }
 
                     ui.child_window("Element Table")
                         .always_vertical_scrollbar(true)
                         .build(|| {
-                            ui.columns(4, "Elements Table", true);
+                            let now = self.timebase.now_utc().naive_utc();
+                            ui.columns(10, "Elements Table", true);
                             ui.text("Selected");
                             ui.next_column();
                             ui.text("Name");
                             ui.next_column();
                             ui.text("NORAD Id");
                             ui.next_column();
-                            ui.text("?");
+                            ui.text("Age");
+                            if ui.is_item_hovered() {
+                                ui.tooltip_text(format!(
+                                    "Of the set closest to the current time, stale after {} days",
+                                    STALE_AGE_DAYS
+                                ));
+                            }
                             ui.next_column();
+                            for header in ["Type", "Owner", "Launch", "Decay", "Status", "RCS (m²)"]
+                            {
+                                ui.text(header);
+                                ui.next_column();
+                            }
                             for id in items {
                                 let _id_scope = ui.push_id_usize(*id as usize);
                                 if let Some(elements) = self.db.get_copy(*id) {
@@ -144,20 +1254,35 @@ impl WorldUi for DbUi {
                                         }
                                     }
                                     ui.next_column();
-                                    if let Some(e_name) = &elements.object_name {
-                                        ui.text(e_name);
+                                    let e_name = elements
+                                        .object_name
+                                        .clone()
+                                        .unwrap_or_else(|| elements.norad_id.to_string());
+                                    if ui.selectable(e_name) {
+                                        self.inspected = Some(*id);
                                     }
                                     ui.next_column();
                                     ui.text(format!("{}", elements.norad_id));
                                     ui.next_column();
-                                    ui.text("TBA");
+                                    if let Some(set) = self.db.closest(*id, now) {
+                                        age_text(ui, element_age_days(&set.elements, now));
+                                    }
                                     ui.next_column();
+                                    satcat_columns(ui, self.db.satcat(*id));
                                 }
                             }
                         });
                 });
+            self.visible = visible;
         }
 
+        self.inspection_ui(world, ui);
+        self.sources_ui(ui);
+        self.space_track_ui(ui);
+        self.maneuver_ui(ui);
+        self.import_ui(ui);
+        self.export_ui(ui);
+
         for remove in to_remove {
             self.remove(world, remove);
         }
@@ -179,6 +1304,7 @@ impl WorldUi for DbUi {
         timebase: &mut Timebase,
     ) -> Result<(), String> {
         self.engine.update_timebase(timebase.clone());
+        self.timebase = timebase.clone();
 
         while let Some(update) = self.engine.get_more() {
             if let Some(entity) = self.tracked_items.get(&update.id) {
@@ -195,6 +1321,58 @@ impl WorldUi for DbUi {
     }
 }
 
+/// Days, highlighted when stale
+fn age_text(ui: &Ui, age: f64) {
+    let text = if age.abs() < 1.0 {
+        format!("{:.1} h", age * 24.0)
+    } else {
+        format!("{:.1} d", age)
+    };
+    if age.abs() > STALE_AGE_DAYS {
+        ui.text_colored([1.0, 0.6, 0.0, 1.0], format!("{} stale", text));
+    } else {
+        ui.text(text);
+    }
+}
+
+/// Type, owner, launch, decay, status and RCS, empty if the SATCAT does not know the object
+fn satcat_columns(ui: &Ui, entry: Option<&SatcatEntry>) {
+    match entry {
+        Some(entry) => {
+            ui.text(entry.object_type.to_string());
+            ui.next_column();
+            ui.text(&entry.owner);
+            ui.next_column();
+            ui.text(date_text(entry.launch_date));
+            if ui.is_item_hovered() {
+                ui.tooltip_text(&entry.launch_site);
+            }
+            ui.next_column();
+            ui.text(date_text(entry.decay_date));
+            ui.next_column();
+            ui.text(entry.ops_status.to_string());
+            ui.next_column();
+            match (entry.rcs, entry.rcs_size) {
+                (Some(rcs), _) => ui.text(format!("{:.3}", rcs)),
+                (None, Some(size)) => ui.text(size.to_string()),
+                (None, None) => ui.text("-"),
+            }
+            ui.next_column();
+        }
+        None => {
+            for _ in 0..6 {
+                ui.text("-");
+                ui.next_column();
+            }
+        }
+    }
+}
+
+fn date_text(date: Option<NaiveDate>) -> String {
+    date.map(|d| d.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
 pub struct SelectionChanges {
     pub added: Vec<u64>,
     pub removed: Vec<u64>,
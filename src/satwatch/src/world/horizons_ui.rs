@@ -0,0 +1,370 @@
+use crate::components::{MaterialComponent, Trajectory, VertexList, WorldTransform};
+use crate::util::input_events::Event;
+use crate::util::vertex_tools::gen_icosphere;
+use crate::world::world_ui::WorldUi;
+use imgui::*;
+use legion::*;
+use libspace::bodies::Planet;
+use libspace::coordinate::{IcrfStateVector, PlanetaryStateVector};
+use libspace::nasa_horizons::*;
+use libspace::timebase::{et_to_utc, Timebase};
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+/// most points drawn for a trail, longer tables are thinned out
+const TRAIL_POINTS: usize = 2000;
+
+const CENTERS: [Planet; 10] = [
+    Planet::Sun,
+    Planet::Mercury,
+    Planet::Venus,
+    Planet::Earth,
+    Planet::Moon,
+    Planet::Mars,
+    Planet::Jupiter,
+    Planet::Saturn,
+    Planet::Uranus,
+    Planet::Neptune,
+];
+
+struct HorizonsObject {
+    table: VectorTable,
+    marker: Entity,
+    trail: Entity,
+}
+
+pub struct HorizonsUi {
+    visible: bool,
+    client: HorizonsClient,
+    objects: Vec<HorizonsObject>,
+    observer_tables: Vec<ObserverTable>,
+    shown_observer_table: Option<usize>,
+    pending: Option<(HorizonsRequest, Receiver<Result<HorizonsTable, String>>)>,
+    last_error: Option<String>,
+    timebase: Timebase,
+    // request form
+    target: String,
+    /// index into CENTERS
+    center: usize,
+    table_type: TableType,
+    days_before: i32,
+    days_after: i32,
+    step: String,
+    refresh: bool,
+}
+
+impl HorizonsUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            client: HorizonsClient::new(),
+            objects: Vec::new(),
+            observer_tables: Vec::new(),
+            shown_observer_table: None,
+            pending: None,
+            last_error: None,
+            timebase: Timebase::new(),
+            target: String::new(),
+            center: 0,
+            table_type: TableType::Vectors,
+            days_before: 30,
+            days_after: 30,
+            step: "6h".to_string(),
+            refresh: false,
+        }
+    }
+
+    fn request(&self) -> HorizonsRequest {
+        let now = self.timebase.now();
+        let start = now - self.days_before.max(0) as f64 * 86400.0;
+        let stop = now + self.days_after.max(0) as f64 * 86400.0;
+        let center = CENTERS[self.center];
+        match self.table_type {
+            TableType::Vectors => {
+                HorizonsRequest::vectors(&self.target, center, start, stop, &self.step)
+            }
+            TableType::Observer => {
+                HorizonsRequest::observer(&self.target, center, start, stop, &self.step)
+            }
+        }
+    }
+
+    fn fetch(&mut self) {
+        let request = self.request();
+        if let Err(e) = request.validate() {
+            self.last_error = Some(e);
+            return;
+        }
+        log::info!("Asking Horizons for {}", request);
+        self.last_error = None;
+        let rx = self
+            .client
+            .table_in_background(request.clone(), self.refresh);
+        self.pending = Some((request, rx));
+    }
+
+    fn poll_pending(&mut self, gl: &glow::Context, world: &mut World) {
+        let result = match &self.pending {
+            Some((_, rx)) => match rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err("Horizons request died".to_string()),
+            },
+            None => return,
+        };
+        let request = self.pending.take().map(|p| p.0);
+        let added = result.and_then(|table| match table {
+            HorizonsTable::Vectors(table) => self.add_object(gl, world, table),
+            HorizonsTable::Observer(table) => {
+                self.observer_tables.push(table);
+                self.shown_observer_table = Some(self.observer_tables.len() - 1);
+                Ok(())
+            }
+        });
+        if let Err(e) = added {
+            if let Some(request) = request {
+                log::error!("Horizons request {} failed: {}", request, e);
+            }
+            self.last_error = Some(e);
+        }
+    }
+
+    fn add_object(
+        &mut self,
+        gl: &glow::Context,
+        world: &mut World,
+        table: VectorTable,
+    ) -> Result<(), String> {
+        let (verts, index, normal) = gen_icosphere(0.005, 2);
+        let marker = world.push((
+            table.states[0].1,
+            WorldTransform::default(),
+            VertexList::create_triangles(gl, &verts, Some(&index), Some(&normal))?,
+            MaterialComponent("material/sats.toml".to_string()),
+        ));
+        // the states are relative to the center, the view moves the trail along with it
+        let stride = (table.states.len() / TRAIL_POINTS).max(1);
+        let points = table
+            .states
+            .iter()
+            .step_by(stride)
+            .map(|(_, s)| IcrfStateVector {
+                unit: s.unit,
+                position: s.position,
+                velocity: s.velocity,
+            })
+            .collect();
+        let trail = world.push((
+            Trajectory::around(table.center, points),
+            WorldTransform::default(),
+            VertexList::create_lines(gl, &[], None, None)?,
+            MaterialComponent("material/colored_orbit.toml".to_string()),
+        ));
+        log::info!(
+            "Added {} from Horizons, {} states",
+            table.target,
+            table.states.len()
+        );
+        self.objects.push(HorizonsObject {
+            table,
+            marker,
+            trail,
+        });
+        Ok(())
+    }
+
+    fn remove_object(&mut self, world: &mut World, index: usize) {
+        let item = self.objects.remove(index);
+        world.remove(item.marker);
+        world.remove(item.trail);
+    }
+
+    fn observer_table_ui(&self, ui: &Ui, index: usize) {
+        let table = &self.observer_tables[index];
+        ui.text(format!("{} seen from {}", table.target, table.center));
+        ui.child_window("Observer Table")
+            .size([0.0, 200.0])
+            .always_vertical_scrollbar(true)
+            .build(|| {
+                ui.columns(5, "Observer Rows", true);
+                ui.text("Time (UTC)");
+                ui.next_column();
+                ui.text("RA (deg)");
+                ui.next_column();
+                ui.text("Dec (deg)");
+                ui.next_column();
+                ui.text("Range (au)");
+                ui.next_column();
+                ui.text("Range rate (km/s)");
+                ui.next_column();
+                ui.separator();
+                for row in table.rows.iter() {
+                    ui.text(format_et(row.time));
+                    ui.next_column();
+                    ui.text(format!("{:.5}", row.right_ascension));
+                    ui.next_column();
+                    ui.text(format!("{:.5}", row.declination));
+                    ui.next_column();
+                    ui.text(format!("{:.8}", row.range));
+                    ui.next_column();
+                    ui.text(format!("{:.4}", row.range_rate));
+                    ui.next_column();
+                }
+                ui.columns(1, "", false);
+            });
+    }
+}
+
+fn format_et(et: f64) -> String {
+    et_to_utc(et).format("%F %R").to_string()
+}
+
+impl WorldUi for HorizonsUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("JPL Horizons") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        self.poll_pending(gl, world);
+        if !self.visible {
+            return Ok(());
+        }
+
+        let mut do_fetch = false;
+        let mut to_remove = None;
+        let mut to_close = None;
+        let mut clear_cache = false;
+        let mut visible = self.visible;
+        ui.window("JPL Horizons")
+            .opened(&mut visible)
+            .size([550.0, 550.0], Condition::Appearing)
+            .build(|| {
+                ui.input_text("Target", &mut self.target).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "NAIF id, name or designation, like 499, Ceres; or DES=2000433;",
+                    );
+                }
+                let center_names = CENTERS
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>();
+                ui.combo_simple_string("Center", &mut self.center, &center_names);
+                ui.radio_button("Vectors", &mut self.table_type, TableType::Vectors);
+                ui.same_line();
+                ui.radio_button("Observer", &mut self.table_type, TableType::Observer);
+                ui.input_int("Days before now", &mut self.days_before)
+                    .build();
+                ui.input_int("Days after now", &mut self.days_after).build();
+                ui.input_text("Step", &mut self.step).build();
+                ui.checkbox("Ignore cache", &mut self.refresh);
+
+                if self.pending.is_some() {
+                    ui.text("Waiting for Horizons...");
+                } else if ui.button("Fetch") {
+                    do_fetch = true;
+                }
+                ui.same_line();
+                if ui.button("Clear cache") {
+                    clear_cache = true;
+                }
+                if let Some(e) = &self.last_error {
+                    ui.text_wrapped(e);
+                }
+                ui.separator();
+
+                ui.text("Objects");
+                ui.columns(4, "Horizons Objects", true);
+                ui.text("Target");
+                ui.next_column();
+                ui.text("Center");
+                ui.next_column();
+                ui.text("Covers");
+                ui.next_column();
+                ui.next_column();
+                ui.separator();
+                for (i, item) in self.objects.iter().enumerate() {
+                    let _id_scope = ui.push_id_usize(i);
+                    ui.text(&item.table.target);
+                    ui.next_column();
+                    ui.text(item.table.center.to_string());
+                    ui.next_column();
+                    ui.text(format!(
+                        "{} - {}",
+                        format_et(item.table.start()),
+                        format_et(item.table.end())
+                    ));
+                    ui.next_column();
+                    if ui.small_button("Remove") {
+                        to_remove = Some(i);
+                    }
+                    ui.next_column();
+                }
+                ui.columns(1, "", false);
+                ui.separator();
+
+                ui.text("Observer tables");
+                for (i, table) in self.observer_tables.iter().enumerate() {
+                    let _id_scope = ui.push_id_usize(i);
+                    if ui
+                        .selectable_config(format!("{} from {}", table.target, table.center))
+                        .selected(self.shown_observer_table == Some(i))
+                        .build()
+                    {
+                        self.shown_observer_table = Some(i);
+                    }
+                    ui.same_line();
+                    if ui.small_button("Close") {
+                        to_close = Some(i);
+                    }
+                }
+                if let Some(index) = self.shown_observer_table {
+                    self.observer_table_ui(ui, index);
+                }
+            });
+        self.visible = visible;
+
+        if do_fetch {
+            self.fetch();
+        }
+        if let Some(index) = to_remove {
+            self.remove_object(world, index);
+        }
+        if let Some(index) = to_close {
+            self.observer_tables.remove(index);
+            self.shown_observer_table = None;
+        }
+        if clear_cache {
+            if let Err(e) = self.client.clear_cache() {
+                log::error!("Cannot clear the Horizons cache: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.timebase = timebase.clone();
+        for item in self.objects.iter() {
+            // outside of the table the marker stays at the closest end
+            let et = timebase.now().clamp(item.table.start(), item.table.end());
+            if let Some(state) = item.table.state_at(et) {
+                if let Ok(mut entry) = world.entry_mut(item.marker) {
+                    if let Ok(state_vec) = entry.get_component_mut::<PlanetaryStateVector>() {
+                        *state_vec = state;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
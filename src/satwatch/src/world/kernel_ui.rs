@@ -0,0 +1,243 @@
+use crate::util::input_events::Event;
+use crate::world::world_ui::WorldUi;
+use imgui::*;
+use legion::*;
+use libspace::kernels::*;
+use libspace::timebase::{et_to_utc, Timebase};
+use std::path::{Path, PathBuf};
+
+pub struct KernelUi {
+    visible: bool,
+    manager: KernelManager,
+    /// what spice has loaded, refreshed after every change
+    furnished: Vec<FurnishedKernel>,
+    load_path: String,
+    selected: Option<PathBuf>,
+    now: f64,
+}
+
+impl KernelUi {
+    pub fn new() -> Self {
+        let manager = KernelManager::restore();
+        let furnished = manager.furnished();
+        Self {
+            visible: false,
+            manager,
+            furnished,
+            load_path: String::new(),
+            selected: None,
+            now: 0.0,
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.furnished = self.manager.furnished();
+        if let Some(selected) = &self.selected {
+            if !self.furnished.iter().any(|k| &k.path == selected) {
+                self.selected = None;
+            }
+        }
+    }
+
+    fn coverage_ui(&mut self, ui: &Ui, path: &Path) {
+        let now = self.now;
+        match self.manager.coverage(path) {
+            Ok(objects) => {
+                ui.text(format!("{} objects", objects.len()));
+                ui.columns(5, "Coverage Table", true);
+                ui.text("Object");
+                ui.next_column();
+                ui.text("Start (UTC)");
+                ui.next_column();
+                ui.text("End (UTC)");
+                ui.next_column();
+                ui.text("Intervals");
+                ui.next_column();
+                ui.text("Covers now");
+                ui.next_column();
+                ui.separator();
+                for object in objects.iter() {
+                    ui.text(object.display_name());
+                    ui.next_column();
+                    ui.text(format_et(object.start()));
+                    ui.next_column();
+                    ui.text(format_et(object.end()));
+                    ui.next_column();
+                    ui.text(object.intervals.len().to_string());
+                    ui.next_column();
+                    ui.text(if object.covers(now) { "yes" } else { "no" });
+                    ui.next_column();
+                }
+                ui.columns(1, "", false);
+            }
+            Err(e) => ui.text(e),
+        }
+    }
+}
+
+fn format_et(et: Option<f64>) -> String {
+    match et {
+        Some(et) => et_to_utc(et).format("%F %T").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+impl WorldUi for KernelUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Kernels") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, _gl: &glow::Context, _world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let mut to_load = None;
+        let mut to_unload = None;
+        let mut unload_all = false;
+        let mut refresh = false;
+        let mut visible = self.visible;
+        ui.window("Kernels")
+            .opened(&mut visible)
+            .size([600.0, 500.0], Condition::Appearing)
+            .build(|| {
+                ui.input_text("Kernel or meta-kernel", &mut self.load_path)
+                    .build();
+                if ui.button("Load") {
+                    to_load = Some(PathBuf::from(self.load_path.trim()));
+                }
+                ui.same_line();
+                if ui.button("Unload all") {
+                    unload_all = true;
+                }
+                ui.same_line();
+                if ui.button("Refresh") {
+                    refresh = true;
+                }
+                ui.separator();
+
+                ui.text(format!("{} kernels loaded", self.furnished.len()));
+                ui.child_window("Kernel List")
+                    .size([0.0, 200.0])
+                    .always_vertical_scrollbar(true)
+                    .build(|| {
+                        ui.columns(4, "Kernel Table", true);
+                        ui.text("File");
+                        ui.next_column();
+                        ui.text("Type");
+                        ui.next_column();
+                        ui.text("Loaded by");
+                        ui.next_column();
+                        ui.next_column();
+                        ui.separator();
+                        for (i, kernel) in self.furnished.iter().enumerate() {
+                            let _id_scope = ui.push_id_usize(i);
+                            let is_selected = self.selected.as_ref() == Some(&kernel.path);
+                            if ui
+                                .selectable_config(file_name(&kernel.path))
+                                .selected(is_selected)
+                                .build()
+                            {
+                                self.selected = Some(kernel.path.clone());
+                            }
+                            if ui.is_item_hovered() {
+                                ui.tooltip_text(kernel.path.display().to_string());
+                            }
+                            ui.next_column();
+                            ui.text(kernel.kernel_type.to_string());
+                            ui.next_column();
+                            match &kernel.source {
+                                Some(source) => ui.text(file_name(source)),
+                                None => ui.text("-"),
+                            }
+                            ui.next_column();
+                            if self.manager.loaded().contains(&kernel.path)
+                                && ui.small_button("Unload")
+                            {
+                                to_unload = Some(kernel.path.clone());
+                            }
+                            ui.next_column();
+                        }
+                        ui.columns(1, "", false);
+                    });
+
+                // meta-kernels are not furnished themselves, so they only show up here
+                let meta_kernels = self
+                    .manager
+                    .loaded()
+                    .iter()
+                    .filter(|p| self.manager.is_meta_kernel(p))
+                    .cloned()
+                    .collect::<Vec<PathBuf>>();
+                if !meta_kernels.is_empty() {
+                    ui.text("Meta-kernels:");
+                    for (i, path) in meta_kernels.iter().enumerate() {
+                        let _id_scope = ui.push_id_usize(i);
+                        ui.text(file_name(path));
+                        ui.same_line();
+                        if ui.small_button("Unload") {
+                            to_unload = Some(path.clone());
+                        }
+                    }
+                }
+                ui.separator();
+
+                if let Some(selected) = self.selected.clone() {
+                    let kernel_type = self
+                        .furnished
+                        .iter()
+                        .find(|k| k.path == selected)
+                        .map(|k| k.kernel_type);
+                    ui.text(selected.display().to_string());
+                    match kernel_type {
+                        Some(t) if t.has_coverage() => self.coverage_ui(ui, &selected),
+                        Some(t) => ui.text(format!("{} kernels have no time coverage", t)),
+                        None => {}
+                    }
+                }
+            });
+        self.visible = visible;
+
+        if let Some(path) = to_load {
+            match self.manager.load(&path) {
+                Ok(()) => log::info!("Loaded kernel {}", path.display()),
+                Err(e) => log::error!("Cannot load kernel: {}", e),
+            }
+            refresh = true;
+        }
+        if let Some(path) = to_unload {
+            self.manager.unload(&path);
+            refresh = true;
+        }
+        if unload_all {
+            self.manager.unload_all();
+            refresh = true;
+        }
+        if refresh {
+            self.refresh();
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        _world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.now = timebase.now();
+        Ok(())
+    }
+}
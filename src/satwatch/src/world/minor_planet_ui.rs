@@ -0,0 +1,299 @@
+use crate::components::{MaterialComponent, VertexList, WorldTransform};
+use crate::download::{DownloadStatus, Downloader};
+use crate::util::input_events::Event;
+use crate::util::vertex_tools::gen_icosphere;
+use crate::world::world_ui::WorldUi;
+use imgui::*;
+use legion::*;
+use libspace::coordinate::{CoordinateUnit, IcrfStateVector};
+use libspace::small_bodies::*;
+use libspace::timebase::Timebase;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Adding a million asteroids at once is not something the renderer survives
+const MAX_ADD_ALL: usize = 5000;
+
+pub struct MinorPlanetUi {
+    visible: bool,
+    db: MinorPlanetDb,
+    import: Option<MpcImport>,
+    download: Option<Downloader>,
+    import_path: String,
+    search_term: String,
+    class_filter: usize,
+    search_results: Vec<usize>,
+    search_dirty: bool,
+    engine: SmallBodyEngine,
+    tracked_items: HashMap<usize, Entity>,
+}
+
+impl MinorPlanetUi {
+    pub fn new() -> Self {
+        let mpcorb_file = MinorPlanetDb::mpcorb_file();
+        let import = if mpcorb_file.exists() {
+            Some(MpcImport::import_file(mpcorb_file.clone()))
+        } else {
+            None
+        };
+
+        Self {
+            visible: false,
+            db: MinorPlanetDb::new(),
+            import,
+            download: None,
+            import_path: mpcorb_file.to_string_lossy().to_string(),
+            search_term: String::new(),
+            class_filter: 0,
+            search_results: Vec::new(),
+            search_dirty: true,
+            engine: SmallBodyEngine::new(),
+            tracked_items: HashMap::new(),
+        }
+    }
+
+    fn selected_class(&self) -> Option<OrbitClass> {
+        if self.class_filter == 0 {
+            None
+        } else {
+            ALL_ORBIT_CLASSES.get(self.class_filter - 1).copied()
+        }
+    }
+
+    fn add_new(&mut self, gl: &glow::Context, world: &mut World, id: usize) -> Result<(), String> {
+        if self.tracked_items.contains_key(&id) {
+            return Ok(());
+        }
+
+        if let Some(minor_planet) = self.db.get(id) {
+            let (verts, index, normal) = gen_icosphere(0.01, 2);
+            let entity = world.push((
+                IcrfStateVector {
+                    unit: CoordinateUnit::Au,
+                    position: Default::default(),
+                    velocity: Default::default(),
+                },
+                WorldTransform::default(),
+                VertexList::create_triangles(gl, &verts, Some(&index), Some(&normal))?,
+                MaterialComponent("material/sats.toml".to_string()),
+            ));
+            self.tracked_items.insert(id, entity);
+            self.engine.add(id, minor_planet.elements.to_two_body());
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, world: &mut World, id: usize) {
+        if let Some(item) = self.tracked_items.get(&id) {
+            world.remove(*item);
+            self.tracked_items.remove(&id);
+            self.engine.remove(id);
+        }
+    }
+
+    fn update_import(&mut self, world: &mut World) {
+        if let Some(download) = self.download.as_mut() {
+            download.update_status();
+            match download.get_status() {
+                DownloadStatus::FileComplete(path) => {
+                    log::info!("MPCORB download complete");
+                    self.import = Some(MpcImport::import_file(path.clone()));
+                    self.download = None;
+                }
+                DownloadStatus::Error(e) => {
+                    log::error!("MPCORB download failed: {}", e);
+                    self.download = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(import) = self.import.as_mut() {
+            import.update_status();
+            match import.take_result() {
+                Some(Ok(minor_planets)) => {
+                    log::info!("Imported {} minor planets", minor_planets.len());
+                    // indices change with a new catalogue, so nothing tracked survives this
+                    for id in self.tracked_items.keys().copied().collect::<Vec<usize>>() {
+                        self.remove(world, id);
+                    }
+                    self.db = MinorPlanetDb::from_minor_planets(minor_planets);
+                    self.search_dirty = true;
+                    self.import = None;
+                }
+                Some(Err(e)) => {
+                    log::error!("MPCORB import failed: {}", e);
+                    self.import = None;
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+impl WorldUi for MinorPlanetUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Minor Planet Database") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        self.update_import(world);
+        if self.search_dirty {
+            self.search_results = self.db.find(&self.search_term, self.selected_class());
+            self.search_dirty = false;
+        }
+
+        let mut to_add = Vec::new();
+        let mut to_remove = Vec::new();
+
+        if self.visible {
+            ui.window("Minor Planet Database")
+                .opened(&mut self.visible)
+                .size([400.0, 400.0], Condition::Appearing)
+                .build(|| {
+                    if let Some(download) = &self.download {
+                        if let DownloadStatus::Progress(progress) = download.get_status() {
+                            ProgressBar::new((progress.dl_now / progress.dl_total) as f32)
+                                .overlay_text("Downloading MPCORB.DAT")
+                                .build(ui);
+                        } else {
+                            ui.text("Downloading MPCORB.DAT");
+                        }
+                    } else if let Some(import) = &self.import {
+                        if let ImportStatus::Progress {
+                            bytes_read,
+                            bytes_total,
+                        } = import.get_status()
+                        {
+                            ProgressBar::new(*bytes_read as f32 / (*bytes_total).max(1) as f32)
+                                .overlay_text("Importing")
+                                .build(ui);
+                        } else {
+                            ui.text("Importing...");
+                        }
+                    } else {
+                        if ui.button("Download MPCORB") {
+                            self.download = Some(Downloader::download_to_file(
+                                MPCORB_URL,
+                                MinorPlanetDb::mpcorb_file(),
+                            ));
+                        }
+                        ui.input_text("##import_path", &mut self.import_path)
+                            .build();
+                        ui.same_line();
+                        if ui.button("Import") {
+                            self.import =
+                                Some(MpcImport::import_file(PathBuf::from(&self.import_path)));
+                        }
+                    }
+                    ui.separator();
+
+                    if ui
+                        .input_text("Search Minor Planets", &mut self.search_term)
+                        .build()
+                    {
+                        self.search_dirty = true;
+                    }
+                    let mut class_names = vec!["All".to_string()];
+                    class_names.extend(ALL_ORBIT_CLASSES.iter().map(|c| c.to_string()));
+                    if ui.combo_simple_string("Orbit Class", &mut self.class_filter, &class_names) {
+                        self.search_dirty = true;
+                    }
+
+                    let items = &self.search_results;
+                    ui.text(format!(
+                        "Found {} of {} minor planets.",
+                        items.len(),
+                        self.db.len()
+                    ));
+                    ui.same_line();
+                    if items.len() <= MAX_ADD_ALL {
+                        if ui.button("Add all results") {
+                            to_add.extend(items.iter().copied());
+                        }
+                        ui.same_line();
+                    }
+                    if ui.button("Remove All") {
+                        to_remove.extend(self.tracked_items.keys());
+                    }
+
+                    ui.child_window("Minor Planet Table")
+                        .always_vertical_scrollbar(true)
+                        .build(|| {
+                            ui.columns(4, "Minor Planet Table", true);
+                            ui.text("Selected");
+                            ui.next_column();
+                            ui.text("Name");
+                            ui.next_column();
+                            ui.text("Class");
+                            ui.next_column();
+                            ui.text("H");
+                            ui.next_column();
+                            let mut clipper = ListClipper::new(items.len() as i32).begin(ui);
+                            while clipper.step() {
+                                for row in clipper.display_start()..clipper.display_end() {
+                                    let id = items[row as usize];
+                                    let _id_scope = ui.push_id_usize(id);
+                                    if let Some(minor_planet) = self.db.get(id) {
+                                        let mut tracked = self.tracked_items.contains_key(&id);
+                                        if ui.checkbox("Select", &mut tracked) {
+                                            if tracked {
+                                                to_add.push(id);
+                                            } else {
+                                                to_remove.push(id);
+                                            }
+                                        }
+                                        ui.next_column();
+                                        ui.text(minor_planet.display_name());
+                                        ui.next_column();
+                                        ui.text(minor_planet.orbit_class.to_string());
+                                        ui.next_column();
+                                        ui.text(format!("{:.2}", minor_planet.magnitude_h));
+                                        ui.next_column();
+                                    }
+                                }
+                            }
+                        });
+                });
+        }
+
+        for remove in to_remove {
+            self.remove(world, remove);
+        }
+
+        for add in to_add {
+            self.add_new(gl, world, add)
+                .unwrap_or_else(|e| log::error!("Adding minor planet failed: {}", e));
+        }
+
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.engine.update_timebase(timebase.clone());
+
+        while let Some(update) = self.engine.get_more() {
+            if let Some(entity) = self.tracked_items.get(&update.id) {
+                if let Ok(mut entry) = world.entry_mut(*entity) {
+                    if let Ok(state_vec) = entry.get_component_mut::<IcrfStateVector>() {
+                        *state_vec = update.state;
+                    }
+                }
+            } else {
+                self.engine.remove(update.id);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,16 @@
 pub mod world_control;
 pub mod world_ui;
 
+mod accuracy_ui;
+mod comet_ui;
+mod eclipse_ui;
 mod element_db_ui;
+mod horizons_ui;
+mod kernel_ui;
+mod minor_planet_ui;
+mod orbiter_ui;
+mod phenomena_ui;
+mod planet_inspector_ui;
+mod spice_object_ui;
 mod time_ui;
 mod view_ui;
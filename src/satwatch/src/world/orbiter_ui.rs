@@ -0,0 +1,336 @@
+use crate::components::{MaterialComponent, Trajectory, VertexList, WorldTransform};
+use crate::util::input_events::Event;
+use crate::util::orbit_elements_table::orbit_elements_table;
+use crate::util::vertex_tools::gen_icosphere;
+use crate::world::world_ui::WorldUi;
+use imgui::*;
+use legion::*;
+use libspace::bodies::orbiter::Orbiter;
+use libspace::bodies::osculating_elements::OsculatingElements;
+use libspace::bodies::Planet;
+use libspace::coordinate::{
+    CoordinateUnit, IcrfStateVector, PlanetaryReferenceFrame, PlanetaryStateVector,
+};
+use libspace::timebase::Timebase;
+use log::error;
+
+const TRAJECTORY_POINTS: usize = 128;
+/// days, how much of an open orbit to draw
+const OPEN_TRAJECTORY_SPAN: f64 = 2.0;
+
+const CENTRAL_BODIES: [Planet; 9] = [
+    Planet::Mercury,
+    Planet::Venus,
+    Planet::Earth,
+    Planet::Moon,
+    Planet::Mars,
+    Planet::Jupiter,
+    Planet::Saturn,
+    Planet::Uranus,
+    Planet::Neptune,
+];
+
+/// Rough orbits of real spacecraft: name, body, periapsis and apoapsis altitude (km), inclination (degree)
+const PRESETS: [(&str, Planet, f64, f64, f64); 4] = [
+    ("MRO", Planet::Mars, 255.0, 320.0, 92.65),
+    ("MAVEN", Planet::Mars, 150.0, 6200.0, 75.0),
+    ("LRO", Planet::Moon, 30.0, 180.0, 90.0),
+    ("Venus Express", Planet::Venus, 250.0, 66000.0, 90.0),
+];
+
+struct TrackedOrbiter {
+    name: String,
+    orbiter: Orbiter,
+    marker: Entity,
+    trajectory: Entity,
+    /// when the drawn trajectory was made
+    trajectory_time: Option<f64>,
+}
+
+pub struct OrbiterUi {
+    visible: bool,
+    orbiters: Vec<TrackedOrbiter>,
+    inspected: Option<usize>,
+    timebase: Timebase,
+    // new orbiter form
+    name: String,
+    /// index into CENTRAL_BODIES
+    central_body: usize,
+    periapsis_altitude: f64,
+    apoapsis_altitude: f64,
+    inclination: f64,
+    long_ascending: f64,
+    argument_periapsis: f64,
+    mean_anomaly: f64,
+    use_j2: bool,
+}
+
+impl OrbiterUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            orbiters: Vec::new(),
+            inspected: None,
+            timebase: Timebase::new(),
+            name: "Orbiter".to_string(),
+            central_body: 4,
+            periapsis_altitude: 300.0,
+            apoapsis_altitude: 300.0,
+            inclination: 90.0,
+            long_ascending: 0.0,
+            argument_periapsis: 0.0,
+            mean_anomaly: 0.0,
+            use_j2: true,
+        }
+    }
+
+    fn apply_preset(&mut self, preset: &(&str, Planet, f64, f64, f64)) {
+        let (name, planet, periapsis, apoapsis, inclination) = *preset;
+        self.name = name.to_string();
+        self.central_body = CENTRAL_BODIES
+            .iter()
+            .position(|p| *p == planet)
+            .unwrap_or(0);
+        self.periapsis_altitude = periapsis;
+        self.apoapsis_altitude = apoapsis;
+        self.inclination = inclination;
+    }
+
+    /// Orbiter from the form, with the current time as epoch
+    fn form_orbiter(&self) -> Result<Orbiter, String> {
+        let planet = CENTRAL_BODIES[self.central_body];
+        let radius = planet.body().radius_equatorial;
+        let periapsis = radius + self.periapsis_altitude;
+        let apoapsis = radius + self.apoapsis_altitude;
+        if self.periapsis_altitude < 0.0 || apoapsis < periapsis {
+            return Err("Periapsis has to be above the surface and below apoapsis".to_string());
+        }
+        let elements = OsculatingElements {
+            central_body: planet,
+            semi_mayor: (periapsis + apoapsis) / 2.0,
+            eccentricity: (apoapsis - periapsis) / (apoapsis + periapsis),
+            inclination: self.inclination,
+            long_ascending: self.long_ascending,
+            argument_periapsis: self.argument_periapsis,
+            true_anomaly: 0.0,
+        }
+        .with_mean_anomaly(self.mean_anomaly);
+        let mut orbiter =
+            Orbiter::from_equatorial_elements(elements, self.timebase.now_jd_j2000())?;
+        orbiter.use_j2 = self.use_j2;
+        Ok(orbiter)
+    }
+
+    fn add_new(&mut self, gl: &glow::Context, world: &mut World) -> Result<(), String> {
+        let orbiter = self.form_orbiter()?;
+        let (verts, index, normal) = gen_icosphere(0.005, 2);
+        let marker = world.push((
+            PlanetaryStateVector {
+                planet: orbiter.central_body(),
+                reference_frame: PlanetaryReferenceFrame::Inertial,
+                unit: CoordinateUnit::KiloMeter,
+                position: Default::default(),
+                velocity: Default::default(),
+            },
+            WorldTransform::default(),
+            VertexList::create_triangles(gl, &verts, Some(&index), Some(&normal))?,
+            MaterialComponent("material/sats.toml".to_string()),
+        ));
+        let trajectory = world.push((
+            Trajectory::around(orbiter.central_body(), Vec::new()),
+            WorldTransform::default(),
+            VertexList::create_lines(gl, &[], None, None)?,
+            MaterialComponent("material/colored_orbit.toml".to_string()),
+        ));
+        self.orbiters.push(TrackedOrbiter {
+            name: self.name.clone(),
+            orbiter,
+            marker,
+            trajectory,
+            trajectory_time: None,
+        });
+        Ok(())
+    }
+
+    fn remove(&mut self, world: &mut World, id: usize) {
+        let item = self.orbiters.remove(id);
+        world.remove(item.marker);
+        world.remove(item.trajectory);
+        self.inspected = None;
+    }
+
+    fn inspection_ui(&mut self, ui: &Ui) {
+        let id = match self.inspected {
+            Some(id) => id,
+            None => return,
+        };
+        let item = &self.orbiters[id];
+        let state = match item.orbiter.state(&self.timebase) {
+            Ok(state) => state,
+            Err(e) => {
+                error!("Cannot propagate {}: {}", item.name, e);
+                self.inspected = None;
+                return;
+            }
+        };
+        let (node_rate, periapsis_rate, _) = item.orbiter.rates();
+
+        let mut opened = true;
+        ui.window(format!("Orbiter: {}###Orbiter Elements", item.name))
+            .opened(&mut opened)
+            .size([400.0, 400.0], Condition::Appearing)
+            .build(|| {
+                ui.text(format!(
+                    "Node drift {:.4} deg/day, periapsis drift {:.4} deg/day",
+                    node_rate * 86400.0,
+                    periapsis_rate * 86400.0
+                ));
+                ui.text("Elements below are referred to the ICRF.");
+                orbit_elements_table(ui, &state, &self.timebase);
+            });
+        if !opened {
+            self.inspected = None;
+        }
+    }
+}
+
+impl WorldUi for OrbiterUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Orbiters") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        self.inspection_ui(ui);
+        if !self.visible {
+            return Ok(());
+        }
+
+        let mut do_add = false;
+        let mut to_remove = None;
+        let mut visible = self.visible;
+        ui.window("Orbiters")
+            .opened(&mut visible)
+            .size([450.0, 500.0], Condition::Appearing)
+            .build(|| {
+                if ui.collapsing_header("New Orbiter", TreeNodeFlags::DEFAULT_OPEN) {
+                    for preset in PRESETS.iter() {
+                        if ui.small_button(preset.0) {
+                            self.apply_preset(preset);
+                        }
+                        ui.same_line();
+                    }
+                    ui.new_line();
+                    ui.input_text("Name", &mut self.name).build();
+                    let body_names = CENTRAL_BODIES
+                        .iter()
+                        .map(|p| p.to_string())
+                        .collect::<Vec<String>>();
+                    ui.combo_simple_string("Central body", &mut self.central_body, &body_names);
+                    ui.input_scalar("Periapsis altitude (km)", &mut self.periapsis_altitude)
+                        .build();
+                    ui.input_scalar("Apoapsis altitude (km)", &mut self.apoapsis_altitude)
+                        .build();
+                    ui.input_scalar("Inclination (deg)", &mut self.inclination)
+                        .build();
+                    ui.input_scalar("Ascending node (deg)", &mut self.long_ascending)
+                        .build();
+                    ui.input_scalar("Arg. of periapsis (deg)", &mut self.argument_periapsis)
+                        .build();
+                    ui.input_scalar("Mean anomaly (deg)", &mut self.mean_anomaly)
+                        .build();
+                    ui.checkbox("J2", &mut self.use_j2);
+                    ui.text(
+                        "Angles are referred to the equator of the central body, epoch is now.",
+                    );
+                    do_add = ui.button("Add");
+                }
+                ui.separator();
+
+                ui.columns(3, "Orbiter Table", true);
+                ui.text("Name");
+                ui.next_column();
+                ui.text("Around");
+                ui.next_column();
+                ui.next_column();
+                for (i, item) in self.orbiters.iter().enumerate() {
+                    let _id_scope = ui.push_id_usize(i);
+                    if ui.selectable(&item.name) {
+                        self.inspected = Some(i);
+                    }
+                    ui.next_column();
+                    ui.text(item.orbiter.central_body().to_string());
+                    ui.next_column();
+                    if ui.button("Remove") {
+                        to_remove = Some(i);
+                    }
+                    ui.next_column();
+                }
+            });
+        self.visible = visible;
+
+        if do_add {
+            if let Err(e) = self.add_new(gl, world) {
+                error!("Cannot add orbiter: {}", e);
+            }
+        }
+        if let Some(id) = to_remove {
+            self.remove(world, id);
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.timebase = timebase.clone();
+        let now = timebase.now_jd_j2000();
+        for item in self.orbiters.iter_mut() {
+            let state = match item.orbiter.state(timebase) {
+                Ok(state) => state,
+                Err(e) => {
+                    error!("Cannot propagate {}: {}", item.name, e);
+                    continue;
+                }
+            };
+            if let Ok(mut entry) = world.entry_mut(item.marker) {
+                if let Ok(state_vec) = entry.get_component_mut::<PlanetaryStateVector>() {
+                    *state_vec = state;
+                }
+            }
+            // the view moves the orbit along with its planet, it is only made again once it drifted
+            let changed = item.trajectory_time.map_or(true, |since| {
+                item.orbiter
+                    .trajectory_changed(since, now, TRAJECTORY_POINTS, OPEN_TRAJECTORY_SPAN)
+            });
+            if !changed {
+                continue;
+            }
+            if let Ok(mut entry) = world.entry_mut(item.trajectory) {
+                if let Ok(trajectory) = entry.get_component_mut::<Trajectory>() {
+                    trajectory.points = item
+                        .orbiter
+                        .trajectory(now, TRAJECTORY_POINTS, OPEN_TRAJECTORY_SPAN)
+                        .iter()
+                        .map(|s| IcrfStateVector {
+                            unit: s.unit,
+                            position: s.position,
+                            velocity: s.velocity,
+                        })
+                        .collect();
+                    trajectory.origin = None;
+                    item.trajectory_time = Some(now);
+                }
+            }
+        }
+        Ok(())
+    }
+}
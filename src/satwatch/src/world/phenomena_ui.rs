@@ -0,0 +1,170 @@
+use crate::util::input_events::Event;
+use crate::world::world_ui::WorldUi;
+use chrono::{Datelike, Utc};
+use imgui::*;
+use legion::*;
+use libspace::bodies::phenomena::*;
+use libspace::timebase::{et_to_utc, julian_date, Timebase};
+
+pub struct PhenomenaUi {
+    visible: bool,
+    from_year: i32,
+    years: i32,
+    /// 0 is all planets, otherwise an index into PHENOMENA_PLANETS plus one
+    planet_filter: usize,
+    results: Vec<Phenomenon>,
+    search: Option<PhenomenaSearch>,
+    jump_to: Option<f64>,
+}
+
+impl PhenomenaUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            from_year: Utc::now().year(),
+            years: 2,
+            planet_filter: 0,
+            results: Vec::new(),
+            search: None,
+            jump_to: None,
+        }
+    }
+
+    fn search(&mut self) {
+        let from = julian_date(self.from_year, 1, 1.0) - 2451545.0;
+        let to = julian_date(self.from_year + self.years.max(1), 1, 1.0) - 2451545.0;
+        let planet = match self.planet_filter {
+            0 => None,
+            i => Some(PHENOMENA_PLANETS[i - 1]),
+        };
+        self.search = Some(PhenomenaSearch::start(planet, from, to));
+    }
+
+    fn update_search(&mut self) {
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return,
+        };
+        search.update_status();
+        if let Some(results) = search.take_result() {
+            log::info!("Found {} phenomena", results.len());
+            self.results = results;
+            self.search = None;
+        } else if let PhenomenaSearchStatus::Cancelled = search.get_status() {
+            self.search = None;
+        }
+    }
+}
+
+fn details(phenomenon: &Phenomenon) -> String {
+    match phenomenon.kind {
+        PhenomenonKind::Perihelion | PhenomenonKind::Aphelion => {
+            format!("{:.4} au from the sun", phenomenon.sun_distance)
+        }
+        _ => format!(
+            "elongation {:.1} deg, {:.4} au from earth",
+            phenomenon.elongation, phenomenon.earth_distance
+        ),
+    }
+}
+
+impl WorldUi for PhenomenaUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Planetary Phenomena") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, _gl: &glow::Context, _world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let mut do_search = false;
+        ui.window("Planetary Phenomena")
+            .opened(&mut self.visible)
+            .size([500.0, 400.0], Condition::Appearing)
+            .build(|| {
+                ui.input_int("From year", &mut self.from_year).build();
+                ui.input_int("Years", &mut self.years).build();
+                let mut planet_names = vec!["All".to_string()];
+                planet_names.extend(PHENOMENA_PLANETS.iter().map(|p| p.to_string()));
+                ui.combo_simple_string("Planet", &mut self.planet_filter, &planet_names);
+                match self.search.as_ref().map(|s| s.get_status()) {
+                    Some(PhenomenaSearchStatus::Progress { done, total }) => {
+                        ui.text(format!("Searching... {} of {} planets", done, total));
+                    }
+                    Some(_) => ui.text("Searching..."),
+                    None => do_search = ui.button("Search"),
+                }
+                if let Some(search) = &self.search {
+                    ui.same_line();
+                    if ui.button("Cancel") {
+                        search.cancel();
+                    }
+                }
+                ui.text(
+                    "Times are geometric and from approximate elements, expect hours of error.",
+                );
+                ui.separator();
+
+                ui.child_window("Phenomena Table")
+                    .always_vertical_scrollbar(true)
+                    .build(|| {
+                        ui.columns(5, "Phenomena Table", true);
+                        ui.text("Date (UTC)");
+                        ui.next_column();
+                        ui.text("Planet");
+                        ui.next_column();
+                        ui.text("Event");
+                        ui.next_column();
+                        ui.text("Details");
+                        ui.next_column();
+                        ui.next_column();
+                        let mut clipper = ListClipper::new(self.results.len() as i32).begin(ui);
+                        while clipper.step() {
+                            for row in clipper.display_start()..clipper.display_end() {
+                                let _id_scope = ui.push_id_usize(row as usize);
+                                let phenomenon = &self.results[row as usize];
+                                let et = phenomenon.time * 86400.0;
+                                ui.text(et_to_utc(et).format("%F %H:%M").to_string());
+                                ui.next_column();
+                                ui.text(phenomenon.planet.to_string());
+                                ui.next_column();
+                                ui.text(phenomenon.kind.to_string());
+                                ui.next_column();
+                                ui.text(details(phenomenon));
+                                ui.next_column();
+                                if ui.button("Jump to") {
+                                    self.jump_to = Some(et);
+                                }
+                                ui.next_column();
+                            }
+                        }
+                    });
+            });
+
+        if do_search {
+            self.search();
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        _world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.update_search();
+        if let Some(et) = self.jump_to.take() {
+            timebase.set_realtime(false);
+            timebase.set_now(et);
+        }
+        Ok(())
+    }
+}
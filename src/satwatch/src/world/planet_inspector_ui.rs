@@ -0,0 +1,123 @@
+use crate::util::input_events::Event;
+use crate::world::world_ui::WorldUi;
+use imgui::*;
+use legion::*;
+use libspace::bodies::apparent::*;
+use libspace::bodies::Planet;
+use libspace::timebase::Timebase;
+
+pub struct PlanetInspectorUi {
+    visible: bool,
+    /// index into APPEARANCE_BODIES
+    observer: usize,
+    timebase: Option<Timebase>,
+}
+
+impl PlanetInspectorUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            observer: APPEARANCE_BODIES
+                .iter()
+                .position(|p| *p == Planet::Earth)
+                .unwrap_or(0),
+            timebase: None,
+        }
+    }
+}
+
+fn format_angle(degree: f64) -> String {
+    if degree >= 1.0 {
+        format!("{:.2} deg", degree)
+    } else if degree * 60.0 >= 1.0 {
+        format!("{:.2}'", degree * 60.0)
+    } else {
+        format!("{:.2}\"", degree * 3600.0)
+    }
+}
+
+impl WorldUi for PlanetInspectorUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("Planet Inspector") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, _gl: &glow::Context, _world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+        let timebase = match &self.timebase {
+            Some(t) => *t,
+            None => return Ok(()),
+        };
+
+        ui.window("Planet Inspector")
+            .opened(&mut self.visible)
+            .size([700.0, 320.0], Condition::Appearing)
+            .build(|| {
+                let observer_names = APPEARANCE_BODIES
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>();
+                ui.combo_simple_string("Observer", &mut self.observer, &observer_names);
+                let observer_planet = APPEARANCE_BODIES[self.observer];
+                let observer = observer_planet.pos_icrf(&timebase);
+                ui.text("Geometric, as seen from the center of the observer.");
+                ui.separator();
+
+                ui.columns(7, "Planet Inspector Table", true);
+                for header in [
+                    "Body",
+                    "Magnitude",
+                    "Phase angle",
+                    "Illuminated",
+                    "Diameter",
+                    "Elongation",
+                    "Distance",
+                ] {
+                    ui.text(header);
+                    ui.next_column();
+                }
+                for planet in APPEARANCE_BODIES.iter() {
+                    if *planet == observer_planet {
+                        continue;
+                    }
+                    let appearance = appearance(*planet, &observer, &timebase);
+                    ui.text(planet.to_string());
+                    ui.next_column();
+                    match appearance.magnitude {
+                        Some(m) => ui.text(format!("{:.2}", m)),
+                        None => ui.text("-"),
+                    }
+                    ui.next_column();
+                    ui.text(format!("{:.1} deg", appearance.phase_angle));
+                    ui.next_column();
+                    ui.text(format!("{:.1} %", appearance.illuminated_fraction * 100.0));
+                    ui.next_column();
+                    ui.text(format_angle(appearance.angular_diameter));
+                    ui.next_column();
+                    ui.text(format!("{:.1} deg", appearance.elongation));
+                    ui.next_column();
+                    ui.text(format!("{:.4} au", appearance.distance));
+                    ui.next_column();
+                }
+                ui.columns(1, "Planet Inspector Table", false);
+            });
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        _world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.timebase = Some(*timebase);
+        Ok(())
+    }
+}
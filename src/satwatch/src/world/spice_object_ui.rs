@@ -0,0 +1,616 @@
+use crate::components::{
+    BodyRadius, MaterialComponent, SpiceTrail, Trajectory, VertexList, WorldTransform,
+};
+use crate::util::input_events::Event;
+use crate::util::vertex_tools::{gen_icosphere, gen_shape_model};
+use crate::world::world_ui::WorldUi;
+use glam::f64::{DQuat, DVec3};
+use imgui::*;
+use legion::*;
+use libspace::bodies::rotation_model::icrf_to_gl_rotation;
+use libspace::bodies::shape_model::ShapeModel;
+use libspace::coordinate::{CoordinateUnit, IcrfStateVector};
+use libspace::kernels::{frame_to_j2000, Attitude, Instrument, SpiceObject};
+use libspace::timebase::{et_to_utc, Timebase};
+use std::path::Path;
+
+const TRAIL_POINTS: usize = 2000;
+
+struct TrackedObject {
+    object: SpiceObject,
+    marker: Entity,
+    trail: Entity,
+    /// ET the trail was centered on, None for the whole coverage
+    trail_center: Option<f64>,
+    attitude: Option<Attitude>,
+    /// why there is no attitude right now
+    attitude_error: Option<String>,
+    axes: Option<Entity>,
+    boresights: Vec<(Instrument, Entity)>,
+    /// file of the shape model the marker is drawn with
+    shape: Option<String>,
+}
+
+enum Action {
+    Add,
+    Remove(usize),
+    AttitudeFromKernels(usize),
+    AttitudeFromFrame(usize),
+    AttitudeOff(usize),
+    ToggleAxes(usize),
+    AddBoresight(usize),
+    RemoveBoresight(usize, usize),
+    LoadShape(usize),
+    ShapeOff(usize),
+}
+
+pub struct SpiceObjectUi {
+    visible: bool,
+    objects: Vec<TrackedObject>,
+    name_or_id: String,
+    whole_coverage: bool,
+    /// days before and after now, if not drawing the whole coverage
+    trail_span: i32,
+    trail_changed: bool,
+    selected: Option<usize>,
+    frame_name: String,
+    instrument_name: String,
+    /// km
+    axis_length: i32,
+    boresight_length: i32,
+    shape_path: String,
+    timebase: Timebase,
+}
+
+impl SpiceObjectUi {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            objects: Vec::new(),
+            name_or_id: String::new(),
+            whole_coverage: true,
+            trail_span: 30,
+            trail_changed: false,
+            selected: None,
+            frame_name: String::new(),
+            instrument_name: String::new(),
+            axis_length: 5000,
+            boresight_length: 100000,
+            shape_path: String::new(),
+            timebase: Timebase::new(),
+        }
+    }
+
+    fn add(&mut self, gl: &glow::Context, world: &mut World) -> Result<(), String> {
+        let object = SpiceObject::find(&self.name_or_id, &self.timebase)?;
+        if self.objects.iter().any(|o| o.object.id == object.id) {
+            return Err(format!("{} is already shown", object.name));
+        }
+
+        let (verts, index, normal) = gen_icosphere(0.005, 2);
+        let marker = world.push((
+            object.clone(),
+            IcrfStateVector {
+                unit: CoordinateUnit::KiloMeter,
+                position: Default::default(),
+                velocity: Default::default(),
+            },
+            WorldTransform::default(),
+            VertexList::create_triangles(gl, &verts, Some(&index), Some(&normal))?,
+            MaterialComponent("material/sats.toml".to_string()),
+        ));
+        let (from, to, trail_center) = self.trail_window(&object);
+        let trail = world.push((
+            SpiceTrail {
+                object: object.clone(),
+                from,
+                to,
+                points: TRAIL_POINTS,
+                anchor: None,
+            },
+            Trajectory::new(Vec::new()),
+            WorldTransform::default(),
+            VertexList::create_lines(gl, &[], None, None)?,
+            MaterialComponent("material/colored_orbit.toml".to_string()),
+        ));
+        log::info!(
+            "Added {} ({}), placed relative to {}",
+            object.name,
+            object.id,
+            object.center
+        );
+        self.objects.push(TrackedObject {
+            object,
+            marker,
+            trail,
+            trail_center,
+            attitude: None,
+            attitude_error: None,
+            axes: None,
+            boresights: Vec::new(),
+            shape: None,
+        });
+
+        // bodies with a loaded DSK are drawn with their shape
+        if let Some(path) = ShapeModel::find_dsk(self.objects.last().unwrap().object.id) {
+            let index = self.objects.len() - 1;
+            if let Err(e) = self.set_shape(gl, world, index, Some(path.display().to_string())) {
+                log::error!("Cannot use shape model {}: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the marker with a shape model, or as a small sphere again with None.
+    /// Shapes from files naming their body fixed frame are oriented with it, if nothing else is set.
+    fn set_shape(
+        &mut self,
+        gl: &glow::Context,
+        world: &mut World,
+        index: usize,
+        file: Option<String>,
+    ) -> Result<(), String> {
+        let item = &mut self.objects[index];
+        let (list, radius) = match &file {
+            Some(file) => {
+                let model = ShapeModel::load(Path::new(file))?;
+                let radius = model.max_radius();
+                if radius <= 0.0 {
+                    return Err(format!("{} has no extent", file));
+                }
+                log::info!(
+                    "Drawing {} with {}, {} plates, equivalent radius {:.3} km",
+                    item.object.name,
+                    file,
+                    model.plates.len(),
+                    model.equivalent_radius()
+                );
+                if let (None, Some(frame)) = (&item.attitude, &model.frame) {
+                    item.attitude = Some(Attitude::Frame(frame.clone()));
+                }
+                let (verts, index, normal) = gen_shape_model(&model, radius);
+                (
+                    VertexList::create_triangles(gl, &verts, Some(&index), Some(&normal))?,
+                    Some(radius),
+                )
+            }
+            None => {
+                let (verts, index, normal) = gen_icosphere(0.005, 2);
+                (
+                    VertexList::create_triangles(gl, &verts, Some(&index), Some(&normal))?,
+                    None,
+                )
+            }
+        };
+        if let Some(mut entry) = world.entry(item.marker) {
+            entry.add_component(list);
+            match radius {
+                Some(radius) => entry.add_component(BodyRadius(radius)),
+                None => {
+                    entry.remove_component::<BodyRadius>();
+                    if let Ok(transform) = entry.get_component_mut::<WorldTransform>() {
+                        transform.scale = WorldTransform::default().scale;
+                    }
+                }
+            }
+        }
+        item.shape = file;
+        Ok(())
+    }
+
+    fn remove(&mut self, world: &mut World, index: usize) {
+        let item = self.objects.remove(index);
+        world.remove(item.marker);
+        world.remove(item.trail);
+        if let Some(axes) = item.axes {
+            world.remove(axes);
+        }
+        for (_, entity) in item.boresights {
+            world.remove(entity);
+        }
+        self.selected = match self.selected {
+            Some(s) if s == index => None,
+            Some(s) if s > index => Some(s - 1),
+            other => other,
+        };
+    }
+
+    fn apply(
+        &mut self,
+        gl: &glow::Context,
+        world: &mut World,
+        action: Action,
+    ) -> Result<(), String> {
+        match action {
+            Action::Add => self.add(gl, world)?,
+            Action::Remove(i) => self.remove(world, i),
+            Action::AttitudeFromKernels(i) => {
+                let item = &mut self.objects[i];
+                let attitude = Attitude::for_object(item.object.id);
+                log::info!("Attitude of {} from {}", item.object.name, attitude);
+                item.attitude = Some(attitude);
+            }
+            Action::AttitudeFromFrame(i) => {
+                self.objects[i].attitude = Some(Attitude::Frame(self.frame_name.trim().to_string()))
+            }
+            Action::AttitudeOff(i) => {
+                let item = &mut self.objects[i];
+                item.attitude = None;
+                item.attitude_error = None;
+                if let Ok(mut entry) = world.entry_mut(item.marker) {
+                    if let Ok(transform) = entry.get_component_mut::<WorldTransform>() {
+                        transform.rotation = Default::default();
+                    }
+                }
+            }
+            Action::ToggleAxes(i) => match self.objects[i].axes.take() {
+                Some(entity) => {
+                    world.remove(entity);
+                }
+                None => self.objects[i].axes = Some(add_line(gl, world)?),
+            },
+            Action::AddBoresight(i) => {
+                let instrument = Instrument::find(&self.instrument_name)?;
+                let entity = add_line(gl, world)?;
+                self.objects[i].boresights.push((instrument, entity));
+            }
+            Action::RemoveBoresight(i, b) => {
+                let (_, entity) = self.objects[i].boresights.remove(b);
+                world.remove(entity);
+            }
+            Action::LoadShape(i) => {
+                let file = self.shape_path.trim().to_string();
+                self.set_shape(gl, world, i, Some(file))?
+            }
+            Action::ShapeOff(i) => self.set_shape(gl, world, i, None)?,
+        }
+        Ok(())
+    }
+
+    /// From, to and the time the window is centered on, if any
+    fn trail_window(&self, object: &SpiceObject) -> (f64, f64, Option<f64>) {
+        if self.whole_coverage {
+            (object.start(), object.end(), None)
+        } else {
+            let now = self.timebase.now();
+            let span = self.trail_span.max(1) as f64 * 86400.0;
+            (now - span, now + span, Some(now))
+        }
+    }
+
+    /// Moving trail windows follow the time once it moved a quarter of the span
+    fn update_trails(&mut self, world: &mut World) {
+        let span = self.trail_span.max(1) as f64 * 86400.0;
+        let now = self.timebase.now();
+        for i in 0..self.objects.len() {
+            let outdated = match self.objects[i].trail_center {
+                Some(center) => (center - now).abs() > span / 4.0,
+                None => false,
+            };
+            if !self.trail_changed && !outdated {
+                continue;
+            }
+            let (from, to, trail_center) = self.trail_window(&self.objects[i].object);
+            let item = &mut self.objects[i];
+            item.trail_center = trail_center;
+            if let Ok(mut entry) = world.entry_mut(item.trail) {
+                if let Ok(trail) = entry.get_component_mut::<SpiceTrail>() {
+                    trail.from = from;
+                    trail.to = to;
+                    trail.anchor = None;
+                }
+            }
+        }
+        self.trail_changed = false;
+    }
+
+    /// Rotates the markers, axes and boresights and moves the lines along with the markers.
+    /// The lines are in the axes of their frame, they are only made again when their length changes.
+    fn update_attitudes(&mut self, world: &mut World) {
+        let et = self.timebase.now();
+        let axis_length = self.axis_length.max(1) as f64;
+        let boresight_length = self.boresight_length.max(1) as f64;
+        for item in self.objects.iter_mut() {
+            let state = match world
+                .entry(item.marker)
+                .and_then(|e| e.get_component::<IcrfStateVector>().ok().copied())
+            {
+                Some(state) => state,
+                None => continue,
+            };
+
+            let rotation = match &item.attitude {
+                Some(attitude) => match attitude.to_icrf(et) {
+                    Ok(rotation) => {
+                        item.attitude_error = None;
+                        Some(rotation)
+                    }
+                    Err(e) => {
+                        item.attitude_error = Some(e);
+                        None
+                    }
+                },
+                None => None,
+            };
+            if let Some(rotation) = rotation {
+                if let Ok(mut entry) = world.entry_mut(item.marker) {
+                    if let Ok(transform) = entry.get_component_mut::<WorldTransform>() {
+                        transform.rotation = icrf_to_gl_rotation(rotation);
+                    }
+                }
+            }
+
+            if let Some(axes) = item.axes {
+                let points = match rotation {
+                    Some(_) => axis_points(axis_length),
+                    None => Vec::new(),
+                };
+                set_line(world, axes, points, state, rotation);
+            }
+            for (instrument, entity) in item.boresights.iter() {
+                let rotation = frame_to_j2000(&instrument.frame, et).ok();
+                let points = match rotation {
+                    Some(_) => boresight_points(instrument, boresight_length),
+                    None => Vec::new(),
+                };
+                set_line(world, *entity, points, state, rotation);
+            }
+        }
+    }
+
+    fn attitude_ui(&mut self, ui: &Ui, index: usize, action: &mut Option<Action>) {
+        let item = &self.objects[index];
+        ui.text(format!("Attitude of {}", item.object.name));
+        match &item.attitude {
+            Some(attitude) => ui.text(format!("Using {}", attitude)),
+            None => ui.text("Not oriented"),
+        }
+        if let Some(e) = &item.attitude_error {
+            ui.text(e);
+        }
+        if ui.button("From kernels") {
+            *action = Some(Action::AttitudeFromKernels(index));
+        }
+        ui.same_line();
+        if ui.button("Off") {
+            *action = Some(Action::AttitudeOff(index));
+        }
+        ui.input_text("Frame", &mut self.frame_name).build();
+        ui.same_line();
+        if ui.button("Use frame") {
+            *action = Some(Action::AttitudeFromFrame(index));
+        }
+
+        let mut show_axes = item.axes.is_some();
+        if ui.checkbox("Body axes", &mut show_axes) {
+            *action = Some(Action::ToggleAxes(index));
+        }
+        ui.input_int("Axis length (km)", &mut self.axis_length)
+            .build();
+        ui.separator();
+
+        ui.input_text("Instrument", &mut self.instrument_name)
+            .build();
+        ui.same_line();
+        if ui.button("Add boresight") {
+            *action = Some(Action::AddBoresight(index));
+        }
+        ui.input_int("Boresight length (km)", &mut self.boresight_length)
+            .build();
+        for (b, (instrument, _)) in self.objects[index].boresights.iter().enumerate() {
+            let _id_scope = ui.push_id_usize(b);
+            ui.text(format!(
+                "{} ({}), frame {}",
+                instrument.name, instrument.id, instrument.frame
+            ));
+            ui.same_line();
+            if ui.small_button("Remove") {
+                *action = Some(Action::RemoveBoresight(index, b));
+            }
+        }
+        ui.separator();
+
+        match &self.objects[index].shape {
+            Some(file) => ui.text(format!("Shape: {}", file)),
+            None => ui.text("Shape: marker"),
+        }
+        ui.input_text("DSK or OBJ file (km)", &mut self.shape_path)
+            .build();
+        if ui.button("Load shape") {
+            *action = Some(Action::LoadShape(index));
+        }
+        ui.same_line();
+        if ui.button("Use marker") {
+            *action = Some(Action::ShapeOff(index));
+        }
+    }
+}
+
+fn add_line(gl: &glow::Context, world: &mut World) -> Result<Entity, String> {
+    Ok(world.push((
+        Trajectory::attached(Vec::new()),
+        IcrfStateVector {
+            unit: CoordinateUnit::KiloMeter,
+            position: Default::default(),
+            velocity: Default::default(),
+        },
+        WorldTransform::default(),
+        VertexList::create_lines(gl, &[], None, None)?,
+        MaterialComponent("material/colored_orbit.toml".to_string()),
+    )))
+}
+
+/// Places a line at the state and turns it, the points are only replaced if they changed
+fn set_line(
+    world: &mut World,
+    entity: Entity,
+    points: Vec<DVec3>,
+    state: IcrfStateVector,
+    rotation: Option<DQuat>,
+) {
+    if let Ok(mut entry) = world.entry_mut(entity) {
+        if let Ok(position) = entry.get_component_mut::<IcrfStateVector>() {
+            *position = state;
+        }
+        if let (Ok(transform), Some(rotation)) =
+            (entry.get_component_mut::<WorldTransform>(), rotation)
+        {
+            transform.rotation = icrf_to_gl_rotation(rotation);
+        }
+        if let Ok(trajectory) = entry.get_component_mut::<Trajectory>() {
+            if trajectory.points.len() == points.len()
+                && trajectory
+                    .points
+                    .iter()
+                    .zip(points.iter())
+                    .all(|(old, new)| old.position == *new)
+            {
+                return;
+            }
+            trajectory.points = points
+                .into_iter()
+                .map(|p| IcrfStateVector {
+                    unit: CoordinateUnit::KiloMeter,
+                    position: p,
+                    velocity: Default::default(),
+                })
+                .collect();
+            trajectory.origin = None;
+        }
+    }
+}
+
+/// x, y and z of the body frame as one line, going back through the center in between
+fn axis_points(length: f64) -> Vec<DVec3> {
+    vec![
+        DVec3::X * length,
+        DVec3::ZERO,
+        DVec3::Y * length,
+        DVec3::ZERO,
+        DVec3::Z * length,
+    ]
+}
+
+/// The boresight and the edges of the field of view in the instrument frame, all starting at the center
+fn boresight_points(instrument: &Instrument, length: f64) -> Vec<DVec3> {
+    let mut points = vec![DVec3::ZERO, instrument.boresight * length];
+    for bound in instrument.bounds.iter() {
+        points.push(DVec3::ZERO);
+        points.push(bound.normalize_or_zero() * length);
+    }
+    points
+}
+
+fn format_et(et: f64) -> String {
+    et_to_utc(et).format("%F").to_string()
+}
+
+impl WorldUi for SpiceObjectUi {
+    fn main_menu(&mut self, ui: &Ui) {
+        ui.menu("View", || {
+            if ui.menu_item("SPICE Objects") {
+                self.visible = true;
+            }
+        });
+    }
+
+    fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let mut action = None;
+        let mut visible = self.visible;
+        ui.window("SPICE Objects")
+            .opened(&mut visible)
+            .size([500.0, 500.0], Condition::Appearing)
+            .build(|| {
+                ui.text("Needs an SPK covering the object, see the Kernels window.");
+                ui.input_text("NAIF id or name", &mut self.name_or_id)
+                    .build();
+                if ui.button("Add") {
+                    action = Some(Action::Add);
+                }
+                ui.separator();
+
+                self.trail_changed |=
+                    ui.checkbox("Trail over whole coverage", &mut self.whole_coverage);
+                if !self.whole_coverage {
+                    self.trail_changed |= ui
+                        .input_int("Trail days around now", &mut self.trail_span)
+                        .build();
+                }
+                ui.separator();
+
+                ui.columns(5, "Spice Object Table", true);
+                ui.text("Object");
+                ui.next_column();
+                ui.text("Relative to");
+                ui.next_column();
+                ui.text("Coverage");
+                ui.next_column();
+                ui.text("Now");
+                ui.next_column();
+                ui.next_column();
+                ui.separator();
+                let now = self.timebase.now();
+                for (i, item) in self.objects.iter().enumerate() {
+                    let _id_scope = ui.push_id_usize(i);
+                    if ui
+                        .selectable_config(format!("{} ({})", item.object.name, item.object.id))
+                        .selected(self.selected == Some(i))
+                        .build()
+                    {
+                        self.selected = Some(i);
+                    }
+                    ui.next_column();
+                    ui.text(item.object.center.to_string());
+                    ui.next_column();
+                    ui.text(format!(
+                        "{} - {}",
+                        format_et(item.object.start()),
+                        format_et(item.object.end())
+                    ));
+                    ui.next_column();
+                    ui.text(if item.object.covers(now) {
+                        "covered"
+                    } else {
+                        "no data"
+                    });
+                    ui.next_column();
+                    if ui.small_button("Remove") {
+                        action = Some(Action::Remove(i));
+                    }
+                    ui.next_column();
+                }
+                ui.columns(1, "", false);
+
+                if let Some(index) = self.selected {
+                    ui.separator();
+                    self.attitude_ui(ui, index, &mut action);
+                }
+            });
+        self.visible = visible;
+
+        if let Some(action) = action {
+            if let Err(e) = self.apply(gl, world, action) {
+                log::error!("Spice objects: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, _gl: &glow::Context, _world: &mut World, _event: Event) {}
+
+    fn tick(
+        &mut self,
+        _gl: &glow::Context,
+        world: &mut World,
+        timebase: &mut Timebase,
+    ) -> Result<(), String> {
+        self.timebase = timebase.clone();
+        self.update_trails(world);
+        self.update_attitudes(world);
+        Ok(())
+    }
+}
@@ -9,13 +9,17 @@ use libspace::timebase::Timebase;
 pub struct TimeUi {
     visible: bool,
     timebase: Timebase,
+    /// what we handed out last tick, to notice when someone else moved the time
+    last_now: f64,
 }
 
 impl TimeUi {
     pub fn new() -> Self {
+        let timebase = Timebase::new();
         Self {
             visible: true,
-            timebase: Timebase::new(),
+            timebase,
+            last_now: timebase.now(),
         }
     }
 }
@@ -66,8 +70,13 @@ impl WorldUi for TimeUi {
         _world: &mut World,
         timebase: &mut Timebase,
     ) -> Result<(), String> {
+        if timebase.now() != self.last_now {
+            // another window jumped in time, follow it
+            self.timebase = timebase.clone();
+        }
         self.timebase.tick(1.0 / 60.0);
         *timebase = self.timebase.clone();
+        self.last_now = timebase.now();
         Ok(())
     }
 }
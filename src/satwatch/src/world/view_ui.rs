@@ -1,6 +1,10 @@
-use crate::components::{Camera, DirectionalLight, MaterialComponent, VertexList, WorldTransform};
+use crate::components::{
+    BodyRadius, Camera, DirectionalLight, MaterialComponent, SpiceTrail, Trajectory, VertexList,
+    WorldTransform,
+};
 use crate::util::input_events::Event;
-use crate::util::vertex_tools::{gen_icosphere, gen_orbit_points_icrf};
+use crate::util::orbit_elements_table::orbit_elements_table;
+use crate::util::vertex_tools::{gen_icosphere, gen_orbit_points_icrf, gen_shape_model};
 use crate::world::world_ui::WorldUi;
 use glam::f32::*;
 use glam::f64::*;
@@ -8,17 +12,88 @@ use glam::f64::*;
 use imgui::*;
 
 use legion::*;
+use libspace::bodies::lagrange::*;
+use libspace::bodies::shape_model::ShapeModel;
 use libspace::bodies::Planet;
 use libspace::coordinate::{
     CoordinateUnit, IcrfStateVector, PlanetaryReferenceFrame, PlanetaryStateVector,
 };
+use libspace::kernels::SpiceObject;
 use libspace::timebase::Timebase;
+use std::collections::HashMap;
+use std::path::Path;
 
 struct OrbitObjectTag(f64);
 
+/// Bodies with a shape are drawn at least this large, gl units
+const MIN_BODY_SCALE: f64 = 0.005;
+
+const PLANETS: [Planet; 10] = [
+    Planet::Sun,
+    Planet::Mercury,
+    Planet::Venus,
+    Planet::Earth,
+    Planet::Moon,
+    Planet::Mars,
+    Planet::Jupiter,
+    Planet::Saturn,
+    Planet::Uranus,
+    Planet::Neptune,
+];
+
+struct LagrangeMarker(LagrangeSystem, LagrangePoint);
+
+/// Things the view can be centered on
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ViewTarget {
+    Planet(Planet),
+    Lagrange(LagrangeSystem, LagrangePoint),
+    /// NAIF id and center planet of a SpiceObject in the world
+    Spice(i32, Planet),
+}
+
+impl ViewTarget {
+    fn pos_icrf(&self, world: &World, timebase: &Timebase) -> IcrfStateVector {
+        match self {
+            ViewTarget::Planet(planet) => planet.pos_icrf(timebase),
+            ViewTarget::Lagrange(system, point) => system.point_icrf(*point, timebase),
+            ViewTarget::Spice(id, center) => <&SpiceObject>::query()
+                .iter(world)
+                .find(|o| o.id == *id)
+                .and_then(|o| o.state_icrf(*center, timebase).ok())
+                .unwrap_or_else(|| center.pos_icrf(timebase)),
+        }
+    }
+
+    /// The planet the camera position is kept relative to
+    fn planet(&self) -> Planet {
+        match self {
+            ViewTarget::Planet(planet) => *planet,
+            ViewTarget::Lagrange(system, _) => system.secondary,
+            ViewTarget::Spice(_, center) => *center,
+        }
+    }
+
+    /// Where the target is relative to its planet, in km
+    fn offset_from_planet(&self, world: &World, timebase: &Timebase) -> DVec3 {
+        match self {
+            ViewTarget::Planet(_) => DVec3::default(),
+            ViewTarget::Lagrange(_, _) | ViewTarget::Spice(_, _) => {
+                let target = self.pos_icrf(world, timebase);
+                let planet = self.planet().pos_icrf(timebase);
+                target.unit.to(
+                    CoordinateUnit::KiloMeter,
+                    &(target.position - planet.position),
+                )
+            }
+        }
+    }
+}
+
 pub struct ViewUi {
     visible: bool,
-    target_planet: Planet,
+    target: ViewTarget,
+    last_target_offset: Option<DVec3>,
     gl_origin: IcrfStateVector,
     world_scale: f64,
     world_scale_unit: CoordinateUnit,
@@ -26,6 +101,13 @@ pub struct ViewUi {
     camera_rot: Vec3,
     camera_entity: Entity,
     need_orbit_redraw: bool,
+    timebase: Timebase,
+    lagrange_markers: HashMap<LagrangeSystem, Vec<Entity>>,
+    /// index into PLANETS
+    shape_planet: usize,
+    shape_path: String,
+    /// planets drawn with a shape model instead of a sphere, and the file it came from
+    shapes: HashMap<Planet, String>,
 }
 
 impl ViewUi {
@@ -48,7 +130,8 @@ impl ViewUi {
 
         let new = Self {
             visible: true,
-            target_planet: Planet::Earth,
+            target: ViewTarget::Planet(Planet::Earth),
+            last_target_offset: None,
             gl_origin: IcrfStateVector {
                 unit: CoordinateUnit::Meter,
                 position: DVec3::new(0.0, 0.0, 0.0),
@@ -60,6 +143,11 @@ impl ViewUi {
             camera_rot: Vec3::new(0.0, 0.0, 0.0),
             camera_entity,
             need_orbit_redraw: true,
+            timebase: Timebase::new(),
+            lagrange_markers: HashMap::new(),
+            shape_planet: 0,
+            shape_path: String::new(),
+            shapes: HashMap::new(),
         };
 
         new.add_planets(gl, world)?;
@@ -77,6 +165,7 @@ impl ViewUi {
         self.add_planet(gl, world, Planet::Mercury)?;
         self.add_planet(gl, world, Planet::Venus)?;
         self.add_planet(gl, world, Planet::Earth)?;
+        self.add_planet(gl, world, Planet::Moon)?;
         self.add_planet(gl, world, Planet::Mars)?;
         self.add_planet(gl, world, Planet::Jupiter)?;
         self.add_planet(gl, world, Planet::Saturn)?;
@@ -115,14 +204,80 @@ impl ViewUi {
         Ok(())
     }
 
+    fn add_lagrange_markers(
+        &mut self,
+        gl: &glow::Context,
+        world: &mut World,
+        system: LagrangeSystem,
+    ) -> Result<(), String> {
+        let (vert, index, normal) = gen_icosphere(0.01, 2);
+        let mut entities = Vec::new();
+        for point in ALL_LAGRANGE_POINTS {
+            entities.push(world.push((
+                LagrangeMarker(system, point),
+                system.point_icrf(point, &self.timebase),
+                WorldTransform::default(),
+                VertexList::create_triangles(gl, &vert, Some(&index), Some(&normal))?,
+                MaterialComponent("material/sats.toml".to_string()),
+            )));
+        }
+        self.lagrange_markers.insert(system, entities);
+        Ok(())
+    }
+
+    /// Replace the sphere of a planet with a shape model, or go back to the sphere with None
+    fn set_shape(
+        &mut self,
+        gl: &glow::Context,
+        world: &mut World,
+        planet: Planet,
+        file: Option<String>,
+    ) -> Result<(), String> {
+        let (vert, index, normal) = match &file {
+            Some(file) => {
+                let model = ShapeModel::load(Path::new(file))?;
+                log::info!(
+                    "Loaded shape model {} with {} plates, equivalent radius {:.3} km",
+                    file,
+                    model.plates.len(),
+                    model.equivalent_radius()
+                );
+                gen_shape_model(&model, planet.body().radius_mean)
+            }
+            None => gen_icosphere(1.0, 5),
+        };
+        let mut query =
+            <(&Planet, &mut VertexList)>::query().filter(!component::<OrbitObjectTag>());
+        for (_, list) in query.iter_mut(world).filter(|(p, _)| **p == planet) {
+            *list = VertexList::create_triangles(gl, &vert, Some(&index), Some(&normal))?;
+        }
+        match file {
+            Some(file) => self.shapes.insert(planet, file),
+            None => self.shapes.remove(&planet),
+        };
+        Ok(())
+    }
+
+    fn remove_lagrange_markers(&mut self, world: &mut World, system: LagrangeSystem) {
+        if let Some(entities) = self.lagrange_markers.remove(&system) {
+            for entity in entities {
+                world.remove(entity);
+            }
+        }
+    }
+
     fn reset_view(&mut self, _gl: &glow::Context, world: &mut World) {
         self.need_orbit_redraw = true;
         // special case: sun
-        if self.target_planet == Planet::Sun {
+        let planet = self.target.planet();
+        let target_offset = self.target.offset_from_planet(world, &self.timebase);
+        self.last_target_offset = Some(target_offset);
+        if planet == Planet::Sun {
             if let Ok(mut cam_entry) = world.entry_mut(self.camera_entity) {
                 if let Ok(cam_pos) = cam_entry.get_component_mut::<PlanetaryStateVector>() {
-                    cam_pos.position = DVec3::new(0.0, 0.0, 6.0);
-                    cam_pos.planet = self.target_planet;
+                    cam_pos.position = DVec3::new(0.0, 0.0, 6.0)
+                        + CoordinateUnit::KiloMeter.to(CoordinateUnit::Au, &target_offset);
+                    cam_pos.planet = planet;
                     cam_pos.unit = CoordinateUnit::Au;
                 }
                 if let Ok(cam_trans) = cam_entry.get_component_mut::<WorldTransform>() {
@@ -137,8 +292,8 @@ impl ViewUi {
             if let Ok(mut cam_entry) = world.entry_mut(self.camera_entity) {
                 if let Ok(cam_pos) = cam_entry.get_component_mut::<PlanetaryStateVector>() {
                     cam_pos.position =
-                        DVec3::new(self.target_planet.body().radius_mean * 5.0, 0.0, 0.0);
-                    cam_pos.planet = self.target_planet;
+                        DVec3::new(planet.body().radius_mean * 5.0, 0.0, 0.0) + target_offset;
+                    cam_pos.planet = planet;
                     cam_pos.unit = CoordinateUnit::KiloMeter;
                 }
                 if let Ok(cam_trans) = cam_entry.get_component_mut::<WorldTransform>() {
@@ -146,15 +301,21 @@ impl ViewUi {
                 }
             }
             // world scale
-            self.world_scale = self.target_planet.body().radius_mean;
+            self.world_scale = planet.body().radius_mean;
             self.world_scale_unit = CoordinateUnit::KiloMeter;
         }
     }
 
     fn update_camera(&mut self, _gl: &glow::Context, world: &mut World, timebase: &Timebase) {
         let tick_speed: f64 = 1.0 / 60.0;
+        // virtual targets move relative to their planet, and the camera should move along with them
+        let target_offset = self.target.offset_from_planet(world, timebase);
+        let target_moved = target_offset - self.last_target_offset.unwrap_or(target_offset);
+        self.last_target_offset = Some(target_offset);
+
         let mut cam_query = <(&Camera, &mut PlanetaryStateVector, &mut WorldTransform)>::query();
         for (_cam, cam_pos, cam_transform) in cam_query.iter_mut(world) {
+            cam_pos.position += CoordinateUnit::KiloMeter.to(cam_pos.unit, &target_moved);
             let icrf = cam_pos.to_icrf(timebase);
             let mut gl_pos =
                 icrf.to_gl_coord(self.world_scale, self.world_scale_unit, &self.gl_origin);
@@ -191,7 +352,7 @@ impl ViewUi {
                 None,
             );
             cam_transform.rotation = rot;
-            *cam_pos = PlanetaryStateVector::from_icrf(new_icrf, timebase, self.target_planet);
+            *cam_pos = PlanetaryStateVector::from_icrf(new_icrf, timebase, self.target.planet());
         }
     }
 }
@@ -207,62 +368,113 @@ impl WorldUi for ViewUi {
 
     fn ui(&mut self, gl: &glow::Context, world: &mut World, ui: &mut Ui) -> Result<(), String> {
         let mut triggers_reset = false;
+        let mut marker_changes = Vec::new();
+        let mut shape_change = None;
+        let spice_objects = <&SpiceObject>::query()
+            .iter(world)
+            .map(|o| (o.id, o.name.clone(), o.center))
+            .collect::<Vec<(i32, String, Planet)>>();
         if self.visible {
             ui.window("View Control")
                 .opened(&mut self.visible)
                 .build(|| {
+                    let old_target = self.target;
                     if ui.collapsing_header("Target Planet", TreeNodeFlags::DEFAULT_OPEN) {
                         triggers_reset = triggers_reset || ui.button("Reset View");
-                        let old_target = self.target_planet;
-                        ui.radio_button(
-                            Planet::Sun.to_string(),
-                            &mut self.target_planet,
-                            Planet::Sun,
-                        );
-                        ui.radio_button(
-                            Planet::Mercury.to_string(),
-                            &mut self.target_planet,
-                            Planet::Mercury,
-                        );
-                        ui.radio_button(
-                            Planet::Venus.to_string(),
-                            &mut self.target_planet,
-                            Planet::Venus,
-                        );
-                        ui.radio_button(
-                            Planet::Earth.to_string(),
-                            &mut self.target_planet,
-                            Planet::Earth,
-                        );
-                        ui.radio_button(
-                            Planet::Mars.to_string(),
-                            &mut self.target_planet,
-                            Planet::Mars,
-                        );
-                        ui.radio_button(
-                            Planet::Jupiter.to_string(),
-                            &mut self.target_planet,
-                            Planet::Jupiter,
-                        );
-                        ui.radio_button(
-                            Planet::Saturn.to_string(),
-                            &mut self.target_planet,
-                            Planet::Saturn,
-                        );
-                        ui.radio_button(
-                            Planet::Uranus.to_string(),
-                            &mut self.target_planet,
-                            Planet::Uranus,
-                        );
-                        ui.radio_button(
-                            Planet::Neptune.to_string(),
-                            &mut self.target_planet,
-                            Planet::Neptune,
-                        );
-                        triggers_reset = triggers_reset || self.target_planet != old_target;
+                        for planet in PLANETS {
+                            ui.radio_button(
+                                planet.to_string(),
+                                &mut self.target,
+                                ViewTarget::Planet(planet),
+                            );
+                        }
+                    }
+                    if ui.collapsing_header("Lagrange Points", TreeNodeFlags::empty()) {
+                        for (i, system) in LagrangeSystem::all().into_iter().enumerate() {
+                            let _id_scope = ui.push_id_usize(i);
+                            let mut shown = self.lagrange_markers.contains_key(&system);
+                            if ui.checkbox(system.to_string(), &mut shown) {
+                                marker_changes.push((system, shown));
+                            }
+                            for point in ALL_LAGRANGE_POINTS {
+                                ui.same_line();
+                                ui.radio_button(
+                                    point.to_string(),
+                                    &mut self.target,
+                                    ViewTarget::Lagrange(system, point),
+                                );
+                            }
+                        }
+                    }
+                    if !spice_objects.is_empty()
+                        && ui.collapsing_header("SPICE Objects", TreeNodeFlags::empty())
+                    {
+                        for (id, name, center) in spice_objects.iter() {
+                            ui.radio_button(
+                                format!("{} ({})", name, id),
+                                &mut self.target,
+                                ViewTarget::Spice(*id, *center),
+                            );
+                        }
+                    }
+                    if ui.collapsing_header("Shape Models", TreeNodeFlags::empty()) {
+                        let planet_names = PLANETS
+                            .iter()
+                            .map(|p| p.to_string())
+                            .collect::<Vec<String>>();
+                        ui.combo_simple_string("Body", &mut self.shape_planet, &planet_names);
+                        let planet = PLANETS[self.shape_planet];
+                        match self.shapes.get(&planet) {
+                            Some(file) => ui.text(format!("Shape: {}", file)),
+                            None => ui.text("Shape: sphere"),
+                        }
+                        ui.input_text("DSK or OBJ file (km)", &mut self.shape_path)
+                            .build();
+                        if ui.button("Load") {
+                            shape_change = Some((planet, Some(self.shape_path.clone())));
+                        }
+                        ui.same_line();
+                        if ui.button("From loaded DSK") {
+                            match ShapeModel::find_dsk(planet.naif_id()) {
+                                Some(path) => {
+                                    shape_change = Some((planet, Some(path.display().to_string())))
+                                }
+                                None => log::error!("No loaded DSK has a shape of {}", planet),
+                            }
+                        }
+                        ui.same_line();
+                        if ui.button("Use sphere") {
+                            shape_change = Some((planet, None));
+                        }
+                    }
+                    triggers_reset = triggers_reset || self.target != old_target;
+
+                    if let ViewTarget::Planet(planet) = self.target {
+                        if let Some(parent) = planet.parent() {
+                            if ui.collapsing_header("Orbital Elements", TreeNodeFlags::empty()) {
+                                let state = PlanetaryStateVector::from_icrf(
+                                    self.gl_origin,
+                                    &self.timebase,
+                                    parent,
+                                );
+                                orbit_elements_table(ui, &state, &self.timebase);
+                            }
+                        }
                     }
                 });
         }
+        for (system, shown) in marker_changes {
+            if shown {
+                self.add_lagrange_markers(gl, world, system)?;
+            } else {
+                self.remove_lagrange_markers(world, system);
+            }
+        }
+        if let Some((planet, file)) = shape_change {
+            if let Err(e) = self.set_shape(gl, world, planet, file) {
+                log::error!("Cannot load shape model for {}: {}", planet, e);
+            }
+        }
         if triggers_reset {
             self.reset_view(gl, world);
         }
@@ -292,8 +504,17 @@ impl WorldUi for ViewUi {
         world: &mut World,
         timebase: &mut Timebase,
     ) -> Result<(), String> {
+        self.timebase = timebase.clone();
+        // spice objects can go away under the view
+        if let ViewTarget::Spice(id, center) = self.target {
+            if !<&SpiceObject>::query().iter(world).any(|o| o.id == id) {
+                self.target = ViewTarget::Planet(center);
+                self.reset_view(gl, world);
+            }
+        }
+
         // update gl origin to target
-        self.gl_origin = self.target_planet.pos_icrf(timebase);
+        self.gl_origin = self.target.pos_icrf(world, timebase);
 
         // update planet positions
         let mut planet_query =
@@ -318,21 +539,106 @@ impl WorldUi for ViewUi {
             transform.scale = Vec3::new(scale as f32, scale as f32, scale as f32);
         }
 
-        // planet orbits
-        let mut orbit_query = <(&mut OrbitObjectTag, &Planet, &mut VertexList)>::query();
-        for (tag, planet, list) in orbit_query.iter_mut(world) {
+        // planet orbits. The moon's goes around the earth, so it is drawn relative to it and moves along.
+        let mut orbit_query = <(
+            &mut OrbitObjectTag,
+            &Planet,
+            &mut VertexList,
+            &mut WorldTransform,
+        )>::query();
+        for (tag, planet, list, transform) in orbit_query.iter_mut(world) {
+            let centre = planet
+                .parent()
+                .filter(|parent| *parent != Planet::Sun)
+                .map(|parent| parent.pos_icrf(timebase));
             if self.need_orbit_redraw
                 || (tag.0 - timebase.now_jd_j2000()).abs() > planet.body().sidereal_period
             {
+                let relative_to = match centre {
+                    Some(_) => IcrfStateVector {
+                        unit: CoordinateUnit::KiloMeter,
+                        position: DVec3::ZERO,
+                        velocity: DVec3::ZERO,
+                    },
+                    None => self.gl_origin,
+                };
                 let (orb_vert, orb_index) = gen_orbit_points_icrf(
                     planet.rough_pos_list(&timebase),
                     self.world_scale,
                     self.world_scale_unit,
-                    &self.gl_origin,
+                    &relative_to,
                 );
                 tag.0 = timebase.now_jd_j2000();
                 *list = VertexList::create_lines(gl, &orb_vert, Some(&orb_index), None)?;
             }
+            if let Some(centre) = &centre {
+                *transform = WorldTransform::from_icrf(
+                    centre,
+                    &self.gl_origin,
+                    self.world_scale,
+                    self.world_scale_unit,
+                    Some(*transform),
+                );
+            }
+        }
+
+        // spice trails are placed relative to the planet in view
+        let anchor = self.target.planet();
+        let mut spice_trail_query = <(&mut SpiceTrail, &mut Trajectory)>::query();
+        for (trail, trajectory) in spice_trail_query.iter_mut(world) {
+            if trail.anchor != Some(anchor) {
+                trajectory.points =
+                    trail
+                        .object
+                        .trajectory(trail.from, trail.to, trail.points, anchor, timebase);
+                trajectory.origin = None;
+                trail.anchor = Some(anchor);
+            }
+        }
+
+        // everything else that has a trajectory
+        let mut trajectory_query =
+            <(&mut Trajectory, &mut VertexList, &mut WorldTransform)>::query();
+        for (trajectory, list, transform) in trajectory_query.iter_mut(world) {
+            if self.need_orbit_redraw || trajectory.origin.is_none() {
+                // lines around a planet or attached to something are made relative to it, and placed below
+                let relative_to = if trajectory.centre.is_some() || trajectory.attached {
+                    IcrfStateVector {
+                        unit: CoordinateUnit::KiloMeter,
+                        position: DVec3::ZERO,
+                        velocity: DVec3::ZERO,
+                    }
+                } else {
+                    self.gl_origin
+                };
+                let (vert, index) = gen_orbit_points_icrf(
+                    trajectory.points.clone(),
+                    self.world_scale,
+                    self.world_scale_unit,
+                    &relative_to,
+                );
+                *list = VertexList::create_lines(gl, &vert, Some(&index), None)?;
+                trajectory.origin = Some(relative_to);
+            }
+            if let Some(centre) = trajectory.centre {
+                *transform = WorldTransform::from_icrf(
+                    &centre.pos_icrf(timebase),
+                    &self.gl_origin,
+                    self.world_scale,
+                    self.world_scale_unit,
+                    Some(*transform),
+                );
+            } else if trajectory.attached {
+                // placed with the other icrf positions below
+            } else if let Some(origin) = &trajectory.origin {
+                *transform = WorldTransform::from_icrf(
+                    origin,
+                    &self.gl_origin,
+                    self.world_scale,
+                    self.world_scale_unit,
+                    Some(*transform),
+                );
+            }
         }
         self.need_orbit_redraw = false;
 
@@ -349,6 +655,20 @@ impl WorldUi for ViewUi {
             );
         }
 
+        // lagrange points move along with their bodies
+        let mut marker_query = <(&LagrangeMarker, &mut IcrfStateVector)>::query();
+        for (marker, state_vec) in marker_query.iter_mut(world) {
+            *state_vec = marker.0.point_icrf(marker.1, timebase);
+        }
+
+        // spice objects, placed relative to the planet in view when possible
+        let mut spice_query = <(&SpiceObject, &mut IcrfStateVector)>::query();
+        for (object, state_vec) in spice_query.iter_mut(world) {
+            if let Ok(state) = object.state_icrf(anchor, timebase) {
+                *state_vec = state;
+            }
+        }
+
         // update things with icrf positions, as the origin can change too
         let mut icrf_state_query = <(&IcrfStateVector, &mut WorldTransform)>::query();
         for (state_vec, transform) in icrf_state_query.iter_mut(world) {
@@ -361,6 +681,16 @@ impl WorldUi for ViewUi {
             );
         }
 
+        // bodies with a shape are as large as they are, unless that is too small to see
+        let km_per_unit = self
+            .world_scale_unit
+            .to(CoordinateUnit::KiloMeter, &self.world_scale);
+        let mut body_query = <(&BodyRadius, &mut WorldTransform)>::query();
+        for (radius, transform) in body_query.iter_mut(world) {
+            let scale = (radius.0 / km_per_unit).max(MIN_BODY_SCALE) as f32;
+            transform.scale = Vec3::new(scale, scale, scale);
+        }
+
         // camera update needs las, as otherwise its transform is updated along the rest
         self.update_camera(gl, world, timebase);
         Ok(())
@@ -2,7 +2,17 @@ use imgui::*;
 use legion::*;
 
 use crate::util::input_events::Event;
+use crate::world::accuracy_ui::AccuracyUi;
+use crate::world::comet_ui::CometUi;
+use crate::world::eclipse_ui::EclipseUi;
 use crate::world::element_db_ui::DbUi;
+use crate::world::horizons_ui::HorizonsUi;
+use crate::world::kernel_ui::KernelUi;
+use crate::world::minor_planet_ui::MinorPlanetUi;
+use crate::world::orbiter_ui::OrbiterUi;
+use crate::world::phenomena_ui::PhenomenaUi;
+use crate::world::planet_inspector_ui::PlanetInspectorUi;
+use crate::world::spice_object_ui::SpiceObjectUi;
 use crate::world::time_ui::TimeUi;
 use crate::world::view_ui::ViewUi;
 use crate::world::world_ui::WorldUi;
@@ -24,6 +34,16 @@ impl WorldControl {
                 Box::new(TimeUi::new()),
                 Box::new(ViewUi::new(gl, world)?),
                 Box::new(DbUi::new()),
+                Box::new(MinorPlanetUi::new()),
+                Box::new(CometUi::new()),
+                Box::new(PhenomenaUi::new()),
+                Box::new(PlanetInspectorUi::new()),
+                Box::new(EclipseUi::new()),
+                Box::new(OrbiterUi::new()),
+                Box::new(KernelUi::new()),
+                Box::new(SpiceObjectUi::new()),
+                Box::new(HorizonsUi::new()),
+                Box::new(AccuracyUi::new()),
             ],
             last_tick: Instant::now(),
             timebase: Timebase::new(),